@@ -36,19 +36,19 @@ pub struct ConsoleContext {
 }
 
 impl ConsoleContext {
-    fn query_var(&self, key: &str) -> Option<String> {
+    pub fn query_var(&self, key: &str) -> Option<String> {
         match self.vars.get(key) {
             None => None,
             Some(val) => Some((*val.borrow().deref()).get()),
         }
     }
-    fn write_var(&mut self, key: &str, val: &str) -> Option<ConsoleVarResult> {
+    pub fn write_var(&mut self, key: &str, val: &str) -> Option<ConsoleVarResult> {
         match self.vars.get(key) {
             None => None,
             Some(result) => Some(result.borrow_mut().set(val.to_string())),
         }
     }
-    fn add_var(&mut self, key: &str, var: RefCell<Box<ConsoleVar>>) -> &mut Self {
+    pub fn add_var(&mut self, key: &str, var: RefCell<Box<ConsoleVar>>) -> &mut Self {
         self.vars.insert(key.to_string(), var);
         self
     }
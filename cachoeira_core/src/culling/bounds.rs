@@ -0,0 +1,146 @@
+use cgmath::{
+	Array,
+	EuclideanSpace,
+	InnerSpace,
+	Matrix4,
+	Point3,
+	Transform as CgTransform,
+	Vector3,
+};
+use specs::prelude::{
+	Component,
+	DenseVecStorage,
+};
+
+use transform::GlobalTransform;
+
+/// Axis-aligned box in the entity's local space.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Aabb {
+	pub min: Point3<f32>,
+	pub max: Point3<f32>,
+}
+
+impl Aabb {
+	pub fn new(min: Point3<f32>, max: Point3<f32>) -> Self {
+		debug_assert!(min.x <= max.x && min.y <= max.y && min.z <= max.z, "Aabb min must not exceed max");
+		Aabb { min, max }
+	}
+
+	pub fn from_center(center: Point3<f32>, half_extents: Vector3<f32>) -> Self {
+		Aabb::new(center - half_extents, center + half_extents)
+	}
+
+	/// Smallest box holding all `points`, `None` if there are none.
+	pub fn from_points<I>(points: I) -> Option<Self>
+	where
+		I: IntoIterator<Item = Point3<f32>>,
+	{
+		let mut points = points.into_iter();
+		let first = points.next()?;
+		Some(points.fold(Aabb { min: first, max: first }, |aabb, point| aabb.grow(point)))
+	}
+
+	pub fn center(&self) -> Point3<f32> { self.min.midpoint(self.max) }
+
+	pub fn half_extents(&self) -> Vector3<f32> { (self.max - self.min) * 0.5 }
+
+	pub fn grow(self, point: Point3<f32>) -> Self {
+		Aabb {
+			min: Point3::new(self.min.x.min(point.x), self.min.y.min(point.y), self.min.z.min(point.z)),
+			max: Point3::new(self.max.x.max(point.x), self.max.y.max(point.y), self.max.z.max(point.z)),
+		}
+	}
+
+	pub fn union(self, other: &Aabb) -> Self {
+		self.grow(other.min).grow(other.max)
+	}
+
+	pub fn contains(&self, point: Point3<f32>) -> bool {
+		point.x >= self.min.x && point.x <= self.max.x
+			&& point.y >= self.min.y && point.y <= self.max.y
+			&& point.z >= self.min.z && point.z <= self.max.z
+	}
+
+	pub fn intersects(&self, other: &Aabb) -> bool {
+		self.min.x <= other.max.x && self.max.x >= other.min.x
+			&& self.min.y <= other.max.y && self.max.y >= other.min.y
+			&& self.min.z <= other.max.z && self.max.z >= other.min.z
+	}
+
+	/// Box enclosing this one after an affine transform.
+	pub fn transformed(&self, matrix: &Matrix4<f32>) -> Aabb {
+		let center = matrix.transform_point(self.center());
+		let half = self.half_extents();
+		let abs = |column: Vector3<f32>| Vector3::new(column.x.abs(), column.y.abs(), column.z.abs());
+		let extent = abs(matrix.x.truncate()) * half.x
+			+ abs(matrix.y.truncate()) * half.y
+			+ abs(matrix.z.truncate()) * half.z;
+		Aabb { min: center - extent, max: center + extent }
+	}
+
+	/// The box in world space.
+	pub fn world(&self, global: &GlobalTransform) -> Aabb {
+		self.transformed(&global.0)
+	}
+
+	pub fn bounding_sphere(&self) -> BoundingSphere {
+		BoundingSphere { center: self.center(), radius: self.half_extents().magnitude() }
+	}
+}
+
+impl Component for Aabb {
+	type Storage = DenseVecStorage<Self>;
+}
+
+/// Sphere in the entity's local space.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct BoundingSphere {
+	pub center: Point3<f32>,
+	pub radius: f32,
+}
+
+impl BoundingSphere {
+	pub fn new(center: Point3<f32>, radius: f32) -> Self {
+		debug_assert!(radius >= 0., "BoundingSphere radius must not be negative");
+		BoundingSphere { center, radius }
+	}
+
+	pub fn contains(&self, point: Point3<f32>) -> bool {
+		(point - self.center).magnitude2() <= self.radius * self.radius
+	}
+
+	pub fn intersects(&self, other: &BoundingSphere) -> bool {
+		let reach = self.radius + other.radius;
+		(other.center - self.center).magnitude2() <= reach * reach
+	}
+
+	/// Sphere enclosing this one after an affine transform; non-uniform scale takes the
+	/// largest axis.
+	pub fn transformed(&self, matrix: &Matrix4<f32>) -> BoundingSphere {
+		let scale = matrix.x.truncate().magnitude2()
+			.max(matrix.y.truncate().magnitude2())
+			.max(matrix.z.truncate().magnitude2())
+			.sqrt();
+		BoundingSphere { center: matrix.transform_point(self.center), radius: self.radius * scale }
+	}
+
+	/// The sphere in world space.
+	pub fn world(&self, global: &GlobalTransform) -> BoundingSphere {
+		self.transformed(&global.0)
+	}
+
+	pub fn aabb(&self) -> Aabb {
+		Aabb::from_center(self.center, Vector3::from_value(self.radius))
+	}
+}
+
+impl Default for BoundingSphere {
+	fn default() -> Self {
+		BoundingSphere { center: Point3::origin(), radius: 0. }
+	}
+}
+
+impl Component for BoundingSphere {
+	type Storage = DenseVecStorage<Self>;
+}
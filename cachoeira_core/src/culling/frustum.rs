@@ -0,0 +1,109 @@
+use cgmath::{
+	EuclideanSpace,
+	InnerSpace,
+	Matrix4,
+	Point3,
+	Vector3,
+	Vector4,
+};
+
+use culling::{Aabb, BoundingSphere};
+
+/// Plane `normal · p + distance = 0`; the normal points to the inside.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Plane {
+	pub normal: Vector3<f32>,
+	pub distance: f32,
+}
+
+impl Plane {
+	/// Builds a plane from `(a, b, c, d)` coefficients, normalizing them.
+	pub fn from_coefficients(coefficients: Vector4<f32>) -> Plane {
+		let length = coefficients.truncate().magnitude();
+		Plane { normal: coefficients.truncate() / length, distance: coefficients.w / length }
+	}
+
+	/// Signed distance, positive on the inner side.
+	pub fn distance_to(&self, point: Point3<f32>) -> f32 {
+		self.normal.dot(point.to_vec()) + self.distance
+	}
+}
+
+/// Result of testing a volume against a `Frustum`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Containment {
+	Outside,
+	Intersecting,
+	Inside,
+}
+
+/// The six clip planes of a view-projection, in left, right, bottom, top, near, far order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+	pub planes: [Plane; 6],
+}
+
+impl Frustum {
+	/// Extracts the planes of a view-projection with OpenGL-style clip depth (`-w..w`), as
+	/// produced by `cgmath::perspective` and `cgmath::ortho`.
+	pub fn from_matrix(view_projection: &Matrix4<f32>) -> Frustum {
+		let m = view_projection;
+		let row = |i: usize| Vector4::new(m.x[i], m.y[i], m.z[i], m.w[i]);
+		let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+		Frustum {
+			planes: [
+				Plane::from_coefficients(w + x),
+				Plane::from_coefficients(w - x),
+				Plane::from_coefficients(w + y),
+				Plane::from_coefficients(w - y),
+				Plane::from_coefficients(w + z),
+				Plane::from_coefficients(w - z),
+			],
+		}
+	}
+
+	pub fn contains_point(&self, point: Point3<f32>) -> bool {
+		self.planes.iter().all(|plane| plane.distance_to(point) >= 0.)
+	}
+
+	pub fn test_sphere(&self, sphere: &BoundingSphere) -> Containment {
+		let mut containment = Containment::Inside;
+		for plane in &self.planes {
+			let distance = plane.distance_to(sphere.center);
+			if distance < -sphere.radius {
+				return Containment::Outside;
+			}
+			if distance < sphere.radius {
+				containment = Containment::Intersecting;
+			}
+		}
+		containment
+	}
+
+	pub fn test_aabb(&self, aabb: &Aabb) -> Containment {
+		let (center, half) = (aabb.center(), aabb.half_extents());
+		let mut containment = Containment::Inside;
+		for plane in &self.planes {
+			let distance = plane.distance_to(center);
+			// projection of the box onto the plane normal
+			let reach = plane.normal.x.abs() * half.x + plane.normal.y.abs() * half.y + plane.normal.z.abs() * half.z;
+			if distance < -reach {
+				return Containment::Outside;
+			}
+			if distance < reach {
+				containment = Containment::Intersecting;
+			}
+		}
+		containment
+	}
+
+	/// Conservative test: a few volumes near the frustum corners are reported visible.
+	pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+		self.test_sphere(sphere) != Containment::Outside
+	}
+
+	/// Conservative, like `intersects_sphere`.
+	pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+		self.test_aabb(aabb) != Containment::Outside
+	}
+}
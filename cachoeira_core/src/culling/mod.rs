@@ -0,0 +1,15 @@
+//! Bounding volumes and visibility culling.
+//!
+//! Entities carry an `Aabb` or `BoundingSphere` in local space, optionally with `Layers`;
+//! cameras carry a `CullingCamera` and get a `VisibleEntities` list from the `CullingSystem`.
+//! Nothing here touches a GPU.
+
+pub use self::bounds::{Aabb, BoundingSphere};
+pub use self::frustum::{Containment, Frustum, Plane};
+pub use self::system::CullingSystem;
+pub use self::view::{CullingCamera, Layers, VisibleEntities};
+
+mod bounds;
+mod frustum;
+mod system;
+mod view;
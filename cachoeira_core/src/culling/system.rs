@@ -0,0 +1,164 @@
+use rayon::prelude::*;
+use specs::prelude::{
+	Entities,
+	Entity,
+	Join,
+	ParJoin,
+	ReadStorage,
+	System,
+	WriteStorage,
+};
+
+use culling::{
+	Aabb,
+	BoundingSphere,
+	CullingCamera,
+	Frustum,
+	Layers,
+	VisibleEntities,
+};
+use transform::GlobalTransform;
+
+/// Fills each `CullingCamera`'s `VisibleEntities` with the entities whose bounds intersect its
+/// frustum and whose `Layers` overlap the camera's.
+///
+/// Only entities with an `Aabb` or a `BoundingSphere` are considered; when both are present the
+/// sphere is tried first. Entities are tested in parallel, so this belongs after the
+/// `TransformSystem` and before anything that renders.
+pub struct CullingSystem;
+
+impl<'a> System<'a> for CullingSystem {
+	type SystemData = (
+		Entities<'a>,
+		ReadStorage<'a, GlobalTransform>,
+		ReadStorage<'a, Aabb>,
+		ReadStorage<'a, BoundingSphere>,
+		ReadStorage<'a, Layers>,
+		ReadStorage<'a, CullingCamera>,
+		WriteStorage<'a, VisibleEntities>,
+	);
+
+	fn run(&mut self, (entities, globals, aabbs, spheres, layers, cameras, mut visible): Self::SystemData) {
+		#[cfg(feature="profiler")]
+		profile_scope!("culling_system");
+
+		let views: Vec<(Entity, Option<Frustum>, Layers)> = (&*entities, &cameras, &globals)
+			.join()
+			.map(|(entity, camera, global)| {
+				let frustum = if camera.enabled { camera.frustum(global) } else { None };
+				(entity, frustum, camera.layers)
+			})
+			.collect();
+
+		for (camera, frustum, mask) in views {
+			let found = match frustum {
+				Some(frustum) => (&*entities, &globals)
+					.par_join()
+					.filter_map(|(entity, global)| {
+						if is_visible(&frustum, mask, global, aabbs.get(entity), spheres.get(entity), layers.get(entity)) {
+							Some(entity)
+						} else {
+							None
+						}
+					})
+					.collect(),
+				None => Vec::new(),
+			};
+			if let Err(err) = visible.insert(camera, VisibleEntities(found)) {
+				error!("Failure storing visible entities of camera {:?}: {}", camera, err);
+			}
+		}
+	}
+}
+
+fn is_visible(
+	frustum: &Frustum,
+	mask: Layers,
+	global: &GlobalTransform,
+	aabb: Option<&Aabb>,
+	sphere: Option<&BoundingSphere>,
+	layers: Option<&Layers>,
+) -> bool {
+	if !layers.cloned().unwrap_or_default().intersects(mask) {
+		return false;
+	}
+	match (sphere, aabb) {
+		(None, None) => false,
+		(Some(sphere), aabb) => {
+			frustum.intersects_sphere(&sphere.world(global))
+				&& aabb.is_none_or(|aabb| frustum.intersects_aabb(&aabb.world(global)))
+		}
+		(None, Some(aabb)) => frustum.intersects_aabb(&aabb.world(global)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use cgmath::{ortho, perspective, Deg, Matrix4, Point3, Vector3};
+	use specs::prelude::{RunNow, World};
+
+	use culling::{Aabb, BoundingSphere, CullingCamera, Layers, VisibleEntities};
+	use transform::GlobalTransform;
+	use super::CullingSystem;
+
+	fn world() -> World {
+		let mut world = World::new();
+		world.register::<GlobalTransform>();
+		world.register::<Aabb>();
+		world.register::<BoundingSphere>();
+		world.register::<Layers>();
+		world.register::<CullingCamera>();
+		world.register::<VisibleEntities>();
+		world
+	}
+
+	fn at(x: f32, y: f32, z: f32) -> GlobalTransform {
+		GlobalTransform(Matrix4::from_translation(Vector3::new(x, y, z)))
+	}
+
+	#[test]
+	fn culls_by_frustum_and_layers() {
+		let mut world = world();
+		let unit = BoundingSphere::new(Point3::new(0., 0., 0.), 1.);
+		let front = world.create_entity().with(at(0., 0., -10.)).with(unit).build();
+		let behind = world.create_entity().with(at(0., 0., 10.)).with(unit).build();
+		// centre outside, but the box reaches into view
+		let edge = world.create_entity()
+			.with(at(12., 0., -10.))
+			.with(Aabb::from_center(Point3::new(0., 0., 0.), Vector3::new(5., 1., 1.)))
+			.build();
+		let hidden = world.create_entity().with(at(0., 0., -10.)).with(unit).with(Layers::layer(3)).build();
+		let unbounded = world.create_entity().with(at(0., 0., -10.)).build();
+
+		let camera = world.create_entity()
+			.with(GlobalTransform::default())
+			.with(CullingCamera::new(perspective(Deg(90.), 1., 0.1, 100.)).with_layers(Layers::DEFAULT))
+			.build();
+		let side_camera = world.create_entity()
+			.with(GlobalTransform(Matrix4::from_angle_y(Deg(180.))))
+			.with(CullingCamera::new(ortho(-5., 5., -5., 5., 0.1, 100.)))
+			.build();
+
+		CullingSystem.run_now(&world.res);
+
+		let visible = world.read_storage::<VisibleEntities>();
+		let seen = visible.get(camera).unwrap();
+		assert_eq!(seen.0, vec![front, edge]);
+		assert!(!seen.contains(behind) && !seen.contains(hidden) && !seen.contains(unbounded));
+		// turned around: only the entity behind the first camera
+		assert_eq!(visible.get(side_camera).unwrap().0, vec![behind]);
+	}
+
+	#[test]
+	fn world_bounds_follow_transform() {
+		let global = GlobalTransform(
+			Matrix4::from_translation(Vector3::new(1., 2., 3.)) * Matrix4::from_angle_z(Deg(90.)) * Matrix4::from_nonuniform_scale(2., 1., 1.),
+		);
+		let aabb = Aabb::new(Point3::new(-1., -1., -1.), Point3::new(1., 1., 1.)).world(&global);
+		assert_ulps_eq!(aabb.min, Point3::new(0., 0., 2.), epsilon = 1e-5);
+		assert_ulps_eq!(aabb.max, Point3::new(2., 4., 4.), epsilon = 1e-5);
+		let sphere = BoundingSphere::new(Point3::new(1., 0., 0.), 1.).world(&global);
+		assert_ulps_eq!(sphere.center, Point3::new(1., 4., 3.), epsilon = 1e-5);
+		assert_ulps_eq!(sphere.radius, 2.);
+	}
+}
@@ -0,0 +1,107 @@
+use cgmath::{Matrix4, SquareMatrix};
+use specs::prelude::{
+	Component,
+	DenseVecStorage,
+	Entity,
+};
+
+use culling::Frustum;
+use transform::GlobalTransform;
+
+/// Bit mask of the render layers an entity is on, or a camera draws.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Layers(pub u32);
+
+impl Layers {
+	/// Layer 0, where entities without a `Layers` component live.
+	pub const DEFAULT: Layers = Layers(1);
+	pub const ALL: Layers = Layers(!0);
+	pub const NONE: Layers = Layers(0);
+
+	/// Mask holding only `layer`, which must be below 32.
+	pub fn layer(layer: u8) -> Layers {
+		assert!(layer < 32, "Layer index {} out of range", layer);
+		Layers(1 << layer)
+	}
+
+	pub fn with(self, layer: u8) -> Layers {
+		Layers(self.0 | Layers::layer(layer).0)
+	}
+
+	pub fn without(self, layer: u8) -> Layers {
+		Layers(self.0 & !Layers::layer(layer).0)
+	}
+
+	pub fn contains(&self, layer: u8) -> bool {
+		self.0 & Layers::layer(layer).0 != 0
+	}
+
+	pub fn intersects(&self, other: Layers) -> bool {
+		self.0 & other.0 != 0
+	}
+}
+
+impl Default for Layers {
+	fn default() -> Self { Layers::DEFAULT }
+}
+
+impl Component for Layers {
+	type Storage = DenseVecStorage<Self>;
+}
+
+/// Marks an entity as a point of view for the `CullingSystem`.
+///
+/// The view is the inverse of the entity's `GlobalTransform`; `projection` is expected to be
+/// kept up to date by whoever owns the camera.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CullingCamera {
+	pub projection: Matrix4<f32>,
+	pub layers: Layers,
+	pub enabled: bool,
+}
+
+impl CullingCamera {
+	pub fn new(projection: Matrix4<f32>) -> Self {
+		CullingCamera { projection, layers: Layers::ALL, enabled: true }
+	}
+
+	pub fn with_layers(mut self, layers: Layers) -> Self {
+		self.layers = layers;
+		self
+	}
+
+	/// Frustum seen from `global`, `None` if its matrix can't be inverted.
+	pub fn frustum(&self, global: &GlobalTransform) -> Option<Frustum> {
+		global.0.invert().map(|view| Frustum::from_matrix(&(self.projection * view)))
+	}
+}
+
+impl Default for CullingCamera {
+	fn default() -> Self { CullingCamera::new(Matrix4::identity()) }
+}
+
+impl Component for CullingCamera {
+	type Storage = DenseVecStorage<Self>;
+}
+
+/// Entities found visible by a camera on the last `CullingSystem` run, by ascending id.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VisibleEntities(pub Vec<Entity>);
+
+impl VisibleEntities {
+	pub fn iter(&self) -> ::std::slice::Iter<'_, Entity> { self.0.iter() }
+
+	pub fn len(&self) -> usize { self.0.len() }
+
+	pub fn is_empty(&self) -> bool { self.0.is_empty() }
+
+	pub fn contains(&self, entity: Entity) -> bool {
+		self.0.binary_search_by_key(&entity.id(), |e| e.id())
+			.map(|index| self.0[index] == entity)
+			.unwrap_or(false)
+	}
+}
+
+impl Component for VisibleEntities {
+	type Storage = DenseVecStorage<Self>;
+}
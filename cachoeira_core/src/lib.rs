@@ -1,6 +1,7 @@
 #[macro_use]
 pub extern crate cgmath;
 pub extern crate shred;
+pub extern crate shrev;
 pub extern crate specs;
 
 #[macro_use]
@@ -10,6 +11,7 @@ extern crate hibitset;
 #[macro_use]
 extern crate log;
 extern crate rayon;
+extern crate ron;
 #[macro_use]
 extern crate serde;
 extern crate specs_hierarchy;
@@ -23,8 +25,14 @@ pub use timing::*;
 use std::sync::Arc;
 
 pub mod timing;
+pub mod bundle;
 pub mod console;
+pub mod culling;
 pub mod engine_system;
+pub mod orientation;
+pub mod scene;
+pub mod spatial;
+pub mod transform;
 
 pub type ThreadPool = Arc<rayon::ThreadPool>;
 
@@ -1,8 +1,17 @@
 use cgmath::{
+    EuclideanSpace,
+    InnerSpace,
+    Matrix,
     Matrix3,
+    Matrix4,
+    Point3,
+    Quaternion,
+    SquareMatrix,
     Vector3,
 };
 
+use transform::Transform;
+
 #[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Orientation {
     pub forward: Vector3<f32>,
@@ -10,6 +19,22 @@ pub struct Orientation {
     pub up: Vector3<f32>,
 }
 
+impl Orientation {
+    /// Directions of a rotation in the given convention, rather than the engine's.
+    pub fn from_matrix_in(mat: Matrix3<f32>, system: &CoordinateSystem) -> Self {
+        Orientation {
+            forward: mat * system.forward.vector(),
+            right: mat * system.right.vector(),
+            up: mat * system.up.vector(),
+        }
+    }
+
+    /// The unrotated directions of a convention.
+    pub fn of(system: &CoordinateSystem) -> Self {
+        Orientation::from_matrix_in(Matrix3::identity(), system)
+    }
+}
+
 impl From<Matrix3<f32>> for Orientation {
     fn from(mat: Matrix3<f32>) -> Self {
         Orientation { forward: -mat.z, right: mat.x, up: mat.y }
@@ -24,4 +49,224 @@ impl Default for Orientation {
             up: Vector3::unit_y()
         }
     }
-}
\ No newline at end of file
+}
+
+/// A signed coordinate axis.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum Axis {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+impl Axis {
+    pub fn vector(self) -> Vector3<f32> {
+        match self {
+            Axis::PosX => Vector3::unit_x(),
+            Axis::NegX => -Vector3::unit_x(),
+            Axis::PosY => Vector3::unit_y(),
+            Axis::NegY => -Vector3::unit_y(),
+            Axis::PosZ => Vector3::unit_z(),
+            Axis::NegZ => -Vector3::unit_z(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum UpAxis {
+    Y,
+    Z,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum Handedness {
+    Right,
+    Left,
+}
+
+/// Which axes a convention calls right, up and forward.
+///
+/// The engine itself is right-handed, Y-up and looks down -Z; the default is that convention.
+/// Inserted as a resource, it describes the world the application is authored in.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct CoordinateSystem {
+    pub right: Axis,
+    pub up: Axis,
+    pub forward: Axis,
+}
+
+impl CoordinateSystem {
+    /// The engine's convention; also OpenGL and glTF.
+    pub const Y_UP_RIGHT_HANDED: CoordinateSystem = CoordinateSystem { right: Axis::PosX, up: Axis::PosY, forward: Axis::NegZ };
+    /// Unity and Direct3D.
+    pub const Y_UP_LEFT_HANDED: CoordinateSystem = CoordinateSystem { right: Axis::PosX, up: Axis::PosY, forward: Axis::PosZ };
+    /// Blender and 3ds Max, looking down +Y.
+    pub const Z_UP_RIGHT_HANDED: CoordinateSystem = CoordinateSystem { right: Axis::PosX, up: Axis::PosZ, forward: Axis::PosY };
+    /// Unreal, looking down +X.
+    pub const Z_UP_LEFT_HANDED: CoordinateSystem = CoordinateSystem { right: Axis::PosY, up: Axis::PosZ, forward: Axis::PosX };
+
+    pub fn new(up: UpAxis, handedness: Handedness) -> Self {
+        match (up, handedness) {
+            (UpAxis::Y, Handedness::Right) => CoordinateSystem::Y_UP_RIGHT_HANDED,
+            (UpAxis::Y, Handedness::Left) => CoordinateSystem::Y_UP_LEFT_HANDED,
+            (UpAxis::Z, Handedness::Right) => CoordinateSystem::Z_UP_RIGHT_HANDED,
+            (UpAxis::Z, Handedness::Left) => CoordinateSystem::Z_UP_LEFT_HANDED,
+        }
+    }
+
+    /// Checks that the three axes are distinct.
+    pub fn is_valid(&self) -> bool {
+        self.right.vector().cross(self.forward.vector()).magnitude2() > 0.5
+            && self.right.vector().dot(self.up.vector()) == 0.
+            && self.up.vector().dot(self.forward.vector()) == 0.
+    }
+
+    /// Right-handed when looking down `forward` with `up` up puts `right` on the right, the
+    /// way -Z does in OpenGL.
+    pub fn handedness(&self) -> Handedness {
+        if self.right.vector().cross(self.forward.vector()).dot(self.up.vector()) > 0. {
+            Handedness::Right
+        } else {
+            Handedness::Left
+        }
+    }
+
+    pub fn up_axis(&self) -> Option<UpAxis> {
+        match self.up {
+            Axis::PosY => Some(UpAxis::Y),
+            Axis::PosZ => Some(UpAxis::Z),
+            _ => None,
+        }
+    }
+
+    /// Columns are the right, up and forward axes.
+    pub fn basis(&self) -> Matrix3<f32> {
+        Matrix3::from_cols(self.right.vector(), self.up.vector(), self.forward.vector())
+    }
+
+    /// Conversion of data authored in this convention into `target`.
+    pub fn conversion_to(&self, target: &CoordinateSystem) -> Conversion {
+        debug_assert!(self.is_valid() && target.is_valid(), "Coordinate system axes must be distinct");
+        let matrix = target.basis() * self.basis().transpose();
+        Conversion { matrix, determinant: matrix.determinant() }
+    }
+
+    /// Conversion of data authored in this convention into the engine's.
+    pub fn to_engine(&self) -> Conversion {
+        self.conversion_to(&CoordinateSystem::Y_UP_RIGHT_HANDED)
+    }
+
+    /// Conversion of engine data into this convention, e.g. for export.
+    pub fn from_engine(&self) -> Conversion {
+        CoordinateSystem::Y_UP_RIGHT_HANDED.conversion_to(self)
+    }
+}
+
+impl Default for CoordinateSystem {
+    fn default() -> Self { CoordinateSystem::Y_UP_RIGHT_HANDED }
+}
+
+/// A change of basis between two conventions; a signed permutation, so conversions are exact.
+///
+/// Rotations stay proper rotations and scales stay positive even across a handedness flip.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Conversion {
+    matrix: Matrix3<f32>,
+    determinant: f32,
+}
+
+impl Conversion {
+    pub fn matrix(&self) -> Matrix3<f32> { self.matrix }
+
+    /// Whether the conversion mirrors, i.e. the conventions differ in handedness.
+    pub fn flips_handedness(&self) -> bool { self.determinant < 0. }
+
+    pub fn inverse(&self) -> Conversion {
+        Conversion { matrix: self.matrix.transpose(), determinant: self.determinant }
+    }
+
+    pub fn vector(&self, vector: Vector3<f32>) -> Vector3<f32> {
+        self.matrix * vector
+    }
+
+    pub fn point(&self, point: Point3<f32>) -> Point3<f32> {
+        Point3::from_vec(self.matrix * point.to_vec())
+    }
+
+    pub fn quaternion(&self, rotation: Quaternion<f32>) -> Quaternion<f32> {
+        // the rotation axis is a pseudovector, so it flips along with a mirror
+        Quaternion::from_sv(rotation.s, self.matrix * rotation.v * self.determinant)
+    }
+
+    /// Converts a linear map, such as a rotation or scale matrix.
+    pub fn matrix3(&self, matrix: &Matrix3<f32>) -> Matrix3<f32> {
+        self.matrix * *matrix * self.matrix.transpose()
+    }
+
+    /// Converts an affine transform.
+    pub fn matrix4(&self, matrix: &Matrix4<f32>) -> Matrix4<f32> {
+        let change = Matrix4::from(self.matrix);
+        change * *matrix * change.transpose()
+    }
+
+    /// Per-axis scale, which follows its axis through the permutation.
+    pub fn scale(&self, scale: Vector3<f32>) -> Vector3<f32> {
+        let abs = self.matrix * scale;
+        Vector3::new(abs.x.abs(), abs.y.abs(), abs.z.abs())
+    }
+
+    pub fn transform(&self, transform: &Transform) -> Transform {
+        Transform {
+            translation: self.vector(transform.translation),
+            rotation: self.quaternion(transform.rotation),
+            scale: self.scale(transform.scale),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, InnerSpace, Matrix3, Point3, Quaternion, Rotation3, Vector3};
+
+    use transform::Transform;
+    use super::{CoordinateSystem, Handedness, Orientation, UpAxis};
+
+    #[test]
+    fn conventions() {
+        assert_eq!(CoordinateSystem::new(UpAxis::Z, Handedness::Right).handedness(), Handedness::Right);
+        assert_eq!(CoordinateSystem::Y_UP_LEFT_HANDED.handedness(), Handedness::Left);
+        assert_eq!(CoordinateSystem::Z_UP_LEFT_HANDED.handedness(), Handedness::Left);
+        assert_eq!(Orientation::of(&CoordinateSystem::default()), Orientation::default());
+
+        let blender = CoordinateSystem::Z_UP_RIGHT_HANDED.to_engine();
+        assert!(!blender.flips_handedness());
+        assert_eq!(blender.point(Point3::new(1., 2., 3.)), Point3::new(1., 3., -2.));
+        let unity = CoordinateSystem::Y_UP_LEFT_HANDED.to_engine();
+        assert!(unity.flips_handedness());
+        assert_eq!(unity.vector(Vector3::unit_z()), -Vector3::unit_z());
+    }
+
+    #[test]
+    fn transforms_match_their_matrices() {
+        let transform = Transform {
+            translation: Vector3::new(1., 2., 3.),
+            rotation: Quaternion::from_axis_angle(Vector3::new(1., 2., 0.5).normalize(), Deg(70.)),
+            scale: Vector3::new(1., 2., 3.),
+        };
+        for system in &[CoordinateSystem::Z_UP_RIGHT_HANDED, CoordinateSystem::Y_UP_LEFT_HANDED, CoordinateSystem::Z_UP_LEFT_HANDED] {
+            let conversion = system.to_engine();
+            let converted = conversion.transform(&transform);
+            assert_ulps_eq!(converted.matrix(), conversion.matrix4(&transform.matrix()), epsilon = 1e-5);
+            assert_ulps_eq!(
+                Matrix3::from(converted.rotation),
+                conversion.matrix3(&Matrix3::from(transform.rotation)),
+                epsilon = 1e-5
+            );
+            let back = conversion.inverse().transform(&converted);
+            assert_ulps_eq!(back.matrix(), transform.matrix(), epsilon = 1e-5);
+        }
+    }
+}
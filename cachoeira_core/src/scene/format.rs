@@ -0,0 +1,113 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use ron;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use specs::prelude::{
+	Component,
+	DenseVecStorage,
+	Entity,
+	World,
+};
+
+use scene::{ErrorKind, Result, ResultExt};
+use transform::{Transform, Transform2D};
+
+/// Name an entity was spawned under, used to write scenes back out.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Named(pub String);
+
+impl Component for Named {
+	type Storage = DenseVecStorage<Self>;
+}
+
+/// Components a scene carries beyond the transform hierarchy.
+///
+/// Implemented by the application on a struct of optional components, so each project can
+/// describe its own entities without the core knowing about them.
+pub trait SceneComponents: Clone + Default + Serialize + DeserializeOwned {
+	/// Adds the described components to a freshly created entity.
+	fn insert_into(&self, world: &World, entity: Entity) -> Result<()>;
+	/// Reads the components back off an entity when saving.
+	fn extract(world: &World, entity: Entity) -> Self;
+}
+
+impl SceneComponents for () {
+	fn insert_into(&self, _world: &World, _entity: Entity) -> Result<()> { Ok(()) }
+	fn extract(_world: &World, _entity: Entity) -> Self {}
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(bound(deserialize = "C: SceneComponents"))]
+pub struct SceneEntity<C = ()> {
+	pub name: String,
+	/// local name of the parent within the same scene
+	#[serde(default)]
+	pub parent: Option<String>,
+	#[serde(default)]
+	pub transform: Option<Transform>,
+	#[serde(default)]
+	pub transform_2d: Option<Transform2D>,
+	#[serde(default)]
+	pub components: C,
+}
+
+impl<C: SceneComponents> SceneEntity<C> {
+	pub fn new<S: Into<String>>(name: S) -> Self {
+		SceneEntity {
+			name: name.into(),
+			parent: None,
+			transform: None,
+			transform_2d: None,
+			components: C::default(),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(bound(deserialize = "C: SceneComponents"))]
+pub struct Scene<C = ()> {
+	pub entities: Vec<SceneEntity<C>>,
+}
+
+impl<C: SceneComponents> Default for Scene<C> {
+	fn default() -> Self {
+		Scene { entities: Vec::new() }
+	}
+}
+
+impl<C: SceneComponents> Scene<C> {
+	pub fn from_ron_str(source: &str) -> Result<Self> {
+		Ok(ron::de::from_str(source)?)
+	}
+
+	pub fn to_ron_string(&self) -> Result<String> {
+		Ok(ron::ser::to_string_pretty(self, Default::default())?)
+	}
+
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+		let path = path.as_ref();
+		let read = || -> Result<Self> {
+			let mut source = String::new();
+			File::open(path)?.read_to_string(&mut source)?;
+			Self::from_ron_str(&source)
+		};
+		read().chain_err(|| ErrorKind::File(path.display().to_string()))
+	}
+
+	pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+		let path = path.as_ref();
+		let write = || -> Result<()> {
+			let source = self.to_ron_string()?;
+			File::create(path)?.write_all(source.as_bytes())?;
+			Ok(())
+		};
+		write().chain_err(|| ErrorKind::File(path.display().to_string()))
+	}
+
+	pub fn find(&self, name: &str) -> Option<&SceneEntity<C>> {
+		self.entities.iter().find(|entity| entity.name == name)
+	}
+}
@@ -0,0 +1,256 @@
+use std::collections::{HashMap, HashSet};
+
+use specs::prelude::{Entity, World};
+
+use scene::{ErrorKind, Named, Result, Scene, SceneComponents};
+use transform::{
+	GlobalTransform,
+	GlobalTransform2D,
+	Parent,
+	Transform,
+	Transform2D,
+};
+
+/// Per-instance changes applied when instantiating a scene as a prefab.
+#[derive(Clone, Debug)]
+pub struct Overrides<C = ()> {
+	/// existing entity the scene roots get attached to
+	pub parent: Option<Entity>,
+	/// local transforms replacing the scene's, by entity name
+	pub transforms: HashMap<String, Transform>,
+	/// local 2D transforms replacing the scene's, by entity name
+	pub transforms_2d: HashMap<String, Transform2D>,
+	/// components replacing the scene's, by entity name
+	pub components: HashMap<String, C>,
+}
+
+impl<C> Default for Overrides<C> {
+	fn default() -> Self {
+		Overrides {
+			parent: None,
+			transforms: HashMap::new(),
+			transforms_2d: HashMap::new(),
+			components: HashMap::new(),
+		}
+	}
+}
+
+impl<C> Overrides<C> {
+	pub fn new() -> Self {
+		Default::default()
+	}
+
+	pub fn with_parent(mut self, parent: Entity) -> Self {
+		self.parent = Some(parent);
+		self
+	}
+
+	pub fn with_transform<S: Into<String>>(mut self, name: S, transform: Transform) -> Self {
+		self.transforms.insert(name.into(), transform);
+		self
+	}
+
+	pub fn with_transform_2d<S: Into<String>>(mut self, name: S, transform: Transform2D) -> Self {
+		self.transforms_2d.insert(name.into(), transform);
+		self
+	}
+
+	pub fn with_components<S: Into<String>>(mut self, name: S, components: C) -> Self {
+		self.components.insert(name.into(), components);
+		self
+	}
+}
+
+/// Entities created by one instantiation of a scene.
+#[derive(Clone, Debug, Default)]
+pub struct SceneInstance {
+	entities: HashMap<String, Entity>,
+	roots: Vec<Entity>,
+}
+
+impl SceneInstance {
+	pub fn get(&self, name: &str) -> Option<Entity> {
+		self.entities.get(name).cloned()
+	}
+
+	/// Entities without a parent inside the scene.
+	pub fn roots(&self) -> &[Entity] {
+		&self.roots
+	}
+
+	pub fn entities<'a>(&'a self) -> impl Iterator<Item = (&'a str, Entity)> + 'a {
+		self.entities.iter().map(|(name, entity)| (name.as_str(), *entity))
+	}
+}
+
+impl<C: SceneComponents> Scene<C> {
+	/// Checks names are unique and parent links resolve to a tree.
+	pub fn validate(&self) -> Result<()> {
+		let mut parents = HashMap::new();
+		for entity in &self.entities {
+			if parents.insert(entity.name.as_str(), entity.parent.as_ref()).is_some() {
+				bail!(ErrorKind::DuplicateName(entity.name.clone()));
+			}
+		}
+		for entity in &self.entities {
+			let mut visited = HashSet::new();
+			let mut current = entity.name.as_str();
+			while let Some(parent) = parents[current] {
+				if !parents.contains_key(parent.as_str()) {
+					bail!(ErrorKind::UnknownParent(current.to_string(), parent.clone()));
+				}
+				if !visited.insert(current) {
+					bail!(ErrorKind::ParentCycle(entity.name.clone()));
+				}
+				current = parent;
+			}
+		}
+		Ok(())
+	}
+
+	pub fn instantiate(&self, world: &mut World) -> Result<SceneInstance> {
+		self.instantiate_with(world, &Overrides::default())
+	}
+
+	/// Creates a fresh copy of every scene entity; can be called any number of times.
+	///
+	/// Fails without creating anything if an override names an entity the scene lacks.
+	pub fn instantiate_with(&self, world: &mut World, overrides: &Overrides<C>) -> Result<SceneInstance> {
+		self.validate()?;
+		let overridden = overrides.transforms.keys()
+			.chain(overrides.transforms_2d.keys())
+			.chain(overrides.components.keys());
+		for name in overridden {
+			if self.find(name).is_none() {
+				bail!(ErrorKind::UnknownOverride(name.clone()));
+			}
+		}
+		world.register::<Named>();
+		world.register::<Parent>();
+		world.register::<Transform>();
+		world.register::<Transform2D>();
+		world.register::<GlobalTransform>();
+		world.register::<GlobalTransform2D>();
+
+		let mut instance = SceneInstance::default();
+		for scene_entity in &self.entities {
+			let mut builder = world.create_entity().with(Named(scene_entity.name.clone()));
+			let transform = overrides.transforms.get(&scene_entity.name)
+				.or(scene_entity.transform.as_ref());
+			if let Some(transform) = transform {
+				builder = builder.with(transform.clone()).with(GlobalTransform::default());
+			}
+			let transform_2d = overrides.transforms_2d.get(&scene_entity.name)
+				.or(scene_entity.transform_2d.as_ref());
+			if let Some(transform_2d) = transform_2d {
+				builder = builder.with(transform_2d.clone()).with(GlobalTransform2D::default());
+			}
+			instance.entities.insert(scene_entity.name.clone(), builder.build());
+		}
+
+		{
+			let mut parents = world.write_storage::<Parent>();
+			for scene_entity in &self.entities {
+				let entity = instance.entities[&scene_entity.name];
+				let parent = match scene_entity.parent {
+					Some(ref parent) => Some(instance.entities[parent]),
+					None => {
+						instance.roots.push(entity);
+						overrides.parent
+					}
+				};
+				if let Some(parent) = parent {
+					parents.insert(entity, Parent { entity: parent })?;
+				}
+			}
+		}
+
+		for scene_entity in &self.entities {
+			let entity = instance.entities[&scene_entity.name];
+			overrides.components.get(&scene_entity.name)
+				.unwrap_or(&scene_entity.components)
+				.insert_into(world, entity)?;
+		}
+
+		Ok(instance)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use cgmath::{Rad, Vector2, Vector3};
+	use specs::prelude::{Join, World};
+
+	use scene::{ErrorKind, Overrides, Scene};
+	use transform::{Parent, Transform, Transform2D};
+
+	const RIG: &str = r#"(
+		entities: [
+			(name: "light", parent: Some("rig"), transform: Some((translation: (x: 1.0, y: 0.0, z: 0.0)))),
+			(name: "rig", transform: Some((translation: (x: 0.0, y: 2.0, z: 0.0)))),
+		],
+	)"#;
+
+	#[test]
+	fn instantiate_twice_with_overrides() {
+		let scene: Scene = Scene::from_ron_str(RIG).unwrap();
+		let mut world = World::new();
+
+		let first = scene.instantiate(&mut world).unwrap();
+		let moved = Transform { translation: Vector3::new(5., 0., 0.), ..Default::default() };
+		let second = scene.instantiate_with(&mut world, &Overrides::new().with_transform("rig", moved.clone())).unwrap();
+
+		assert_ne!(first.get("rig"), second.get("rig"));
+		assert_eq!(second.roots(), &[second.get("rig").unwrap()]);
+
+		let parents = world.read_storage::<Parent>();
+		let locals = world.read_storage::<Transform>();
+		assert_eq!(parents.get(second.get("light").unwrap()).unwrap().entity, second.get("rig").unwrap());
+		assert_eq!(locals.get(second.get("rig").unwrap()), Some(&moved));
+		assert_eq!(locals.get(first.get("rig").unwrap()).unwrap().translation, Vector3::new(0., 2., 0.));
+	}
+
+	#[test]
+	fn overrides_transform_2d() {
+		let scene: Scene = Scene::from_ron_str(
+			r#"(entities: [(name: "badge", transform_2d: Some((position: (x: 1.0, y: 1.0))))])"#
+		).unwrap();
+		let mut world = World::new();
+		let moved = Transform2D::new(Vector2::new(-3., 4.), Rad(0.5), Vector2::new(2., 2.));
+		let instance = scene.instantiate_with(&mut world, &Overrides::new().with_transform_2d("badge", moved.clone())).unwrap();
+		let original = scene.instantiate(&mut world).unwrap();
+
+		let locals = world.read_storage::<Transform2D>();
+		assert_eq!(locals.get(instance.get("badge").unwrap()), Some(&moved));
+		assert_eq!(locals.get(original.get("badge").unwrap()).unwrap().position, Vector2::new(1., 1.));
+	}
+
+	#[test]
+	fn rejects_unknown_overrides() {
+		let scene: Scene = Scene::from_ron_str(RIG).unwrap();
+		let mut world = World::new();
+		let overrides = Overrides::new().with_transform_2d("lamp", Transform2D::default());
+		match *scene.instantiate_with(&mut world, &overrides).unwrap_err().kind() {
+			ErrorKind::UnknownOverride(ref name) => assert_eq!(name, "lamp"),
+			ref other => panic!("unexpected error {:?}", other),
+		}
+		assert_eq!(world.entities().join().count(), 0);
+	}
+
+	#[test]
+	fn rejects_broken_hierarchies() {
+		let unknown: Scene = Scene::from_ron_str(r#"(entities: [(name: "a", parent: Some("b"))])"#).unwrap();
+		match *unknown.validate().unwrap_err().kind() {
+			ErrorKind::UnknownParent(..) => (),
+			ref other => panic!("unexpected error {:?}", other),
+		}
+
+		let cycle: Scene = Scene::from_ron_str(
+			r#"(entities: [(name: "a", parent: Some("b")), (name: "b", parent: Some("a"))])"#
+		).unwrap();
+		match *cycle.validate().unwrap_err().kind() {
+			ErrorKind::ParentCycle(..) => (),
+			ref other => panic!("unexpected error {:?}", other),
+		}
+	}
+}
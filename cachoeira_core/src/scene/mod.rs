@@ -0,0 +1,51 @@
+//! RON scene files describing `Transform`/`Parent` hierarchies by local name.
+//!
+//! A scene is also a prefab: every `instantiate` call creates a fresh set of entities.
+//!
+//! ```ron
+//! (
+//!     entities: [
+//!         (name: "rig", transform: Some((translation: (x: 0.0, y: 2.0, z: 0.0)))),
+//!         (name: "light", parent: Some("rig"), transform: Some((translation: (x: 1.0, y: 0.0, z: 0.0)))),
+//!     ],
+//! )
+//! ```
+
+pub use self::format::{Named, Scene, SceneComponents, SceneEntity};
+pub use self::load::{Overrides, SceneInstance};
+
+mod format;
+mod load;
+mod save;
+
+error_chain! {
+	foreign_links {
+		Io(::std::io::Error);
+		Deserialize(::ron::de::Error);
+		Serialize(::ron::ser::Error);
+		Storage(::specs::error::Error);
+	}
+
+	errors {
+		File(path: String) {
+			description("scene file error")
+			display("In scene file '{}'", path)
+		}
+		DuplicateName(name: String) {
+			description("duplicate scene entity name")
+			display("Scene entity name '{}' is used more than once", name)
+		}
+		UnknownParent(name: String, parent: String) {
+			description("unknown scene parent")
+			display("Scene entity '{}' refers to unknown parent '{}'", name, parent)
+		}
+		ParentCycle(name: String) {
+			description("cyclic scene hierarchy")
+			display("Scene entity '{}' is its own ancestor", name)
+		}
+		UnknownOverride(name: String) {
+			description("override of an unknown scene entity")
+			display("Override names unknown scene entity '{}'", name)
+		}
+	}
+}
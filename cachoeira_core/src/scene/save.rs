@@ -0,0 +1,153 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use specs::prelude::{Entity, World};
+
+use scene::{Named, Scene, SceneComponents, SceneEntity};
+use transform::{ParentHierarchy, Transform, Transform2D};
+
+impl<C: SceneComponents> Scene<C> {
+	/// Captures `root` and all of its descendants.
+	///
+	/// `root` becomes the only scene root and keeps its local transform. Entities are named after
+	/// their `Named` component, falling back to their id, with suffixes to keep names unique.
+	pub fn from_subtree(world: &World, root: Entity) -> Scene<C> {
+		let hierarchy = world.read_resource::<ParentHierarchy>();
+		let names = world.read_storage::<Named>();
+		let locals = world.read_storage::<Transform>();
+		let locals_2d = world.read_storage::<Transform2D>();
+
+		let mut used = HashSet::new();
+		let mut scene_names: HashMap<Entity, String> = HashMap::new();
+		let mut queue: VecDeque<(Entity, Option<Entity>)> = VecDeque::new();
+		queue.push_back((root, None));
+		let mut scene = Scene::default();
+
+		while let Some((entity, parent)) = queue.pop_front() {
+			let base = names.get(entity)
+				.map(|name| name.0.clone())
+				.unwrap_or_else(|| format!("entity_{}", entity.id()));
+			let mut name = base.clone();
+			let mut suffix = 1;
+			while !used.insert(name.clone()) {
+				suffix += 1;
+				name = format!("{}_{}", base, suffix);
+			}
+
+			scene.entities.push(SceneEntity {
+				name: name.clone(),
+				parent: parent.map(|parent| scene_names[&parent].clone()),
+				transform: locals.get(entity).cloned(),
+				transform_2d: locals_2d.get(entity).cloned(),
+				components: C::extract(world, entity),
+			});
+			scene_names.insert(entity, name);
+			queue.extend(hierarchy.children(entity).unwrap_or(&[]).iter().map(|child| (*child, Some(entity))));
+		}
+
+		scene
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::env;
+
+	use cgmath::{Deg, Quaternion, Rad, Rotation3, Vector2, Vector3};
+	use specs::prelude::{DispatcherBuilder, Entity, World};
+	use specs_hierarchy::HierarchySystem;
+
+	use scene::{Named, Scene};
+	use transform::{Parent, Transform, Transform2D};
+
+	// an unnamed `outside` root above the captured `rig`, with 3D and 2D descendants
+	fn rig(world: &mut World) -> (Entity, Entity) {
+		let mut dispatcher = DispatcherBuilder::new()
+			.with(HierarchySystem::<Parent>::new(), "parent_hierarchy_system", &[])
+			.build();
+		dispatcher.setup(&mut world.res);
+		world.register::<Named>();
+		world.register::<Transform>();
+		world.register::<Transform2D>();
+
+		let outside = world.create_entity().with(Transform::default()).build();
+		let rig = world.create_entity()
+			.with(Named("rig".to_owned()))
+			.with(Parent { entity: outside })
+			.with(Transform {
+				translation: Vector3::new(0., 2., 0.),
+				rotation: Quaternion::from_angle_y(Deg(30.)),
+				..Default::default()
+			})
+			.build();
+		world.create_entity()
+			.with(Named("light".to_owned()))
+			.with(Parent { entity: rig })
+			.with(Transform { translation: Vector3::new(1., 0., 0.), ..Default::default() })
+			.build();
+		let label = world.create_entity()
+			.with(Named("light".to_owned()))
+			.with(Parent { entity: rig })
+			.with(Transform2D::new(Vector2::new(3., -1.), Rad(0.5), Vector2::new(2., 1.)))
+			.build();
+		world.create_entity()
+			.with(Parent { entity: label })
+			.with(Transform2D::new(Vector2::new(0., 1.), Rad(0.), Vector2::new(1., 1.)))
+			.build();
+		world.create_entity().with(Named("stray".to_owned())).with(Parent { entity: outside }).build();
+		dispatcher.dispatch(&world.res);
+		world.maintain();
+		(rig, label)
+	}
+
+	#[test]
+	fn captures_subtree() {
+		let mut world = World::new();
+		let (rig, label) = rig(&mut world);
+		let scene: Scene = Scene::from_subtree(&world, rig);
+
+		let mut names: Vec<&str> = scene.entities.iter().map(|entity| entity.name.as_str()).collect();
+		names.sort();
+		let unnamed = scene.entities.iter()
+			.find(|entity| entity.name.starts_with("entity_"))
+			.unwrap();
+		assert_eq!(names, vec![unnamed.name.as_str(), "light", "light_2", "rig"]);
+
+		// the root is cut loose from whatever held it, and comes first
+		assert_eq!(scene.entities[0].name, "rig");
+		assert_eq!(scene.entities[0].parent, None);
+		assert_eq!(world.read_storage::<Transform>().get(rig), scene.entities[0].transform.as_ref());
+		let labels = world.read_storage::<Transform2D>();
+		let saved_label = scene.entities.iter()
+			.find(|entity| entity.transform_2d.as_ref() == labels.get(label))
+			.unwrap();
+		assert_eq!(saved_label.parent, Some("rig".to_owned()));
+		assert_eq!(unnamed.parent, Some(saved_label.name.clone()));
+		assert!(scene.find("stray").is_none());
+	}
+
+	#[test]
+	fn round_trips_through_a_file() {
+		let mut world = World::new();
+		let (rig, _) = rig(&mut world);
+		let scene: Scene = Scene::from_subtree(&world, rig);
+		let path = env::temp_dir().join("cachoeira_scene_round_trip.ron");
+		scene.save(&path).unwrap();
+		let loaded: Scene = Scene::load(&path).unwrap();
+
+		let mut copy = World::new();
+		let instance = loaded.instantiate(&mut copy).unwrap();
+		let (parents, locals, locals_2d) = (
+			copy.read_storage::<Parent>(),
+			copy.read_storage::<Transform>(),
+			copy.read_storage::<Transform2D>(),
+		);
+		assert_eq!(instance.roots(), &[instance.get("rig").unwrap()]);
+		for saved in &scene.entities {
+			let entity = instance.get(&saved.name).unwrap();
+			assert_eq!(locals.get(entity), saved.transform.as_ref(), "{}", saved.name);
+			assert_eq!(locals_2d.get(entity), saved.transform_2d.as_ref(), "{}", saved.name);
+			let parent = parents.get(entity).map(|parent| parent.entity);
+			assert_eq!(parent, saved.parent.as_ref().and_then(|name| instance.get(name)), "{}", saved.name);
+		}
+	}
+}
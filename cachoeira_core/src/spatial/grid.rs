@@ -0,0 +1,531 @@
+use std::cmp::Ordering;
+
+use cgmath::{
+	InnerSpace,
+	Point2, Point3,
+	Vector2, Vector3,
+};
+use fnv::FnvHashMap;
+use rayon::prelude::*;
+use specs::prelude::Entity;
+use specs::world::Index;
+
+use ThreadPool;
+
+/// Integer coordinates of a grid cell; 2D grids keep `z` at zero.
+pub type CellKey = [i32; 3];
+
+/// Point types a `SpatialGrid` can index.
+pub trait SpatialPoint: Copy + Send + Sync + 'static {
+	type Vector: Copy + Send + Sync;
+	const DIMENSIONS: usize;
+
+	fn to_point3(&self) -> Point3<f32>;
+	fn to_vector3(vector: Self::Vector) -> Vector3<f32>;
+}
+
+impl SpatialPoint for Point3<f32> {
+	type Vector = Vector3<f32>;
+	const DIMENSIONS: usize = 3;
+
+	fn to_point3(&self) -> Point3<f32> { *self }
+	fn to_vector3(vector: Vector3<f32>) -> Vector3<f32> { vector }
+}
+
+impl SpatialPoint for Point2<f32> {
+	type Vector = Vector2<f32>;
+	const DIMENSIONS: usize = 2;
+
+	fn to_point3(&self) -> Point3<f32> { Point3::new(self.x, self.y, 0.) }
+	fn to_vector3(vector: Vector2<f32>) -> Vector3<f32> { vector.extend(0.) }
+}
+
+/// Uniform hash grid over entity positions.
+///
+/// Cells are hashed, so the indexed space is unbounded; pick a cell size close to the typical
+/// query radius. Kept up to date by the `SpatialIndexSystem`.
+#[derive(Clone, Debug)]
+pub struct SpatialGrid<P: SpatialPoint> {
+	cell_size: f32,
+	cells: FnvHashMap<CellKey, Vec<(Entity, Point3<f32>)>>,
+	// keyed by id, so entities can be dropped after their generation moved on
+	entries: FnvHashMap<Index, CellKey>,
+	// occupied cell range, only ever grows; bounds nearest-neighbour searches
+	extent: Option<(CellKey, CellKey)>,
+	_marker: ::std::marker::PhantomData<P>,
+}
+
+pub type SpatialGrid2 = SpatialGrid<Point2<f32>>;
+pub type SpatialGrid3 = SpatialGrid<Point3<f32>>;
+
+impl<P: SpatialPoint> Default for SpatialGrid<P> {
+	fn default() -> Self {
+		SpatialGrid::new(1.)
+	}
+}
+
+impl<P: SpatialPoint> SpatialGrid<P> {
+	pub fn new(cell_size: f32) -> Self {
+		assert!(cell_size > 0., "Spatial grid cell size must be positive");
+		SpatialGrid {
+			cell_size,
+			cells: FnvHashMap::default(),
+			entries: FnvHashMap::default(),
+			extent: None,
+			_marker: ::std::marker::PhantomData,
+		}
+	}
+
+	pub fn cell_size(&self) -> f32 { self.cell_size }
+
+	pub fn len(&self) -> usize { self.entries.len() }
+
+	pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+	pub fn contains(&self, entity: Entity) -> bool { self.entries.contains_key(&entity.id()) }
+
+	pub fn clear(&mut self) {
+		self.cells.clear();
+		self.entries.clear();
+		self.extent = None;
+	}
+
+	/// Inserts `entity`, or moves it if it is already indexed.
+	pub fn insert(&mut self, entity: Entity, position: P) {
+		let position = position.to_point3();
+		let key = self.cell_of(position);
+		match self.entries.insert(entity.id(), key) {
+			Some(old_key) if old_key == key => {
+				if let Some(entry) = self.cells.get_mut(&key).and_then(|cell| cell.iter_mut().find(|e| e.0.id() == entity.id())) {
+					*entry = (entity, position);
+				}
+				return;
+			}
+			Some(old_key) => self.remove_from_cell(old_key, entity.id()),
+			None => (),
+		}
+		self.cells.entry(key).or_default().push((entity, position));
+		self.extent = Some(match self.extent {
+			Some((min, max)) => (
+				[min[0].min(key[0]), min[1].min(key[1]), min[2].min(key[2])],
+				[max[0].max(key[0]), max[1].max(key[1]), max[2].max(key[2])],
+			),
+			None => (key, key),
+		});
+	}
+
+	pub fn remove(&mut self, entity: Entity) -> bool {
+		self.remove_index(entity.id())
+	}
+
+	/// Removes whichever entity is indexed under `id`, whatever its generation.
+	pub fn remove_index(&mut self, id: Index) -> bool {
+		match self.entries.remove(&id) {
+			Some(key) => {
+				self.remove_from_cell(key, id);
+				true
+			}
+			None => false,
+		}
+	}
+
+	pub fn position(&self, entity: Entity) -> Option<Point3<f32>> {
+		let key = self.entries.get(&entity.id())?;
+		self.cells[key].iter().find(|e| e.0 == entity).map(|e| e.1)
+	}
+
+	/// Calls `visit` with every entity within `radius` of `center` and its squared distance.
+	pub fn for_each_in_radius<F>(&self, center: P, radius: f32, mut visit: F)
+	where
+		F: FnMut(Entity, f32),
+	{
+		let center = center.to_point3();
+		let radius2 = radius * radius;
+		let offset = Vector3::new(radius, radius, radius);
+		self.for_each_cell(center - offset, center + offset, |cell| {
+			for &(entity, position) in cell {
+				let distance2 = (position - center).magnitude2();
+				if distance2 <= radius2 {
+					visit(entity, distance2);
+				}
+			}
+		});
+	}
+
+	pub fn query_radius(&self, center: P, radius: f32) -> Vec<Entity> {
+		let mut found = Vec::new();
+		self.for_each_in_radius(center, radius, |entity, _| found.push(entity));
+		found
+	}
+
+	/// Entities inside the axis-aligned box spanned by `min` and `max`.
+	pub fn query_box(&self, min: P, max: P) -> Vec<Entity> {
+		let (min, max) = (min.to_point3(), max.to_point3());
+		let mut found = Vec::new();
+		self.for_each_cell(min, max, |cell| {
+			found.extend(cell.iter()
+				.filter(|&&(_, p)| {
+					p.x >= min.x && p.x <= max.x
+					&& p.y >= min.y && p.y <= max.y
+					&& (P::DIMENSIONS == 2 || (p.z >= min.z && p.z <= max.z))
+				})
+				.map(|&(entity, _)| entity));
+		});
+		found
+	}
+
+	/// Up to `k` closest entities to `point`, nearest first, with their distances.
+	pub fn nearest(&self, point: P, k: usize) -> Vec<(Entity, f32)> {
+		let (min, max) = match self.extent {
+			Some(extent) if k > 0 => extent,
+			_ => return Vec::new(),
+		};
+		let point = point.to_point3();
+		let center = self.cell_of(point);
+		// rings needed before every occupied cell has been visited
+		let max_ring = (0..3)
+			.map(|axis| (center[axis] - min[axis]).abs().max((max[axis] - center[axis]).abs()))
+			.max()
+			.unwrap_or(0);
+
+		let mut found: Vec<(Entity, f32)> = Vec::new();
+		for ring in 0..max_ring + 1 {
+			self.for_each_ring_cell(center, ring, |cell| {
+				found.extend(cell.iter().map(|&(entity, position)| (entity, (position - point).magnitude2())));
+			});
+			if found.len() >= k {
+				found.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+				found.truncate(k);
+				// anything beyond this ring is at least `ring` cells away
+				let reach = ring as f32 * self.cell_size;
+				if found[k - 1].1 <= reach * reach {
+					break;
+				}
+			}
+		}
+		found.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+		found.truncate(k);
+		found.into_iter().map(|(entity, distance2)| (entity, distance2.sqrt())).collect()
+	}
+
+	/// Entities within `thickness` of the ray, with the distance along the ray, nearest first.
+	///
+	/// Cells are walked along the ray, but only across the occupied cell range, so
+	/// `max_distance` may be infinite; a NaN or negative one finds nothing. `thickness` is meant
+	/// to stay below the cell size.
+	pub fn query_ray(&self, origin: P, direction: P::Vector, max_distance: f32, thickness: f32) -> Vec<(Entity, f32)> {
+		let origin = origin.to_point3();
+		let direction = P::to_vector3(direction);
+		let (min, max) = match self.extent {
+			Some(extent) if max_distance >= 0. => extent,
+			_ => return Vec::new(),
+		};
+		if ulps_eq!(direction.magnitude2(), 0.) {
+			return Vec::new();
+		}
+		let direction = direction.normalize();
+		let thickness2 = thickness * thickness;
+		let padding = if thickness > 0. { 1 } else { 0 };
+
+		// clip the ray to the occupied range, padded by the cells `thickness` can reach into
+		let (mut t_start, mut t_end) = (0., max_distance);
+		for axis in 0..P::DIMENSIONS {
+			let low = (min[axis] as f32 - padding as f32) * self.cell_size;
+			let high = (max[axis] as f32 + 1. + padding as f32) * self.cell_size;
+			let d = direction[axis];
+			if d == 0. {
+				if origin[axis] < low || origin[axis] > high {
+					return Vec::new();
+				}
+				continue;
+			}
+			let (t0, t1) = ((low - origin[axis]) / d, (high - origin[axis]) / d);
+			t_start = t0.min(t1).max(t_start);
+			t_end = t0.max(t1).min(t_end);
+		}
+		if t_start > t_end {
+			return Vec::new();
+		}
+		let start = origin + direction * t_start;
+
+		let mut visited = ::fnv::FnvHashSet::default();
+		let mut found = Vec::new();
+		let mut test_cell = |key: CellKey, found: &mut Vec<(Entity, f32)>| {
+			if !visited.insert(key) {
+				return;
+			}
+			if let Some(cell) = self.cells.get(&key) {
+				for &(entity, position) in cell {
+					let t = (position - origin).dot(direction);
+					if t < 0. || t > max_distance {
+						continue;
+					}
+					if (origin + direction * t - position).magnitude2() <= thickness2 {
+						found.push((entity, t));
+					}
+				}
+			}
+		};
+
+		// Amanatides & Woo traversal, from where the ray enters the occupied range
+		let mut key = self.cell_of(start);
+		let mut step = [0i32; 3];
+		let mut t_max = [f32::INFINITY; 3];
+		let mut t_delta = [f32::INFINITY; 3];
+		for axis in 0..P::DIMENSIONS {
+			let d = direction[axis];
+			if d > 0. {
+				step[axis] = 1;
+				t_max[axis] = t_start + ((key[axis] as f32 + 1.) * self.cell_size - start[axis]) / d;
+				t_delta[axis] = self.cell_size / d;
+			} else if d < 0. {
+				step[axis] = -1;
+				t_max[axis] = t_start + (key[axis] as f32 * self.cell_size - start[axis]) / d;
+				t_delta[axis] = -self.cell_size / d;
+			}
+		}
+
+		let z_padding = if P::DIMENSIONS == 3 { padding } else { 0 };
+		loop {
+			for dx in -padding..padding + 1 {
+				for dy in -padding..padding + 1 {
+					for dz in -z_padding..z_padding + 1 {
+						test_cell([key[0].saturating_add(dx), key[1].saturating_add(dy), key[2].saturating_add(dz)], &mut found);
+					}
+				}
+			}
+			let axis = (0..3).min_by(|a, b| t_max[*a].partial_cmp(&t_max[*b]).unwrap_or(Ordering::Equal)).unwrap();
+			if t_max[axis] > t_end {
+				break;
+			}
+			key[axis] = key[axis].saturating_add(step[axis]);
+			t_max[axis] += t_delta[axis];
+		}
+
+		found.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+		found
+	}
+
+	/// Runs many radius queries at once on the pool.
+	pub fn par_query_radius(&self, pool: &ThreadPool, queries: &[(P, f32)]) -> Vec<Vec<Entity>> {
+		pool.install(|| {
+			queries.par_iter()
+				.map(|&(center, radius)| self.query_radius(center, radius))
+				.collect()
+		})
+	}
+
+	/// Runs many nearest-neighbour queries at once on the pool.
+	pub fn par_nearest(&self, pool: &ThreadPool, points: &[P], k: usize) -> Vec<Vec<(Entity, f32)>> {
+		pool.install(|| {
+			points.par_iter()
+				.map(|&point| self.nearest(point, k))
+				.collect()
+		})
+	}
+
+	fn cell_of(&self, position: Point3<f32>) -> CellKey {
+		let cell = |value: f32| (value / self.cell_size).floor() as i32;
+		match P::DIMENSIONS {
+			2 => [cell(position.x), cell(position.y), 0],
+			_ => [cell(position.x), cell(position.y), cell(position.z)],
+		}
+	}
+
+	fn remove_from_cell(&mut self, key: CellKey, id: Index) {
+		let now_empty = match self.cells.get_mut(&key) {
+			Some(cell) => {
+				if let Some(index) = cell.iter().position(|e| e.0.id() == id) {
+					cell.swap_remove(index);
+				}
+				cell.is_empty()
+			}
+			None => false,
+		};
+		if now_empty {
+			self.cells.remove(&key);
+		}
+	}
+
+	fn for_each_cell<F>(&self, min: Point3<f32>, max: Point3<f32>, mut visit: F)
+	where
+		F: FnMut(&[(Entity, Point3<f32>)]),
+	{
+		let (extent_min, extent_max) = match self.extent {
+			Some(extent) => extent,
+			None => return,
+		};
+		// nothing lies outside the occupied range, so the loops below stay within it
+		let (mut min, mut max) = (self.cell_of(min), self.cell_of(max));
+		for axis in 0..3 {
+			min[axis] = min[axis].max(extent_min[axis]);
+			max[axis] = max[axis].min(extent_max[axis]);
+			if min[axis] > max[axis] {
+				return;
+			}
+		}
+		let cells = (0..3).try_fold(1u64, |cells, axis| {
+			cells.checked_mul((i64::from(max[axis]) - i64::from(min[axis]) + 1) as u64)
+		});
+		if cells.is_none_or(|cells| cells > self.cells.len() as u64) {
+			// sparse grid: cheaper to scan the occupied cells
+			for (key, cell) in &self.cells {
+				if (0..3).all(|axis| key[axis] >= min[axis] && key[axis] <= max[axis]) {
+					visit(cell);
+				}
+			}
+			return;
+		}
+		for x in min[0]..=max[0] {
+			for y in min[1]..=max[1] {
+				for z in min[2]..=max[2] {
+					if let Some(cell) = self.cells.get(&[x, y, z]) {
+						visit(cell);
+					}
+				}
+			}
+		}
+	}
+
+	// cells at exactly `ring` steps (Chebyshev distance) from `center`
+	fn for_each_ring_cell<F>(&self, center: CellKey, ring: i32, mut visit: F)
+	where
+		F: FnMut(&[(Entity, Point3<f32>)]),
+	{
+		let z_ring = if P::DIMENSIONS == 3 { ring } else { 0 };
+		for x in -ring..ring + 1 {
+			for y in -ring..ring + 1 {
+				for z in -z_ring..z_ring + 1 {
+					if x.abs() != ring && y.abs() != ring && z.abs() != ring {
+						continue;
+					}
+					if let Some(cell) = self.cells.get(&[center[0] + x, center[1] + y, center[2] + z]) {
+						visit(cell);
+					}
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::f32;
+
+	use cgmath::{InnerSpace, Point2, Point3, Vector2, Vector3};
+	use specs::prelude::{Entity, World};
+
+	use super::{SpatialGrid2, SpatialGrid3};
+
+	fn scatter(world: &mut World, count: usize) -> Vec<(Entity, Point3<f32>)> {
+		(0..count)
+			.map(|i| {
+				let i = i as f32;
+				let position = Point3::new((i * 7.3) % 40. - 20., (i * 3.1) % 40. - 20., (i * 1.7) % 10.);
+				(world.create_entity().build(), position)
+			})
+			.collect()
+	}
+
+	#[test]
+	fn radius_and_nearest_match_brute_force() {
+		let mut world = World::new();
+		let points = scatter(&mut world, 400);
+		let mut grid = SpatialGrid3::new(2.);
+		for &(entity, position) in &points {
+			grid.insert(entity, position);
+		}
+		let center = Point3::new(1.5, -3., 4.);
+
+		let mut found = grid.query_radius(center, 5.);
+		let mut expected: Vec<_> = points.iter()
+			.filter(|&&(_, p)| (p - center).magnitude() <= 5.)
+			.map(|&(entity, _)| entity)
+			.collect();
+		found.sort();
+		expected.sort();
+		assert_eq!(found, expected);
+
+		let mut by_distance = points.clone();
+		by_distance.sort_by(|a, b| (a.1 - center).magnitude().partial_cmp(&(b.1 - center).magnitude()).unwrap());
+		let nearest: Vec<_> = grid.nearest(center, 4).into_iter().map(|(entity, _)| entity).collect();
+		let expected: Vec<_> = by_distance[..4].iter().map(|&(entity, _)| entity).collect();
+		assert_eq!(nearest, expected);
+	}
+
+	#[test]
+	fn moves_and_removals_2d() {
+		let mut world = World::new();
+		let (a, b) = (world.create_entity().build(), world.create_entity().build());
+		let mut grid = SpatialGrid2::new(1.);
+		grid.insert(a, Point2::new(0.5, 0.5));
+		grid.insert(b, Point2::new(10., 10.));
+		assert_eq!(grid.query_ray(Point2::new(0., 0.), Vector2::new(1., 1.), 100., 0.1).len(), 2);
+
+		grid.insert(a, Point2::new(9.5, 9.5));
+		assert_eq!(grid.nearest(Point2::new(9., 9.), 1)[0].0, a);
+		assert!(grid.remove(b));
+		assert_eq!(grid.query_radius(Point2::new(10., 10.), 1.), vec![a]);
+	}
+
+	#[test]
+	fn infinite_rays_stop_at_the_occupied_cells() {
+		let mut world = World::new();
+		let empty = SpatialGrid3::new(1.);
+		assert!(empty.query_ray(Point3::new(0., 0., 0.), Vector3::new(1., 0., 0.), f32::INFINITY, 0.1).is_empty());
+
+		let (a, b) = (world.create_entity().build(), world.create_entity().build());
+		let mut grid = SpatialGrid3::new(1.);
+		grid.insert(a, Point3::new(5.5, 0.5, 0.5));
+		grid.insert(b, Point3::new(-20.5, 0.5, 0.5));
+		let hits = grid.query_ray(Point3::new(-100., 0.5, 0.5), Vector3::new(1., 0., 0.), f32::INFINITY, 0.1);
+		assert_eq!(hits.iter().map(|hit| hit.0).collect::<Vec<_>>(), vec![b, a]);
+		assert_ulps_eq!(hits[1].1, 105.5, epsilon = 1e-3);
+		// pointing away from, or passing beside, the occupied cells
+		assert!(grid.query_ray(Point3::new(10., 0.5, 0.5), Vector3::new(1., 0., 0.), f32::INFINITY, 0.1).is_empty());
+		assert!(grid.query_ray(Point3::new(0., 50., 0.5), Vector3::new(1., 0., 0.), f32::INFINITY, 0.1).is_empty());
+		assert!(grid.query_ray(Point3::new(0., 0.5, 0.5), Vector3::new(1., 0., 0.), f32::NAN, 0.1).is_empty());
+	}
+
+	#[test]
+	fn casts_3d_rays_diagonally() {
+		let mut world = World::new();
+		let points = scatter(&mut world, 200);
+		let mut grid = SpatialGrid3::new(2.);
+		for &(entity, position) in &points {
+			grid.insert(entity, position);
+		}
+		let (origin, direction) = (Point3::new(-25., -25., -1.), Vector3::new(1., 1., 0.2).normalize());
+
+		let found = grid.query_ray(origin, direction, 60., 1.5);
+		let mut expected: Vec<_> = points.iter()
+			.filter_map(|&(entity, p)| {
+				let t = (p - origin).dot(direction);
+				let inside = (0. ..=60.).contains(&t) && (origin + direction * t - p).magnitude() <= 1.5;
+				if inside { Some((entity, t)) } else { None }
+			})
+			.collect();
+		expected.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+		assert!(!expected.is_empty());
+		assert_eq!(found.iter().map(|hit| hit.0).collect::<Vec<_>>(), expected.iter().map(|hit| hit.0).collect::<Vec<_>>());
+	}
+
+	#[test]
+	fn huge_boxes_scan_only_occupied_cells() {
+		let mut world = World::new();
+		let points = scatter(&mut world, 50);
+		let mut grid = SpatialGrid3::new(0.5);
+		for &(entity, position) in &points {
+			grid.insert(entity, position);
+		}
+		let mut found = grid.query_box(Point3::new(-1e30, -1e30, -1e30), Point3::new(1e30, 1e30, 1e30));
+		let mut expected: Vec<_> = points.iter().map(|&(entity, _)| entity).collect();
+		found.sort();
+		expected.sort();
+		assert_eq!(found, expected);
+		assert_eq!(grid.query_radius(Point3::new(0., 0., 0.), f32::MAX).len(), points.len());
+
+		let inside = grid.query_box(Point3::new(-20., -20., 0.), Point3::new(0., 0., 5.));
+		assert_eq!(inside.len(), points.iter().filter(|&&(_, p)| p.x <= 0. && p.y <= 0. && p.z <= 5.).count());
+	}
+}
@@ -0,0 +1,8 @@
+//! Spatial acceleration over entity positions, for proximity, range, nearest-neighbour and
+//! ray queries.
+
+pub use self::grid::{CellKey, SpatialGrid, SpatialGrid2, SpatialGrid3, SpatialPoint};
+pub use self::system::SpatialIndexSystem;
+
+mod grid;
+mod system;
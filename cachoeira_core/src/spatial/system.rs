@@ -0,0 +1,219 @@
+use cgmath::{Point2, Point3};
+use hibitset::BitSet;
+use specs::prelude::{
+	Entities,
+	InsertedFlag,
+	Join,
+	ModifiedFlag,
+	ReadStorage,
+	ReaderId,
+	RemovedFlag,
+	Resources,
+	System,
+	Write,
+	WriteStorage,
+};
+
+use spatial::SpatialGrid;
+use transform::{GlobalTransform, GlobalTransform2D};
+
+/// Keeps a `SpatialGrid` resource in sync with world positions.
+///
+/// `SpatialIndexSystem<GlobalTransform>` maintains a `SpatialGrid3`, and
+/// `SpatialIndexSystem<GlobalTransform2D>` a `SpatialGrid2`. Only entities whose global
+/// transform was inserted, modified or removed since the last run are touched, so it belongs
+/// after the `TransformSystem`.
+pub struct SpatialIndexSystem<T> {
+	cell_size: f32,
+	changed: BitSet,
+	removed: BitSet,
+	inserted_id: Option<ReaderId<InsertedFlag>>,
+	modified_id: Option<ReaderId<ModifiedFlag>>,
+	removed_id: Option<ReaderId<RemovedFlag>>,
+	_marker: ::std::marker::PhantomData<T>,
+}
+
+impl<T> SpatialIndexSystem<T> {
+	pub fn new(cell_size: f32) -> Self {
+		SpatialIndexSystem {
+			cell_size,
+			changed: BitSet::new(),
+			removed: BitSet::new(),
+			inserted_id: None,
+			modified_id: None,
+			removed_id: None,
+			_marker: ::std::marker::PhantomData,
+		}
+	}
+}
+
+macro_rules! impl_spatial_index_system {
+	($global:ty, $point:ty, $name:expr, $position:path) => {
+		impl<'a> System<'a> for SpatialIndexSystem<$global> {
+			type SystemData = (
+				Entities<'a>,
+				ReadStorage<'a, $global>,
+				Write<'a, SpatialGrid<$point>>,
+			);
+
+			fn run(&mut self, (entities, globals, mut grid): Self::SystemData) {
+				#[cfg(feature="profiler")]
+				profile_scope!($name);
+
+				self.changed.clear();
+				self.removed.clear();
+				globals.populate_inserted(self.inserted_id.as_mut().unwrap(), &mut self.changed);
+				globals.populate_modified(self.modified_id.as_mut().unwrap(), &mut self.changed);
+				globals.populate_removed(self.removed_id.as_mut().unwrap(), &mut self.removed);
+
+				// removals first, so an id reused by a new entity this frame is re-added below
+				for id in (&self.removed).join() {
+					grid.remove_index(id);
+				}
+				for (entity, global, _) in (&*entities, &globals, &self.changed).join() {
+					grid.insert(entity, $position(global));
+				}
+			}
+
+			fn setup(&mut self, res: &mut Resources) {
+				use specs::prelude::SystemData;
+				Self::SystemData::setup(res);
+				res.insert(SpatialGrid::<$point>::new(self.cell_size));
+				let mut globals = WriteStorage::<$global>::fetch(res);
+				self.inserted_id = Some(globals.track_inserted());
+				self.modified_id = Some(globals.track_modified());
+				self.removed_id = Some(globals.track_removed());
+			}
+		}
+	};
+}
+
+impl_spatial_index_system!(GlobalTransform, Point3<f32>, "spatial_index_system", position_3d);
+impl_spatial_index_system!(GlobalTransform2D, Point2<f32>, "spatial_index_2d_system", position_2d);
+
+fn position_3d(global: &GlobalTransform) -> Point3<f32> {
+	let w = global.0.w;
+	Point3::new(w.x, w.y, w.z)
+}
+
+fn position_2d(global: &GlobalTransform2D) -> Point2<f32> {
+	let z = global.0.z;
+	Point2::new(z.x, z.y)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+
+	use cgmath::{Matrix4, Point2, Point3, Rad, Vector2, Vector3};
+	use rayon::ThreadPoolBuilder;
+	use specs::prelude::{Dispatcher, DispatcherBuilder, Entity, World};
+
+	use spatial::{SpatialGrid2, SpatialGrid3, SpatialIndexSystem};
+	use transform::{GlobalTransform, GlobalTransform2D, Transform2D};
+
+	fn setup() -> (World, Dispatcher<'static, 'static>) {
+		let mut world = World::new();
+		let mut dispatcher = DispatcherBuilder::new()
+			.with(SpatialIndexSystem::<GlobalTransform>::new(1.), "spatial_index_system", &[])
+			.with(SpatialIndexSystem::<GlobalTransform2D>::new(1.), "spatial_index_2d_system", &[])
+			.build();
+		dispatcher.setup(&mut world.res);
+		(world, dispatcher)
+	}
+
+	fn step(world: &mut World, dispatcher: &mut Dispatcher) {
+		dispatcher.dispatch(&world.res);
+		world.maintain();
+	}
+
+	fn at(x: f32, y: f32, z: f32) -> GlobalTransform {
+		GlobalTransform(Matrix4::from_translation(Vector3::new(x, y, z)))
+	}
+
+	fn spawn(world: &mut World, x: f32, y: f32, z: f32) -> Entity {
+		world.create_entity().with(at(x, y, z)).build()
+	}
+
+	#[test]
+	fn syncs_inserts_moves_and_removals() {
+		let (mut world, mut dispatcher) = setup();
+		let (a, b) = (spawn(&mut world, 0.5, 0.5, 0.5), spawn(&mut world, 4.5, 0.5, 0.5));
+		step(&mut world, &mut dispatcher);
+		assert_eq!(world.read_resource::<SpatialGrid3>().len(), 2);
+		assert_eq!(world.read_resource::<SpatialGrid3>().position(a), Some(Point3::new(0.5, 0.5, 0.5)));
+
+		*world.write_storage::<GlobalTransform>().get_mut(a).unwrap() = at(10., 0., 0.);
+		step(&mut world, &mut dispatcher);
+		{
+			let grid = world.read_resource::<SpatialGrid3>();
+			assert_eq!(grid.position(a), Some(Point3::new(10., 0., 0.)));
+			assert_eq!(grid.query_radius(Point3::new(0.5, 0.5, 0.5), 1.), Vec::<Entity>::new());
+			assert_eq!(grid.query_radius(Point3::new(10., 0., 0.), 1.), vec![a]);
+		}
+
+		world.write_storage::<GlobalTransform>().remove(b);
+		step(&mut world, &mut dispatcher);
+		let grid = world.read_resource::<SpatialGrid3>();
+		assert!(!grid.contains(b));
+		assert_eq!(grid.len(), 1);
+	}
+
+	#[test]
+	fn reused_ids_replace_deleted_entities() {
+		let (mut world, mut dispatcher) = setup();
+		let old = spawn(&mut world, 0.5, 0.5, 0.5);
+		step(&mut world, &mut dispatcher);
+
+		world.delete_entity(old).unwrap();
+		world.maintain();
+		let new = spawn(&mut world, 7.5, 0.5, 0.5);
+		assert_eq!(new.id(), old.id());
+		step(&mut world, &mut dispatcher);
+
+		let grid = world.read_resource::<SpatialGrid3>();
+		assert_eq!(grid.len(), 1);
+		assert_eq!(grid.position(old), None);
+		assert_eq!(grid.position(new), Some(Point3::new(7.5, 0.5, 0.5)));
+		assert_eq!(grid.query_radius(Point3::new(0.5, 0.5, 0.5), 1.), Vec::<Entity>::new());
+	}
+
+	#[test]
+	fn indexes_2d_globals() {
+		let (mut world, mut dispatcher) = setup();
+		let sprite = world.create_entity()
+			.with(GlobalTransform2D(Transform2D::new(Vector2::new(3., -2.), Rad(0.), Vector2::new(1., 1.)).matrix()))
+			.build();
+		step(&mut world, &mut dispatcher);
+		assert_eq!(world.read_resource::<SpatialGrid2>().query_radius(Point2::new(3., -2.5), 1.), vec![sprite]);
+		assert!(world.read_resource::<SpatialGrid3>().is_empty());
+	}
+
+	#[test]
+	fn queries_the_synced_grid() {
+		let (mut world, mut dispatcher) = setup();
+		let row: Vec<Entity> = (0..5).map(|i| spawn(&mut world, i as f32 * 2. + 0.5, 0.5, 0.5)).collect();
+		let above = spawn(&mut world, 2.5, 3.5, 0.5);
+		step(&mut world, &mut dispatcher);
+		let grid = world.read_resource::<SpatialGrid3>();
+
+		let mut boxed = grid.query_box(Point3::new(0., 0., 0.), Point3::new(3., 4., 1.));
+		boxed.sort();
+		assert_eq!(boxed, vec![row[0], row[1], above]);
+
+		let ray = grid.query_ray(Point3::new(2.5, -10., 0.5), Vector3::new(0., 1., 0.), 100., 0.1);
+		assert_eq!(ray.iter().map(|hit| hit.0).collect::<Vec<_>>(), vec![row[1], above]);
+		assert_ulps_eq!(ray[1].1, 13.5, epsilon = 1e-4);
+
+		let pool = Arc::new(ThreadPoolBuilder::new().num_threads(2).build().unwrap());
+		let queries = [(Point3::new(8.5, 0.5, 0.5), 0.5), (Point3::new(2.5, 2., 0.5), 1.6)];
+		let mut radius = grid.par_query_radius(&pool, &queries);
+		radius[1].sort();
+		assert_eq!(radius, vec![vec![row[4]], vec![row[1], above]]);
+
+		let nearest = grid.par_nearest(&pool, &[Point3::new(0., 0.5, 0.5), Point3::new(2.5, 5., 0.5)], 2);
+		let entities: Vec<Vec<Entity>> = nearest.iter().map(|hits| hits.iter().map(|hit| hit.0).collect()).collect();
+		assert_eq!(entities, vec![vec![row[0], row[1]], vec![above, row[1]]]);
+		assert_ulps_eq!(nearest[0][0].1, 0.5, epsilon = 1e-5);
+	}
+}
@@ -16,6 +16,8 @@ pub struct Time {
 	absolute_real_time: Duration,
 	absolute_time: Duration,
 	time_scale: f32,
+	/// scaled time elapsed but not yet consumed by fixed updates
+	fixed_accumulator: Duration,
 }
 
 impl Time {
@@ -36,6 +38,22 @@ impl Time {
 		duration_to_secs_f64(self.absolute_real_time)
 	}
 	pub fn time_scale(&self) -> f32 { self.time_scale }
+
+	/// Fraction of a fixed step elapsed since the last fixed update, from 0 to 1.
+	///
+	/// Follows the accumulator fed by `set_delta_*` and drained by `finish_fixed_update`.
+	pub fn fixed_step_alpha(&self) -> f32 {
+		if self.fixed_seconds > 0.0 {
+			(duration_to_secs(self.fixed_accumulator) / self.fixed_seconds).min(1.0)
+		} else {
+			0.0
+		}
+	}
+
+	/// Whether a whole fixed step has accumulated and should be run.
+	pub fn needs_fixed_update(&self) -> bool {
+		self.fixed_accumulator >= self.fixed_time
+	}
 	
 	pub fn set_delta_seconds(&mut self, secs: f32) {
 		self.delta_seconds = secs * self.time_scale;
@@ -45,6 +63,7 @@ impl Time {
 		
 		self.absolute_time += self.delta_time;
 		self.absolute_real_time += self.delta_real_time;
+		self.fixed_accumulator += self.delta_time;
 	}
 
 	pub fn set_delta_time(&mut self, duration: Duration) {
@@ -55,6 +74,7 @@ impl Time {
 
 		self.absolute_time += self.delta_time;
 		self.absolute_real_time += self.delta_real_time;
+		self.fixed_accumulator += self.delta_time;
 	}
 
 	pub fn set_fixed_seconds(&mut self, secs: f32) {
@@ -80,6 +100,9 @@ impl Time {
 
 	pub fn finish_fixed_update(&mut self) {
 		self.last_fixed_update += self.fixed_time;
+		self.fixed_accumulator = self.fixed_accumulator
+			.checked_sub(self.fixed_time)
+			.unwrap_or_default();
 	}
 }
 
@@ -98,6 +121,7 @@ impl Default for Time {
 			absolute_real_time: Duration::default(),
 			absolute_time: Duration::default(),
 			time_scale: 1.0,
+			fixed_accumulator: Duration::default(),
 		}
 	}
 }
@@ -156,7 +180,7 @@ impl Stopwatch {
 
 #[cfg(test)]
 mod tests {
-	use super::Stopwatch;
+	use super::{Stopwatch, Time};
 	use std::thread;
 	use std::time::Duration;
 
@@ -196,6 +220,25 @@ mod tests {
 	    assert_eq!(0, watch.elapsed().subsec_nanos());
 	}
 
+	#[test]
+	fn fixed_step_alpha() {
+		let mut time = Time::default();
+		time.set_fixed_seconds(0.5);
+		time.set_time_scale(2.0);
+		assert_eq!(time.fixed_step_alpha(), 0.0);
+
+		time.set_delta_seconds(0.125);
+		assert_eq!(time.fixed_step_alpha(), 0.5);
+		assert!(!time.needs_fixed_update());
+
+		time.set_delta_seconds(0.25);
+		assert!(time.needs_fixed_update());
+		assert_eq!(time.fixed_step_alpha(), 1.0);
+		time.finish_fixed_update();
+		assert!(!time.needs_fixed_update());
+		assert_eq!(time.fixed_step_alpha(), 0.5);
+	}
+
 	#[test]
 	fn restart() {
 	    const DURATION0: u64 = 2;
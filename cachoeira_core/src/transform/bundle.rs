@@ -10,6 +10,7 @@ use transform::*;
 #[derive(Default)]
 pub struct TransformBundle<'a> {
 	dep: &'a[&'a str],
+	floating_origin: bool,
 }
 
 impl<'a> TransformBundle<'a> {
@@ -21,6 +22,12 @@ impl<'a> TransformBundle<'a> {
 		self.dep = dep;
 		self
 	}
+
+	/// Also rebase the scene around the `OriginAnchor`, see `FloatingOrigin`.
+	pub fn with_floating_origin(mut self) -> Self {
+		self.floating_origin = true;
+		self
+	}
 }
 
 impl<'a, 'b, 'c> SystemBundle<'a, 'b> for TransformBundle<'c> {
@@ -30,11 +37,31 @@ impl<'a, 'b, 'c> SystemBundle<'a, 'b> for TransformBundle<'c> {
 	    	"parent_hierarchy_system",
 	    	self.dep,
 	    );
+	    builder.add(
+	    	SubtreeSystem::new(),
+	    	"subtree_system",
+	    	&["parent_hierarchy_system"],
+	    );
+	    let transform_deps: &[&str] = if self.floating_origin {
+	    	builder.add(
+	    		FloatingOriginSystem::new(),
+	    		"floating_origin_system",
+	    		self.dep,
+	    	);
+	    	&["subtree_system", "floating_origin_system"]
+	    } else {
+	    	&["subtree_system"]
+	    };
 	    builder.add(
 	    	TransformSystem::new(),
 	    	"transform_system",
-	    	&["parent_hierarchy_system"],
+	    	transform_deps,
+	    );
+	    builder.add(
+	    	ConstraintSystem::new(),
+	    	"transform_constraint_system",
+	    	&["transform_system"],
 	    );
-	    Ok(());
+	    Ok(())
 	}
 }
\ No newline at end of file
@@ -0,0 +1,155 @@
+use cgmath::Vector3;
+use specs::prelude::{
+	Component,
+	DenseVecStorage,
+	Entity,
+};
+
+/// Per-axis selection used when copying from another entity.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AxisMask {
+	pub x: bool,
+	pub y: bool,
+	pub z: bool,
+}
+
+impl AxisMask {
+	pub const ALL: AxisMask = AxisMask { x: true, y: true, z: true };
+	pub const NONE: AxisMask = AxisMask { x: false, y: false, z: false };
+
+	pub fn any(&self) -> bool { self.x || self.y || self.z }
+
+	/// Takes the masked components from `source`, the rest from `base`.
+	pub fn select(&self, base: Vector3<f32>, source: Vector3<f32>) -> Vector3<f32> {
+		Vector3::new(
+			if self.x { source.x } else { base.x },
+			if self.y { source.y } else { base.y },
+			if self.z { source.z } else { base.z },
+		)
+	}
+}
+
+impl Default for AxisMask {
+	fn default() -> Self { AxisMask::ALL }
+}
+
+/// Frame a follow offset is expressed in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OffsetSpace {
+	World,
+	/// rotates along with the followed entity
+	Target,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClampVolume {
+	Box { min: Vector3<f32>, max: Vector3<f32> },
+	Sphere { center: Vector3<f32>, radius: f32 },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConstraintKind {
+	/// turns the entity's forward (-Z) towards the target
+	LookAt { target: Entity, up: Vector3<f32> },
+	/// moves towards the target plus an offset; `damping` is a time constant in seconds,
+	/// zero snaps immediately
+	Follow { target: Entity, offset: Vector3<f32>, space: OffsetSpace, damping: f32 },
+	CopyPosition { target: Entity, axes: AxisMask },
+	/// masks apply to euler angles
+	CopyRotation { target: Entity, axes: AxisMask },
+	CopyScale { target: Entity, axes: AxisMask },
+	Clamp(ClampVolume),
+}
+
+/// A world-space constraint, blended over the result of the previous one by `weight`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Constraint {
+	pub kind: ConstraintKind,
+	pub weight: f32,
+	pub enabled: bool,
+	// damped position carried between frames by `Follow`
+	pub(crate) follow_position: Option<Vector3<f32>>,
+}
+
+impl Constraint {
+	pub fn new(kind: ConstraintKind) -> Self {
+		Constraint { kind, weight: 1., enabled: true, follow_position: None }
+	}
+
+	pub fn look_at(target: Entity) -> Self {
+		Constraint::new(ConstraintKind::LookAt { target, up: Vector3::unit_y() })
+	}
+
+	pub fn follow(target: Entity, offset: Vector3<f32>, damping: f32) -> Self {
+		Constraint::new(ConstraintKind::Follow { target, offset, space: OffsetSpace::World, damping })
+	}
+
+	pub fn copy_position(target: Entity, axes: AxisMask) -> Self {
+		Constraint::new(ConstraintKind::CopyPosition { target, axes })
+	}
+
+	pub fn copy_rotation(target: Entity, axes: AxisMask) -> Self {
+		Constraint::new(ConstraintKind::CopyRotation { target, axes })
+	}
+
+	pub fn copy_scale(target: Entity, axes: AxisMask) -> Self {
+		Constraint::new(ConstraintKind::CopyScale { target, axes })
+	}
+
+	pub fn clamp(volume: ClampVolume) -> Self {
+		Constraint::new(ConstraintKind::Clamp(volume))
+	}
+
+	pub fn with_weight(mut self, weight: f32) -> Self {
+		debug_assert!((0.0..=1.0).contains(&weight), "Constraint weight must be within [0, 1]");
+		self.weight = weight;
+		self
+	}
+
+	/// Up vector for `LookAt`; ignored by other kinds.
+	pub fn with_up(mut self, up: Vector3<f32>) -> Self {
+		if let ConstraintKind::LookAt { up: ref mut current, .. } = self.kind {
+			*current = up;
+		}
+		self
+	}
+
+	/// Offset frame for `Follow`; ignored by other kinds.
+	pub fn with_offset_space(mut self, offset_space: OffsetSpace) -> Self {
+		if let ConstraintKind::Follow { ref mut space, .. } = self.kind {
+			*space = offset_space;
+		}
+		self
+	}
+
+	/// The entity this constraint reads from, if any.
+	pub fn target(&self) -> Option<Entity> {
+		match self.kind {
+			ConstraintKind::LookAt { target, .. }
+			| ConstraintKind::Follow { target, .. }
+			| ConstraintKind::CopyPosition { target, .. }
+			| ConstraintKind::CopyRotation { target, .. }
+			| ConstraintKind::CopyScale { target, .. } => Some(target),
+			ConstraintKind::Clamp(_) => None,
+		}
+	}
+}
+
+/// Constraints evaluated in order after transform propagation, overriding the `GlobalTransform`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Constraints(pub Vec<Constraint>);
+
+impl Constraints {
+	pub fn new() -> Self {
+		Default::default()
+	}
+
+	pub fn with(mut self, constraint: Constraint) -> Self {
+		self.0.push(constraint);
+		self
+	}
+}
+
+impl Component for Constraints {
+	type Storage = DenseVecStorage<Self>;
+}
@@ -21,7 +21,8 @@ use specs::prelude::{
 };
 
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
 pub struct Transform {
     pub rotation: Quaternion<f32>,
     pub scale: Vector3<f32>,
@@ -29,9 +30,18 @@ pub struct Transform {
 }
 
 impl Transform {
-	#[inline]
+	/// Turns the entity so its forward axis (-Z) points at `position`, keeping +Y as close to
+	/// `up` as possible; looking straight along `up` picks any perpendicular side axis.
 	pub fn look_at(&mut self, position: Point3<f32>, up: Vector3<f32>) -> &mut Self {
-		self.rotation = Quaternion::look_at(Point3::from_vec(self.translation) - position, up);
+		let z = (Point3::from_vec(self.translation) - position).normalize();
+		let x = up.cross(z);
+		let x = if ulps_eq!(x.magnitude2(), 0.) {
+			let fallback = if z.y.abs() < 0.9 { Vector3::unit_y() } else { Vector3::unit_x() };
+			fallback.cross(z).normalize()
+		} else {
+			x.normalize()
+		};
+		self.rotation = Quaternion::from(Matrix3::from_cols(x, z.cross(x), z));
 		debug_assert!(
 			self.rotation.s.is_finite()
 			&& self.rotation.v.x.is_finite()
@@ -47,19 +57,19 @@ impl Transform {
 		let quat: Matrix3<f32> = self.rotation.into();
 		let x = Vector4 {
 			x: quat.x.x * self.scale.x,
-			y: quat.x.y * self.scale.y,
-			z: quat.x.z * self.scale.z,
+			y: quat.x.y * self.scale.x,
+			z: quat.x.z * self.scale.x,
 			w: 0.0,
 		};
 		let y = Vector4 {
-			x: quat.y.x * self.scale.x,
+			x: quat.y.x * self.scale.y,
 			y: quat.y.y * self.scale.y,
-			z: quat.y.z * self.scale.z,
+			z: quat.y.z * self.scale.y,
 			w: 0.0,
 		};
 		let z = Vector4 {
-			x: quat.z.x * self.scale.x,
-			y: quat.z.y * self.scale.y,
+			x: quat.z.x * self.scale.z,
+			y: quat.z.y * self.scale.z,
 			z: quat.z.z * self.scale.z,
 			w: 0.0,
 		};
@@ -95,32 +105,32 @@ impl Transform {
 
 	#[inline]
 	pub fn move_forward(&mut self, amount: f32) -> &mut Self {
-		self.move_local(Vector3::new(0.0, 0.0, -amount));
+		self.move_local(Vector3::new(0.0, 0.0, -amount))
 	}
 
 	#[inline]
 	pub fn move_backward(&mut self, amount: f32) -> &mut Self {
-		self.move_local(Vector3::new(0.0, 0.0, amount));
+		self.move_local(Vector3::new(0.0, 0.0, amount))
 	}
 
 	#[inline]
 	pub fn move_left(&mut self, amount: f32) -> &mut Self {
-		self.move_local(Vector3::new(-amount, 0.0, 0.0));
+		self.move_local(Vector3::new(-amount, 0.0, 0.0))
 	}
 
 	#[inline]
 	pub fn move_right(&mut self, amount: f32) -> &mut Self {
-		self.move_local(Vector3::new(amount, 0.0, 0.0));
+		self.move_local(Vector3::new(amount, 0.0, 0.0))
 	}
 
 	#[inline]
 	pub fn move_up(&mut self, amount: f32) -> &mut Self {
-		self.move_local(Vector3::new(0.0, amount, 0.0));
+		self.move_local(Vector3::new(0.0, amount, 0.0))
 	}
 
 	#[inline]
 	pub fn move_down(&mut self, amount: f32) -> &mut Self {
-		self.move_local(Vector3::new(0.0, -amount, 0.0));
+		self.move_local(Vector3::new(0.0, -amount, 0.0))
 	}
 
 	#[inline]
@@ -136,7 +146,7 @@ impl Transform {
 
 
 	#[inline]
-	pub fn rotate_global<A: Into<Rad<f32>>>(&mut self, axis: Vector3<f32>, angle: A) -> &mod Self {
+	pub fn rotate_global<A: Into<Rad<f32>>>(&mut self, axis: Vector3<f32>, angle: A) -> &mut Self {
 		debug_assert!(
 			!ulps_eq!(axis.magnitude2(), Zero::zero()),
 			"Axis of rotation must not be zero"
@@ -147,7 +157,7 @@ impl Transform {
 	}
 
 	#[inline]
-	pub fn rotate_local<A: Into<Rad<f32>>>(&mut self, axis: Vector3<f32>, angle: A) -> &mod Self {
+	pub fn rotate_local<A: Into<Rad<f32>>>(&mut self, axis: Vector3<f32>, angle: A) -> &mut Self {
 		debug_assert!(
 			!ulps_eq!(axis.magnitude2(), Zero::zero()),
 			"Axis of rotation must not be zero"
@@ -175,6 +185,38 @@ impl Transform {
 		use cgmath::SquareMatrix;
 		self.matrix().invert().unwrap()
 	}
+
+	/// Splits an affine matrix back into translation, rotation and scale.
+	///
+	/// Shear cannot be represented and is lost; a mirrored basis is reported as a negative x scale.
+	pub fn from_matrix(matrix: &Matrix4<f32>) -> Transform {
+		use cgmath::SquareMatrix;
+		let basis = Matrix3::from_cols(matrix.x.truncate(), matrix.y.truncate(), matrix.z.truncate());
+		let mut scale = Vector3::new(basis.x.magnitude(), basis.y.magnitude(), basis.z.magnitude());
+		if basis.determinant() < 0. {
+			scale.x = -scale.x;
+		}
+		let rotation = if ulps_eq!(scale.x * scale.y * scale.z, Zero::zero()) {
+			Quaternion::one()
+		} else {
+			Quaternion::from(Matrix3::from_cols(basis.x / scale.x, basis.y / scale.y, basis.z / scale.z)).normalize()
+		};
+		Transform { translation: matrix.w.truncate(), rotation, scale }
+	}
+
+	/// Blends towards `other`: lerps translation and scale, slerps rotation along the shortest arc.
+	pub fn interpolate(&self, other: &Transform, alpha: f32) -> Transform {
+		let target = if self.rotation.dot(other.rotation) < 0. {
+			-other.rotation
+		} else {
+			other.rotation
+		};
+		Transform {
+			translation: self.translation.lerp(other.translation, alpha),
+			rotation: self.rotation.slerp(target, alpha),
+			scale: self.scale.lerp(other.scale, alpha),
+		}
+	}
 }
 
 impl Default for Transform {
@@ -249,7 +291,7 @@ impl CgTransform<Point2<f32>> for Transform {
 		rot.rotate_vector(vec.mul_element_wise(self.scale.truncate()))
 	}
 	fn inverse_transform_vector(&self, vec: Vector2<f32>) -> Option<Vector2<f32>> {
-		if ulps_eq!(self.scale, &Vector2::zero()) {
+		if ulps_eq!(self.scale.truncate(), &Vector2::zero()) {
 			None
 		} else {
 			let rot: Basis2<f32> = Rotation2::from_angle(-Euler::from(self.rotation).z);
@@ -270,7 +312,7 @@ impl CgTransform<Point2<f32>> for Transform {
 		}
 	}
 	fn inverse_transform(&self) -> Option<Self> {
-		if ulps_eq!(self.scale, Vector2::zero()) {
+		if ulps_eq!(self.scale.truncate(), Vector2::zero()) {
 			None
 		} else {
 			let scale = 1. / self.scale;
@@ -0,0 +1,157 @@
+use cgmath::{
+	Angle,
+	Array,
+	InnerSpace,
+	Matrix2, Matrix3, Matrix4,
+	SquareMatrix,
+	Point2,
+	Rad,
+	Vector2, Vector4,
+	Zero,
+};
+use specs::prelude::{
+	Component,
+	DenseVecStorage,
+	FlaggedStorage,
+};
+
+/// Local transform for entities living on the XY plane.
+///
+/// Applied as scale, then skew, then rotation, then translation. When parented to 3D entities
+/// it is embedded at z = 0 with no depth scaling.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Transform2D {
+	pub position: Vector2<f32>,
+	pub angle: Rad<f32>,
+	pub scale: Vector2<f32>,
+	/// shear angles along x and y, zero for no skew
+	pub skew: Vector2<Rad<f32>>,
+}
+
+impl Transform2D {
+	pub fn new(position: Vector2<f32>, angle: Rad<f32>, scale: Vector2<f32>) -> Self {
+		Transform2D { position, angle, scale, ..Default::default() }
+	}
+
+	#[inline]
+	pub fn linear(&self) -> Matrix2<f32> {
+		let rotation = Matrix2::from_angle(self.angle);
+		let scale = Matrix2::from_diagonal(self.scale);
+		if self.skew.x == Rad::zero() && self.skew.y == Rad::zero() {
+			rotation * scale
+		} else {
+			rotation * Matrix2::new(1., self.skew.y.tan(), self.skew.x.tan(), 1.) * scale
+		}
+	}
+
+	#[inline]
+	pub fn matrix(&self) -> Matrix3<f32> {
+		let linear = self.linear();
+		Matrix3 {
+			x: linear.x.extend(0.),
+			y: linear.y.extend(0.),
+			z: self.position.extend(1.),
+		}
+	}
+
+	/// The same transform embedded in 3D, acting on the XY plane.
+	#[inline]
+	pub fn matrix4(&self) -> Matrix4<f32> {
+		let linear = self.linear();
+		Matrix4 {
+			x: linear.x.extend(0.).extend(0.),
+			y: linear.y.extend(0.).extend(0.),
+			z: Vector4::unit_z(),
+			w: Vector4::new(self.position.x, self.position.y, 0., 1.),
+		}
+	}
+
+	/// Places the entity at `eye`, turned so its local x axis points at `center`.
+	pub fn look_at(&mut self, eye: Point2<f32>, center: Point2<f32>) -> &mut Self {
+		let direction = center - eye;
+		debug_assert!(
+			!ulps_eq!(direction.magnitude2(), Zero::zero()),
+			"'look_at' needs distinct eye and center"
+		);
+		self.position = Vector2::new(eye.x, eye.y);
+		self.angle = Rad::atan2(direction.y, direction.x);
+		self
+	}
+
+	#[inline]
+	pub fn move_global(&mut self, translation: Vector2<f32>) -> &mut Self {
+		self.position += translation;
+		self
+	}
+
+	#[inline]
+	pub fn move_local(&mut self, translation: Vector2<f32>) -> &mut Self {
+		self.position += Matrix2::from_angle(self.angle) * translation;
+		self
+	}
+
+	#[inline]
+	pub fn rotate<A: Into<Rad<f32>>>(&mut self, angle: A) -> &mut Self {
+		self.angle = (self.angle + angle.into()).normalize();
+		self
+	}
+
+	pub fn transform_point(&self, point: Point2<f32>) -> Point2<f32> {
+		let p = self.linear() * Vector2::new(point.x, point.y) + self.position;
+		Point2::new(p.x, p.y)
+	}
+
+	pub fn transform_vector(&self, vec: Vector2<f32>) -> Vector2<f32> {
+		self.linear() * vec
+	}
+
+	pub fn view_matrix(&self) -> Matrix3<f32> {
+		self.matrix().invert().unwrap()
+	}
+
+	/// Splits an affine 2D matrix into position, angle, scale and x skew.
+	///
+	/// Any transform built with only an x skew round-trips exactly.
+	pub fn from_matrix(matrix: &Matrix3<f32>) -> Transform2D {
+		let (x, y) = (matrix.x.truncate(), matrix.y.truncate());
+		let scale_x = x.magnitude();
+		if ulps_eq!(scale_x, 0.) {
+			return Transform2D { position: matrix.z.truncate(), ..Default::default() };
+		}
+		let angle = Rad::atan2(x.y, x.x);
+		// remaining upper-triangular part once the rotation is undone
+		let shear = x.dot(y) / scale_x;
+		let scale_y = (x.x * y.y - x.y * y.x) / scale_x;
+		let skew_x = if ulps_eq!(scale_y, 0.) { Rad::zero() } else { Rad::atan(shear / scale_y) };
+		Transform2D {
+			position: matrix.z.truncate(),
+			angle,
+			scale: Vector2::new(scale_x, scale_y),
+			skew: Vector2::new(skew_x, Rad::zero()),
+		}
+	}
+
+	/// Sets the local transform so the entity ends up at `world` under `parent`.
+	pub fn set_world_matrix(&mut self, parent: &Matrix3<f32>, world: &Matrix3<f32>) -> &mut Self {
+		if let Some(inverse) = parent.invert() {
+			*self = Transform2D::from_matrix(&(inverse * world));
+		}
+		self
+	}
+}
+
+impl Default for Transform2D {
+	fn default() -> Self {
+		Transform2D {
+			position: Vector2::zero(),
+			angle: Rad::zero(),
+			scale: Vector2::from_value(1.),
+			skew: Vector2::new(Rad::zero(), Rad::zero()),
+		}
+	}
+}
+
+impl Component for Transform2D {
+	type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
+}
@@ -1,11 +1,38 @@
+pub use self::constraint::{
+	AxisMask,
+	ClampVolume,
+	Constraint,
+	ConstraintKind,
+	Constraints,
+	OffsetSpace,
+};
 pub use self::local_transform::Transform;
+pub use self::local_transform_2d::Transform2D;
+pub use self::origin::{
+	FloatingOrigin,
+	OriginAnchor,
+	OriginShift,
+};
 pub use self::parent::{
 	HierarchyEvent,
 	Parent,
 	ParentHierarchy,
 };
+pub use self::previous_transform::{
+	InterpolatedTransform,
+	PreviousTransform,
+	Teleported,
+};
+pub use self::subtree::{SubtreeEvent, SubtreePolicy};
 pub use self::transform::GlobalTransform;
+pub use self::transform_2d::GlobalTransform2D;
 
+mod constraint;
+mod origin;
 mod parent;
 mod local_transform;
-mod transform;
\ No newline at end of file
+mod local_transform_2d;
+mod previous_transform;
+mod subtree;
+mod transform;
+mod transform_2d;
\ No newline at end of file
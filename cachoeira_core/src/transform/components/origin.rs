@@ -0,0 +1,56 @@
+use cgmath::{Vector3, Zero};
+use specs::prelude::{
+	Component,
+	NullStorage,
+};
+
+/// Marks the entity (usually the active camera) whose distance from the origin triggers a rebase.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OriginAnchor;
+
+impl Component for OriginAnchor {
+	type Storage = NullStorage<Self>;
+}
+
+/// Resource tracking where the f32 scene origin sits in the f64 world.
+///
+/// Whenever the `OriginAnchor` strays further than `threshold` from the origin, every root is
+/// shifted back by the anchor's position and an `OriginShift` event is fired.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloatingOrigin {
+	pub threshold: f32,
+	/// world position of the scene origin
+	pub offset: Vector3<f64>,
+}
+
+impl FloatingOrigin {
+	pub fn new(threshold: f32) -> Self {
+		FloatingOrigin { threshold, offset: Vector3::zero() }
+	}
+
+	/// Absolute world position of a scene-space position.
+	pub fn to_world(&self, position: Vector3<f32>) -> Vector3<f64> {
+		self.offset + position.cast::<f64>().unwrap()
+	}
+
+	/// Scene-space position of an absolute world position, e.g. a geo-referenced point.
+	pub fn to_scene(&self, position: Vector3<f64>) -> Vector3<f32> {
+		(position - self.offset).cast::<f32>().unwrap()
+	}
+}
+
+impl Default for FloatingOrigin {
+	fn default() -> Self {
+		FloatingOrigin::new(1000.)
+	}
+}
+
+/// Fired after the scene was rebased; world-space state kept outside transforms
+/// should be moved by `shift` too.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OriginShift {
+	/// added to every scene-space position
+	pub shift: Vector3<f32>,
+	/// new `FloatingOrigin::offset`
+	pub origin: Vector3<f64>,
+}
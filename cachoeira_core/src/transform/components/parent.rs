@@ -9,6 +9,10 @@ use specs_hierarchy::{
 	Parent as HParent,
 };
 
+pub use specs_hierarchy::HierarchyEvent;
+
+pub type ParentHierarchy = Hierarchy<Parent>;
+
 #[derive(Debug, Clone, Eq, Ord, PartialEq, PartialOrd)]
 pub struct Parent {
     pub entity: Entity,
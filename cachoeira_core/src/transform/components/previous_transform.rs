@@ -0,0 +1,42 @@
+use cgmath::{Matrix4, One};
+use specs::prelude::{
+	Component,
+	DenseVecStorage,
+	NullStorage,
+};
+
+use transform::Transform;
+
+/// Local transform as it was at the start of the last fixed step.
+///
+/// Opt-in: only entities carrying this component get an `InterpolatedTransform`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PreviousTransform(pub Transform);
+
+impl Component for PreviousTransform {
+	type Storage = DenseVecStorage<Self>;
+}
+
+impl From<Transform> for PreviousTransform {
+	fn from(transform: Transform) -> Self { PreviousTransform(transform) }
+}
+
+/// Marks an entity whose transform jumped this step, so it is not interpolated for one frame.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Teleported;
+
+impl Component for Teleported {
+	type Storage = NullStorage<Self>;
+}
+
+/// World matrix to render with, blended between the previous and current fixed steps.
+#[derive(Debug, Copy, Clone)]
+pub struct InterpolatedTransform(pub Matrix4<f32>);
+
+impl Component for InterpolatedTransform {
+	type Storage = DenseVecStorage<Self>;
+}
+
+impl Default for InterpolatedTransform {
+	fn default() -> Self { InterpolatedTransform(Matrix4::one()) }
+}
@@ -0,0 +1,29 @@
+use specs::prelude::{
+	Component,
+	DenseVecStorage,
+	Entity,
+};
+
+/// What happens to an entity's children when the entity is deleted.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum SubtreePolicy {
+	/// delete the children, and theirs according to their own policies
+	#[default]
+	Cascade,
+	/// attach the children to the closest surviving ancestor, keeping their world pose
+	ReparentToGrandparent,
+	/// turn the children into roots, keeping their world pose
+	DetachToRoot,
+}
+
+impl Component for SubtreePolicy {
+	type Storage = DenseVecStorage<Self>;
+}
+
+/// Fired by the `SubtreeSystem` for every child affected by its parent's deletion.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SubtreeEvent {
+	Deleted(Entity),
+	Reparented { entity: Entity, parent: Entity },
+	Detached(Entity),
+}
@@ -7,7 +7,7 @@ use specs::prelude::{
 	FlaggedStorage,
 };
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Deserialize, Serialize)]
 pub struct GlobalTransform(pub Matrix4<f32>);
 
 impl GlobalTransform {
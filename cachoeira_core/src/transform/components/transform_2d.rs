@@ -0,0 +1,70 @@
+use std::borrow::Borrow;
+
+use cgmath::{Matrix3, Matrix4, One, Vector4};
+use specs::prelude::{
+	Component,
+	DenseVecStorage,
+	FlaggedStorage,
+};
+
+#[derive(Debug, Copy, Clone, Deserialize, Serialize)]
+pub struct GlobalTransform2D(pub Matrix3<f32>);
+
+impl GlobalTransform2D {
+	pub fn new() -> Self { Default::default() }
+
+	pub fn is_finite(&self) -> bool {
+		for i in 0..3 {
+			for j in 0..3 {
+				if !self.0[i][j].is_finite() {
+					return false;
+				}
+			}
+		}
+		true
+	}
+
+	/// Flattens a 3D world matrix onto the XY plane, dropping depth.
+	pub fn from_matrix4(matrix: &Matrix4<f32>) -> Self {
+		GlobalTransform2D(Matrix3::new(
+			matrix.x.x, matrix.x.y, 0.,
+			matrix.y.x, matrix.y.y, 0.,
+			matrix.w.x, matrix.w.y, 1.,
+		))
+	}
+
+	/// Embeds this transform in 3D, acting on the XY plane.
+	pub fn to_matrix4(&self) -> Matrix4<f32> {
+		let m = &self.0;
+		Matrix4 {
+			x: Vector4::new(m.x.x, m.x.y, 0., 0.),
+			y: Vector4::new(m.y.x, m.y.y, 0., 0.),
+			z: Vector4::unit_z(),
+			w: Vector4::new(m.z.x, m.z.y, 0., 1.),
+		}
+	}
+}
+
+impl Component for GlobalTransform2D {
+	type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
+}
+
+impl Default for GlobalTransform2D {
+	fn default() -> Self { GlobalTransform2D(Matrix3::one()) }
+}
+
+impl From<[[f32; 3]; 3]> for GlobalTransform2D {
+	fn from(matrix: [[f32; 3]; 3]) -> Self { GlobalTransform2D(matrix.into()) }
+}
+
+impl From<GlobalTransform2D> for [[f32; 3]; 3] {
+	fn from(transform: GlobalTransform2D) -> Self { transform.0.into() }
+}
+
+impl AsRef<[[f32; 3]; 3]> for GlobalTransform2D {
+	fn as_ref(&self) -> &[[f32; 3]; 3] { self.0.as_ref() }
+}
+
+impl Borrow<[[f32; 3]; 3]> for GlobalTransform2D {
+	fn borrow(&self) -> &[[f32; 3]; 3] { self.0.as_ref() }
+}
@@ -2,7 +2,9 @@
 pub use self::bundle::TransformBundle;
 pub use self::components::*;
 pub use self::systems::*;
+pub use self::world::*;
 
 pub mod components;
 pub mod systems;
-pub mod bundle;
\ No newline at end of file
+pub mod bundle;
+pub mod world;
\ No newline at end of file
@@ -0,0 +1,415 @@
+use cgmath::{
+	EuclideanSpace,
+	Euler,
+	InnerSpace,
+	Point3,
+	Quaternion,
+	Rad,
+	Rotation,
+	Matrix4,
+	Vector3,
+};
+use hibitset::BitSet;
+use specs::prelude::{
+	Entities,
+	Entity,
+	Join,
+	Read,
+	ReadExpect,
+	ReadStorage,
+	System,
+	WriteStorage,
+};
+
+use timing::Time;
+use transform::{
+	ClampVolume,
+	Constraint,
+	ConstraintKind,
+	Constraints,
+	GlobalTransform,
+	OffsetSpace,
+	Parent,
+	ParentHierarchy,
+	Transform,
+	Transform2D,
+};
+use super::transform::local_matrix;
+
+/// Applies `Constraints` on top of the propagated `GlobalTransform`s.
+///
+/// Runs after the `TransformSystem`. Constrained entities are visited parents-first, and the
+/// descendants of a constrained entity are re-propagated so props stay attached to it.
+/// Targets are read as they stand when the constrained entity is visited.
+///
+/// Each frame starts over from the propagated pose, parent global times local, so weights do
+/// not build up on entities that did not move. Entities without a `Transform` or `Transform2D`
+/// are left alone.
+#[derive(Default)]
+pub struct ConstraintSystem {
+	dirty: BitSet,
+}
+
+impl ConstraintSystem {
+	pub fn new() -> Self {
+		ConstraintSystem { dirty: BitSet::default() }
+	}
+}
+
+impl<'a> System<'a> for ConstraintSystem {
+	type SystemData = (
+		Entities<'a>,
+		Read<'a, Time>,
+		ReadExpect<'a, ParentHierarchy>,
+		ReadStorage<'a, Transform>,
+		ReadStorage<'a, Transform2D>,
+		ReadStorage<'a, Parent>,
+		WriteStorage<'a, Constraints>,
+		WriteStorage<'a, GlobalTransform>,
+	);
+
+	fn run(&mut self, (entities, time, hierarchy, locals, locals_2d, parents, mut constraints, mut globals): Self::SystemData) {
+		#[cfg(feature="profiler")]
+		profile_scope!("transform_constraint_system");
+
+		self.dirty.clear();
+		let delta = time.delta_seconds();
+
+		for (entity, constraints, _) in (&*entities, &mut constraints, !&parents).join() {
+			if let Some(local) = local_matrix(entity, &locals, &locals_2d) {
+				if apply_constraints(entity, local, constraints, &mut globals, delta) {
+					self.dirty.add(entity.id());
+				}
+			}
+		}
+
+		for entity in hierarchy.all() {
+			let parent = match parents.get(*entity) {
+				Some(parent) => parent.entity,
+				None => continue,
+			};
+			let constraints = constraints.get_mut(*entity);
+			if constraints.is_none() && !self.dirty.contains(parent.id()) {
+				continue;
+			}
+			let pose = match local_matrix(*entity, &locals, &locals_2d) {
+				Some(local) => globals.get(parent).map_or(local, |parent_global| parent_global.0 * local),
+				None => continue,
+			};
+			let written = match constraints {
+				Some(constraints) => apply_constraints(*entity, pose, constraints, &mut globals, delta),
+				None => match globals.get_mut(*entity) {
+					Some(global) => {
+						global.0 = pose;
+						true
+					}
+					None => false,
+				},
+			};
+			if written {
+				self.dirty.add(entity.id());
+			}
+		}
+	}
+}
+
+// blends each constraint over `propagated`, the unconstrained world matrix of the entity
+fn apply_constraints(
+	entity: Entity,
+	propagated: Matrix4<f32>,
+	constraints: &mut Constraints,
+	globals: &mut WriteStorage<GlobalTransform>,
+	delta: f32,
+) -> bool {
+	if globals.get(entity).is_none() {
+		return false;
+	}
+	let mut pose = Transform::from_matrix(&propagated);
+
+	for constraint in constraints.0.iter_mut().filter(|c| c.enabled && c.weight > 0.) {
+		let target = match constraint.target() {
+			Some(target) => match globals.get(target) {
+				Some(global) => Some(Transform::from_matrix(&global.0)),
+				// dangling targets are skipped rather than snapping to the origin
+				None => continue,
+			},
+			None => None,
+		};
+		let solved = solve(constraint, &pose, target.as_ref(), delta);
+		pose = pose.interpolate(&solved, constraint.weight.min(1.));
+	}
+
+	if let Some(global) = globals.get_mut(entity) {
+		global.0 = pose.matrix();
+		debug_assert!(
+			global.is_finite(),
+			"Entity {:?} had a non-finite constrained transform", entity
+		);
+	}
+	true
+}
+
+fn solve(constraint: &mut Constraint, pose: &Transform, target: Option<&Transform>, delta: f32) -> Transform {
+	let mut solved = pose.clone();
+	match (&constraint.kind, target) {
+		(&ConstraintKind::LookAt { up, .. }, Some(target)) => {
+			let direction = target.translation - pose.translation;
+			if !ulps_eq!(direction.magnitude2(), 0.) {
+				solved.look_at(Point3::from_vec(target.translation), up);
+			}
+		}
+		(&ConstraintKind::Follow { offset, space, damping, .. }, Some(target)) => {
+			let offset = match space {
+				OffsetSpace::World => offset,
+				OffsetSpace::Target => target.rotation.rotate_vector(offset),
+			};
+			let desired = target.translation + offset;
+			let position = match constraint.follow_position {
+				Some(previous) if damping > 0. => previous.lerp(desired, 1. - (-delta / damping).exp()),
+				_ => desired,
+			};
+			constraint.follow_position = Some(position);
+			solved.translation = position;
+		}
+		(&ConstraintKind::CopyPosition { axes, .. }, Some(target)) => {
+			solved.translation = axes.select(pose.translation, target.translation);
+		}
+		(&ConstraintKind::CopyRotation { axes, .. }, Some(target)) => {
+			solved.rotation = if axes.x && axes.y && axes.z {
+				target.rotation
+			} else {
+				let (base, source) = (Euler::from(pose.rotation), Euler::from(target.rotation));
+				let mixed = axes.select(
+					Vector3::new(base.x.0, base.y.0, base.z.0),
+					Vector3::new(source.x.0, source.y.0, source.z.0),
+				);
+				Quaternion::from(Euler::new(Rad(mixed.x), Rad(mixed.y), Rad(mixed.z)))
+			};
+		}
+		(&ConstraintKind::CopyScale { axes, .. }, Some(target)) => {
+			solved.scale = axes.select(pose.scale, target.scale);
+		}
+		(&ConstraintKind::Clamp(ClampVolume::Box { min, max }), _) => {
+			let p = pose.translation;
+			solved.translation = Vector3::new(
+				p.x.max(min.x).min(max.x),
+				p.y.max(min.y).min(max.y),
+				p.z.max(min.z).min(max.z),
+			);
+		}
+		(&ConstraintKind::Clamp(ClampVolume::Sphere { center, radius }), _) => {
+			let offset = pose.translation - center;
+			if offset.magnitude2() > radius * radius {
+				solved.translation = center + offset.normalize_to(radius);
+			}
+		}
+		_ => (),
+	}
+	solved
+}
+
+#[cfg(test)]
+mod tests {
+	use cgmath::{Deg, Euler, Matrix4, Quaternion, Rotation3, Vector3, Vector4};
+	use specs::prelude::{Dispatcher, DispatcherBuilder, Entity, World};
+	use specs_hierarchy::HierarchySystem;
+
+	use timing::Time;
+	use transform::{
+		AxisMask,
+		ClampVolume,
+		Constraint,
+		Constraints,
+		GlobalTransform,
+		OffsetSpace,
+		Parent,
+		Transform,
+		TransformSystem,
+	};
+	use super::ConstraintSystem;
+
+	fn setup() -> (World, Dispatcher<'static, 'static>) {
+		let mut world = World::new();
+		let mut dispatcher = DispatcherBuilder::new()
+			.with(HierarchySystem::<Parent>::new(), "parent_hierarchy_system", &[])
+			.with(TransformSystem::new(), "transform_system", &["parent_hierarchy_system"])
+			.with(ConstraintSystem::new(), "transform_constraint_system", &["transform_system"])
+			.build();
+		dispatcher.setup(&mut world.res);
+		(world, dispatcher)
+	}
+
+	fn spawn(world: &mut World, x: f32, constraints: Option<Constraints>) -> Entity {
+		let transform = Transform { translation: Vector3::new(x, 0., 0.), ..Default::default() };
+		let builder = world.create_entity().with(transform).with(GlobalTransform::default());
+		match constraints {
+			Some(constraints) => builder.with(constraints).build(),
+			None => builder.build(),
+		}
+	}
+
+	fn spawn_with(world: &mut World, transform: Transform, constraint: Option<Constraint>) -> Entity {
+		let builder = world.create_entity().with(transform).with(GlobalTransform::default());
+		match constraint {
+			Some(constraint) => builder.with(Constraints::new().with(constraint)).build(),
+			None => builder.build(),
+		}
+	}
+
+	fn step(world: &mut World, dispatcher: &mut Dispatcher) {
+		dispatcher.dispatch(&world.res);
+		world.maintain();
+	}
+
+	fn global(world: &World, entity: Entity) -> Matrix4<f32> {
+		world.read_storage::<GlobalTransform>().get(entity).unwrap().0
+	}
+
+	fn position(world: &World, entity: Entity) -> Vector3<f32> {
+		global(world, entity).w.truncate()
+	}
+
+	fn forward(world: &World, entity: Entity) -> Vector3<f32> {
+		(global(world, entity) * -Vector4::unit_z()).truncate()
+	}
+
+	#[test]
+	fn weight_holds_on_static_entities() {
+		let (mut world, mut dispatcher) = setup();
+		let target = spawn(&mut world, 10., None);
+		let half = Constraints::new().with(Constraint::copy_position(target, AxisMask::ALL).with_weight(0.5));
+		let follower = spawn(&mut world, 0., Some(half));
+		let prop = world.create_entity()
+			.with(Transform { translation: Vector3::new(0., 1., 0.), ..Default::default() })
+			.with(GlobalTransform::default())
+			.with(Parent { entity: follower })
+			.build();
+
+		// nothing moves after the first frame, so propagation leaves the globals alone
+		for _ in 0..4 {
+			dispatcher.dispatch(&world.res);
+			world.maintain();
+			assert_ulps_eq!(position(&world, follower), Vector3::new(5., 0., 0.));
+			assert_ulps_eq!(position(&world, prop), Vector3::new(5., 1., 0.));
+		}
+	}
+
+	#[test]
+	fn applies_in_order() {
+		let (mut world, mut dispatcher) = setup();
+		let target = spawn(&mut world, 10., None);
+		let clamp = Constraint::clamp(ClampVolume::Box {
+			min: Vector3::new(-4., -4., -4.),
+			max: Vector3::new(4., 4., 4.),
+		});
+		let copy = Constraint::copy_position(target, AxisMask::ALL);
+		let copy_then_clamp = spawn(&mut world, 0., Some(Constraints::new().with(copy.clone()).with(clamp.clone())));
+		let clamp_then_copy = spawn(&mut world, 0., Some(Constraints::new().with(clamp).with(copy)));
+		dispatcher.dispatch(&world.res);
+		world.maintain();
+
+		assert_ulps_eq!(position(&world, copy_then_clamp), Vector3::new(4., 0., 0.));
+		assert_ulps_eq!(position(&world, clamp_then_copy), Vector3::new(10., 0., 0.));
+	}
+
+	#[test]
+	fn looks_at_target() {
+		let (mut world, mut dispatcher) = setup();
+		let target = spawn(&mut world, 5., None);
+		let above = spawn_with(&mut world, Transform { translation: Vector3::new(0., 5., 0.), ..Default::default() }, None);
+		let looker = spawn(&mut world, 0., Some(Constraints::new().with(Constraint::look_at(target))));
+		// the target sits straight along `up`, so another side axis is picked
+		let upward = spawn(&mut world, 0., Some(Constraints::new().with(Constraint::look_at(above))));
+		step(&mut world, &mut dispatcher);
+
+		assert_ulps_eq!(forward(&world, looker), Vector3::new(1., 0., 0.), epsilon = 1e-5);
+		assert_ulps_eq!((global(&world, looker) * Vector4::unit_y()).truncate(), Vector3::new(0., 1., 0.), epsilon = 1e-5);
+		assert_ulps_eq!(forward(&world, upward), Vector3::new(0., 1., 0.), epsilon = 1e-5);
+		assert!(GlobalTransform(global(&world, upward)).is_finite());
+	}
+
+	#[test]
+	fn blends_rotation_by_weight() {
+		let (mut world, mut dispatcher) = setup();
+		let target = spawn(&mut world, 5., None);
+		let looker = spawn(&mut world, 0., Some(Constraints::new().with(Constraint::look_at(target).with_weight(0.5))));
+		step(&mut world, &mut dispatcher);
+
+		// halfway between facing -Z and facing +X
+		let half = 0.5f32.sqrt();
+		assert_ulps_eq!(forward(&world, looker), Vector3::new(half, 0., -half), epsilon = 1e-5);
+	}
+
+	#[test]
+	fn follows_with_damping() {
+		let (mut world, mut dispatcher) = setup();
+		world.write_resource::<Time>().set_delta_seconds(0.5);
+		let target = spawn(&mut world, 0., None);
+		let follower = spawn(&mut world, 0., Some(Constraints::new().with(Constraint::follow(target, Vector3::new(0., 1., 0.), 1.))));
+		// the first frame snaps onto the target
+		step(&mut world, &mut dispatcher);
+		assert_ulps_eq!(position(&world, follower), Vector3::new(0., 1., 0.));
+
+		world.write_storage::<Transform>().get_mut(target).unwrap().translation = Vector3::new(10., 0., 0.);
+		step(&mut world, &mut dispatcher);
+		let eased = 10. * (1. - (-0.5f32).exp());
+		assert_ulps_eq!(position(&world, follower), Vector3::new(eased, 1., 0.), epsilon = 1e-4);
+		step(&mut world, &mut dispatcher);
+		assert!(position(&world, follower).x > eased && position(&world, follower).x < 10.);
+	}
+
+	#[test]
+	fn follows_in_target_space() {
+		let (mut world, mut dispatcher) = setup();
+		let target = spawn_with(&mut world, Transform {
+			translation: Vector3::new(3., 0., 0.),
+			rotation: Quaternion::from_angle_z(Deg(90.)),
+			..Default::default()
+		}, None);
+		let follow = Constraint::follow(target, Vector3::new(2., 0., 0.), 0.).with_offset_space(OffsetSpace::Target);
+		let follower = spawn(&mut world, 0., Some(Constraints::new().with(follow)));
+		step(&mut world, &mut dispatcher);
+
+		// the offset turns with the target, from +X onto +Y
+		assert_ulps_eq!(position(&world, follower), Vector3::new(3., 2., 0.), epsilon = 1e-5);
+	}
+
+	#[test]
+	fn copies_masked_rotation_and_scale() {
+		let (mut world, mut dispatcher) = setup();
+		let target = spawn_with(&mut world, Transform {
+			rotation: Quaternion::from_angle_z(Deg(60.)),
+			scale: Vector3::new(2., 3., 4.),
+			..Default::default()
+		}, None);
+		let tilted = Transform { rotation: Quaternion::from_angle_x(Deg(30.)), ..Default::default() };
+		let z_only = AxisMask { x: false, y: false, z: true };
+		let rotation = spawn_with(&mut world, tilted.clone(), Some(Constraint::copy_rotation(target, z_only)));
+		let all = spawn_with(&mut world, tilted, Some(Constraint::copy_rotation(target, AxisMask::ALL)));
+		let scale = spawn_with(&mut world, Transform::default(), Some(Constraint::copy_scale(target, AxisMask { x: true, y: false, z: true })));
+		step(&mut world, &mut dispatcher);
+
+		let rotation = Transform::from_matrix(&global(&world, rotation)).rotation;
+		let expected = Quaternion::from(Euler::new(Deg(30.), Deg(0.), Deg(60.)));
+		assert_ulps_eq!(rotation, expected, epsilon = 1e-5);
+		let all = Transform::from_matrix(&global(&world, all)).rotation;
+		assert_ulps_eq!(all, Quaternion::from_angle_z(Deg(60.)), epsilon = 1e-5);
+		let scale = Transform::from_matrix(&global(&world, scale)).scale;
+		assert_ulps_eq!(scale, Vector3::new(2., 1., 4.), epsilon = 1e-5);
+	}
+
+	#[test]
+	fn clamps_into_sphere() {
+		let (mut world, mut dispatcher) = setup();
+		let sphere = || Some(Constraints::new().with(Constraint::clamp(ClampVolume::Sphere {
+			center: Vector3::new(1., 0., 0.),
+			radius: 2.,
+		})));
+		let outside = spawn(&mut world, 7., sphere());
+		let inside = spawn(&mut world, -0.5, sphere());
+		step(&mut world, &mut dispatcher);
+
+		assert_ulps_eq!(position(&world, outside), Vector3::new(3., 0., 0.), epsilon = 1e-5);
+		assert_ulps_eq!(position(&world, inside), Vector3::new(-0.5, 0., 0.), epsilon = 1e-5);
+	}
+}
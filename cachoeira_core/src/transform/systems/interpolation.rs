@@ -0,0 +1,228 @@
+use cgmath::{Matrix4, One};
+use specs::prelude::{
+	Entities,
+	Entity,
+	Join,
+	Read,
+	ReadExpect,
+	ReadStorage,
+	System,
+	WriteStorage,
+};
+
+use timing::Time;
+use transform::{
+	GlobalTransform,
+	InterpolatedTransform,
+	Parent,
+	ParentHierarchy,
+	PreviousTransform,
+	Teleported,
+	Transform,
+};
+
+/// Snapshots every `Transform` into its `PreviousTransform`.
+///
+/// Belongs at the start of the fixed-step dispatcher, before anything moves entities.
+#[derive(Default)]
+pub struct PreviousTransformSystem;
+
+impl PreviousTransformSystem {
+	pub fn new() -> Self {
+		Default::default()
+	}
+}
+
+impl<'a> System<'a> for PreviousTransformSystem {
+	type SystemData = (
+		ReadStorage<'a, Transform>,
+		WriteStorage<'a, PreviousTransform>,
+	);
+
+	fn run(&mut self, (locals, mut previous): Self::SystemData) {
+		#[cfg(feature="profiler")]
+		profile_scope!("previous_transform_system");
+
+		for (local, previous) in (&locals, &mut previous).join() {
+			previous.0 = local.clone();
+		}
+	}
+}
+
+/// Produces `InterpolatedTransform`s for rendering between two fixed steps.
+///
+/// Entities with a `PreviousTransform` are blended by `Time::fixed_step_alpha`, and their
+/// descendants follow the blended parent instead of its latest `GlobalTransform`.
+/// `Teleported` entities snap to their current transform and lose the flag.
+#[derive(Default)]
+pub struct TransformInterpolationSystem;
+
+impl TransformInterpolationSystem {
+	pub fn new() -> Self {
+		Default::default()
+	}
+}
+
+impl<'a> System<'a> for TransformInterpolationSystem {
+	type SystemData = (
+		Entities<'a>,
+		Read<'a, Time>,
+		ReadExpect<'a, ParentHierarchy>,
+		ReadStorage<'a, Transform>,
+		ReadStorage<'a, Parent>,
+		ReadStorage<'a, GlobalTransform>,
+		WriteStorage<'a, PreviousTransform>,
+		WriteStorage<'a, Teleported>,
+		WriteStorage<'a, InterpolatedTransform>,
+	);
+
+	fn run(&mut self, (entities, time, hierarchy, locals, parents, globals, mut previous, mut teleported, mut interpolated): Self::SystemData) {
+		#[cfg(feature="profiler")]
+		profile_scope!("transform_interpolation_system");
+
+		let alpha = time.fixed_step_alpha();
+
+		// roots can be blended directly into world space
+		for (entity, local, previous, _) in (&*entities, &locals, &mut previous, !&parents).join() {
+			let matrix = blend(local, previous, teleported.get(entity).is_some(), alpha).matrix();
+			insert_interpolated(&mut interpolated, entity, matrix);
+		}
+
+		// children are visited after their parents, so blended parents are already up to date
+		for entity in hierarchy.all() {
+			let (parent, local) = match (parents.get(*entity), locals.get(*entity)) {
+				(Some(parent), Some(local)) => (parent, local),
+				_ => continue,
+			};
+
+			let parent_interpolated = interpolated.get(parent.entity).map(|i| i.0);
+			let local_matrix = match previous.get_mut(*entity) {
+				Some(previous) => blend(local, previous, teleported.get(*entity).is_some(), alpha).matrix(),
+				None if parent_interpolated.is_some() => local.matrix(),
+				None => continue,
+			};
+
+			let parent_matrix = parent_interpolated
+				.or_else(|| globals.get(parent.entity).map(|g| g.0))
+				.unwrap_or(Matrix4::one());
+			insert_interpolated(&mut interpolated, *entity, parent_matrix * local_matrix);
+		}
+
+		teleported.clear();
+	}
+}
+
+fn blend(local: &Transform, previous: &mut PreviousTransform, teleported: bool, alpha: f32) -> Transform {
+	if teleported {
+		previous.0 = local.clone();
+		local.clone()
+	} else {
+		previous.0.interpolate(local, alpha)
+	}
+}
+
+fn insert_interpolated(storage: &mut WriteStorage<InterpolatedTransform>, entity: Entity, matrix: Matrix4<f32>) {
+	if let Err(err) = storage.insert(entity, InterpolatedTransform(matrix)) {
+		error!("Failure interpolating transform of entity {:?}: {}", entity, err);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use cgmath::{Deg, Matrix4, Quaternion, Rotation3, Vector3};
+	use specs::prelude::{Dispatcher, DispatcherBuilder, Entity, World};
+	use specs_hierarchy::HierarchySystem;
+
+	use timing::Time;
+	use transform::{InterpolatedTransform, Parent, PreviousTransform, Transform};
+	use super::TransformInterpolationSystem;
+
+	fn setup() -> (World, Dispatcher<'static, 'static>) {
+		let mut world = World::new();
+		let mut dispatcher = DispatcherBuilder::new()
+			.with(HierarchySystem::<Parent>::new(), "parent_hierarchy_system", &[])
+			.with(TransformInterpolationSystem::new(), "interpolation_system", &["parent_hierarchy_system"])
+			.build();
+		dispatcher.setup(&mut world.res);
+		(world, dispatcher)
+	}
+
+	// a frame `alpha` of a one second fixed step after the last fixed update
+	fn render(world: &mut World, dispatcher: &mut Dispatcher, alpha: f32) {
+		let mut time = Time::default();
+		time.set_fixed_seconds(1.);
+		time.set_delta_seconds(alpha);
+		world.add_resource(time);
+		dispatcher.dispatch(&world.res);
+		world.maintain();
+	}
+
+	fn interpolated(world: &World, entity: Entity) -> Option<Matrix4<f32>> {
+		world.read_storage::<InterpolatedTransform>().get(entity).map(|i| i.0)
+	}
+
+	#[test]
+	fn blends_by_fixed_step_alpha() {
+		let (mut world, mut dispatcher) = setup();
+		let current = Transform {
+			translation: Vector3::new(10., 0., 0.),
+			rotation: Quaternion::from_angle_z(Deg(90.)),
+			..Default::default()
+		};
+		let root = world.create_entity()
+			.with(current)
+			.with(PreviousTransform::default())
+			.build();
+		let offset = Transform { translation: Vector3::new(0., 1., 0.), ..Default::default() };
+		let child = world.create_entity()
+			.with(offset.clone())
+			.with(PreviousTransform(offset.clone()))
+			.with(Parent { entity: root })
+			.build();
+
+		for &alpha in &[0., 0.5, 1.] {
+			render(&mut world, &mut dispatcher, alpha);
+			let expected = Transform {
+				translation: Vector3::new(10. * alpha, 0., 0.),
+				rotation: Quaternion::from_angle_z(Deg(90. * alpha)),
+				..Default::default()
+			}.matrix();
+			assert_ulps_eq!(interpolated(&world, root).unwrap(), expected, epsilon = 1e-5);
+			assert_ulps_eq!(interpolated(&world, child).unwrap(), expected * offset.matrix(), epsilon = 1e-5);
+		}
+	}
+
+	#[test]
+	fn slerps_rotation_along_shortest_arc() {
+		let from = Transform { rotation: Quaternion::from_angle_z(Deg(10.)), ..Default::default() };
+		// -q is the same rotation as q, blending must not take the long way round
+		let to = Transform { rotation: -Quaternion::from_angle_z(Deg(-70.)), ..Default::default() };
+		for &(alpha, angle) in &[(0., 10.), (0.25, -10.), (0.5, -30.), (1., -70.)] {
+			let blended = from.interpolate(&to, alpha);
+			let expected = Transform { rotation: Quaternion::from_angle_z(Deg(angle)), ..Default::default() };
+			assert_ulps_eq!(blended.matrix(), expected.matrix(), epsilon = 1e-5);
+		}
+	}
+
+	#[test]
+	fn entity_added_mid_step() {
+		let (mut world, mut dispatcher) = setup();
+		let root = world.create_entity()
+			.with(Transform { translation: Vector3::new(4., 0., 0.), ..Default::default() })
+			.with(PreviousTransform::default())
+			.build();
+		render(&mut world, &mut dispatcher, 0.25);
+
+		// spawned after the last fixed step, so it never got a previous transform
+		let local = Transform { translation: Vector3::new(0., 0., 2.), ..Default::default() };
+		let child = world.create_entity().with(local.clone()).with(Parent { entity: root }).build();
+		let loose = world.create_entity().with(local.clone()).build();
+		render(&mut world, &mut dispatcher, 0.5);
+
+		let parent = Matrix4::from_translation(Vector3::new(2., 0., 0.));
+		assert_ulps_eq!(interpolated(&world, root).unwrap(), parent, epsilon = 1e-5);
+		assert_ulps_eq!(interpolated(&world, child).unwrap(), parent * local.matrix(), epsilon = 1e-5);
+		// not opted in, rendered from its GlobalTransform
+		assert_eq!(interpolated(&world, loose), None);
+	}
+}
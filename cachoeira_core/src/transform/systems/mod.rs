@@ -0,0 +1,11 @@
+pub use self::constraint::ConstraintSystem;
+pub use self::interpolation::{PreviousTransformSystem, TransformInterpolationSystem};
+pub use self::origin::FloatingOriginSystem;
+pub use self::subtree::SubtreeSystem;
+pub use self::transform::TransformSystem;
+
+mod constraint;
+mod interpolation;
+mod origin;
+mod subtree;
+mod transform;
@@ -0,0 +1,166 @@
+use cgmath::InnerSpace;
+use shrev::EventChannel;
+use specs::prelude::{
+	Join,
+	ReadStorage,
+	System,
+	Write,
+	WriteStorage,
+};
+
+use transform::{
+	ClampVolume,
+	ConstraintKind,
+	Constraints,
+	FloatingOrigin,
+	GlobalTransform,
+	OriginAnchor,
+	OriginShift,
+	Parent,
+	PreviousTransform,
+	Transform,
+	Transform2D,
+};
+
+/// Keeps the `OriginAnchor` near the origin so f32 transforms stay precise far from it.
+///
+/// Runs before the `TransformSystem`, which then propagates the shifted roots. Reads the
+/// anchor's `GlobalTransform` from the previous frame.
+///
+/// Besides root transforms, the world-space positions held by components are shifted too:
+/// previous transforms, damped `Follow` positions and `Clamp` volumes. Follow offsets and
+/// look-at up vectors are directions and stay as they are.
+#[derive(Default)]
+pub struct FloatingOriginSystem;
+
+impl FloatingOriginSystem {
+	pub fn new() -> Self {
+		Default::default()
+	}
+}
+
+impl<'a> System<'a> for FloatingOriginSystem {
+	type SystemData = (
+		Write<'a, FloatingOrigin>,
+		Write<'a, EventChannel<OriginShift>>,
+		ReadStorage<'a, OriginAnchor>,
+		ReadStorage<'a, Parent>,
+		ReadStorage<'a, GlobalTransform>,
+		WriteStorage<'a, Transform>,
+		WriteStorage<'a, Transform2D>,
+		WriteStorage<'a, PreviousTransform>,
+		WriteStorage<'a, Constraints>,
+	);
+
+	fn run(&mut self, (mut origin, mut events, anchors, parents, globals, mut locals, mut locals_2d, mut previous, mut constraints): Self::SystemData) {
+		#[cfg(feature="profiler")]
+		profile_scope!("floating_origin_system");
+
+		let anchor = match (&anchors, &globals).join().next() {
+			Some((_, global)) => global.0.w.truncate(),
+			None => return,
+		};
+		if anchor.magnitude2() <= origin.threshold * origin.threshold {
+			return;
+		}
+
+		let shift = -anchor;
+		origin.offset -= shift.cast::<f64>().unwrap();
+
+		for (local, _) in (&mut locals, !&parents).join() {
+			local.translation += shift;
+		}
+		for (local, _) in (&mut locals_2d, !&parents).join() {
+			local.position += shift.truncate();
+		}
+		// keep interpolation from streaking across the jump
+		for (previous, _) in (&mut previous, !&parents).join() {
+			previous.0.translation += shift;
+		}
+		for constraints in (&mut constraints).join() {
+			for constraint in constraints.0.iter_mut() {
+				if let Some(ref mut position) = constraint.follow_position {
+					*position += shift;
+				}
+				match constraint.kind {
+					ConstraintKind::Clamp(ClampVolume::Box { ref mut min, ref mut max }) => {
+						*min += shift;
+						*max += shift;
+					}
+					ConstraintKind::Clamp(ClampVolume::Sphere { ref mut center, .. }) => *center += shift,
+					_ => (),
+				}
+			}
+		}
+
+		debug!("Rebased scene origin to {:?}", origin.offset);
+		events.single_write(OriginShift { shift, origin: origin.offset });
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use cgmath::Vector3;
+	use specs::prelude::{DispatcherBuilder, World};
+	use specs_hierarchy::HierarchySystem;
+
+	use transform::{
+		ClampVolume,
+		Constraint,
+		ConstraintKind,
+		ConstraintSystem,
+		Constraints,
+		FloatingOrigin,
+		GlobalTransform,
+		OriginAnchor,
+		Parent,
+		Transform,
+		TransformSystem,
+	};
+	use super::FloatingOriginSystem;
+
+	#[test]
+	fn rebases_past_threshold() {
+		let mut world = World::new();
+		let mut dispatcher = DispatcherBuilder::new()
+			.with(HierarchySystem::<Parent>::new(), "parent_hierarchy_system", &[])
+			.with(FloatingOriginSystem::new(), "floating_origin_system", &[])
+			.with(TransformSystem::new(), "transform_system", &["parent_hierarchy_system", "floating_origin_system"])
+			.with(ConstraintSystem::new(), "transform_constraint_system", &["transform_system"])
+			.build();
+		dispatcher.setup(&mut world.res);
+		world.add_resource(FloatingOrigin::new(100.));
+
+		let at = |x: f32| Transform { translation: Vector3::new(x, 0., 0.), ..Default::default() };
+		let anchor = world.create_entity().with(at(150.)).with(GlobalTransform::default()).with(OriginAnchor).build();
+		let fenced = world.create_entity()
+			.with(at(200.))
+			.with(GlobalTransform::default())
+			.with(Constraints::new().with(Constraint::clamp(ClampVolume::Box {
+				min: Vector3::new(140., -10., -10.),
+				max: Vector3::new(180., 10., 10.),
+			})))
+			.build();
+
+		// the anchor's global is only known after the first frame, the rebase happens on the second
+		for _ in 0..3 {
+			dispatcher.dispatch(&world.res);
+			world.maintain();
+		}
+
+		let origin = world.read_resource::<FloatingOrigin>();
+		assert_eq!(origin.offset, Vector3::new(150., 0., 0.));
+		let globals = world.read_storage::<GlobalTransform>();
+		assert_eq!(globals.get(anchor).unwrap().0.w.truncate(), Vector3::new(0., 0., 0.));
+		let fenced_position = globals.get(fenced).unwrap().0.w.truncate();
+		assert_eq!(fenced_position, Vector3::new(30., 0., 0.));
+		assert_eq!(origin.to_world(fenced_position), Vector3::new(180., 0., 0.));
+		let constraints = world.read_storage::<Constraints>();
+		match constraints.get(fenced).unwrap().0[0].kind {
+			ConstraintKind::Clamp(ClampVolume::Box { min, max }) => {
+				assert_eq!((min.x, max.x), (-10., 30.));
+			}
+			ref other => panic!("unexpected constraint {:?}", other),
+		}
+	}
+}
@@ -0,0 +1,402 @@
+use cgmath::{Matrix4, One};
+use fnv::FnvHashMap;
+use hibitset::BitSet;
+use shrev::EventChannel;
+use specs::prelude::{
+	Entities,
+	Entity,
+	ReadExpect,
+	ReadStorage,
+	ReaderId,
+	Resources,
+	System,
+	Write,
+	WriteStorage,
+};
+
+use transform::{
+	GlobalTransform,
+	GlobalTransform2D,
+	HierarchyEvent,
+	Parent,
+	ParentHierarchy,
+	SubtreeEvent,
+	SubtreePolicy,
+	Transform,
+	Transform2D,
+};
+
+/// Settles the children of deleted entities according to their parents' `SubtreePolicy`,
+/// writing a `SubtreeEvent` for each child it deletes or moves.
+///
+/// Belongs after the `HierarchySystem` and before the `TransformSystem`, which then propagates
+/// the transforms of the moved children.
+#[derive(Default)]
+pub struct SubtreeSystem {
+	tracker: SubtreeTracker,
+	parent_events_id: Option<ReaderId<HierarchyEvent>>,
+}
+
+impl SubtreeSystem {
+	pub fn new() -> Self {
+		Default::default()
+	}
+}
+
+impl<'a> System<'a> for SubtreeSystem {
+	type SystemData = (
+		Entities<'a>,
+		ReadExpect<'a, ParentHierarchy>,
+		ReadStorage<'a, SubtreePolicy>,
+		Write<'a, EventChannel<SubtreeEvent>>,
+		WriteStorage<'a, Parent>,
+		WriteStorage<'a, Transform>,
+		WriteStorage<'a, Transform2D>,
+		ReadStorage<'a, GlobalTransform>,
+		ReadStorage<'a, GlobalTransform2D>,
+	);
+
+	fn run(&mut self, (entities, hierarchy, policies, mut events, mut parents, mut locals, mut locals_2d, globals, globals_2d): Self::SystemData) {
+		#[cfg(feature="profiler")]
+		profile_scope!("subtree_system");
+
+		let removed: Vec<Entity> = hierarchy
+			.changed()
+			.read(self.parent_events_id.as_mut().unwrap())
+			.filter_map(|event| match *event {
+				HierarchyEvent::Removed(entity) => Some(entity),
+				HierarchyEvent::Modified(_) => None,
+			})
+			.collect();
+
+		// delete or detach them according to their former parents' policies
+		if !removed.is_empty() {
+			self.tracker.resolve(removed, &mut SubtreeStorages {
+				entities: &entities,
+				parents: &mut parents,
+				locals: &mut locals,
+				locals_2d: &mut locals_2d,
+				globals: &globals,
+				globals_2d: &globals_2d,
+				events: &mut events,
+			});
+		}
+
+		self.tracker.clear();
+		for entity in hierarchy.all() {
+			if let Some(parent) = parents.get(*entity) {
+				self.tracker.record(*entity, parent.entity, policies.get(parent.entity));
+			}
+		}
+	}
+
+	fn setup(&mut self, res: &mut Resources) {
+		use specs::prelude::SystemData;
+		Self::SystemData::setup(res);
+		self.parent_events_id = Some(res.fetch_mut::<ParentHierarchy>().track());
+	}
+}
+
+// storages touched when settling the children of deleted entities
+struct SubtreeStorages<'s, 'a: 's> {
+	entities: &'s Entities<'a>,
+	parents: &'s mut WriteStorage<'a, Parent>,
+	locals: &'s mut WriteStorage<'a, Transform>,
+	locals_2d: &'s mut WriteStorage<'a, Transform2D>,
+	globals: &'s ReadStorage<'a, GlobalTransform>,
+	globals_2d: &'s ReadStorage<'a, GlobalTransform2D>,
+	events: &'s mut EventChannel<SubtreeEvent>,
+}
+
+// remembers last frame's parent links and non-default policies, since both are gone by the
+// time the hierarchy reports a deleted parent
+#[derive(Default)]
+struct SubtreeTracker {
+	parent_of: FnvHashMap<Entity, Entity>,
+	policy_of: FnvHashMap<Entity, SubtreePolicy>,
+}
+
+impl SubtreeTracker {
+	fn clear(&mut self) {
+		self.parent_of.clear();
+		self.policy_of.clear();
+	}
+
+	fn record(&mut self, child: Entity, parent: Entity, policy: Option<&SubtreePolicy>) {
+		self.parent_of.insert(child, parent);
+		match policy {
+			Some(&SubtreePolicy::Cascade) | None => (),
+			Some(policy) => {
+				self.policy_of.insert(parent, *policy);
+			}
+		}
+	}
+
+	fn depth(&self, entity: Entity) -> usize {
+		let mut depth = 0;
+		let mut current = entity;
+		while let Some(parent) = self.parent_of.get(&current) {
+			depth += 1;
+			current = *parent;
+			if depth > self.parent_of.len() {
+				break;
+			}
+		}
+		depth
+	}
+
+	// applies the parents' policies to entities the hierarchy reported as removed; moved
+	// children get their locals rewritten, which flags them for the `TransformSystem`
+	fn resolve(&self, mut removed: Vec<Entity>, storages: &mut SubtreeStorages) {
+		removed.sort_by_key(|entity| self.depth(*entity));
+		removed.dedup();
+
+		let entities = storages.entities;
+		let mut deleted = BitSet::new();
+		for entity in removed {
+			if !survives(entities, &deleted, entity) {
+				continue;
+			}
+
+			let old_parent = match self.parent_of.get(&entity) {
+				Some(parent) => *parent,
+				None => continue,
+			};
+			if survives(entities, &deleted, old_parent) {
+				// not a deletion: detached by hand, or below a child that was kept; make sure
+				// the hierarchy picks the link up again
+				if let Some(parent) = storages.parents.get(entity).cloned() {
+					if survives(entities, &deleted, parent.entity) {
+						insert_parent(storages.parents, entity, parent.entity);
+					}
+				}
+				continue;
+			}
+
+			match self.policy_of.get(&old_parent).cloned().unwrap_or_default() {
+				SubtreePolicy::Cascade => {
+					if let Err(err) = entities.delete(entity) {
+						error!("Failure removing entity {:?}: {}", entity, err);
+					}
+					deleted.add(entity.id());
+					storages.events.single_write(SubtreeEvent::Deleted(entity));
+				}
+				SubtreePolicy::ReparentToGrandparent => {
+					let mut ancestor = self.parent_of.get(&old_parent).cloned();
+					while let Some(candidate) = ancestor {
+						if survives(entities, &deleted, candidate) {
+							break;
+						}
+						ancestor = self.parent_of.get(&candidate).cloned();
+					}
+					attach(entity, ancestor, storages);
+					storages.events.single_write(match ancestor {
+						Some(parent) => SubtreeEvent::Reparented { entity, parent },
+						None => SubtreeEvent::Detached(entity),
+					});
+				}
+				SubtreePolicy::DetachToRoot => {
+					attach(entity, None, storages);
+					storages.events.single_write(SubtreeEvent::Detached(entity));
+				}
+			}
+		}
+	}
+}
+
+// moves `entity` under `parent` (or to the root) without changing its world pose
+fn attach(entity: Entity, parent: Option<Entity>, storages: &mut SubtreeStorages) {
+	let (globals, globals_2d) = (storages.globals, storages.globals_2d);
+	let world_matrix = |entity: Entity| globals.get(entity).map(|global| global.0)
+		.or_else(|| globals_2d.get(entity).map(|global| global.to_matrix4()));
+
+	if let Some(world) = world_matrix(entity) {
+		let parent_world = parent.and_then(world_matrix).unwrap_or(Matrix4::one());
+		if let Some(local) = storages.locals.get_mut(entity) {
+			local.set_world_matrix(&parent_world, &world);
+		}
+		if let Some(local) = storages.locals_2d.get_mut(entity) {
+			local.set_world_matrix(
+				&GlobalTransform2D::from_matrix4(&parent_world).0,
+				&GlobalTransform2D::from_matrix4(&world).0,
+			);
+		}
+	}
+
+	match parent {
+		Some(parent) => insert_parent(storages.parents, entity, parent),
+		None => {
+			storages.parents.remove(entity);
+		}
+	}
+}
+
+fn survives(entities: &Entities, deleted: &BitSet, entity: Entity) -> bool {
+	entities.is_alive(entity) && !deleted.contains(entity.id())
+}
+
+// the hierarchy already dropped `entity`, so the link is removed and inserted again: a plain
+// overwrite would only flag it as modified, which the hierarchy expects of entities it still holds
+fn insert_parent(parents: &mut WriteStorage<Parent>, entity: Entity, parent: Entity) {
+	parents.remove(entity);
+	if let Err(err) = parents.insert(entity, Parent { entity: parent }) {
+		error!("Failure reparenting entity {:?}: {}", entity, err);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use cgmath::Vector3;
+	use shrev::{EventChannel, ReaderId};
+	use specs::prelude::{Dispatcher, DispatcherBuilder, Entity, World};
+	use specs_hierarchy::HierarchySystem;
+
+	use transform::{
+		GlobalTransform,
+		Parent,
+		SubtreeEvent,
+		SubtreePolicy,
+		SubtreeSystem,
+		Transform,
+		TransformSystem,
+	};
+
+	struct Scene {
+		world: World,
+		dispatcher: Dispatcher<'static, 'static>,
+		events: ReaderId<SubtreeEvent>,
+	}
+
+	impl Scene {
+		fn new() -> Self {
+			let mut world = World::new();
+			let mut dispatcher = DispatcherBuilder::new()
+				.with(HierarchySystem::<Parent>::new(), "parent_hierarchy_system", &[])
+				.with(SubtreeSystem::new(), "subtree_system", &["parent_hierarchy_system"])
+				.with(TransformSystem::new(), "transform_system", &["subtree_system"])
+				.build();
+			dispatcher.setup(&mut world.res);
+			let events = world.write_resource::<EventChannel<SubtreeEvent>>().register_reader();
+			Scene { world, dispatcher, events }
+		}
+
+		fn spawn(&mut self, parent: Option<Entity>, offset: [f32; 3], policy: Option<SubtreePolicy>) -> Entity {
+			let transform = Transform { translation: offset.into(), ..Default::default() };
+			let mut builder = self.world.create_entity().with(transform).with(GlobalTransform::default());
+			if let Some(entity) = parent {
+				builder = builder.with(Parent { entity });
+			}
+			if let Some(policy) = policy {
+				builder = builder.with(policy);
+			}
+			builder.build()
+		}
+
+		// a few frames, enough for deletions to settle
+		fn run(&mut self) -> Vec<SubtreeEvent> {
+			for _ in 0..3 {
+				self.dispatcher.dispatch(&self.world.res);
+				self.world.maintain();
+			}
+			self.world.read_resource::<EventChannel<SubtreeEvent>>().read(&mut self.events).cloned().collect()
+		}
+
+		fn parent(&self, entity: Entity) -> Option<Entity> {
+			self.world.read_storage::<Parent>().get(entity).map(|parent| parent.entity)
+		}
+
+		fn position(&self, entity: Entity) -> Vector3<f32> {
+			self.world.read_storage::<GlobalTransform>().get(entity).unwrap().0.w.truncate()
+		}
+	}
+
+	#[test]
+	fn cascade_deletes_subtree() {
+		let mut scene = Scene::new();
+		let root = scene.spawn(None, [1., 0., 0.], None);
+		let child = scene.spawn(Some(root), [0., 1., 0.], None);
+		let grandchild = scene.spawn(Some(child), [0., 0., 1.], Some(SubtreePolicy::DetachToRoot));
+		let sibling = scene.spawn(None, [0., 0., 0.], None);
+		scene.run();
+
+		scene.world.delete_entity(root).unwrap();
+		let events = scene.run();
+		assert!(!scene.world.is_alive(child) && !scene.world.is_alive(grandchild));
+		assert!(scene.world.is_alive(sibling));
+		assert_eq!(events, vec![SubtreeEvent::Deleted(child), SubtreeEvent::Deleted(grandchild)]);
+	}
+
+	#[test]
+	fn reparents_to_grandparent() {
+		let mut scene = Scene::new();
+		let root = scene.spawn(None, [1., 0., 0.], None);
+		let middle = scene.spawn(Some(root), [0., 1., 0.], Some(SubtreePolicy::ReparentToGrandparent));
+		let child = scene.spawn(Some(middle), [0., 0., 1.], None);
+		let grandchild = scene.spawn(Some(child), [0., 0., 1.], None);
+		scene.run();
+
+		scene.world.delete_entity(middle).unwrap();
+		let events = scene.run();
+		assert_eq!(events, vec![SubtreeEvent::Reparented { entity: child, parent: root }]);
+		assert_eq!(scene.parent(child), Some(root));
+		assert_eq!(scene.parent(grandchild), Some(child));
+		assert_ulps_eq!(scene.position(child), Vector3::new(1., 1., 1.));
+		assert_ulps_eq!(scene.position(grandchild), Vector3::new(1., 1., 2.));
+
+		// later moves of the new parent still reach the whole subtree
+		scene.world.write_storage::<Transform>().get_mut(root).unwrap().translation = Vector3::new(5., 0., 0.);
+		scene.run();
+		assert_ulps_eq!(scene.position(grandchild), Vector3::new(5., 1., 2.));
+	}
+
+	#[test]
+	fn detaches_to_root() {
+		let mut scene = Scene::new();
+		let root = scene.spawn(None, [1., 0., 0.], Some(SubtreePolicy::DetachToRoot));
+		let child = scene.spawn(Some(root), [0., 1., 0.], None);
+		let grandchild = scene.spawn(Some(child), [0., 0., 1.], None);
+		scene.run();
+
+		scene.world.delete_entity(root).unwrap();
+		let events = scene.run();
+		assert_eq!(events, vec![SubtreeEvent::Detached(child)]);
+		assert_eq!(scene.parent(child), None);
+		assert_eq!(scene.parent(grandchild), Some(child));
+		assert_eq!(scene.world.read_storage::<Transform>().get(child).unwrap().translation, Vector3::new(1., 1., 0.));
+		assert_ulps_eq!(scene.position(grandchild), Vector3::new(1., 1., 1.));
+	}
+
+	#[test]
+	fn mixed_policies_in_nested_deletions() {
+		let mut scene = Scene::new();
+		let root = scene.spawn(None, [1., 0., 0.], None);
+		let a = scene.spawn(Some(root), [0., 1., 0.], Some(SubtreePolicy::ReparentToGrandparent));
+		let b = scene.spawn(Some(a), [0., 0., 1.], Some(SubtreePolicy::Cascade));
+		let c = scene.spawn(Some(b), [1., 0., 0.], Some(SubtreePolicy::DetachToRoot));
+		let d = scene.spawn(Some(c), [0., 1., 0.], None);
+		let e = scene.spawn(Some(a), [0., 0., 2.], None);
+		let f = scene.spawn(Some(e), [0., 0., 1.], None);
+		scene.run();
+
+		// a and b go together: c follows b's policy, d then follows c's
+		scene.world.delete_entity(a).unwrap();
+		scene.world.delete_entity(b).unwrap();
+		let mut events = scene.run();
+		events.sort_by_key(|event| format!("{:?}", event));
+		let mut expected = vec![
+			SubtreeEvent::Deleted(c),
+			SubtreeEvent::Detached(d),
+			SubtreeEvent::Reparented { entity: e, parent: root },
+		];
+		expected.sort_by_key(|event| format!("{:?}", event));
+		assert_eq!(events, expected);
+
+		assert!(!scene.world.is_alive(c));
+		assert_eq!(scene.parent(d), None);
+		assert_ulps_eq!(scene.position(d), Vector3::new(2., 2., 1.));
+		assert_eq!(scene.parent(e), Some(root));
+		assert_eq!(scene.parent(f), Some(e));
+		assert_ulps_eq!(scene.position(e), Vector3::new(1., 1., 2.));
+		assert_ulps_eq!(scene.position(f), Vector3::new(1., 1., 3.));
+	}
+}
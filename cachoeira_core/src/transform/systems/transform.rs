@@ -0,0 +1,316 @@
+use std::ops::Deref;
+
+use cgmath::Matrix4;
+use fnv::FnvHashMap;
+use hibitset::BitSet;
+
+use specs::prelude::{
+	Entities,
+	Entity,
+	InsertedFlag,
+	Join,
+	ModifiedFlag,
+	ReadExpect,
+	ReadStorage,
+	ReaderId,
+	Resources,
+	System,
+	WriteStorage
+};
+use specs::storage::{MaskedStorage, Storage};
+
+use transform::{
+	GlobalTransform,
+	GlobalTransform2D,
+	HierarchyEvent,
+	Parent,
+	ParentHierarchy,
+	Transform,
+	Transform2D,
+};
+
+
+pub struct TransformSystem {
+	local_modified: BitSet,
+	global_modified: BitSet,
+	// full world matrices of entities with only a GlobalTransform2D, so their 3D descendants
+	// keep the depth and tilt of 3D ancestors above them
+	worlds_2d: FnvHashMap<Entity, Matrix4<f32>>,
+
+	inserted_local_id: Option<ReaderId<InsertedFlag>>,
+	modified_local_id: Option<ReaderId<ModifiedFlag>>,
+	inserted_local_2d_id: Option<ReaderId<InsertedFlag>>,
+	modified_local_2d_id: Option<ReaderId<ModifiedFlag>>,
+
+	parent_events_id: Option<ReaderId<HierarchyEvent>>,
+}
+
+impl TransformSystem {
+	pub fn new() -> TransformSystem {
+		TransformSystem {
+			inserted_local_id: None,
+			modified_local_id: None,
+			inserted_local_2d_id: None,
+			modified_local_2d_id: None,
+			parent_events_id: None,
+			local_modified: BitSet::default(),
+			global_modified: BitSet::default(),
+			worlds_2d: FnvHashMap::default(),
+		}
+	}
+}
+
+impl<'a> System<'a> for TransformSystem {
+	type SystemData = (
+		Entities<'a>,
+		ReadExpect<'a, ParentHierarchy>,
+		ReadStorage<'a, Transform>,
+		ReadStorage<'a, Transform2D>,
+		ReadStorage<'a, Parent>,
+		WriteStorage<'a, GlobalTransform>,
+		WriteStorage<'a, GlobalTransform2D>,
+	);
+
+	fn run(&mut self, (entities, hierarchy, locals, locals_2d, parents, mut globals, mut globals_2d): Self::SystemData) {
+		#[cfg(feature="profiler")]
+		profile_scope!("transform_system");
+
+		self.local_modified.clear();
+		self.global_modified.clear();
+		self.worlds_2d.retain(|entity, _| entities.is_alive(*entity));
+
+		locals.populate_inserted(
+			self.inserted_local_id.as_mut().unwrap(),
+			&mut self.local_modified,
+		);
+		locals.populate_modified(
+			self.modified_local_id.as_mut().unwrap(),
+			&mut self.local_modified,
+		);
+		locals_2d.populate_inserted(
+			self.inserted_local_2d_id.as_mut().unwrap(),
+			&mut self.local_modified,
+		);
+		locals_2d.populate_modified(
+			self.modified_local_2d_id.as_mut().unwrap(),
+			&mut self.local_modified,
+		);
+
+		for event in hierarchy
+			.changed()
+			.read(self.parent_events_id.as_mut().unwrap())
+		{
+			// collect all modified entities; removed ones are recomputed as roots unless deleted
+			match *event {
+				HierarchyEvent::Removed(entity) | HierarchyEvent::Modified(entity) => {
+					self.local_modified.add(entity.id());
+				}
+			}
+		}
+
+		// chain children to parents that were transformed previously
+		for (entity, _, _) in (
+			&*entities,
+			&self.local_modified,
+			!&parents,
+		).join()
+		{
+			if let Some(local) = local_matrix(entity, &locals, &locals_2d) {
+				if write_globals(entity, local, &mut globals, &mut globals_2d, &mut self.worlds_2d) {
+					self.global_modified.add(entity.id());
+				}
+			}
+		}
+
+		// compute the modified transforms with the parents
+		for entity in hierarchy.all() {
+			let self_dirty = self.local_modified.contains(entity.id());
+			if let (Some(parent), Some(local)) = (parents.get(*entity), local_matrix(*entity, &locals, &locals_2d)) {
+				let parent_dirty = self.global_modified.contains(parent.entity.id());
+				if parent_dirty || self_dirty {
+					// 2D parents act on the XY plane of their 3D children and vice versa
+					let parent_global = globals.get(parent.entity).map(|global| global.0)
+						.or_else(|| self.worlds_2d.get(&parent.entity).cloned())
+						.or_else(|| globals_2d.get(parent.entity).map(|global| global.to_matrix4()));
+					let combined_transform = match parent_global {
+						Some(parent_global) => parent_global * local,
+						None => local,
+					};
+
+					if write_globals(*entity, combined_transform, &mut globals, &mut globals_2d, &mut self.worlds_2d) {
+						self.global_modified.add(entity.id());
+					}
+				}
+			}
+		}
+	}
+
+	fn setup(&mut self, res: &mut Resources) {
+		use specs::prelude::SystemData;
+		Self::SystemData::setup(res);
+		let mut hierarchy = res.fetch_mut::<ParentHierarchy>();
+		let mut locals = WriteStorage::<Transform>::fetch(res);
+		let mut locals_2d = WriteStorage::<Transform2D>::fetch(res);
+		self.parent_events_id = Some(hierarchy.track());
+		self.inserted_local_id = Some(locals.track_inserted());
+		self.modified_local_id = Some(locals.track_modified());
+		self.inserted_local_2d_id = Some(locals_2d.track_inserted());
+		self.modified_local_2d_id = Some(locals_2d.track_modified());
+	}
+}
+
+pub(crate) fn local_matrix<L, L2>(
+	entity: Entity,
+	locals: &Storage<Transform, L>,
+	locals_2d: &Storage<Transform2D, L2>,
+) -> Option<Matrix4<f32>>
+where
+	L: Deref<Target = MaskedStorage<Transform>>,
+	L2: Deref<Target = MaskedStorage<Transform2D>>,
+{
+	locals.get(entity).map(Transform::matrix)
+		.or_else(|| locals_2d.get(entity).map(Transform2D::matrix4))
+}
+
+// writes whichever of the 3D and 2D globals the entity carries, remembering the full matrix of
+// 2D-only entities
+fn write_globals(
+	entity: Entity,
+	matrix: Matrix4<f32>,
+	globals: &mut WriteStorage<GlobalTransform>,
+	globals_2d: &mut WriteStorage<GlobalTransform2D>,
+	worlds_2d: &mut FnvHashMap<Entity, Matrix4<f32>>,
+) -> bool {
+	let mut written = false;
+	if let Some(global) = globals.get_mut(entity) {
+		global.0 = matrix;
+		debug_assert!(
+			global.is_finite(),
+			"Entity {:?} had a non-finite Transform", entity
+		);
+		written = true;
+	}
+	if let Some(global) = globals_2d.get_mut(entity) {
+		*global = GlobalTransform2D::from_matrix4(&matrix);
+		debug_assert!(
+			global.is_finite(),
+			"Entity {:?} had a non-finite Transform2D", entity
+		);
+		if !written {
+			worlds_2d.insert(entity, matrix);
+		}
+		written = true;
+	}
+	written
+}
+
+#[cfg(test)]
+mod tests {
+	use cgmath::{Deg, Matrix3, Quaternion, Rad, Rotation3, Vector2, Vector3};
+	use specs::prelude::{Dispatcher, DispatcherBuilder, World};
+	use specs_hierarchy::HierarchySystem;
+
+	use transform::{GlobalTransform, GlobalTransform2D, Parent, Transform, Transform2D};
+	use super::TransformSystem;
+
+	fn setup() -> (World, Dispatcher<'static, 'static>) {
+		let mut world = World::new();
+		let mut dispatcher = DispatcherBuilder::new()
+			.with(HierarchySystem::<Parent>::new(), "parent_hierarchy_system", &[])
+			.with(TransformSystem::new(), "transform_system", &["parent_hierarchy_system"])
+			.build();
+		dispatcher.setup(&mut world.res);
+		(world, dispatcher)
+	}
+
+	#[test]
+	fn propagates_transform_2d() {
+		let (mut world, mut dispatcher) = setup();
+		let parent = Transform2D::new(Vector2::new(3., 0.), Rad::from(Deg(90.)), Vector2::new(2., 2.));
+		let child = Transform2D::new(Vector2::new(1., 0.), Rad::from(Deg(-90.)), Vector2::new(1., 0.5));
+		let root = world.create_entity().with(parent.clone()).with(GlobalTransform2D::default()).build();
+		let leaf = world.create_entity()
+			.with(child.clone())
+			.with(GlobalTransform2D::default())
+			.with(Parent { entity: root })
+			.build();
+		dispatcher.dispatch(&world.res);
+		world.maintain();
+
+		let globals = world.read_storage::<GlobalTransform2D>();
+		assert_ulps_eq!(globals.get(root).unwrap().0, parent.matrix(), epsilon = 1e-5);
+		let expected = Matrix3::new(
+			2., 0., 0.,
+			0., 1., 0.,
+			3., 2., 1.,
+		);
+		assert_ulps_eq!(globals.get(leaf).unwrap().0, expected, epsilon = 1e-5);
+	}
+
+	#[test]
+	fn mixes_2d_and_3d_parents() {
+		let (mut world, mut dispatcher) = setup();
+		let root = world.create_entity()
+			.with(Transform {
+				translation: Vector3::new(1., 2., 3.),
+				rotation: Quaternion::from_angle_z(Deg(90.)),
+				..Default::default()
+			})
+			.with(GlobalTransform::default())
+			.build();
+		// 2D under 3D: placed on the parent's XY plane
+		let sprite = world.create_entity()
+			.with(Transform2D::new(Vector2::new(2., 0.), Rad(0.), Vector2::new(1., 1.)))
+			.with(GlobalTransform2D::default())
+			.with(Parent { entity: root })
+			.build();
+		// 3D under 2D: keeps the grandparent's depth, which the 2D global flattens away
+		let model = world.create_entity()
+			.with(Transform { translation: Vector3::new(1., 0., 5.), ..Default::default() })
+			.with(GlobalTransform::default())
+			.with(Parent { entity: sprite })
+			.build();
+		dispatcher.dispatch(&world.res);
+		world.maintain();
+
+		let expected = Matrix3::new(
+			0., 1., 0.,
+			-1., 0., 0.,
+			1., 4., 1.,
+		);
+		let sprite_global = world.read_storage::<GlobalTransform2D>().get(sprite).unwrap().0;
+		assert_ulps_eq!(sprite_global, expected, epsilon = 1e-5);
+		let model_global = world.read_storage::<GlobalTransform>().get(model).unwrap().0;
+		assert_ulps_eq!(model_global.w.truncate(), Vector3::new(1., 5., 8.), epsilon = 1e-5);
+		assert_ulps_eq!(model_global.x.truncate(), Vector3::new(0., 1., 0.), epsilon = 1e-5);
+	}
+
+	#[test]
+	fn keeps_3d_ancestors_across_2d_nodes() {
+		let (mut world, mut dispatcher) = setup();
+		let root = world.create_entity()
+			.with(Transform { rotation: Quaternion::from_angle_x(Deg(90.)), ..Default::default() })
+			.with(GlobalTransform::default())
+			.build();
+		let layer = world.create_entity()
+			.with(Transform2D::default())
+			.with(GlobalTransform2D::default())
+			.with(Parent { entity: root })
+			.build();
+		let model = world.create_entity()
+			.with(Transform { translation: Vector3::new(0., 1., 0.), ..Default::default() })
+			.with(GlobalTransform::default())
+			.with(Parent { entity: layer })
+			.build();
+		dispatcher.dispatch(&world.res);
+		world.maintain();
+		let position = |world: &World| world.read_storage::<GlobalTransform>().get(model).unwrap().0.w.truncate();
+		assert_ulps_eq!(position(&world), Vector3::new(0., 0., 1.), epsilon = 1e-5);
+
+		// only the 3D root moves; the change still crosses the 2D node
+		world.write_storage::<Transform>().get_mut(root).unwrap().translation = Vector3::new(0., 0., 4.);
+		dispatcher.dispatch(&world.res);
+		world.maintain();
+		assert_ulps_eq!(position(&world), Vector3::new(0., 0., 5.), epsilon = 1e-5);
+	}
+}
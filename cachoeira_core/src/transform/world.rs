@@ -0,0 +1,419 @@
+//! World-space queries and edits on top of local `Transform`s.
+//!
+//! World poses are read from `GlobalTransform`, so they reflect the last run of the
+//! `TransformSystem`; edits go to the local `Transform` and propagate on its next run.
+
+use std::ops::Deref;
+
+use cgmath::{
+	ElementWise,
+	EuclideanSpace,
+	InnerSpace,
+	Matrix3, Matrix4,
+	One,
+	Point3,
+	Quaternion,
+	SquareMatrix,
+	Transform as CgTransform,
+	Vector3,
+};
+use specs::prelude::{Entity, WriteStorage};
+use specs::storage::{MaskedStorage, Storage};
+
+use transform::{GlobalTransform, GlobalTransform2D, Parent, Transform, Transform2D};
+
+/// What happens to an entity's local transform when its parent changes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReparentMode {
+	/// adjust the local transform so the entity stays where it is in the world
+	KeepWorld,
+	/// keep the local transform, so the entity moves along with its new parent
+	KeepLocal,
+	/// reset the local transform, snapping the entity onto its new parent
+	Reset,
+}
+
+impl Transform {
+	pub fn world_matrix(&self, parent: &Matrix4<f32>) -> Matrix4<f32> {
+		parent * self.matrix()
+	}
+
+	pub fn world_position(&self, parent: &Matrix4<f32>) -> Vector3<f32> {
+		parent.transform_point(Point3::from_vec(self.translation)).to_vec()
+	}
+
+	pub fn set_world_position(&mut self, parent: &Matrix4<f32>, position: Vector3<f32>) -> &mut Self {
+		if let Some(inverse) = parent.invert() {
+			self.translation = inverse.transform_point(Point3::from_vec(position)).to_vec();
+		}
+		self
+	}
+
+	pub fn world_rotation(&self, parent: &Matrix4<f32>) -> Quaternion<f32> {
+		matrix_rotation(parent) * self.rotation
+	}
+
+	pub fn set_world_rotation(&mut self, parent: &Matrix4<f32>, rotation: Quaternion<f32>) -> &mut Self {
+		self.rotation = (matrix_rotation(parent).conjugate() * rotation).normalize();
+		self
+	}
+
+	/// Lossy when the parent is rotated and non-uniformly scaled, as shear is not representable.
+	pub fn world_scale(&self, parent: &Matrix4<f32>) -> Vector3<f32> {
+		matrix_scale(parent).mul_element_wise(self.scale)
+	}
+
+	pub fn set_world_scale(&mut self, parent: &Matrix4<f32>, scale: Vector3<f32>) -> &mut Self {
+		let parent_scale = matrix_scale(parent);
+		if !ulps_eq!(parent_scale.x * parent_scale.y * parent_scale.z, 0.) {
+			self.scale = scale.div_element_wise(parent_scale);
+		}
+		self
+	}
+
+	/// Sets the whole local transform from a world matrix.
+	pub fn set_world_matrix(&mut self, parent: &Matrix4<f32>, world: &Matrix4<f32>) -> &mut Self {
+		if let Some(inverse) = parent.invert() {
+			*self = Transform::from_matrix(&(inverse * world));
+		}
+		self
+	}
+}
+
+impl GlobalTransform {
+	pub fn position(&self) -> Vector3<f32> {
+		self.0.w.truncate()
+	}
+
+	pub fn rotation(&self) -> Quaternion<f32> {
+		matrix_rotation(&self.0)
+	}
+
+	pub fn scale(&self) -> Vector3<f32> {
+		matrix_scale(&self.0)
+	}
+}
+
+/// World matrix of `entity`'s parent, or identity for roots.
+pub fn parent_matrix<P, G>(
+	entity: Entity,
+	parents: &Storage<Parent, P>,
+	globals: &Storage<GlobalTransform, G>,
+) -> Matrix4<f32>
+where
+	P: Deref<Target = MaskedStorage<Parent>>,
+	G: Deref<Target = MaskedStorage<GlobalTransform>>,
+{
+	parents.get(entity)
+		.and_then(|parent| globals.get(parent.entity))
+		.map(|global| global.0)
+		.unwrap_or(Matrix4::identity())
+}
+
+/// Moves `entity` under `new_parent`, or to the root when `None`.
+///
+/// Entities with a `Transform2D` keep their place on the XY plane; 2D and 3D parents mix as
+/// they do in the `TransformSystem`.
+#[allow(clippy::too_many_arguments)]
+pub fn reparent<G, G2>(
+	entity: Entity,
+	new_parent: Option<Entity>,
+	mode: ReparentMode,
+	parents: &mut WriteStorage<Parent>,
+	locals: &mut WriteStorage<Transform>,
+	locals_2d: &mut WriteStorage<Transform2D>,
+	globals: &Storage<GlobalTransform, G>,
+	globals_2d: &Storage<GlobalTransform2D, G2>,
+) where
+	G: Deref<Target = MaskedStorage<GlobalTransform>>,
+	G2: Deref<Target = MaskedStorage<GlobalTransform2D>>,
+{
+	let world_matrix = |entity: Entity| globals.get(entity).map(|global| global.0)
+		.or_else(|| globals_2d.get(entity).map(|global| global.to_matrix4()));
+	let world = world_matrix(entity);
+	let parent_world = new_parent
+		.and_then(world_matrix)
+		.unwrap_or(Matrix4::identity());
+
+	if let Some(local) = locals.get_mut(entity) {
+		match mode {
+			ReparentMode::KeepWorld => if let Some(world) = world {
+				local.set_world_matrix(&parent_world, &world);
+			},
+			ReparentMode::KeepLocal => (),
+			ReparentMode::Reset => *local = Transform::default(),
+		}
+	}
+	if let Some(local) = locals_2d.get_mut(entity) {
+		match mode {
+			ReparentMode::KeepWorld => if let Some(world) = world {
+				local.set_world_matrix(
+					&GlobalTransform2D::from_matrix4(&parent_world).0,
+					&GlobalTransform2D::from_matrix4(&world).0,
+				);
+			},
+			ReparentMode::KeepLocal => (),
+			ReparentMode::Reset => *local = Transform2D::default(),
+		}
+	}
+
+	match new_parent {
+		Some(parent) => if let Err(err) = parents.insert(entity, Parent { entity: parent }) {
+			error!("Failure reparenting entity {:?}: {}", entity, err);
+		},
+		None => {
+			parents.remove(entity);
+		}
+	}
+}
+
+/// Maps a point in `from`'s local space into `to`'s local space.
+pub fn point_between<G>(
+	from: Entity,
+	to: Entity,
+	point: Point3<f32>,
+	globals: &Storage<GlobalTransform, G>,
+) -> Option<Point3<f32>>
+where
+	G: Deref<Target = MaskedStorage<GlobalTransform>>,
+{
+	let (from, to) = (globals.get(from)?, globals.get(to)?);
+	to.0.invert().map(|inverse| inverse.transform_point(from.0.transform_point(point)))
+}
+
+/// Maps a direction in `from`'s local space into `to`'s local space, ignoring translation.
+pub fn direction_between<G>(
+	from: Entity,
+	to: Entity,
+	direction: Vector3<f32>,
+	globals: &Storage<GlobalTransform, G>,
+) -> Option<Vector3<f32>>
+where
+	G: Deref<Target = MaskedStorage<GlobalTransform>>,
+{
+	let (from, to) = (globals.get(from)?, globals.get(to)?);
+	to.0.invert().map(|inverse| inverse.transform_vector(from.0.transform_vector(direction)))
+}
+
+fn matrix_scale(matrix: &Matrix4<f32>) -> Vector3<f32> {
+	Vector3::new(
+		matrix.x.truncate().magnitude(),
+		matrix.y.truncate().magnitude(),
+		matrix.z.truncate().magnitude(),
+	)
+}
+
+fn matrix_rotation(matrix: &Matrix4<f32>) -> Quaternion<f32> {
+	let scale = matrix_scale(matrix);
+	if ulps_eq!(scale.x * scale.y * scale.z, 0.) {
+		return Quaternion::one();
+	}
+	Quaternion::from(Matrix3::from_cols(
+		matrix.x.truncate() / scale.x,
+		matrix.y.truncate() / scale.y,
+		matrix.z.truncate() / scale.z,
+	)).normalize()
+}
+
+#[cfg(test)]
+mod tests {
+	use cgmath::{Deg, Matrix4, Point3, Quaternion, Rad, Rotation3, Vector2, Vector3, Vector4};
+	use specs::prelude::{Dispatcher, DispatcherBuilder, Entity, World};
+	use specs_hierarchy::HierarchySystem;
+
+	use transform::{
+		direction_between,
+		point_between,
+		reparent,
+		GlobalTransform,
+		GlobalTransform2D,
+		Parent,
+		ReparentMode,
+		Transform,
+		Transform2D,
+		TransformSystem,
+	};
+
+	fn setup() -> (World, Dispatcher<'static, 'static>) {
+		let mut world = World::new();
+		let mut dispatcher = DispatcherBuilder::new()
+			.with(HierarchySystem::<Parent>::new(), "parent_hierarchy_system", &[])
+			.with(TransformSystem::new(), "transform_system", &["parent_hierarchy_system"])
+			.build();
+		dispatcher.setup(&mut world.res);
+		(world, dispatcher)
+	}
+
+	fn step(world: &mut World, dispatcher: &mut Dispatcher) {
+		dispatcher.dispatch(&world.res);
+		world.maintain();
+	}
+
+	fn spawn(world: &mut World, transform: Transform, parent: Option<Entity>) -> Entity {
+		let builder = world.create_entity().with(transform).with(GlobalTransform::default());
+		match parent {
+			Some(entity) => builder.with(Parent { entity }).build(),
+			None => builder.build(),
+		}
+	}
+
+	fn move_to(world: &mut World, entity: Entity, parent: Option<Entity>, mode: ReparentMode) {
+		let (mut parents, mut locals, mut locals_2d) = (world.write_storage(), world.write_storage(), world.write_storage());
+		let (globals, globals_2d) = (world.read_storage(), world.read_storage());
+		reparent(entity, parent, mode, &mut parents, &mut locals, &mut locals_2d, &globals, &globals_2d);
+	}
+
+	fn global(world: &World, entity: Entity) -> Matrix4<f32> {
+		world.read_storage::<GlobalTransform>().get(entity).unwrap().0
+	}
+
+	#[test]
+	fn matrix_scales_each_axis() {
+		let transform = Transform {
+			translation: Vector3::new(1., 2., 3.),
+			rotation: Quaternion::from_angle_z(Deg(90.)),
+			scale: Vector3::new(2., 3., 4.),
+		};
+		let expected = Matrix4::from_translation(transform.translation)
+			* Matrix4::from_angle_z(Deg(90.))
+			* Matrix4::from_nonuniform_scale(2., 3., 4.);
+		assert_ulps_eq!(transform.matrix(), expected, epsilon = 1e-5);
+		// x is stretched by 2 before turning onto y
+		assert_ulps_eq!((transform.matrix() * Vector4::unit_x()).truncate(), Vector3::new(0., 2., 0.), epsilon = 1e-5);
+	}
+
+	#[test]
+	fn from_matrix_round_trip() {
+		let transform = Transform {
+			translation: Vector3::new(1., -2., 3.),
+			rotation: Quaternion::from_angle_y(Deg(30.)),
+			scale: Vector3::new(2., 2., 0.5),
+		};
+		let decomposed = Transform::from_matrix(&transform.matrix());
+		assert_ulps_eq!(decomposed.translation, transform.translation, epsilon = 1e-5);
+		assert_ulps_eq!(decomposed.rotation, transform.rotation, epsilon = 1e-5);
+		assert_ulps_eq!(decomposed.scale, transform.scale, epsilon = 1e-5);
+	}
+
+	#[test]
+	fn set_world_position_under_parent() {
+		let parent = Transform {
+			translation: Vector3::new(10., 0., 0.),
+			rotation: Quaternion::from_angle_z(Deg(90.)),
+			scale: Vector3::new(2., 2., 2.),
+		}.matrix();
+		let mut child = Transform::default();
+		child.set_world_position(&parent, Vector3::new(10., 4., 0.));
+		assert_ulps_eq!(child.translation, Vector3::new(2., 0., 0.), epsilon = 1e-5);
+		assert_ulps_eq!(child.world_position(&parent), Vector3::new(10., 4., 0.), epsilon = 1e-5);
+	}
+
+	#[test]
+	fn set_world_matrix_keeps_pose() {
+		let parent = Transform {
+			translation: Vector3::new(0., 5., 0.),
+			rotation: Quaternion::from_angle_x(Deg(45.)),
+			..Default::default()
+		}.matrix();
+		let world: Matrix4<f32> = Transform {
+			translation: Vector3::new(1., 2., 3.),
+			rotation: Quaternion::from_angle_y(Deg(-60.)),
+			..Default::default()
+		}.matrix();
+		let mut child = Transform::default();
+		child.set_world_matrix(&parent, &world);
+		assert_ulps_eq!(child.world_matrix(&parent), world, epsilon = 1e-5);
+	}
+
+	#[test]
+	fn reparent_modes() {
+		let (mut world, mut dispatcher) = setup();
+		let old_parent = spawn(&mut world, Transform {
+			translation: Vector3::new(0., 5., 0.),
+			rotation: Quaternion::from_angle_x(Deg(45.)),
+			..Default::default()
+		}, None);
+		let new_parent = spawn(&mut world, Transform {
+			translation: Vector3::new(3., 0., 0.),
+			rotation: Quaternion::from_angle_z(Deg(90.)),
+			scale: Vector3::new(2., 2., 2.),
+		}, None);
+		let local = Transform { translation: Vector3::new(1., 2., 3.), ..Default::default() };
+		let child = spawn(&mut world, local.clone(), Some(old_parent));
+		step(&mut world, &mut dispatcher);
+		let before = global(&world, child);
+
+		move_to(&mut world, child, Some(new_parent), ReparentMode::KeepWorld);
+		step(&mut world, &mut dispatcher);
+		assert_eq!(world.read_storage::<Parent>().get(child), Some(&Parent { entity: new_parent }));
+		assert_ulps_eq!(global(&world, child), before, epsilon = 1e-5);
+
+		move_to(&mut world, child, None, ReparentMode::KeepWorld);
+		step(&mut world, &mut dispatcher);
+		assert!(world.read_storage::<Parent>().get(child).is_none());
+		assert_ulps_eq!(global(&world, child), before, epsilon = 1e-5);
+
+		world.write_storage::<Transform>().insert(child, local.clone()).unwrap();
+		move_to(&mut world, child, Some(new_parent), ReparentMode::KeepLocal);
+		step(&mut world, &mut dispatcher);
+		assert_eq!(world.read_storage::<Transform>().get(child), Some(&local));
+		assert_ulps_eq!(global(&world, child), global(&world, new_parent) * local.matrix(), epsilon = 1e-5);
+
+		move_to(&mut world, child, Some(old_parent), ReparentMode::Reset);
+		step(&mut world, &mut dispatcher);
+		assert_ulps_eq!(global(&world, child), global(&world, old_parent), epsilon = 1e-5);
+	}
+
+	#[test]
+	fn reparent_keeps_2d_pose() {
+		let (mut world, mut dispatcher) = setup();
+		let old_parent = world.create_entity()
+			.with(Transform2D::new(Vector2::new(4., 1.), Rad::from(Deg(30.)), Vector2::new(2., 2.)))
+			.with(GlobalTransform2D::default())
+			.build();
+		// a 3D parent, which the 2D child sees through its XY plane
+		let new_parent = spawn(&mut world, Transform {
+			translation: Vector3::new(-2., 3., 7.),
+			rotation: Quaternion::from_angle_z(Deg(-90.)),
+			..Default::default()
+		}, None);
+		let sprite = world.create_entity()
+			.with(Transform2D::new(Vector2::new(1., 0.), Rad(0.), Vector2::new(1., 3.)))
+			.with(GlobalTransform2D::default())
+			.with(Parent { entity: old_parent })
+			.build();
+		step(&mut world, &mut dispatcher);
+		let before = world.read_storage::<GlobalTransform2D>().get(sprite).unwrap().0;
+
+		move_to(&mut world, sprite, Some(new_parent), ReparentMode::KeepWorld);
+		step(&mut world, &mut dispatcher);
+		let after = world.read_storage::<GlobalTransform2D>().get(sprite).unwrap().0;
+		assert_ulps_eq!(after, before, epsilon = 1e-5);
+	}
+
+	#[test]
+	fn maps_points_and_directions_between_entities() {
+		let (mut world, mut dispatcher) = setup();
+		let from = spawn(&mut world, Transform {
+			translation: Vector3::new(1., 0., 0.),
+			rotation: Quaternion::from_angle_z(Deg(90.)),
+			..Default::default()
+		}, None);
+		let to = spawn(&mut world, Transform {
+			translation: Vector3::new(0., 0., 2.),
+			scale: Vector3::new(2., 2., 2.),
+			..Default::default()
+		}, None);
+		let unplaced = world.create_entity().with(Transform::default()).build();
+		step(&mut world, &mut dispatcher);
+
+		let globals = world.read_storage::<GlobalTransform>();
+		// (1, 0, 0) in `from` is (1, 1, 0) in the world, then (0.5, 0.5, -1) in `to`
+		let point = point_between(from, to, Point3::new(1., 0., 0.), &globals).unwrap();
+		assert_ulps_eq!(point, Point3::new(0.5, 0.5, -1.), epsilon = 1e-5);
+		let direction = direction_between(from, to, Vector3::new(1., 0., 0.), &globals).unwrap();
+		assert_ulps_eq!(direction, Vector3::new(0., 0.5, 0.), epsilon = 1e-5);
+		assert!(point_between(from, unplaced, Point3::new(0., 0., 0.), &globals).is_none());
+		assert!(direction_between(unplaced, to, Vector3::new(0., 0., 1.), &globals).is_none());
+	}
+}
@@ -0,0 +1,389 @@
+use cgmath::{
+    ortho,
+    perspective,
+    frustum,
+    EuclideanSpace,
+    InnerSpace,
+    Matrix4,
+    One,
+    Point2,
+    Point3,
+    Rad,
+    SquareMatrix,
+    Vector2,
+    Vector3,
+    Vector4,
+};
+use specs::prelude::{
+    Component,
+    DenseVecStorage,
+    Entities,
+    Entity,
+    Join,
+    ReadStorage,
+    System,
+    WriteStorage,
+};
+
+use cachoeira_core::culling::CullingCamera;
+use cachoeira_core::transform::GlobalTransform;
+
+/// How a camera maps view space to clip space.
+///
+/// All variants produce OpenGL-style clip depth, looking down -Z.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Projection {
+    Perspective {
+        fov_y: Rad<f32>,
+        aspect: f32,
+        near: f32,
+        far: f32,
+    },
+    Orthographic {
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+        near: f32,
+        far: f32,
+    },
+    /// Asymmetric perspective frustum, with extents measured on the near plane; used to match
+    /// a physical screen or projection surface that isn't centred on the eye.
+    OffAxis {
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+        near: f32,
+        far: f32,
+    },
+}
+
+impl Projection {
+    pub fn perspective<A: Into<Rad<f32>>>(fov_y: A, aspect: f32, near: f32, far: f32) -> Self {
+        Projection::Perspective { fov_y: fov_y.into(), aspect, near, far }
+    }
+
+    pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Self {
+        Projection::Orthographic { left, right, bottom, top, near, far }
+    }
+
+    pub fn off_axis(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Self {
+        Projection::OffAxis { left, right, bottom, top, near, far }
+    }
+
+    /// Off-axis frustum through a rectangular screen given by two opposite corners in the
+    /// camera's local space.
+    ///
+    /// The screen must be parallel to the camera's XY plane and in front of it, so orient the
+    /// camera entity with the surface and place it at the eye.
+    pub fn off_axis_screen(lower_left: Point3<f32>, upper_right: Point3<f32>, near: f32, far: f32) -> Self {
+        debug_assert!(
+            ulps_eq!(lower_left.z, upper_right.z) && lower_left.z < 0.,
+            "Screen must be parallel to the view plane and in front of the eye"
+        );
+        let scale = near / -lower_left.z;
+        Projection::OffAxis {
+            left: lower_left.x * scale,
+            right: upper_right.x * scale,
+            bottom: lower_left.y * scale,
+            top: upper_right.y * scale,
+            near,
+            far,
+        }
+    }
+
+    pub fn near(&self) -> f32 {
+        match *self {
+            Projection::Perspective { near, .. }
+            | Projection::Orthographic { near, .. }
+            | Projection::OffAxis { near, .. } => near,
+        }
+    }
+
+    pub fn far(&self) -> f32 {
+        match *self {
+            Projection::Perspective { far, .. }
+            | Projection::Orthographic { far, .. }
+            | Projection::OffAxis { far, .. } => far,
+        }
+    }
+
+    /// Width over height of the view volume.
+    pub fn aspect(&self) -> f32 {
+        match *self {
+            Projection::Perspective { aspect, .. } => aspect,
+            Projection::Orthographic { left, right, bottom, top, .. }
+            | Projection::OffAxis { left, right, bottom, top, .. } => (right - left) / (top - bottom),
+        }
+    }
+
+    /// Adapts to a new viewport shape; orthographic and off-axis volumes keep their height and
+    /// centre.
+    pub fn set_aspect(&mut self, new_aspect: f32) {
+        match *self {
+            Projection::Perspective { ref mut aspect, .. } => *aspect = new_aspect,
+            Projection::Orthographic { ref mut left, ref mut right, bottom, top, .. }
+            | Projection::OffAxis { ref mut left, ref mut right, bottom, top, .. } => {
+                let center = (*left + *right) * 0.5;
+                let half_width = (top - bottom) * new_aspect * 0.5;
+                *left = center - half_width;
+                *right = center + half_width;
+            }
+        }
+    }
+
+    pub fn is_orthographic(&self) -> bool {
+        matches!(*self, Projection::Orthographic { .. })
+    }
+
+    pub fn matrix(&self) -> Matrix4<f32> {
+        match *self {
+            Projection::Perspective { fov_y, aspect, near, far } => perspective(fov_y, aspect, near, far),
+            Projection::Orthographic { left, right, bottom, top, near, far } => ortho(left, right, bottom, top, near, far),
+            Projection::OffAxis { left, right, bottom, top, near, far } => frustum(left, right, bottom, top, near, far),
+        }
+    }
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Projection::perspective(Rad(::std::f32::consts::FRAC_PI_3), 16. / 9., 0.1, 1000.)
+    }
+}
+
+/// A half-line in world space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    /// unit length
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    pub fn at(&self, distance: f32) -> Point3<f32> {
+        self.origin + self.direction * distance
+    }
+}
+
+/// A point of view; matrices are filled in by the `CameraSystem` from the `GlobalTransform`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub projection: Projection,
+    view: Matrix4<f32>,
+    projection_matrix: Matrix4<f32>,
+    view_projection: Matrix4<f32>,
+    inverse_view_projection: Matrix4<f32>,
+}
+
+impl Camera {
+    pub fn new(projection: Projection) -> Self {
+        let mut camera = Camera {
+            projection,
+            view: Matrix4::one(),
+            projection_matrix: Matrix4::one(),
+            view_projection: Matrix4::one(),
+            inverse_view_projection: Matrix4::one(),
+        };
+        camera.update(Matrix4::one());
+        camera
+    }
+
+    pub fn perspective<A: Into<Rad<f32>>>(fov_y: A, aspect: f32, near: f32, far: f32) -> Self {
+        Camera::new(Projection::perspective(fov_y, aspect, near, far))
+    }
+
+    pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Self {
+        Camera::new(Projection::orthographic(left, right, bottom, top, near, far))
+    }
+
+    pub fn off_axis(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Self {
+        Camera::new(Projection::off_axis(left, right, bottom, top, near, far))
+    }
+
+    pub fn view(&self) -> Matrix4<f32> { self.view }
+    pub fn projection_matrix(&self) -> Matrix4<f32> { self.projection_matrix }
+    pub fn view_projection(&self) -> Matrix4<f32> { self.view_projection }
+    pub fn inverse_view_projection(&self) -> Matrix4<f32> { self.inverse_view_projection }
+
+    /// Recomputes the matrices for a new view matrix and the current projection.
+    pub fn update(&mut self, view: Matrix4<f32>) {
+        self.view = view;
+        self.projection_matrix = self.projection.matrix();
+        self.view_projection = self.projection_matrix * view;
+        self.inverse_view_projection = self.view_projection.invert().unwrap_or(Matrix4::one());
+    }
+
+    /// Normalized device coordinates of a world point: x and y in -1..1, z in -1..1 from near to
+    /// far. `None` for points behind the eye.
+    pub fn world_to_ndc(&self, point: Point3<f32>) -> Option<Point3<f32>> {
+        let clip = self.view_projection * point.to_homogeneous();
+        if clip.w <= 0. {
+            return None;
+        }
+        Some(Point3::from_homogeneous(clip))
+    }
+
+    /// Pixel position of a world point in a viewport of `size` pixels, origin at the top left;
+    /// `z` is the depth from 0 at the near plane to 1 at the far plane.
+    pub fn world_to_screen(&self, point: Point3<f32>, size: Vector2<f32>) -> Option<Point3<f32>> {
+        self.world_to_ndc(point).map(|ndc| Point3::new(
+            (ndc.x + 1.) * 0.5 * size.x,
+            (1. - ndc.y) * 0.5 * size.y,
+            (ndc.z + 1.) * 0.5,
+        ))
+    }
+
+    /// Ray from the near plane through a normalized device position.
+    pub fn ndc_ray(&self, ndc: Point2<f32>) -> Ray {
+        let unproject = |z: f32| {
+            Point3::from_homogeneous(self.inverse_view_projection * Vector4::new(ndc.x, ndc.y, z, 1.))
+        };
+        let (near, far) = (unproject(-1.), unproject(1.));
+        Ray { origin: near, direction: (far - near).normalize() }
+    }
+
+    /// Ray through a pixel of a viewport of `size` pixels, origin at the top left.
+    pub fn screen_ray(&self, position: Point2<f32>, size: Vector2<f32>) -> Ray {
+        self.ndc_ray(Point2::new(
+            position.x / size.x * 2. - 1.,
+            1. - position.y / size.y * 2.,
+        ))
+    }
+
+    /// Camera position in world space.
+    pub fn position(&self) -> Point3<f32> {
+        self.view.invert()
+            .map(|world| Point3::from_vec(world.w.truncate()))
+            .unwrap_or(Point3::origin())
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self { Camera::new(Projection::default()) }
+}
+
+impl Component for Camera {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// The camera renderers draw from when not told otherwise.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ActiveCamera {
+    pub entity: Option<Entity>,
+}
+
+impl ActiveCamera {
+    pub fn new(entity: Entity) -> Self {
+        ActiveCamera { entity: Some(entity) }
+    }
+}
+
+/// Updates the matrices of every `Camera` from its `GlobalTransform`.
+///
+/// Cameras without a `GlobalTransform` sit at the origin. Entities also carrying a
+/// `CullingCamera` get its projection kept in sync, so it belongs after the `TransformSystem`
+/// and before the `CullingSystem`.
+pub struct CameraSystem;
+
+impl<'a> System<'a> for CameraSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, GlobalTransform>,
+        WriteStorage<'a, Camera>,
+        WriteStorage<'a, CullingCamera>,
+    );
+
+    fn run(&mut self, (entities, globals, mut cameras, mut culling): Self::SystemData) {
+        #[cfg(feature="profiler")]
+        profile_scope!("camera_system");
+
+        for (entity, camera) in (&*entities, &mut cameras).join() {
+            let view = match globals.get(entity) {
+                Some(global) => global.0.invert().unwrap_or(Matrix4::one()),
+                None => Matrix4::one(),
+            };
+            camera.update(view);
+            if let Some(culling) = culling.get_mut(entity) {
+                culling.projection = camera.projection_matrix;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, InnerSpace, Matrix4, Point2, Point3, Quaternion, Rotation3, Vector2, Vector3};
+    use specs::prelude::{DispatcherBuilder, World};
+
+    use cachoeira_core::culling::CullingCamera;
+    use cachoeira_core::transform::{GlobalTransform, Transform};
+    use super::{ActiveCamera, Camera, CameraSystem, Projection};
+
+    #[test]
+    fn screen_round_trip() {
+        let eye = Transform {
+            translation: Vector3::new(1., 2., 5.),
+            rotation: Quaternion::from_angle_y(Deg(30.)),
+            scale: Vector3::new(1., 1., 1.),
+        };
+        let size = Vector2::new(640., 480.);
+        for mut camera in [Camera::perspective(Deg(60.), 4. / 3., 0.1, 100.), Camera::orthographic(-4., 4., -3., 3., 0.1, 100.)] {
+            camera.update(eye.view_matrix());
+            let point = Point3::new(0.5, 1., -3.);
+            let screen = camera.world_to_screen(point, size).unwrap();
+            let ray = camera.screen_ray(Point2::new(screen.x, screen.y), size);
+            let along = (point - ray.origin).dot(ray.direction);
+            assert_ulps_eq!(ray.at(along), point, epsilon = 1e-3);
+        }
+    }
+
+    #[test]
+    fn centred_off_axis_matches_perspective() {
+        let screen = Projection::off_axis_screen(Point3::new(-2., -1., -2.), Point3::new(2., 1., -2.), 0.5, 50.);
+        let symmetric = Projection::perspective(Deg(2. * 26.565_05), 2., 0.5, 50.);
+        assert_ulps_eq!(screen.matrix(), symmetric.matrix(), epsilon = 1e-4);
+        assert_ulps_eq!(screen.aspect(), 2.);
+    }
+
+    #[test]
+    fn system_follows_active_camera_and_aspect() {
+        let mut world = World::new();
+        let mut dispatcher = DispatcherBuilder::new().with(CameraSystem, "camera_system", &[]).build();
+        dispatcher.setup(&mut world.res);
+        let eye = |x: f32| GlobalTransform(Matrix4::from_translation(Vector3::new(x, 0., 10.)));
+        let main = world.create_entity()
+            .with(Camera::perspective(Deg(60.), 1., 0.1, 100.))
+            .with(CullingCamera::new(Matrix4::from_scale(1.)))
+            .with(eye(-3.))
+            .build();
+        let inset = world.create_entity()
+            .with(Camera::orthographic(-1., 1., -1., 1., 0.1, 100.))
+            .with(eye(3.))
+            .build();
+        let unplaced = world.create_entity().with(Camera::default()).build();
+        world.add_resource(ActiveCamera::new(inset));
+        dispatcher.dispatch(&world.res);
+
+        let active_position = |world: &World| {
+            let entity = world.read_resource::<ActiveCamera>().entity.unwrap();
+            world.read_storage::<Camera>().get(entity).unwrap().position()
+        };
+        assert_ulps_eq!(active_position(&world), Point3::new(3., 0., 10.), epsilon = 1e-5);
+        world.write_resource::<ActiveCamera>().entity = Some(main);
+        assert_ulps_eq!(active_position(&world), Point3::new(-3., 0., 10.), epsilon = 1e-5);
+        assert_ulps_eq!(world.read_storage::<Camera>().get(unplaced).unwrap().position(), Point3::new(0., 0., 0.));
+
+        // a resized viewport only touches the projection, which the culling camera follows
+        world.write_storage::<Camera>().get_mut(main).unwrap().projection.set_aspect(2.);
+        world.write_storage::<Camera>().get_mut(inset).unwrap().projection.set_aspect(2.);
+        dispatcher.dispatch(&world.res);
+        let cameras = world.read_storage::<Camera>();
+        let main_camera = cameras.get(main).unwrap();
+        assert_ulps_eq!(main_camera.projection_matrix(), Projection::perspective(Deg(60.), 2., 0.1, 100.).matrix());
+        let culling = world.read_storage::<CullingCamera>().get(main).unwrap().projection;
+        assert_ulps_eq!(culling, main_camera.projection_matrix());
+        assert_eq!(cameras.get(inset).unwrap().projection, Projection::orthographic(-2., 2., -1., 1., 0.1, 100.));
+        assert_ulps_eq!(main_camera.view_projection(), main_camera.projection_matrix() * main_camera.view());
+    }
+}
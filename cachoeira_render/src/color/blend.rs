@@ -0,0 +1,126 @@
+use color::LinearRgb;
+
+/// Separable blend modes of the W3C compositing spec, plus additive light.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum BlendMode {
+    /// source over destination
+    Normal,
+    /// adds light, not clamped, for HDR accumulation
+    Add,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    Difference,
+}
+
+impl BlendMode {
+    /// Blended colour of straight (not premultiplied) `source` over `backdrop` channels.
+    fn channel(self, source: f32, backdrop: f32) -> f32 {
+        match self {
+            BlendMode::Normal | BlendMode::Add => source,
+            BlendMode::Multiply => source * backdrop,
+            BlendMode::Screen => source + backdrop - source * backdrop,
+            BlendMode::Overlay => if backdrop <= 0.5 {
+                source * backdrop * 2.
+            } else {
+                BlendMode::Screen.channel(source, backdrop * 2. - 1.)
+            },
+            BlendMode::Darken => source.min(backdrop),
+            BlendMode::Lighten => source.max(backdrop),
+            BlendMode::Difference => (source - backdrop).abs(),
+        }
+    }
+}
+
+/// Linear RGB with the colour multiplied by alpha, so that blending and filtering don't bleed
+/// the colour of transparent pixels.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Premultiplied {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub alpha: f32,
+}
+
+impl Premultiplied {
+    pub const TRANSPARENT: Premultiplied = Premultiplied { r: 0., g: 0., b: 0., alpha: 0. };
+
+    pub fn unpremultiply(&self) -> LinearRgb {
+        if self.alpha <= 0. {
+            return LinearRgb::TRANSPARENT;
+        }
+        LinearRgb { r: self.r / self.alpha, g: self.g / self.alpha, b: self.b / self.alpha, alpha: self.alpha }
+    }
+
+    /// Porter-Duff source over.
+    pub fn over(self, backdrop: Premultiplied) -> Premultiplied {
+        self.blend(backdrop, BlendMode::Normal)
+    }
+
+    /// Composites `self` onto `backdrop` with `mode`.
+    pub fn blend(self, backdrop: Premultiplied, mode: BlendMode) -> Premultiplied {
+        if mode == BlendMode::Add {
+            return Premultiplied {
+                r: self.r + backdrop.r,
+                g: self.g + backdrop.g,
+                b: self.b + backdrop.b,
+                alpha: (self.alpha + backdrop.alpha).min(1.),
+            };
+        }
+        let (source, straight) = (self, backdrop.unpremultiply());
+        let own = self.unpremultiply();
+        let both = source.alpha * backdrop.alpha;
+        let channel = |s: f32, b: f32, straight_s: f32, straight_b: f32| {
+            s * (1. - backdrop.alpha) + b * (1. - source.alpha) + both * mode.channel(straight_s, straight_b)
+        };
+        Premultiplied {
+            r: channel(self.r, backdrop.r, own.r, straight.r),
+            g: channel(self.g, backdrop.g, own.g, straight.g),
+            b: channel(self.b, backdrop.b, own.b, straight.b),
+            alpha: source.alpha + backdrop.alpha - both,
+        }
+    }
+}
+
+impl LinearRgb {
+    pub fn premultiply(&self) -> Premultiplied {
+        Premultiplied { r: self.r * self.alpha, g: self.g * self.alpha, b: self.b * self.alpha, alpha: self.alpha }
+    }
+
+    /// Composites `self` onto `backdrop`, both straight alpha.
+    pub fn blend(&self, backdrop: LinearRgb, mode: BlendMode) -> LinearRgb {
+        self.premultiply().blend(backdrop.premultiply(), mode).unpremultiply()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use color::{BlendMode, LinearRgb};
+
+    #[test]
+    fn blend_modes() {
+        let backdrop = LinearRgb::new(0.25, 0.5, 1.);
+        let half_red = LinearRgb::new(1., 0., 0.).with_alpha(0.5);
+        assert_eq!(half_red.blend(backdrop, BlendMode::Normal), LinearRgb::new(0.625, 0.25, 0.5));
+        assert_eq!(half_red.blend(backdrop, BlendMode::Multiply), LinearRgb::new(0.25, 0.25, 0.5));
+        assert_eq!(half_red.blend(backdrop, BlendMode::Add), LinearRgb::new(0.75, 0.5, 1.));
+        assert_eq!(LinearRgb::new(0.5, 0.5, 0.5).blend(backdrop, BlendMode::Screen), LinearRgb::new(0.625, 0.75, 1.));
+        assert_eq!(LinearRgb::WHITE.blend(backdrop, BlendMode::Difference), LinearRgb::new(0.75, 0.5, 0.));
+    }
+
+    #[test]
+    fn keeps_source_over_transparent_backdrop() {
+        // in any mode
+        let half_red = LinearRgb::new(1., 0., 0.).with_alpha(0.5);
+        assert_eq!(half_red.blend(LinearRgb::TRANSPARENT, BlendMode::Overlay), half_red);
+    }
+
+    #[test]
+    fn premultiplies_alpha() {
+        let half_red = LinearRgb::new(1., 0., 0.).with_alpha(0.5);
+        let premultiplied = half_red.premultiply();
+        assert_eq!((premultiplied.r, premultiplied.unpremultiply()), (0.5, half_red));
+    }
+}
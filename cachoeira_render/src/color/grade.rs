@@ -0,0 +1,224 @@
+use std::sync::{Arc, RwLock};
+
+use rayon::prelude::*;
+
+use color::{linear_to_srgb, LinearRgb, Lut, LutInterpolation, Xyz};
+use graph::{PassId, RenderGraph, ResourceId};
+use software::{Framebuffer, SoftwareBackend};
+
+/// Encoding applied to linear light before the LUT, i.e. what the LUT expects as input.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum Transfer {
+    Linear,
+    #[default]
+    Srgb,
+    /// pure power law, e.g. 2.4 for BT.1886 displays
+    Gamma(f32),
+}
+
+impl Transfer {
+    pub fn encode(self, linear: f32) -> f32 {
+        match self {
+            Transfer::Linear => linear,
+            Transfer::Srgb => linear_to_srgb(linear),
+            Transfer::Gamma(gamma) => linear.max(0.).powf(1. / gamma),
+        }
+    }
+}
+
+/// Colour correction for one output, e.g. a projector or an LED wall processor.
+///
+/// Turns the linear framebuffer into the output signal, in order: white balance gains, the
+/// `shaper` encoding, the LUT (cross-faded into `blend` if given), then per-channel gamma.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorGrade {
+    /// per-channel gains in linear light, see `temperature_gains`
+    pub white_balance: [f32; 3],
+    pub shaper: Transfer,
+    pub lut: Option<Arc<Lut>>,
+    /// second LUT and how far to fade into it, 0 to 1; both are sampled, see `Lut::mix` to bake
+    pub blend: Option<(Arc<Lut>, f32)>,
+    pub interpolation: LutInterpolation,
+    /// per-channel gamma correction of the output signal, `out = in^(1 / gamma)`
+    pub gamma: [f32; 3],
+}
+
+impl Default for ColorGrade {
+    fn default() -> Self {
+        ColorGrade {
+            white_balance: [1.; 3],
+            shaper: Transfer::Srgb,
+            lut: None,
+            blend: None,
+            interpolation: LutInterpolation::Tetrahedral,
+            gamma: [1.; 3],
+        }
+    }
+}
+
+impl ColorGrade {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_lut(mut self, lut: Arc<Lut>) -> Self {
+        self.lut = Some(lut);
+        self
+    }
+
+    pub fn with_white_balance(mut self, gains: [f32; 3]) -> Self {
+        self.white_balance = gains;
+        self
+    }
+
+    pub fn with_gamma(mut self, gamma: [f32; 3]) -> Self {
+        self.gamma = gamma;
+        self
+    }
+
+    pub fn apply(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let mut signal = [0.; 3];
+        for c in 0..3 {
+            signal[c] = self.shaper.encode(rgb[c] * self.white_balance[c]);
+        }
+        if let Some(ref lut) = self.lut {
+            let graded = lut.apply(signal, self.interpolation);
+            signal = match self.blend {
+                Some((ref target, t)) => {
+                    let faded = target.apply(signal, self.interpolation);
+                    [graded[0] + (faded[0] - graded[0]) * t, graded[1] + (faded[1] - graded[1]) * t, graded[2] + (faded[2] - graded[2]) * t]
+                }
+                None => graded,
+            };
+        }
+        for (value, &gamma) in signal.iter_mut().zip(&self.gamma) {
+            if gamma != 1. {
+                *value = value.max(0.).powf(1. / gamma);
+            }
+        }
+        signal
+    }
+
+    /// Grades RGBA pixels in place and in parallel, leaving alpha alone.
+    pub fn apply_to(&self, pixels: &mut [[f32; 4]]) {
+        pixels.par_chunks_mut(1024).for_each(|chunk| {
+            for pixel in chunk {
+                let rgb = self.apply([pixel[0], pixel[1], pixel[2]]);
+                pixel[..3].copy_from_slice(&rgb);
+            }
+        });
+    }
+
+    /// Adds a pass grading `source` into `destination`, which must be the same size; `grade`
+    /// is read every frame, so it can be adjusted live.
+    pub fn add_pass(
+        grade: Arc<RwLock<ColorGrade>>,
+        graph: &mut RenderGraph<SoftwareBackend>,
+        source: ResourceId,
+        destination: ResourceId,
+    ) -> PassId {
+        graph.add_pass("color grade").reads(source).writes(destination).execute(move |context| {
+            let (source, destination) = context.target_pair(source, destination)?;
+            copy_colors(source, destination)?;
+            grade.read().map_err(|_| "Color grade lock poisoned".to_owned())?.apply_to(destination.colors_mut());
+            Ok(())
+        })
+    }
+}
+
+fn copy_colors(source: &Framebuffer, destination: &mut Framebuffer) -> Result<(), String> {
+    if (source.width(), source.height()) != (destination.width(), destination.height()) {
+        return Err(format!(
+            "Cannot grade a {}x{} target into a {}x{} one",
+            source.width(), source.height(), destination.width(), destination.height(),
+        ));
+    }
+    destination.colors_mut().copy_from_slice(source.colors());
+    Ok(())
+}
+
+/// White balance gains that tint D65 white towards a black body at `kelvin`, valid from 1667 K
+/// to 25000 K; 6500 K gives no change and the largest gain is 1.
+pub fn temperature_gains(kelvin: f32) -> [f32; 3] {
+    let white = planckian_rgb(6500.);
+    let target = planckian_rgb(kelvin);
+    let gains = [target.r / white.r, target.g / white.g, target.b / white.b];
+    let max = gains[0].max(gains[1]).max(gains[2]);
+    [gains[0] / max, gains[1] / max, gains[2] / max]
+}
+
+// Kim et al.'s cubic fit of the Planckian locus, in CIE xy
+fn planckian_rgb(kelvin: f32) -> LinearRgb {
+    let t = f64::from(kelvin.clamp(1667., 25000.));
+    let x = if t <= 4000. {
+        -0.2661239e9 / (t * t * t) - 0.2343589e6 / (t * t) + 0.8776956e3 / t + 0.179910
+    } else {
+        -3.0258469e9 / (t * t * t) + 2.1070379e6 / (t * t) + 0.2226347e3 / t + 0.240390
+    };
+    let y = if t <= 2222. {
+        -1.1063814 * x * x * x - 1.34811020 * x * x + 2.18555832 * x - 0.20219683
+    } else if t <= 4000. {
+        -0.9549476 * x * x * x - 1.37418593 * x * x + 2.09137015 * x - 0.16748867
+    } else {
+        3.0817580 * x * x * x - 5.87338670 * x * x + 3.75112997 * x - 0.37001483
+    };
+    Xyz::new((x / y) as f32, 1., ((1. - x - y) / y) as f32).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+
+    use color::{temperature_gains, ColorGrade, Lut, Transfer};
+    use graph::{RenderGraph, TargetDesc, TargetFormat};
+    use software::{Framebuffer, SoftwareBackend};
+
+    fn inverting_grade() -> ColorGrade {
+        let invert = Arc::new(Lut::from_fn(2, |rgb| [1. - rgb[0], 1. - rgb[1], 1. - rgb[2]]));
+        let mut grade = ColorGrade::new().with_white_balance([0.5, 1., 1.]).with_lut(invert);
+        grade.shaper = Transfer::Linear;
+        grade
+    }
+
+    #[test]
+    fn temperature_gains_keep_red() {
+        let warm = temperature_gains(3200.);
+        assert_eq!(warm[0], 1.);
+        assert!(warm[1] < 0.9 && warm[2] < warm[1]);
+        assert!(temperature_gains(6500.).iter().all(|gain| (gain - 1.).abs() < 1e-6));
+    }
+
+    #[test]
+    fn balances_then_applies_the_lut() {
+        assert_eq!(inverting_grade().apply([1., 0.25, 0.]), [0.5, 0.75, 1.]);
+    }
+
+    #[test]
+    fn blends_towards_a_second_lut() {
+        let mut grade = inverting_grade();
+        grade.blend = Some((Arc::new(Lut::from_fn(2, |rgb| rgb)), 0.5));
+        assert_eq!(grade.apply([1., 0.25, 0.]), [0.5, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn applies_gamma_per_channel() {
+        let mut grade = inverting_grade();
+        grade.gamma = [1., 0.5, 1.];
+        assert_eq!(grade.apply([1., 0.5, 0.]), [0.5, 0.25, 1.]);
+    }
+
+    #[test]
+    fn grades_framebuffers() {
+        let mut grade = inverting_grade();
+        grade.gamma = [1., 0.5, 1.];
+        let desc = TargetDesc::new(4, 4, TargetFormat::Rgba32F);
+        let mut graph = RenderGraph::<SoftwareBackend>::new();
+        let mut scene = Framebuffer::new(4, 4);
+        scene.clear([0.25, 0.5, 1., 0.5], 1.);
+        let scene = graph.import_target("scene", desc, scene);
+        let output = graph.import_target("projector", desc, Framebuffer::new(4, 4));
+        ColorGrade::add_pass(Arc::new(RwLock::new(grade)), &mut graph, scene, output);
+        graph.execute(&mut SoftwareBackend::new()).unwrap();
+        assert_eq!(graph.target(output).unwrap().pixel(3, 3), [0.875, 0.25, 0., 0.5]);
+    }
+}
@@ -0,0 +1,139 @@
+use color::{LinearRgb, Mix, Oklab, Oklch, Srgb};
+use texture::Texture;
+
+/// Colour space in which a gradient blends between neighbouring stops.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Interpolation {
+    /// physically linear, as light adds up
+    Linear,
+    /// gamma-encoded, as most image editors do
+    Srgb,
+    /// perceptually even lightness
+    #[default]
+    Oklab,
+    /// perceptually even, keeping saturation along the shorter hue arc
+    Oklch,
+}
+
+impl Interpolation {
+    pub fn mix(self, from: LinearRgb, to: LinearRgb, t: f32) -> LinearRgb {
+        match self {
+            Interpolation::Linear => from.mix(to, t),
+            Interpolation::Srgb => Srgb::from(from).mix(Srgb::from(to), t).into(),
+            Interpolation::Oklab => Oklab::from(from).mix(Oklab::from(to), t).into(),
+            Interpolation::Oklch => Oklch::from(from).mix(Oklch::from(to), t).into(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct GradientStop {
+    pub position: f32,
+    pub color: LinearRgb,
+}
+
+/// Colours at positions along [0, 1], sorted by position.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Gradient {
+    stops: Vec<GradientStop>,
+    #[serde(default)]
+    pub interpolation: Interpolation,
+}
+
+impl Gradient {
+    pub fn new(interpolation: Interpolation) -> Self {
+        Gradient { stops: Vec::new(), interpolation }
+    }
+
+    /// Evenly spaced stops from first to last.
+    pub fn from_colors<C, I>(colors: I, interpolation: Interpolation) -> Self
+        where C: Into<LinearRgb>, I: IntoIterator<Item = C>
+    {
+        let colors: Vec<LinearRgb> = colors.into_iter().map(Into::into).collect();
+        let last = (colors.len().max(2) - 1) as f32;
+        let stops = colors.into_iter()
+            .enumerate()
+            .map(|(i, color)| GradientStop { position: i as f32 / last, color })
+            .collect();
+        Gradient { stops, interpolation }
+    }
+
+    /// Adds a stop, after any existing stops at the same position so that they make a hard edge.
+    pub fn with_stop<C: Into<LinearRgb>>(mut self, position: f32, color: C) -> Self {
+        let index = self.stops.iter().position(|stop| stop.position > position).unwrap_or(self.stops.len());
+        self.stops.insert(index, GradientStop { position, color: color.into() });
+        self
+    }
+
+    pub fn stops(&self) -> &[GradientStop] { &self.stops }
+
+    /// Colour at `t`, holding the end colours outside the stops; transparent without stops.
+    pub fn sample(&self, t: f32) -> LinearRgb {
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return LinearRgb::TRANSPARENT,
+        };
+        if t < first.position || t.is_nan() {
+            return first.color;
+        }
+        if t >= last.position {
+            return last.color;
+        }
+        let next = self.stops.iter().position(|stop| stop.position > t).unwrap();
+        let (from, to) = (&self.stops[next - 1], &self.stops[next]);
+        let span = to.position - from.position;
+        self.interpolation.mix(from.color, to.color, (t - from.position) / span)
+    }
+
+    /// `count` colours spaced evenly from 0 to 1 inclusive.
+    pub fn samples(&self, count: usize) -> Vec<LinearRgb> {
+        let last = (count.max(2) - 1) as f32;
+        (0..count).map(|i| self.sample(i as f32 / last)).collect()
+    }
+
+    /// One row of `width` texel centres, for lookup on the renderer side with a clamping sampler.
+    pub fn to_texture(&self, width: u32) -> Texture {
+        let pixels = (0..width).map(|x| self.sample((x as f32 + 0.5) / width as f32).into()).collect();
+        Texture::new(width, 1, pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use color::{Gradient, Interpolation, LinearRgb, Oklch, Srgb};
+
+    #[test]
+    fn samples_between_stops() {
+        let gradient = Gradient::new(Interpolation::Linear)
+            .with_stop(1., LinearRgb::WHITE)
+            .with_stop(0.5, LinearRgb::new(1., 0., 0.))
+            .with_stop(0.5, LinearRgb::BLACK);
+        assert_eq!(gradient.sample(-1.), LinearRgb::new(1., 0., 0.));
+        assert_eq!(gradient.sample(0.5), LinearRgb::BLACK);
+        assert_eq!(gradient.sample(0.75), LinearRgb::new(0.5, 0.5, 0.5));
+        assert_eq!(gradient.sample(2.), LinearRgb::WHITE);
+    }
+
+    #[test]
+    fn interpolates_in_its_colour_space() {
+        // sRGB midpoint of black and white is its mid grey, OKLab's is perceptually even
+        let colors = vec![Srgb::new(0., 0., 0.), Srgb::new(1., 1., 1.)];
+        let srgb = Gradient::from_colors(colors.clone(), Interpolation::Srgb).sample(0.5);
+        assert_ulps_eq!(Srgb::from(srgb).r, 0.5, max_ulps = 4);
+        let oklab = Gradient::from_colors(colors, Interpolation::Oklab).sample(0.5);
+        assert_relative_eq!(Oklch::from(oklab).l, 0.5, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn bakes_texel_centres() {
+        let texture = Gradient::from_colors(vec![LinearRgb::BLACK, LinearRgb::WHITE], Interpolation::Linear).to_texture(4);
+        assert_eq!((texture.width(), texture.height()), (4, 1));
+        assert_eq!(texture.pixel(0, 0), [0.125, 0.125, 0.125, 1.]);
+    }
+
+    #[test]
+    fn empty_gradient_is_transparent() {
+        assert_eq!(Gradient::default().interpolation, Interpolation::Oklab);
+        assert_eq!(Gradient::default().samples(3), vec![LinearRgb::TRANSPARENT; 3]);
+    }
+}
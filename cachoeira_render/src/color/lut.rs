@@ -0,0 +1,343 @@
+use std::cmp::Ordering;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use rayon::prelude::*;
+
+/// How a 3D LUT is sampled between its lattice points; 1D LUTs are always linear.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum LutInterpolation {
+    Trilinear,
+    /// splits each lattice cell into six tetrahedra along the grey axis; keeps neutrals neutral
+    /// and is what most grading tools use
+    #[default]
+    Tetrahedral,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LutDimensions {
+    /// a curve per channel
+    One,
+    /// a lattice over the whole RGB cube
+    Three,
+}
+
+/// A lookup table in the Adobe/Resolve `.cube` format, mapping RGB to RGB.
+///
+/// Inputs are scaled from `domain_min`..`domain_max` to the table and clamped to it; outputs
+/// are whatever the table holds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lut {
+    pub title: Option<String>,
+    pub domain_min: [f32; 3],
+    pub domain_max: [f32; 3],
+    dimensions: LutDimensions,
+    size: usize,
+    // 3D: red varies fastest, then green, then blue
+    table: Vec<[f32; 3]>,
+}
+
+impl Lut {
+    /// Table with `size` entries per channel, and `size`³ in 3D; panics unless they match.
+    pub fn new(dimensions: LutDimensions, size: usize, table: Vec<[f32; 3]>) -> Self {
+        assert!(size >= 2, "LUT needs at least 2 entries per channel");
+        assert_eq!(table.len(), entry_count(dimensions, size), "LUT table length must match its size");
+        Lut { title: None, domain_min: [0.; 3], domain_max: [1.; 3], dimensions, size, table }
+    }
+
+    /// 3D LUT with `function` evaluated at each lattice point of the unit cube.
+    pub fn from_fn<F: Fn([f32; 3]) -> [f32; 3]>(size: usize, function: F) -> Self {
+        let step = 1. / (size.max(2) - 1) as f32;
+        let table = (0..size * size * size)
+            .map(|i| function([(i % size) as f32 * step, (i / size % size) as f32 * step, (i / (size * size)) as f32 * step]))
+            .collect();
+        Lut::new(LutDimensions::Three, size, table)
+    }
+
+    pub fn identity(dimensions: LutDimensions, size: usize) -> Self {
+        match dimensions {
+            LutDimensions::Three => Lut::from_fn(size, |rgb| rgb),
+            LutDimensions::One => {
+                let step = 1. / (size.max(2) - 1) as f32;
+                Lut::new(dimensions, size, (0..size).map(|i| [i as f32 * step; 3]).collect())
+            }
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Lut, String> {
+        let path = path.as_ref();
+        fs::read_to_string(path)
+            .map_err(|error| error.to_string())
+            .and_then(|source| Lut::parse(&source))
+            .map_err(|error| format!("In LUT file '{}': {}", path.display(), error))
+    }
+
+    /// Parses `.cube` text with either `LUT_1D_SIZE` or `LUT_3D_SIZE`; unknown keywords are
+    /// skipped.
+    pub fn parse(source: &str) -> Result<Lut, String> {
+        let mut title = None;
+        let mut shape = None;
+        let mut domain = ([0.; 3], [1.; 3]);
+        let mut table = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            let error = |message: &str| format!("Line {}: {}", index + 1, message);
+            let line = line.split('#').next().unwrap().trim();
+            let keyword = match line.split_whitespace().next() {
+                Some(keyword) => keyword,
+                None => continue,
+            };
+            let arguments = line[keyword.len()..].trim();
+            let numbers = |text: &str| -> Result<Vec<f32>, String> {
+                text.split_whitespace()
+                    .map(|number| number.parse().map_err(|_| error(&format!("invalid number '{}'", number))))
+                    .collect()
+            };
+            let first = keyword.chars().next().unwrap();
+            if first.is_ascii_digit() || first == '-' || first == '+' || first == '.' {
+                let values = numbers(line)?;
+                if values.len() != 3 {
+                    return Err(error("table rows need 3 values"));
+                }
+                table.push([values[0], values[1], values[2]]);
+                continue;
+            }
+            if !table.is_empty() {
+                return Err(error(&format!("'{}' after table data", keyword)));
+            }
+            match keyword {
+                "TITLE" => title = Some(arguments.trim_matches('"').to_owned()),
+                "LUT_1D_SIZE" | "LUT_3D_SIZE" => {
+                    let dimensions = if keyword == "LUT_1D_SIZE" { LutDimensions::One } else { LutDimensions::Three };
+                    let limit = if dimensions == LutDimensions::One { 65536 } else { 256 };
+                    let size = match arguments.parse::<usize>() {
+                        Ok(size) if size >= 2 && size <= limit => size,
+                        _ => return Err(error(&format!("{} must be 2 to {}", keyword, limit))),
+                    };
+                    if shape.is_some() {
+                        return Err(error("only one of LUT_1D_SIZE and LUT_3D_SIZE is supported"));
+                    }
+                    shape = Some((dimensions, size));
+                }
+                "DOMAIN_MIN" | "DOMAIN_MAX" => {
+                    let values = numbers(arguments)?;
+                    if values.len() != 3 {
+                        return Err(error(&format!("{} needs 3 values", keyword)));
+                    }
+                    let bound = if keyword == "DOMAIN_MIN" { &mut domain.0 } else { &mut domain.1 };
+                    bound.copy_from_slice(&values);
+                }
+                "LUT_1D_INPUT_RANGE" | "LUT_3D_INPUT_RANGE" => {
+                    let values = numbers(arguments)?;
+                    if values.len() != 2 {
+                        return Err(error(&format!("{} needs 2 values", keyword)));
+                    }
+                    domain = ([values[0]; 3], [values[1]; 3]);
+                }
+                _ => {}
+            }
+        }
+
+        let (dimensions, size) = shape.ok_or_else(|| "Missing LUT_1D_SIZE or LUT_3D_SIZE".to_owned())?;
+        if table.len() != entry_count(dimensions, size) {
+            return Err(format!("Expected {} table rows, found {}", entry_count(dimensions, size), table.len()));
+        }
+        if (0..3).any(|c| domain.1[c].partial_cmp(&domain.0[c]) != Some(Ordering::Greater)) {
+            return Err("DOMAIN_MAX must be above DOMAIN_MIN".to_owned());
+        }
+        Ok(Lut { title, domain_min: domain.0, domain_max: domain.1, dimensions, size, table })
+    }
+
+    /// Writes `.cube` text that `parse` reads back unchanged.
+    pub fn to_cube(&self) -> String {
+        let mut cube = String::new();
+        if let Some(ref title) = self.title {
+            writeln!(cube, "TITLE \"{}\"", title).unwrap();
+        }
+        let keyword = match self.dimensions {
+            LutDimensions::One => "LUT_1D_SIZE",
+            LutDimensions::Three => "LUT_3D_SIZE",
+        };
+        writeln!(cube, "{} {}", keyword, self.size).unwrap();
+        let (min, max) = (self.domain_min, self.domain_max);
+        writeln!(cube, "DOMAIN_MIN {} {} {}", min[0], min[1], min[2]).unwrap();
+        writeln!(cube, "DOMAIN_MAX {} {} {}", max[0], max[1], max[2]).unwrap();
+        for row in &self.table {
+            writeln!(cube, "{} {} {}", row[0], row[1], row[2]).unwrap();
+        }
+        cube
+    }
+
+    pub fn dimensions(&self) -> LutDimensions { self.dimensions }
+    pub fn size(&self) -> usize { self.size }
+    pub fn table(&self) -> &[[f32; 3]] { &self.table }
+
+    pub fn apply(&self, rgb: [f32; 3], interpolation: LutInterpolation) -> [f32; 3] {
+        let scale = (self.size - 1) as f32;
+        let mut index = [0; 3];
+        let mut fraction = [0.; 3];
+        for c in 0..3 {
+            let t = (rgb[c] - self.domain_min[c]) / (self.domain_max[c] - self.domain_min[c]);
+            let position = if t > 0. { t.min(1.) * scale } else { 0. };
+            index[c] = (position as usize).min(self.size - 2);
+            fraction[c] = position - index[c] as f32;
+        }
+        match self.dimensions {
+            LutDimensions::One => {
+                let mut out = [0.; 3];
+                for c in 0..3 {
+                    let (low, high) = (self.table[index[c]][c], self.table[index[c] + 1][c]);
+                    out[c] = low + (high - low) * fraction[c];
+                }
+                out
+            }
+            LutDimensions::Three => self.sample_lattice(index, fraction, interpolation),
+        }
+    }
+
+    /// Applies the LUT to RGBA pixels in parallel, leaving alpha alone.
+    pub fn apply_to(&self, pixels: &mut [[f32; 4]], interpolation: LutInterpolation) {
+        pixels.par_chunks_mut(1024).for_each(|chunk| {
+            for pixel in chunk {
+                let rgb = self.apply([pixel[0], pixel[1], pixel[2]], interpolation);
+                pixel[..3].copy_from_slice(&rgb);
+            }
+        });
+    }
+
+    /// LUT going from `self` at `t` = 0 to `other` at `t` = 1, baked onto the lattice and domain
+    /// of `self`, or of `other` when only that is 3D.
+    pub fn mix(&self, other: &Lut, t: f32, interpolation: LutInterpolation) -> Lut {
+        let base = if self.dimensions == LutDimensions::One && other.dimensions == LutDimensions::Three { other } else { self };
+        let step = 1. / (base.size - 1) as f32;
+        let input = |i: usize, c: usize| base.domain_min[c] + (base.domain_max[c] - base.domain_min[c]) * i as f32 * step;
+        let size = base.size;
+        let table = (0..entry_count(base.dimensions, size))
+            .into_par_iter()
+            .map(|i| {
+                let rgb = match base.dimensions {
+                    LutDimensions::One => [input(i, 0), input(i, 1), input(i, 2)],
+                    LutDimensions::Three => [input(i % size, 0), input(i / size % size, 1), input(i / (size * size), 2)],
+                };
+                let (from, to) = (self.apply(rgb, interpolation), other.apply(rgb, interpolation));
+                [from[0] + (to[0] - from[0]) * t, from[1] + (to[1] - from[1]) * t, from[2] + (to[2] - from[2]) * t]
+            })
+            .collect();
+        Lut { title: None, domain_min: base.domain_min, domain_max: base.domain_max, dimensions: base.dimensions, size, table }
+    }
+
+    fn sample_lattice(&self, index: [usize; 3], fraction: [f32; 3], interpolation: LutInterpolation) -> [f32; 3] {
+        let size = self.size;
+        let base = index[0] + index[1] * size + index[2] * size * size;
+        // corner with red, green and blue offsets as the bits of `corner`
+        let at = |corner: usize| self.table[base + (corner & 1) + (corner >> 1 & 1) * size + (corner >> 2) * size * size];
+        let [r, g, b] = fraction;
+        let mut out = [0.; 3];
+        match interpolation {
+            LutInterpolation::Trilinear => {
+                let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+                for (c, value) in out.iter_mut().enumerate() {
+                    let bottom = lerp(lerp(at(0)[c], at(1)[c], r), lerp(at(2)[c], at(3)[c], r), g);
+                    let top = lerp(lerp(at(4)[c], at(5)[c], r), lerp(at(6)[c], at(7)[c], r), g);
+                    *value = lerp(bottom, top, b);
+                }
+            }
+            LutInterpolation::Tetrahedral => {
+                // walks from the black to the white corner of the cell, largest fraction first
+                const RED: usize = 1;
+                const GREEN: usize = 2;
+                const BLUE: usize = 4;
+                let (first, second) = if r > g {
+                    if g > b {
+                        (RED, RED | GREEN)
+                    } else if r > b {
+                        (RED, RED | BLUE)
+                    } else {
+                        (BLUE, RED | BLUE)
+                    }
+                } else if b > g {
+                    (BLUE, GREEN | BLUE)
+                } else if b > r {
+                    (GREEN, GREEN | BLUE)
+                } else {
+                    (GREEN, RED | GREEN)
+                };
+                let weight = |axis: usize| match axis {
+                    RED => r,
+                    GREEN => g,
+                    _ => b,
+                };
+                let (w1, w2, w3) = (weight(first), weight(second ^ first), weight(7 ^ second));
+                let (c0, c1, c2, c3) = (at(0), at(first), at(second), at(7));
+                for c in 0..3 {
+                    out[c] = c0[c] + w1 * (c1[c] - c0[c]) + w2 * (c2[c] - c1[c]) + w3 * (c3[c] - c2[c]);
+                }
+            }
+        }
+        out
+    }
+}
+
+fn entry_count(dimensions: LutDimensions, size: usize) -> usize {
+    match dimensions {
+        LutDimensions::One => size,
+        LutDimensions::Three => size * size * size,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use color::{Lut, LutDimensions, LutInterpolation};
+
+    fn invert() -> Lut {
+        let cube = "# comment\nTITLE \"invert\"\nLUT_3D_SIZE 2\nLUT_IN_VIDEO_RANGE\n\
+            1 1 1\n0 1 1\n1 0 1\n0 0 1\n1 1 0\n0 1 0\n1 0 0\n0 0 0\n";
+        Lut::parse(cube).unwrap()
+    }
+
+    #[test]
+    fn parses_and_writes_cube_files() {
+        let invert = invert();
+        assert_eq!(invert.title, Some("invert".to_owned()));
+        assert_eq!(Lut::parse(&invert.to_cube()), Ok(invert.clone()));
+    }
+
+    #[test]
+    fn rejects_malformed_cube_files() {
+        assert_eq!(Lut::parse("LUT_3D_SIZE 2\n0 0 0\n"), Err("Expected 8 table rows, found 1".to_owned()));
+        assert_eq!(Lut::parse("LUT_1D_SIZE 2\n0 0 x\n"), Err("Line 2: invalid number 'x'".to_owned()));
+        assert!(Lut::parse("LUT_1D_SIZE 2\nDOMAIN_MIN 1 0 0\nDOMAIN_MAX 1 1 1\n0 0 0\n1 1 1\n").is_err());
+    }
+
+    #[test]
+    fn samples_a_linear_lattice_exactly() {
+        let invert = invert();
+        for &interpolation in &[LutInterpolation::Trilinear, LutInterpolation::Tetrahedral] {
+            assert_eq!(invert.apply([0.25, 0.5, 1.5], interpolation), [0.75, 0.5, 0.]);
+        }
+    }
+
+    #[test]
+    fn tetrahedral_follows_the_grey_diagonal() {
+        // on a non-linear lattice tetrahedral follows the cell's grey diagonal, trilinear blends all 8 corners
+        let product = Lut::from_fn(3, |rgb| [rgb[0] * rgb[1], rgb[1] * rgb[2], rgb[2] * rgb[0]]);
+        let grey = [0.3, 0.3, 0.3];
+        let (trilinear, tetrahedral) = (product.apply(grey, LutInterpolation::Trilinear), product.apply(grey, LutInterpolation::Tetrahedral));
+        assert!((0..3).all(|c| (trilinear[c] - 0.09).abs() < 1e-6 && (tetrahedral[c] - 0.15).abs() < 1e-6));
+    }
+
+    #[test]
+    fn maps_1d_curves_over_their_domain() {
+        let curve = Lut::parse("LUT_1D_SIZE 3\nDOMAIN_MAX 2 2 2\n0 0 0\n0.5 0.25 1\n1 1 1\n").unwrap();
+        assert_eq!(curve.apply([0.5, 1., 3.], LutInterpolation::Trilinear), [0.25, 0.25, 1.]);
+    }
+
+    #[test]
+    fn mixes_into_a_3d_lut() {
+        let halfway = Lut::identity(LutDimensions::One, 2).mix(&invert(), 0.5, LutInterpolation::Tetrahedral);
+        assert_eq!(halfway.dimensions(), LutDimensions::Three);
+        let mut pixels = vec![[0.2, 0.4, 0.9, 0.5]; 3];
+        halfway.apply_to(&mut pixels, LutInterpolation::Tetrahedral);
+        assert_eq!(pixels[2], [0.5, 0.5, 0.5, 0.5]);
+    }
+}
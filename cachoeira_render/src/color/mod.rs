@@ -0,0 +1,614 @@
+//! Colour types and conversions between sRGB, linear RGB, HSV, HSL, CIE XYZ, OKLab and OKLCh.
+//!
+//! Everything goes through `LinearRgb` (linear sRGB primaries, D65 white), which is also what
+//! textures and the renderer work in. Components are `f32`, nominally in 0..1; out-of-gamut
+//! values survive conversions rather than being clamped. Hues are in degrees.
+//!
+//! `Lut` and `ColorGrade` turn rendered frames into calibrated output signals on the CPU.
+
+use std::f32::consts::PI;
+
+pub use self::blend::{BlendMode, Premultiplied};
+pub use self::grade::{temperature_gains, ColorGrade, Transfer};
+pub use self::gradient::{Gradient, GradientStop, Interpolation};
+pub use self::lut::{Lut, LutDimensions, LutInterpolation};
+pub use self::palette::Palette;
+
+mod blend;
+mod grade;
+mod gradient;
+mod lut;
+mod palette;
+
+/// A colour space every other one can be converted to and from, through `LinearRgb`.
+pub trait Color: Copy + From<LinearRgb> + Into<LinearRgb> {
+    fn alpha(&self) -> f32;
+
+    fn convert<T: Color>(self) -> T {
+        let linear: LinearRgb = self.into();
+        T::from(linear)
+    }
+}
+
+/// Interpolation within a colour space.
+pub trait Mix: Copy {
+    /// `self` at `t` = 0, `other` at `t` = 1.
+    fn mix(self, other: Self, t: f32) -> Self;
+}
+
+/// Gamma-encoded sRGB, as found in image files, colour pickers and CSS.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Srgb {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub alpha: f32,
+}
+
+/// Linear-light RGB with sRGB primaries; the space for lighting, blending and rendering.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct LinearRgb {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub alpha: f32,
+}
+
+/// Hue, saturation and value over gamma-encoded sRGB.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Hsv {
+    pub hue: f32,
+    pub saturation: f32,
+    pub value: f32,
+    pub alpha: f32,
+}
+
+/// Hue, saturation and lightness over gamma-encoded sRGB.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Hsl {
+    pub hue: f32,
+    pub saturation: f32,
+    pub lightness: f32,
+    pub alpha: f32,
+}
+
+/// CIE 1931 XYZ, D65 white with Y = 1.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Xyz {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub alpha: f32,
+}
+
+/// Björn Ottosson's perceptual OKLab: lightness and two opponent axes.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Oklab {
+    pub l: f32,
+    pub a: f32,
+    pub b: f32,
+    pub alpha: f32,
+}
+
+/// OKLab in polar form: lightness, chroma and hue.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Oklch {
+    pub l: f32,
+    pub chroma: f32,
+    pub hue: f32,
+    pub alpha: f32,
+}
+
+impl Srgb {
+    pub fn new(r: f32, g: f32, b: f32) -> Self {
+        Srgb { r, g, b, alpha: 1. }
+    }
+
+    pub fn with_alpha(self, alpha: f32) -> Self {
+        Srgb { alpha, ..self }
+    }
+
+    pub fn from_rgba8(rgba: [u8; 4]) -> Self {
+        Srgb {
+            r: rgba[0] as f32 / 255.,
+            g: rgba[1] as f32 / 255.,
+            b: rgba[2] as f32 / 255.,
+            alpha: rgba[3] as f32 / 255.,
+        }
+    }
+
+    /// Clamped and rounded to 8 bits per channel.
+    pub fn to_rgba8(&self) -> [u8; 4] {
+        let byte = |c: f32| (c.clamp(0., 1.) * 255. + 0.5) as u8;
+        [byte(self.r), byte(self.g), byte(self.b), byte(self.alpha)]
+    }
+
+    /// Parses `#rgb`, `#rrggbb` or `#rrggbbaa`, the `#` being optional.
+    pub fn from_hex(hex: &str) -> Result<Self, String> {
+        let digits = hex.trim_start_matches('#');
+        let nibble = |i: usize| {
+            digits.get(i..i + 1)
+                .and_then(|digit| u8::from_str_radix(digit, 16).ok())
+                .ok_or_else(|| format!("Invalid hex colour '{}'", hex))
+        };
+        let byte = |i: usize| -> Result<u8, String> { Ok(nibble(i * 2)? << 4 | nibble(i * 2 + 1)?) };
+        match digits.len() {
+            3 => Ok(Srgb::from_rgba8([nibble(0)? * 17, nibble(1)? * 17, nibble(2)? * 17, 255])),
+            6 => Ok(Srgb::from_rgba8([byte(0)?, byte(1)?, byte(2)?, 255])),
+            8 => Ok(Srgb::from_rgba8([byte(0)?, byte(1)?, byte(2)?, byte(3)?])),
+            _ => Err(format!("Invalid hex colour '{}'", hex)),
+        }
+    }
+
+    /// `#rrggbb`, or `#rrggbbaa` when not opaque.
+    pub fn to_hex(&self) -> String {
+        let [r, g, b, a] = self.to_rgba8();
+        if a == 255 {
+            format!("#{:02x}{:02x}{:02x}", r, g, b)
+        } else {
+            format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+        }
+    }
+}
+
+impl LinearRgb {
+    pub const BLACK: LinearRgb = LinearRgb { r: 0., g: 0., b: 0., alpha: 1. };
+    pub const WHITE: LinearRgb = LinearRgb { r: 1., g: 1., b: 1., alpha: 1. };
+    pub const TRANSPARENT: LinearRgb = LinearRgb { r: 0., g: 0., b: 0., alpha: 0. };
+
+    pub fn new(r: f32, g: f32, b: f32) -> Self {
+        LinearRgb { r, g, b, alpha: 1. }
+    }
+
+    pub fn with_alpha(self, alpha: f32) -> Self {
+        LinearRgb { alpha, ..self }
+    }
+
+    /// Relative luminance, Y of XYZ.
+    pub fn luminance(&self) -> f32 {
+        0.2126729 * self.r + 0.7151522 * self.g + 0.0721750 * self.b
+    }
+
+    pub fn to_array(&self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.alpha]
+    }
+}
+
+impl Hsv {
+    pub fn new(hue: f32, saturation: f32, value: f32) -> Self {
+        Hsv { hue, saturation, value, alpha: 1. }
+    }
+}
+
+impl Hsl {
+    pub fn new(hue: f32, saturation: f32, lightness: f32) -> Self {
+        Hsl { hue, saturation, lightness, alpha: 1. }
+    }
+}
+
+impl Xyz {
+    pub fn new(x: f32, y: f32, z: f32) -> Self {
+        Xyz { x, y, z, alpha: 1. }
+    }
+}
+
+impl Oklab {
+    pub fn new(l: f32, a: f32, b: f32) -> Self {
+        Oklab { l, a, b, alpha: 1. }
+    }
+
+    /// Euclidean distance, a decent measure of perceived difference.
+    pub fn distance(&self, other: &Oklab) -> f32 {
+        ((self.l - other.l).powi(2) + (self.a - other.a).powi(2) + (self.b - other.b).powi(2)).sqrt()
+    }
+}
+
+impl Oklch {
+    pub fn new(l: f32, chroma: f32, hue: f32) -> Self {
+        Oklch { l, chroma, hue, alpha: 1. }
+    }
+}
+
+/// sRGB transfer function, mirrored for negative values.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    let magnitude = c.abs();
+    let linear = if magnitude <= 0.04045 { magnitude / 12.92 } else { ((magnitude + 0.055) / 1.055).powf(2.4) };
+    if c < 0. { -linear } else { linear }
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+    let magnitude = c.abs();
+    let encoded = if magnitude <= 0.0031308 { magnitude * 12.92 } else { 1.055 * magnitude.powf(1. / 2.4) - 0.055 };
+    if c < 0. { -encoded } else { encoded }
+}
+
+/// Hue wrapped into 0..360.
+fn wrap_hue(hue: f32) -> f32 {
+    let hue = hue % 360.;
+    if hue < 0. { hue + 360. } else { hue }
+}
+
+/// Interpolates hues along the shorter way round.
+fn mix_hue(from: f32, to: f32, t: f32) -> f32 {
+    let mut delta = wrap_hue(to - from);
+    if delta > 180. {
+        delta -= 360.;
+    }
+    wrap_hue(from + delta * t)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+// Conversions, with linear RGB as the hub.
+
+impl From<Srgb> for LinearRgb {
+    fn from(c: Srgb) -> Self {
+        LinearRgb { r: srgb_to_linear(c.r), g: srgb_to_linear(c.g), b: srgb_to_linear(c.b), alpha: c.alpha }
+    }
+}
+
+impl From<LinearRgb> for Srgb {
+    fn from(c: LinearRgb) -> Self {
+        Srgb { r: linear_to_srgb(c.r), g: linear_to_srgb(c.g), b: linear_to_srgb(c.b), alpha: c.alpha }
+    }
+}
+
+impl From<Srgb> for Hsv {
+    fn from(c: Srgb) -> Self {
+        let (max, min) = (c.r.max(c.g).max(c.b), c.r.min(c.g).min(c.b));
+        Hsv {
+            hue: hue(c, max, max - min),
+            saturation: if max > 0. { (max - min) / max } else { 0. },
+            value: max,
+            alpha: c.alpha,
+        }
+    }
+}
+
+impl From<Hsv> for Srgb {
+    fn from(c: Hsv) -> Self {
+        let chroma = c.value * c.saturation;
+        from_hue(c.hue, chroma, c.value - chroma, c.alpha)
+    }
+}
+
+impl From<Srgb> for Hsl {
+    fn from(c: Srgb) -> Self {
+        let (max, min) = (c.r.max(c.g).max(c.b), c.r.min(c.g).min(c.b));
+        let lightness = (max + min) * 0.5;
+        let divisor = 1. - (2. * lightness - 1.).abs();
+        Hsl {
+            hue: hue(c, max, max - min),
+            saturation: if divisor > 0. { (max - min) / divisor } else { 0. },
+            lightness,
+            alpha: c.alpha,
+        }
+    }
+}
+
+impl From<Hsl> for Srgb {
+    fn from(c: Hsl) -> Self {
+        let chroma = (1. - (2. * c.lightness - 1.).abs()) * c.saturation;
+        from_hue(c.hue, chroma, c.lightness - chroma * 0.5, c.alpha)
+    }
+}
+
+/// Hexcone hue of `c`, 0 for greys.
+fn hue(c: Srgb, max: f32, delta: f32) -> f32 {
+    if delta <= 0. {
+        0.
+    } else if max == c.r {
+        wrap_hue(60. * (c.g - c.b) / delta)
+    } else if max == c.g {
+        60. * ((c.b - c.r) / delta + 2.)
+    } else {
+        60. * ((c.r - c.g) / delta + 4.)
+    }
+}
+
+/// sRGB from a hue, the chroma and the amount added to every channel.
+fn from_hue(hue: f32, chroma: f32, offset: f32, alpha: f32) -> Srgb {
+    let sector = wrap_hue(hue) / 60.;
+    let x = chroma * (1. - (sector % 2. - 1.).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.),
+        1 => (x, chroma, 0.),
+        2 => (0., chroma, x),
+        3 => (0., x, chroma),
+        4 => (x, 0., chroma),
+        _ => (chroma, 0., x),
+    };
+    Srgb { r: r + offset, g: g + offset, b: b + offset, alpha }
+}
+
+impl From<Hsv> for Hsl {
+    fn from(c: Hsv) -> Self {
+        Hsl::from(Srgb::from(c))
+    }
+}
+
+impl From<Hsl> for Hsv {
+    fn from(c: Hsl) -> Self {
+        Hsv::from(Srgb::from(c))
+    }
+}
+
+impl From<LinearRgb> for Xyz {
+    fn from(c: LinearRgb) -> Self {
+        Xyz {
+            x: 0.4124564 * c.r + 0.3575761 * c.g + 0.1804375 * c.b,
+            y: 0.2126729 * c.r + 0.7151522 * c.g + 0.072175 * c.b,
+            z: 0.0193339 * c.r + 0.119192 * c.g + 0.9503041 * c.b,
+            alpha: c.alpha,
+        }
+    }
+}
+
+impl From<Xyz> for LinearRgb {
+    fn from(c: Xyz) -> Self {
+        LinearRgb {
+            r: 3.2404542 * c.x - 1.5371385 * c.y - 0.4985314 * c.z,
+            g: -0.969266 * c.x + 1.8760108 * c.y + 0.041556 * c.z,
+            b: 0.0556434 * c.x - 0.2040259 * c.y + 1.0572252 * c.z,
+            alpha: c.alpha,
+        }
+    }
+}
+
+impl From<LinearRgb> for Oklab {
+    fn from(c: LinearRgb) -> Self {
+        let l = (0.41222146 * c.r + 0.53633255 * c.g + 0.051445995 * c.b).cbrt();
+        let m = (0.2119035 * c.r + 0.6806995 * c.g + 0.10739696 * c.b).cbrt();
+        let s = (0.08830246 * c.r + 0.28171885 * c.g + 0.6299787 * c.b).cbrt();
+        Oklab {
+            l: 0.21045426 * l + 0.7936178 * m - 0.004072047 * s,
+            a: 1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+            b: 0.025904037 * l + 0.78277177 * m - 0.80867577 * s,
+            alpha: c.alpha,
+        }
+    }
+}
+
+impl From<Oklab> for LinearRgb {
+    fn from(c: Oklab) -> Self {
+        let l = (c.l + 0.39633778 * c.a + 0.21580376 * c.b).powi(3);
+        let m = (c.l - 0.105561346 * c.a - 0.06385417 * c.b).powi(3);
+        let s = (c.l - 0.08948418 * c.a - 1.2914855 * c.b).powi(3);
+        LinearRgb {
+            r: 4.0767417 * l - 3.3077116 * m + 0.23096994 * s,
+            g: -1.268438 * l + 2.6097574 * m - 0.34131938 * s,
+            b: -0.0041960864 * l - 0.7034186 * m + 1.7076147 * s,
+            alpha: c.alpha,
+        }
+    }
+}
+
+impl From<Oklab> for Oklch {
+    fn from(c: Oklab) -> Self {
+        Oklch {
+            l: c.l,
+            chroma: (c.a * c.a + c.b * c.b).sqrt(),
+            hue: wrap_hue(c.b.atan2(c.a) * 180. / PI),
+            alpha: c.alpha,
+        }
+    }
+}
+
+impl From<Oklch> for Oklab {
+    fn from(c: Oklch) -> Self {
+        let (sin, cos) = (c.hue * PI / 180.).sin_cos();
+        Oklab { l: c.l, a: c.chroma * cos, b: c.chroma * sin, alpha: c.alpha }
+    }
+}
+
+impl From<Hsv> for LinearRgb {
+    fn from(c: Hsv) -> Self { Srgb::from(c).into() }
+}
+
+impl From<LinearRgb> for Hsv {
+    fn from(c: LinearRgb) -> Self { Srgb::from(c).into() }
+}
+
+impl From<Hsl> for LinearRgb {
+    fn from(c: Hsl) -> Self { Srgb::from(c).into() }
+}
+
+impl From<LinearRgb> for Hsl {
+    fn from(c: LinearRgb) -> Self { Srgb::from(c).into() }
+}
+
+impl From<Oklch> for LinearRgb {
+    fn from(c: Oklch) -> Self { Oklab::from(c).into() }
+}
+
+impl From<LinearRgb> for Oklch {
+    fn from(c: LinearRgb) -> Self { Oklab::from(c).into() }
+}
+
+impl From<[f32; 4]> for LinearRgb {
+    fn from(c: [f32; 4]) -> Self {
+        LinearRgb { r: c[0], g: c[1], b: c[2], alpha: c[3] }
+    }
+}
+
+impl From<LinearRgb> for [f32; 4] {
+    fn from(c: LinearRgb) -> Self { c.to_array() }
+}
+
+impl Color for LinearRgb {
+    fn alpha(&self) -> f32 { self.alpha }
+}
+
+impl Color for Srgb {
+    fn alpha(&self) -> f32 { self.alpha }
+}
+
+impl Color for Hsv {
+    fn alpha(&self) -> f32 { self.alpha }
+}
+
+impl Color for Hsl {
+    fn alpha(&self) -> f32 { self.alpha }
+}
+
+impl Color for Xyz {
+    fn alpha(&self) -> f32 { self.alpha }
+}
+
+impl Color for Oklab {
+    fn alpha(&self) -> f32 { self.alpha }
+}
+
+impl Color for Oklch {
+    fn alpha(&self) -> f32 { self.alpha }
+}
+
+impl Mix for LinearRgb {
+    fn mix(self, other: Self, t: f32) -> Self {
+        LinearRgb {
+            r: lerp(self.r, other.r, t),
+            g: lerp(self.g, other.g, t),
+            b: lerp(self.b, other.b, t),
+            alpha: lerp(self.alpha, other.alpha, t),
+        }
+    }
+}
+
+impl Mix for Srgb {
+    fn mix(self, other: Self, t: f32) -> Self {
+        Srgb {
+            r: lerp(self.r, other.r, t),
+            g: lerp(self.g, other.g, t),
+            b: lerp(self.b, other.b, t),
+            alpha: lerp(self.alpha, other.alpha, t),
+        }
+    }
+}
+
+impl Mix for Xyz {
+    fn mix(self, other: Self, t: f32) -> Self {
+        Xyz {
+            x: lerp(self.x, other.x, t),
+            y: lerp(self.y, other.y, t),
+            z: lerp(self.z, other.z, t),
+            alpha: lerp(self.alpha, other.alpha, t),
+        }
+    }
+}
+
+impl Mix for Oklab {
+    fn mix(self, other: Self, t: f32) -> Self {
+        Oklab {
+            l: lerp(self.l, other.l, t),
+            a: lerp(self.a, other.a, t),
+            b: lerp(self.b, other.b, t),
+            alpha: lerp(self.alpha, other.alpha, t),
+        }
+    }
+}
+
+/// Hues take the shorter way round; a grey end takes the other end's hue, so fading to white
+/// does not sweep through the spectrum.
+impl Mix for Oklch {
+    fn mix(self, other: Self, t: f32) -> Self {
+        let (from, to) = powerless_hues(self.hue, self.chroma, other.hue, other.chroma);
+        Oklch {
+            l: lerp(self.l, other.l, t),
+            chroma: lerp(self.chroma, other.chroma, t),
+            hue: mix_hue(from, to, t),
+            alpha: lerp(self.alpha, other.alpha, t),
+        }
+    }
+}
+
+impl Mix for Hsv {
+    fn mix(self, other: Self, t: f32) -> Self {
+        let (from, to) = powerless_hues(self.hue, self.saturation, other.hue, other.saturation);
+        Hsv {
+            hue: mix_hue(from, to, t),
+            saturation: lerp(self.saturation, other.saturation, t),
+            value: lerp(self.value, other.value, t),
+            alpha: lerp(self.alpha, other.alpha, t),
+        }
+    }
+}
+
+impl Mix for Hsl {
+    fn mix(self, other: Self, t: f32) -> Self {
+        let (from, to) = powerless_hues(self.hue, self.saturation, other.hue, other.saturation);
+        Hsl {
+            hue: mix_hue(from, to, t),
+            saturation: lerp(self.saturation, other.saturation, t),
+            lightness: lerp(self.lightness, other.lightness, t),
+            alpha: lerp(self.alpha, other.alpha, t),
+        }
+    }
+}
+
+fn powerless_hues(from: f32, from_chroma: f32, to: f32, to_chroma: f32) -> (f32, f32) {
+    const GREY: f32 = 1e-4;
+    match (from_chroma < GREY, to_chroma < GREY) {
+        (true, false) => (to, to),
+        (false, true) => (from, from),
+        _ => (from, to),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Color, Hsl, Hsv, LinearRgb, Mix, Oklab, Oklch, Srgb, Xyz};
+
+    fn close(a: [f32; 4], b: [f32; 4]) -> bool {
+        a.iter().zip(&b).all(|(a, b)| (a - b).abs() < 1e-4)
+    }
+
+    #[test]
+    fn parses_and_formats_hex() {
+        let orange = Srgb::from_hex("#ff8000").unwrap();
+        assert_eq!(orange.to_hex(), "#ff8000");
+        assert_eq!(Srgb::from_hex("#f80").unwrap().to_rgba8(), [255, 136, 0, 255]);
+        assert!(Srgb::from_hex("#12345").is_err());
+    }
+
+    #[test]
+    fn converts_to_known_values() {
+        let orange = Srgb::new(1., 0.5019608, 0.);
+        let linear: LinearRgb = orange.into();
+        assert!((linear.g - 0.2158605).abs() < 1e-5);
+        let hsv: Hsv = orange.into();
+        assert!(close([hsv.hue, hsv.saturation, hsv.value, hsv.alpha], [30.117647, 1., 1., 1.]));
+        let hsl: Hsl = orange.into();
+        assert!(close([hsl.hue, hsl.saturation, hsl.lightness, hsl.alpha], [30.117647, 1., 0.5, 1.]));
+
+        // white in OKLab is (1, 0, 0) and has Y = 1 in XYZ
+        let white: Oklab = LinearRgb::WHITE.convert();
+        assert!(close([white.l, white.a, white.b, white.alpha], [1., 0., 0., 1.]));
+        let white: Xyz = LinearRgb::WHITE.convert();
+        assert!((white.y - 1.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn round_trips_through_every_space() {
+        let colors = [Srgb::new(0.2, 0.7, 0.1), Srgb::new(1., 0., 0.5).with_alpha(0.5), Srgb::new(0., 0., 0.)];
+        for &color in &colors {
+            let expected = [color.r, color.g, color.b, color.alpha];
+            let round_trip = |c: Srgb| [c.r, c.g, c.b, c.alpha];
+            assert!(close(round_trip(color.convert::<Hsv>().convert()), expected));
+            assert!(close(round_trip(color.convert::<Hsl>().convert()), expected));
+            assert!(close(round_trip(color.convert::<Xyz>().convert()), expected));
+            assert!(close(round_trip(color.convert::<Oklab>().convert()), expected));
+            assert!(close(round_trip(color.convert::<Oklch>().convert()), expected));
+        }
+    }
+
+    #[test]
+    fn hue_interpolation() {
+        let (red, magenta) = (Hsv::new(10., 1., 1.), Hsv::new(300., 1., 1.));
+        assert!((red.mix(magenta, 0.5).hue - 335.).abs() < 1e-4);
+        // no sweep through the spectrum when one end is grey
+        let (blue, white) = (Oklch::from(LinearRgb::new(0., 0., 1.)), Oklch::from(LinearRgb::WHITE));
+        assert!((blue.mix(white, 0.5).hue - blue.hue).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,97 @@
+use color::{Gradient, Interpolation, LinearRgb, Oklab, Srgb};
+
+const PICO8: &[&str] = &[
+    "#000000", "#1d2b53", "#7e2553", "#008751", "#ab5236", "#5f574f", "#c2c3c7", "#fff1e8",
+    "#ff004d", "#ffa300", "#ffec27", "#00e436", "#29adff", "#83769c", "#ff77a8", "#ffccaa",
+];
+const VIRIDIS: &[&str] = &[
+    "#440154", "#482878", "#3e4989", "#31688e", "#26828e", "#1f9e89", "#35b779", "#6ece58", "#b5de2b", "#fde725",
+];
+const MAGMA: &[&str] = &[
+    "#000004", "#180f3d", "#440f76", "#721f81", "#9e2f7f", "#cd4071", "#f1605d", "#fd9668", "#feca8d", "#fcfdbf",
+];
+const INFERNO: &[&str] = &[
+    "#000004", "#1b0c41", "#4a0c6b", "#781c6d", "#a52c60", "#cf4446", "#ed6925", "#fb9b06", "#f7d13d", "#fcffa4",
+];
+const GRAYSCALE: &[&str] = &["#000000", "#ffffff"];
+
+/// An ordered set of colours, e.g. for indexed art or as gradient stops.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Palette {
+    pub colors: Vec<Srgb>,
+}
+
+impl Palette {
+    /// Names accepted by `named`.
+    pub const NAMES: &'static [&'static str] = &["pico8", "viridis", "magma", "inferno", "grayscale"];
+
+    pub fn new(colors: Vec<Srgb>) -> Self {
+        Palette { colors }
+    }
+
+    /// Built-in palette, the matplotlib colour maps as ten evenly spaced samples.
+    pub fn named(name: &str) -> Option<Palette> {
+        let hexes = match name {
+            "pico8" => PICO8,
+            "viridis" => VIRIDIS,
+            "magma" => MAGMA,
+            "inferno" => INFERNO,
+            "grayscale" => GRAYSCALE,
+            _ => return None,
+        };
+        Some(Palette::new(hexes.iter().map(|hex| Srgb::from_hex(hex).unwrap()).collect()))
+    }
+
+    pub fn len(&self) -> usize { self.colors.len() }
+
+    pub fn is_empty(&self) -> bool { self.colors.is_empty() }
+
+    /// Colour at `index`, wrapping around.
+    pub fn get(&self, index: usize) -> Option<Srgb> {
+        if self.colors.is_empty() {
+            return None;
+        }
+        Some(self.colors[index % self.colors.len()])
+    }
+
+    /// Index of the perceptually closest colour.
+    pub fn nearest<C: Into<LinearRgb>>(&self, color: C) -> Option<usize> {
+        let target = Oklab::from(color.into());
+        self.colors.iter()
+            .map(|&color| target.distance(&Oklab::from(LinearRgb::from(color))))
+            .enumerate()
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .map(|(index, _)| index)
+    }
+
+    /// The colours as evenly spaced gradient stops.
+    pub fn gradient(&self, interpolation: Interpolation) -> Gradient {
+        Gradient::from_colors(self.colors.iter().cloned(), interpolation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use color::{Interpolation, Palette, Srgb};
+
+    #[test]
+    fn named_palettes() {
+        for name in Palette::NAMES {
+            assert!(Palette::named(name).is_some_and(|palette| palette.len() >= 2), "{}", name);
+        }
+        assert_eq!(Palette::named("unknown"), None);
+    }
+
+    #[test]
+    fn finds_nearest_colour() {
+        let pico8 = Palette::named("pico8").unwrap();
+        assert_eq!(pico8.get(16), Some(Srgb::new(0., 0., 0.)));
+        assert_eq!(pico8.nearest(Srgb::from_hex("#fe0a50").unwrap()), Some(8));
+    }
+
+    #[test]
+    fn spreads_into_a_gradient() {
+        let viridis = Palette::named("viridis").unwrap().gradient(Interpolation::Oklab);
+        assert_eq!(Srgb::from(viridis.sample(1.)).to_hex(), "#fde725");
+    }
+}
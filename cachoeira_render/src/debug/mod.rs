@@ -0,0 +1,318 @@
+//! Immediate-mode debug drawing.
+//!
+//! Any system can push lines, shapes and labels into the `DebugLines` resource while the frame
+//! runs, and a `DebugRenderer` draws them over the scene at the end of it. Entries last one
+//! frame unless given a duration, so the usual pattern is to draw again every frame:
+//!
+//! ```ignore
+//! debug.sphere(trigger.center, trigger.radius, [0., 1., 0., 1.]);
+//! debug.line(from, to, DebugStyle::new([1., 0., 0., 1.]).lasting(2.).on_top());
+//! ```
+
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point2, Point3, Vector2, Vector3, Vector4};
+use specs::prelude::{Read, System, Write};
+
+use cachoeira_core::culling::Aabb;
+use cachoeira_core::transform::GlobalTransform;
+use cachoeira_core::Time;
+
+use camera::Camera;
+
+pub use self::render::DebugRenderer;
+
+mod render;
+
+/// Segments used for circles and spheres.
+pub const CIRCLE_SEGMENTS: usize = 32;
+
+/// How a debug entry is drawn; a plain `[f32; 4]` color converts into a one-frame,
+/// depth-tested style.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DebugStyle {
+    /// linear, non-premultiplied
+    pub color: [f32; 4],
+    /// seconds of real time left to draw the entry; 0 draws it in this frame only
+    pub duration: f32,
+    /// hidden behind scene geometry; off for entries that must show through walls
+    pub depth_test: bool,
+}
+
+impl DebugStyle {
+    pub fn new(color: [f32; 4]) -> Self {
+        DebugStyle { color, duration: 0., depth_test: true }
+    }
+
+    pub fn lasting(mut self, seconds: f32) -> Self {
+        self.duration = seconds;
+        self
+    }
+
+    pub fn on_top(mut self) -> Self {
+        self.depth_test = false;
+        self
+    }
+}
+
+impl From<[f32; 4]> for DebugStyle {
+    fn from(color: [f32; 4]) -> Self { DebugStyle::new(color) }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DebugLine {
+    pub start: Point3<f32>,
+    pub end: Point3<f32>,
+    pub style: DebugStyle,
+}
+
+/// Text anchored to a world position; it is up to a text renderer to draw it, at the screen
+/// position found by `DebugLines::screen_labels`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DebugLabel {
+    pub position: Point3<f32>,
+    pub text: String,
+    pub style: DebugStyle,
+}
+
+/// Resource collecting the debug lines and labels to draw. Shapes are broken into lines as
+/// they are added.
+#[derive(Clone, Debug, Default)]
+pub struct DebugLines {
+    lines: Vec<DebugLine>,
+    labels: Vec<DebugLabel>,
+}
+
+impl DebugLines {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn lines(&self) -> &[DebugLine] { &self.lines }
+    pub fn labels(&self) -> &[DebugLabel] { &self.labels }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty() && self.labels.is_empty()
+    }
+
+    /// Drops every entry, whatever its duration.
+    pub fn clear(&mut self) {
+        self.lines.clear();
+        self.labels.clear();
+    }
+
+    /// Counts `seconds` off every entry's duration, dropping the ones that have run out;
+    /// one-frame entries go on the first call.
+    pub fn advance(&mut self, seconds: f32) {
+        for line in &mut self.lines {
+            line.style.duration -= seconds;
+        }
+        for label in &mut self.labels {
+            label.style.duration -= seconds;
+        }
+        let live = |style: &DebugStyle| style.duration > 0.;
+        self.lines.retain(|line| live(&line.style));
+        self.labels.retain(|label| live(&label.style));
+    }
+
+    pub fn line<S: Into<DebugStyle>>(&mut self, start: Point3<f32>, end: Point3<f32>, style: S) {
+        self.lines.push(DebugLine { start, end, style: style.into() });
+    }
+
+    /// Line with a four-pronged head at `end`, a fifth of its length.
+    pub fn arrow<S: Into<DebugStyle>>(&mut self, start: Point3<f32>, end: Point3<f32>, style: S) {
+        let style = style.into();
+        self.line(start, end, style);
+        let direction = end - start;
+        let length = direction.magnitude();
+        if length <= 0. || length.is_nan() {
+            return;
+        }
+        let (side, up) = perpendiculars(direction / length);
+        let (head, spread) = (length * 0.2, length * 0.08);
+        let base = end - direction / length * head;
+        for &offset in &[side, -side, up, -up] {
+            self.line(end, base + offset * spread, style);
+        }
+    }
+
+    /// Edges of an axis-aligned box.
+    pub fn aabb<S: Into<DebugStyle>>(&mut self, aabb: &Aabb, style: S) {
+        let (center, half) = (aabb.center(), aabb.half_extents());
+        let matrix = Matrix4::from_translation(center.to_vec()) * Matrix4::from_nonuniform_scale(half.x, half.y, half.z);
+        self.cuboid(&matrix, style);
+    }
+
+    /// Edges of the cube from -1 to 1 on each axis, transformed by `matrix`; oriented boxes
+    /// pass their transform scaled by their half extents.
+    pub fn cuboid<S: Into<DebugStyle>>(&mut self, matrix: &Matrix4<f32>, style: S) {
+        let corners = cube_corners(|x, y, z| Point3::from_homogeneous(matrix * Vector4::new(x, y, z, 1.)));
+        self.box_edges(&corners, style.into());
+    }
+
+    /// Three great circles, around the X, Y and Z axes.
+    pub fn sphere<S: Into<DebugStyle>>(&mut self, center: Point3<f32>, radius: f32, style: S) {
+        let style = style.into();
+        for axis in &[Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()] {
+            self.circle(center, *axis, radius, style);
+        }
+    }
+
+    pub fn circle<S: Into<DebugStyle>>(&mut self, center: Point3<f32>, normal: Vector3<f32>, radius: f32, style: S) {
+        let style = style.into();
+        let magnitude2 = normal.magnitude2();
+        if magnitude2 <= 0. || magnitude2.is_nan() {
+            return;
+        }
+        let (u, v) = perpendiculars(normal.normalize());
+        let point = |i: usize| {
+            let angle = i as f32 / CIRCLE_SEGMENTS as f32 * 2. * ::std::f32::consts::PI;
+            center + (u * angle.cos() + v * angle.sin()) * radius
+        };
+        for i in 0..CIRCLE_SEGMENTS {
+            self.line(point(i), point(i + 1), style);
+        }
+    }
+
+    /// Edges of the volume a view-projection matrix sees, given its inverse, such as
+    /// `Camera::inverse_view_projection`.
+    pub fn frustum<S: Into<DebugStyle>>(&mut self, inverse_view_projection: &Matrix4<f32>, style: S) {
+        let corners = cube_corners(|x, y, z| Point3::from_homogeneous(inverse_view_projection * Vector4::new(x, y, z, 1.)));
+        self.box_edges(&corners, style.into());
+    }
+
+    /// The local X, Y and Z axes of a transform in red, green and blue, `length` long before
+    /// the transform's scale; the style's color is ignored.
+    pub fn axes<S: Into<DebugStyle>>(&mut self, transform: &GlobalTransform, length: f32, style: S) {
+        let style = style.into();
+        let matrix = &transform.0;
+        let origin = Point3::from_vec(matrix.w.truncate());
+        let colors = [[1., 0., 0., 1.], [0., 1., 0., 1.], [0., 0., 1., 1.]];
+        for (axis, &color) in [matrix.x, matrix.y, matrix.z].iter().zip(&colors) {
+            self.line(origin, origin + axis.truncate() * length, DebugStyle { color, ..style });
+        }
+    }
+
+    /// Square grid on the horizontal plane through `center`, `cells` wide in each direction.
+    pub fn grid<S: Into<DebugStyle>>(&mut self, center: Point3<f32>, cell_size: f32, cells: u32, style: S) {
+        let style = style.into();
+        let half = cells as f32 * cell_size * 0.5;
+        for i in 0..cells + 1 {
+            let offset = i as f32 * cell_size - half;
+            self.line(center + Vector3::new(offset, 0., -half), center + Vector3::new(offset, 0., half), style);
+            self.line(center + Vector3::new(-half, 0., offset), center + Vector3::new(half, 0., offset), style);
+        }
+    }
+
+    pub fn label<T: Into<String>, S: Into<DebugStyle>>(&mut self, position: Point3<f32>, text: T, style: S) {
+        self.labels.push(DebugLabel { position, text: text.into(), style: style.into() });
+    }
+
+    /// Where each label lands in a viewport of `size` pixels, origin at the top left; labels
+    /// behind the camera are left out.
+    pub fn screen_labels(&self, camera: &Camera, size: Vector2<f32>) -> Vec<(Point2<f32>, &DebugLabel)> {
+        self.labels.iter()
+            .filter_map(|label| camera.world_to_screen(label.position, size).map(|screen| (Point2::new(screen.x, screen.y), label)))
+            .collect()
+    }
+
+    // corners indexed by bits x, y, z
+    fn box_edges(&mut self, corners: &[Point3<f32>; 8], style: DebugStyle) {
+        for a in 0..8 {
+            for &bit in &[1, 2, 4] {
+                if a & bit == 0 {
+                    self.line(corners[a], corners[a | bit], style);
+                }
+            }
+        }
+    }
+}
+
+fn cube_corners<F: Fn(f32, f32, f32) -> Point3<f32>>(corner: F) -> [Point3<f32>; 8] {
+    let sign = |bit: usize| if bit == 0 { -1. } else { 1. };
+    let mut corners = [Point3::new(0., 0., 0.); 8];
+    for (i, point) in corners.iter_mut().enumerate() {
+        *point = corner(sign(i & 1), sign(i & 2), sign(i & 4));
+    }
+    corners
+}
+
+// two unit vectors perpendicular to a unit vector and to each other
+fn perpendiculars(direction: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    let other = if direction.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+    let side = direction.cross(other).normalize();
+    (side, direction.cross(side))
+}
+
+/// Expires `DebugLines` entries as real time passes, so that pausing the game keeps them up.
+///
+/// Belongs before the systems that draw: one-frame entries from the frame before, already
+/// rendered, are dropped, and this frame's survive until they are.
+pub struct DebugLinesSystem;
+
+impl<'a> System<'a> for DebugLinesSystem {
+    type SystemData = (Read<'a, Time>, Write<'a, DebugLines>);
+
+    fn run(&mut self, (time, mut lines): Self::SystemData) {
+        lines.advance(time.delta_real_seconds());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Matrix4, Point3, Vector3};
+
+    use cachoeira_core::transform::GlobalTransform;
+
+    use super::{DebugLines, DebugStyle, CIRCLE_SEGMENTS};
+
+    #[test]
+    fn breaks_shapes_into_lines() {
+        let mut debug = DebugLines::new();
+        let origin = Point3::new(0., 0., 0.);
+        debug.arrow(origin, Point3::new(0., 0., -2.), [1.; 4]);
+        debug.sphere(origin, 1., [1.; 4]);
+        assert_eq!(debug.lines().len(), 5 + 3 * CIRCLE_SEGMENTS);
+        // arrow head prongs end a fifth of the way back
+        assert_relative_eq!(debug.lines()[1].end.z, -1.6);
+    }
+
+    #[test]
+    fn skips_degenerate_shapes() {
+        let mut debug = DebugLines::new();
+        let origin = Point3::new(0., 0., 0.);
+        debug.arrow(origin, origin, [1.; 4]);
+        debug.circle(origin, Vector3::new(0., 0., 0.), 1., [1.; 4]);
+        debug.circle(origin, Vector3::new(f32::NAN, 0., 0.), 1., [1.; 4]);
+        // the arrow keeps its zero-length shaft but grows no head
+        assert_eq!(debug.lines().len(), 1);
+    }
+
+    #[test]
+    fn expires_entries_by_duration() {
+        let mut debug = DebugLines::new();
+        let origin = Point3::new(0., 0., 0.);
+        debug.line(origin, Point3::new(1., 0., 0.), [1.; 4]);
+        debug.sphere(origin, 1., DebugStyle::new([1.; 4]).lasting(1.));
+        debug.label(origin, "trigger", [1.; 4]);
+
+        debug.advance(0.5);
+        assert_eq!(debug.lines().len(), 3 * CIRCLE_SEGMENTS);
+        assert!(debug.labels().is_empty());
+        debug.advance(0.6);
+        assert!(debug.is_empty());
+    }
+
+    #[test]
+    fn colors_axes_over_the_given_style() {
+        let mut debug = DebugLines::new();
+        let transform = GlobalTransform(Matrix4::from_translation(Vector3::new(1., 2., 3.)) * Matrix4::from_scale(2.));
+        debug.axes(&transform, 0.5, DebugStyle::new([1.; 4]).lasting(2.).on_top());
+
+        let colors = [[1., 0., 0., 1.], [0., 1., 0., 1.], [0., 0., 1., 1.]];
+        assert_eq!(debug.lines().len(), 3);
+        for (line, &color) in debug.lines().iter().zip(&colors) {
+            assert_eq!(line.style, DebugStyle { color, duration: 2., depth_test: false });
+            assert_eq!(line.start, Point3::new(1., 2., 3.));
+        }
+        assert_eq!(debug.lines()[1].end, Point3::new(1., 3., 3.));
+    }
+}
@@ -0,0 +1,137 @@
+use std::sync::{Arc, RwLock};
+
+use cgmath::{InnerSpace, Matrix4, Point3, Vector2};
+
+use camera::Camera;
+use debug::{DebugLine, DebugLines};
+use device::{Device, DrawCall, Vertex};
+use pipeline::{Blend, CullMode, DepthTest, Pipeline, Shading};
+
+/// Draws `DebugLines` as screen-aligned quads of a constant width in pixels, through any
+/// `Device`.
+pub struct DebugRenderer {
+    /// line width in pixels
+    pub width: f32,
+    depth_tested: Arc<RwLock<Pipeline>>,
+    on_top: Arc<RwLock<Pipeline>>,
+}
+
+impl DebugRenderer {
+    pub fn new() -> Self {
+        let pipeline = Pipeline::new()
+            .with_shading(Shading::Unlit)
+            .with_cull_mode(CullMode::None)
+            .with_blend(Blend::Alpha);
+        DebugRenderer {
+            width: 1.5,
+            depth_tested: Arc::new(RwLock::new(pipeline.with_depth(DepthTest::LessEqual, false))),
+            on_top: Arc::new(RwLock::new(pipeline.with_depth(DepthTest::Always, false))),
+        }
+    }
+
+    pub fn with_width(mut self, width: f32) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Draws the lines seen by `camera` over the contents of `device`, the depth-tested ones
+    /// first. Leaves the device on a debug pipeline.
+    pub fn draw<D: Device + ?Sized>(&self, device: &mut D, lines: &DebugLines, camera: &Camera) -> Result<(), String> {
+        let (width, height) = device.size();
+        let size = Vector2::new(width as f32, height as f32);
+        let view_projection = camera.view_projection();
+        for &(depth_test, pipeline) in &[(true, &self.depth_tested), (false, &self.on_top)] {
+            let mut vertices = Vec::new();
+            for line in lines.lines().iter().filter(|line| line.style.depth_test == depth_test) {
+                if let Some(quad) = quad(line, &view_projection, size, self.width * 0.5) {
+                    vertices.extend_from_slice(&quad);
+                }
+            }
+            if vertices.is_empty() {
+                continue;
+            }
+            let indices: Vec<u32> = (0..vertices.len() as u32 / 4)
+                .flat_map(|quad| [0, 1, 2, 0, 2, 3].iter().map(move |corner| quad * 4 + corner).collect::<Vec<_>>())
+                .collect();
+            device.set_pipeline(pipeline.clone())?;
+            device.draw(&DrawCall::new(&vertices, &indices))?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for DebugRenderer {
+    fn default() -> Self { DebugRenderer::new() }
+}
+
+// corners in normalized device coordinates, so the draw call needs no matrices; the ends are
+// pushed out by half the width, squaring off joints
+fn quad(line: &DebugLine, view_projection: &Matrix4<f32>, size: Vector2<f32>, half_width: f32) -> Option<[Vertex; 4]> {
+    let mut start = view_projection * line.start.to_homogeneous();
+    let mut end = view_projection * line.end.to_homogeneous();
+    let (d_start, d_end) = (start.z + start.w, end.z + end.w);
+    if d_start < 0. && d_end < 0. {
+        return None;
+    }
+    if d_start < 0. {
+        start = start.lerp(end, d_start / (d_start - d_end));
+    } else if d_end < 0. {
+        end = end.lerp(start, d_end / (d_end - d_start));
+    }
+    let (start, end) = (Point3::from_homogeneous(start), Point3::from_homogeneous(end));
+
+    let direction = Vector2::new((end.x - start.x) * size.x, (end.y - start.y) * size.y);
+    let length = direction.magnitude();
+    let along = if length > 1e-6 { direction / length } else { Vector2::unit_x() };
+    let across = Vector2::new(-along.y, along.x);
+    let to_ndc = |pixels: Vector2<f32>| Vector2::new(pixels.x * 2. / size.x, pixels.y * 2. / size.y);
+    let (along, across) = (to_ndc(along * half_width), to_ndc(across * half_width));
+
+    let corner = |point: &Point3<f32>, offset: Vector2<f32>| {
+        Vertex::new([point.x + offset.x, point.y + offset.y, point.z]).with_color(line.style.color)
+    };
+    Some([
+        corner(&start, -along - across),
+        corner(&start, -along + across),
+        corner(&end, along + across),
+        corner(&end, along - across),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, Point3};
+
+    use camera::Camera;
+    use debug::{DebugLines, DebugRenderer, DebugStyle};
+    use device::Device;
+    use software::SoftwareDevice;
+
+    #[test]
+    fn draws_lines_with_and_without_depth() {
+        let camera = Camera::perspective(Deg(90.), 1., 0.1, 100.);
+        let mut debug = DebugLines::new();
+        debug.line(Point3::new(-10., 0., -5.), Point3::new(10., 0., -5.), [1., 0., 0., 1.]);
+        debug.line(Point3::new(0., -10., -5.), Point3::new(0., 10., -5.), DebugStyle::new([0., 0., 1., 1.]).on_top());
+        // crosses the near plane; only the part in front shows
+        debug.line(Point3::new(2.5, 0., 1.), Point3::new(2.5, 0., -5.), [0., 1., 0., 1.]);
+
+        let mut device = SoftwareDevice::new(32, 32);
+        device.clear([0., 0., 0., 1.], 1.);
+        DebugRenderer::new().draw(&mut device, &debug, &camera).unwrap();
+        let frame = device.framebuffer();
+        assert_eq!(frame.pixel(4, 16), [1., 0., 0., 1.]);
+        assert_eq!(frame.pixel(4, 12), [0., 0., 0., 1.]);
+        assert_eq!(frame.pixel(16, 4), [0., 0., 1., 1.]);
+        // drawn last, on top of the red line
+        assert_eq!(frame.pixel(16, 16), [0., 0., 1., 1.]);
+        assert_eq!(frame.pixel(26, 16), [0., 1., 0., 1.]);
+        assert_eq!(frame.pixel(31, 16)[1], 1.);
+
+        // a full depth buffer hides everything but the line on top
+        device.clear([0., 0., 0., 1.], 0.);
+        DebugRenderer::new().draw(&mut device, &debug, &camera).unwrap();
+        assert_eq!(device.framebuffer().pixel(4, 16), [0., 0., 0., 1.]);
+        assert_eq!(device.framebuffer().pixel(16, 4), [0., 0., 1., 1.]);
+    }
+}
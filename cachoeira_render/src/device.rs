@@ -1,11 +1,150 @@
-use super::pipeline;
 use std::sync::{Arc, RwLock};
 
-struct Device {
+use cgmath::{InnerSpace, Matrix4, One, Vector3};
+
+use super::pipeline;
+use texture::{Sampler, Texture};
+
+/// A vertex as consumed by a `Device`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl Vertex {
+    /// White vertex facing +Z.
+    pub fn new(position: [f32; 3]) -> Self {
+        Vertex { position, normal: [0., 0., 1.], uv: [0., 0.], color: [1.; 4] }
+    }
+
+    pub fn with_normal(mut self, normal: [f32; 3]) -> Self {
+        self.normal = normal;
+        self
+    }
+
+    pub fn with_uv(mut self, uv: [f32; 2]) -> Self {
+        self.uv = uv;
+        self
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+/// Light used by the `Flat` and `Lambert` shading modes.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct DirectionalLight {
+    /// direction the light travels in, world space
+    pub direction: Vector3<f32>,
+    pub color: [f32; 3],
+    pub ambient: [f32; 3],
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vector3<f32>, color: [f32; 3]) -> Self {
+        DirectionalLight { direction: direction.normalize(), color, ambient: [0.; 3] }
+    }
+
+    pub fn with_ambient(mut self, ambient: [f32; 3]) -> Self {
+        self.ambient = ambient;
+        self
+    }
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        DirectionalLight::new(-Vector3::unit_y(), [1.; 3])
+    }
 }
 
-trait UsesPipeline {
+/// An indexed triangle list and everything needed to draw it once.
+#[derive(Clone, Copy, Debug)]
+pub struct DrawCall<'a> {
+    pub vertices: &'a [Vertex],
+    pub indices: &'a [u32],
+    pub model: Matrix4<f32>,
+    pub view_projection: Matrix4<f32>,
+    pub texture: Option<&'a Texture>,
+    pub sampler: Sampler,
+    /// multiplied into every vertex color
+    pub color: [f32; 4],
+    pub light: DirectionalLight,
+}
+
+impl<'a> DrawCall<'a> {
+    pub fn new(vertices: &'a [Vertex], indices: &'a [u32]) -> Self {
+        DrawCall {
+            vertices,
+            indices,
+            model: Matrix4::one(),
+            view_projection: Matrix4::one(),
+            texture: None,
+            sampler: Sampler::default(),
+            color: [1.; 4],
+            light: DirectionalLight::default(),
+        }
+    }
+
+    pub fn with_model(mut self, model: Matrix4<f32>) -> Self {
+        self.model = model;
+        self
+    }
+
+    pub fn with_view_projection(mut self, view_projection: Matrix4<f32>) -> Self {
+        self.view_projection = view_projection;
+        self
+    }
+
+    pub fn with_texture(mut self, texture: &'a Texture, sampler: Sampler) -> Self {
+        self.texture = Some(texture);
+        self.sampler = sampler;
+        self
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_light(mut self, light: DirectionalLight) -> Self {
+        self.light = light;
+        self
+    }
+
+    /// Checks the index list before anything is drawn.
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.indices.len().is_multiple_of(3) {
+            return Err(format!("Index count {} is not a multiple of 3", self.indices.len()));
+        }
+        match self.indices.iter().find(|&&index| index as usize >= self.vertices.len()) {
+            Some(index) => Err(format!("Index {} out of range for {} vertices", index, self.vertices.len())),
+            None => Ok(()),
+        }
+    }
+}
+
+pub trait UsesPipeline {
+    /// Returns whether the pipeline actually changed.
     fn set_pipeline(&mut self, pipeline: Arc<RwLock<pipeline::Pipeline>>) -> Result<bool, String>;
     
 }
 
+/// A render target that draws triangle lists, whatever does the drawing.
+pub trait Device: UsesPipeline {
+    /// Width and height in pixels.
+    fn size(&self) -> (u32, u32);
+
+    fn resize(&mut self, width: u32, height: u32);
+
+    fn clear(&mut self, color: [f32; 4], depth: f32);
+
+    fn draw(&mut self, call: &DrawCall) -> Result<(), String>;
+
+    /// Current contents as 8-bit RGBA rows, top to bottom.
+    fn read_rgba8(&self) -> Result<Vec<u8>, String>;
+}
@@ -0,0 +1,117 @@
+use fnv::FnvHashSet;
+
+use graph::{GraphBackend, PassId, ResourceDesc, ResourceId};
+use graph::pass::Pass;
+use graph::resource::ResourceInfo;
+
+/// Execution plan of a `RenderGraph`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CompiledGraph {
+    /// passes to run, in order
+    pub order: Vec<PassId>,
+    /// passes whose results nothing uses
+    pub culled: Vec<PassId>,
+    // physical slot of every resource; unused transients point past `descs`
+    pub(crate) slots: Vec<usize>,
+    // description of every physical slot, imported resources included
+    pub(crate) descs: Vec<ResourceDesc>,
+}
+
+impl CompiledGraph {
+    /// Number of distinct physical resources, after aliasing.
+    pub fn physical_count(&self) -> usize { self.descs.len() }
+
+    /// Whether two resources were assigned the same memory.
+    pub fn aliased(&self, a: ResourceId, b: ResourceId) -> bool {
+        a != b && self.slots[a.0] == self.slots[b.0] && self.slots[a.0] < self.descs.len()
+    }
+}
+
+pub(crate) fn compile<B: GraphBackend>(resources: &[ResourceInfo], passes: &[Pass<B>]) -> Result<CompiledGraph, String> {
+    for pass in passes {
+        for resource in pass.reads.iter().chain(&pass.writes) {
+            if resource.0 >= resources.len() {
+                return Err(format!("Pass '{}' uses unknown resource {:?}", pass.name, resource));
+            }
+        }
+    }
+
+    // walk backwards from the outputs; a write without a read ends the interest in what
+    // earlier passes left in that resource
+    let mut needed: FnvHashSet<ResourceId> = resources.iter()
+        .enumerate()
+        .filter(|&(_, info)| info.output || info.imported)
+        .map(|(index, _)| ResourceId(index))
+        .collect();
+    let mut alive = vec![false; passes.len()];
+    for (index, pass) in passes.iter().enumerate().rev() {
+        if !pass.side_effect && !pass.writes.iter().any(|resource| needed.contains(resource)) {
+            continue;
+        }
+        alive[index] = true;
+        for resource in &pass.writes {
+            if !resources[resource.0].imported && !resources[resource.0].output {
+                needed.remove(resource);
+            }
+        }
+        needed.extend(pass.reads.iter().cloned());
+    }
+
+    let order: Vec<PassId> = (0..passes.len()).filter(|&index| alive[index]).map(PassId).collect();
+    let culled = (0..passes.len()).filter(|&index| !alive[index]).map(PassId).collect();
+
+    // lifetimes of transients over the execution order
+    let mut first_use = vec![None; resources.len()];
+    let mut last_use = vec![0; resources.len()];
+    for (step, pass) in order.iter().map(|id| &passes[id.0]).enumerate() {
+        for resource in &pass.reads {
+            let info = &resources[resource.0];
+            if !info.imported && first_use[resource.0].is_none() {
+                return Err(format!("Pass '{}' reads '{}' before any pass writes it", pass.name, info.name));
+            }
+            last_use[resource.0] = step;
+        }
+        for resource in &pass.writes {
+            if first_use[resource.0].is_none() {
+                first_use[resource.0] = Some(step);
+            }
+            last_use[resource.0] = step;
+        }
+    }
+
+    // transients are packed into slots greedily, by first use; a slot is free again once the
+    // step of its last user is over
+    let mut transients: Vec<(usize, usize)> = first_use.iter()
+        .enumerate()
+        .filter(|&(index, first)| first.is_some() && !resources[index].imported)
+        .map(|(index, first)| (first.unwrap(), index))
+        .collect();
+    transients.sort();
+
+    let mut slots = vec![usize::MAX; resources.len()];
+    let mut descs = Vec::new();
+    let mut free_after: Vec<usize> = Vec::new();
+    for (first, index) in transients {
+        let desc = resources[index].desc;
+        let reusable = (0..descs.len()).find(|&slot| descs[slot] == desc && free_after[slot] < first);
+        let slot = match reusable {
+            Some(slot) => slot,
+            None => {
+                descs.push(desc);
+                free_after.push(0);
+                descs.len() - 1
+            }
+        };
+        // outputs are read back after the frame, so their memory is never handed on
+        free_after[slot] = if resources[index].output { usize::MAX } else { last_use[index] };
+        slots[index] = slot;
+    }
+    for (index, info) in resources.iter().enumerate() {
+        if info.imported {
+            descs.push(info.desc);
+            slots[index] = descs.len() - 1;
+        }
+    }
+
+    Ok(CompiledGraph { order, culled, slots, descs })
+}
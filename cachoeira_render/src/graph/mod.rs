@@ -0,0 +1,23 @@
+//! Render graph: passes declare the targets and buffers they read and write, and the graph
+//! works out what to run, in which order, and what memory can be shared.
+//!
+//! Backends plug in through `GraphBackend`; see `software::SoftwareBackend`.
+
+pub use self::compile::CompiledGraph;
+pub use self::pass::{PassBuilder, PassContext};
+pub use self::render_graph::RenderGraph;
+pub use self::resource::{
+    BufferDesc,
+    GraphBackend,
+    PassId,
+    Physical,
+    ResourceDesc,
+    ResourceId,
+    TargetDesc,
+    TargetFormat,
+};
+
+mod compile;
+mod pass;
+mod render_graph;
+mod resource;
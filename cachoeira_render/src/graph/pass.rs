@@ -0,0 +1,151 @@
+use graph::{GraphBackend, PassId, Physical, RenderGraph, ResourceId};
+
+pub(crate) type PassFn<B> = Box<dyn FnMut(&mut PassContext<B>) -> Result<(), String>>;
+
+pub(crate) struct Pass<B: GraphBackend> {
+    pub name: String,
+    pub reads: Vec<ResourceId>,
+    pub writes: Vec<ResourceId>,
+    pub side_effect: bool,
+    pub execute: PassFn<B>,
+}
+
+/// Declares a pass's resources; finished by `execute`.
+pub struct PassBuilder<'g, B: GraphBackend + 'g> {
+    graph: &'g mut RenderGraph<B>,
+    name: String,
+    reads: Vec<ResourceId>,
+    writes: Vec<ResourceId>,
+    side_effect: bool,
+}
+
+impl<'g, B: GraphBackend + 'g> PassBuilder<'g, B> {
+    pub(crate) fn new(graph: &'g mut RenderGraph<B>, name: String) -> Self {
+        PassBuilder { graph, name, reads: Vec::new(), writes: Vec::new(), side_effect: false }
+    }
+
+    /// The pass samples or loads `resource`.
+    pub fn reads(mut self, resource: ResourceId) -> Self {
+        self.reads.push(resource);
+        self
+    }
+
+    /// The pass writes `resource`, discarding what was there unless it also `reads` it.
+    pub fn writes(mut self, resource: ResourceId) -> Self {
+        self.writes.push(resource);
+        self
+    }
+
+    /// Shorthand for `reads` plus `writes`, e.g. to draw on top of earlier passes.
+    pub fn modifies(self, resource: ResourceId) -> Self {
+        self.reads(resource).writes(resource)
+    }
+
+    /// Keeps the pass even if nothing uses its output, e.g. readbacks.
+    pub fn side_effect(mut self) -> Self {
+        self.side_effect = true;
+        self
+    }
+
+    pub fn execute<F>(self, execute: F) -> PassId
+    where
+        F: FnMut(&mut PassContext<B>) -> Result<(), String> + 'static,
+    {
+        let pass = Pass {
+            name: self.name,
+            reads: self.reads,
+            writes: self.writes,
+            side_effect: self.side_effect,
+            execute: Box::new(execute),
+        };
+        self.graph.push_pass(pass)
+    }
+}
+
+/// What a pass sees while running: the backend and the resources it declared.
+pub struct PassContext<'a, B: GraphBackend + 'a> {
+    pub(crate) name: &'a str,
+    pub(crate) reads: &'a [ResourceId],
+    pub(crate) writes: &'a [ResourceId],
+    pub(crate) slots: &'a [usize],
+    pub(crate) physical: &'a mut [Option<Physical<B>>],
+    pub(crate) backend: &'a mut B,
+}
+
+impl<'a, B: GraphBackend + 'a> PassContext<'a, B> {
+    pub fn name(&self) -> &str { self.name }
+
+    pub fn backend(&mut self) -> &mut B { self.backend }
+
+    pub fn target(&self, resource: ResourceId) -> Result<&B::Target, String> {
+        let slot = self.slot(resource, false)?;
+        match self.physical[slot] {
+            Some(Physical::Target(ref target)) => Ok(target),
+            _ => Err(format!("Pass '{}': resource {:?} is not a target", self.name, resource)),
+        }
+    }
+
+    pub fn target_mut(&mut self, resource: ResourceId) -> Result<&mut B::Target, String> {
+        let slot = self.slot(resource, true)?;
+        let name = self.name;
+        match self.physical[slot] {
+            Some(Physical::Target(ref mut target)) => Ok(target),
+            _ => Err(format!("Pass '{}': resource {:?} is not a target", name, resource)),
+        }
+    }
+
+    pub fn buffer(&self, resource: ResourceId) -> Result<&B::Buffer, String> {
+        let slot = self.slot(resource, false)?;
+        match self.physical[slot] {
+            Some(Physical::Buffer(ref buffer)) => Ok(buffer),
+            _ => Err(format!("Pass '{}': resource {:?} is not a buffer", self.name, resource)),
+        }
+    }
+
+    pub fn buffer_mut(&mut self, resource: ResourceId) -> Result<&mut B::Buffer, String> {
+        let slot = self.slot(resource, true)?;
+        let name = self.name;
+        match self.physical[slot] {
+            Some(Physical::Buffer(ref mut buffer)) => Ok(buffer),
+            _ => Err(format!("Pass '{}': resource {:?} is not a buffer", name, resource)),
+        }
+    }
+
+    /// Reads one target while writing another, e.g. for post-processing.
+    pub fn target_pair(&mut self, source: ResourceId, destination: ResourceId) -> Result<(&B::Target, &mut B::Target), String> {
+        let (read, write) = (self.slot(source, false)?, self.slot(destination, true)?);
+        if read == write {
+            return Err(format!("Pass '{}': {:?} and {:?} share memory", self.name, source, destination));
+        }
+        let name = self.name;
+        let (low, high) = self.physical.split_at_mut(read.max(write));
+        let (read, write) = if read < write {
+            (&low[read], &mut high[0])
+        } else {
+            // the read slot sits at the start of `high`
+            let (write_slot, read_slot) = (&mut low[write], &high[0]);
+            (read_slot, write_slot)
+        };
+        match (read, write) {
+            (&Some(Physical::Target(ref read)), &mut Some(Physical::Target(ref mut write))) => Ok((read, write)),
+            _ => Err(format!("Pass '{}': {:?} and {:?} must both be targets", name, source, destination)),
+        }
+    }
+
+    fn slot(&self, resource: ResourceId, write: bool) -> Result<usize, String> {
+        let declared = if write {
+            self.writes.contains(&resource)
+        } else {
+            self.reads.contains(&resource) || self.writes.contains(&resource)
+        };
+        if !declared {
+            return Err(format!(
+                "Pass '{}' did not declare {} {:?}",
+                self.name,
+                if write { "writing" } else { "using" },
+                resource,
+            ));
+        }
+        Ok(self.slots[resource.0])
+    }
+}
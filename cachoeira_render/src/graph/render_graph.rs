@@ -0,0 +1,290 @@
+use graph::{
+    BufferDesc,
+    CompiledGraph,
+    GraphBackend,
+    PassBuilder,
+    PassContext,
+    PassId,
+    Physical,
+    ResourceDesc,
+    ResourceId,
+    TargetDesc,
+};
+use graph::compile::compile;
+use graph::pass::Pass;
+use graph::resource::ResourceInfo;
+
+/// A frame described as passes over declared resources.
+///
+/// Passes are added in submission order. Compiling culls passes whose writes nothing reads,
+/// and lets transient resources with disjoint lifetimes and equal descriptions share memory.
+/// Physical resources are kept between executions, so a graph that doesn't change allocates
+/// only once.
+pub struct RenderGraph<B: GraphBackend> {
+    resources: Vec<ResourceInfo>,
+    passes: Vec<Pass<B>>,
+    compiled: Option<CompiledGraph>,
+    physical: Vec<Option<Physical<B>>>,
+    // resources handed in from outside, until the first compile places them
+    imports: Vec<(ResourceId, Physical<B>)>,
+}
+
+impl<B: GraphBackend> RenderGraph<B> {
+    pub fn new() -> Self {
+        RenderGraph {
+            resources: Vec::new(),
+            passes: Vec::new(),
+            compiled: None,
+            physical: Vec::new(),
+            imports: Vec::new(),
+        }
+    }
+
+    /// Declares a target that lives only within the frame.
+    pub fn create_target(&mut self, name: &str, desc: TargetDesc) -> ResourceId {
+        self.declare(name, ResourceDesc::Target(desc), false)
+    }
+
+    /// Declares a buffer that lives only within the frame.
+    pub fn create_buffer(&mut self, name: &str, desc: BufferDesc) -> ResourceId {
+        self.declare(name, ResourceDesc::Buffer(desc), false)
+    }
+
+    /// Hands an existing target, such as a backbuffer, to the graph. Imported resources count
+    /// as outputs and are never aliased.
+    pub fn import_target(&mut self, name: &str, desc: TargetDesc, target: B::Target) -> ResourceId {
+        let id = self.declare(name, ResourceDesc::Target(desc), true);
+        self.imports.push((id, Physical::Target(target)));
+        id
+    }
+
+    pub fn import_buffer(&mut self, name: &str, desc: BufferDesc, buffer: B::Buffer) -> ResourceId {
+        let id = self.declare(name, ResourceDesc::Buffer(desc), true);
+        self.imports.push((id, Physical::Buffer(buffer)));
+        id
+    }
+
+    /// Keeps a transient resource alive past the frame so it can be read back.
+    pub fn mark_output(&mut self, resource: ResourceId) {
+        self.resources[resource.0].output = true;
+        self.invalidate();
+    }
+
+    pub fn add_pass(&mut self, name: &str) -> PassBuilder<'_, B> {
+        PassBuilder::new(self, name.to_owned())
+    }
+
+    pub fn resource_name(&self, resource: ResourceId) -> &str {
+        &self.resources[resource.0].name
+    }
+
+    pub fn pass_name(&self, pass: PassId) -> &str {
+        &self.passes[pass.0].name
+    }
+
+    /// The plan of the last compile, if the graph hasn't changed since.
+    pub fn compiled(&self) -> Option<&CompiledGraph> {
+        self.compiled.as_ref()
+    }
+
+    pub fn compile(&mut self) -> Result<&CompiledGraph, String> {
+        if self.compiled.is_none() {
+            let compiled = compile(&self.resources, &self.passes)?;
+            self.place(&compiled);
+            self.compiled = Some(compiled);
+        }
+        Ok(self.compiled.as_ref().unwrap())
+    }
+
+    /// Runs the live passes in order, allocating what's missing from `backend`.
+    pub fn execute(&mut self, backend: &mut B) -> Result<(), String> {
+        self.compile()?;
+        let compiled = self.compiled.as_ref().unwrap();
+        for (slot, desc) in compiled.descs.iter().enumerate() {
+            if self.physical[slot].is_none() {
+                self.physical[slot] = Some(Physical::create(backend, desc));
+            }
+        }
+        for pass_id in &compiled.order {
+            let pass = &mut self.passes[pass_id.0];
+            let mut context = PassContext {
+                name: &pass.name,
+                reads: &pass.reads,
+                writes: &pass.writes,
+                slots: &compiled.slots,
+                physical: &mut self.physical,
+                backend: &mut *backend,
+            };
+            (pass.execute)(&mut context)?;
+        }
+        Ok(())
+    }
+
+    /// An imported or output target, once the graph has been compiled.
+    pub fn target(&self, resource: ResourceId) -> Option<&B::Target> {
+        match self.physical_of(resource) {
+            Some(Physical::Target(target)) => Some(target),
+            _ => None,
+        }
+    }
+
+    pub fn buffer(&self, resource: ResourceId) -> Option<&B::Buffer> {
+        match self.physical_of(resource) {
+            Some(Physical::Buffer(buffer)) => Some(buffer),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn push_pass(&mut self, pass: Pass<B>) -> PassId {
+        self.passes.push(pass);
+        self.invalidate();
+        PassId(self.passes.len() - 1)
+    }
+
+    fn declare(&mut self, name: &str, desc: ResourceDesc, imported: bool) -> ResourceId {
+        self.resources.push(ResourceInfo { name: name.to_owned(), desc, imported, output: false });
+        self.invalidate();
+        ResourceId(self.resources.len() - 1)
+    }
+
+    fn physical_of(&self, resource: ResourceId) -> Option<&Physical<B>> {
+        let info = self.resources.get(resource.0)?;
+        if !info.imported && !info.output {
+            return None;
+        }
+        let compiled = self.compiled.as_ref()?;
+        let slot = *compiled.slots.get(resource.0)?;
+        self.physical.get(slot).and_then(|physical| physical.as_ref())
+    }
+
+    // takes the imported resources out of their old slots before a recompile moves them
+    fn invalidate(&mut self) {
+        if let Some(compiled) = self.compiled.take() {
+            for (index, info) in self.resources.iter().enumerate() {
+                if info.imported && index < compiled.slots.len() {
+                    if let Some(physical) = self.physical[compiled.slots[index]].take() {
+                        self.imports.push((ResourceId(index), physical));
+                    }
+                }
+            }
+            self.physical.clear();
+        }
+    }
+
+    fn place(&mut self, compiled: &CompiledGraph) {
+        self.physical = (0..compiled.descs.len()).map(|_| None).collect();
+        for (id, physical) in self.imports.drain(..) {
+            self.physical[compiled.slots[id.0]] = Some(physical);
+        }
+    }
+}
+
+impl<B: GraphBackend> Default for RenderGraph<B> {
+    fn default() -> Self { RenderGraph::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use device::{DrawCall, Vertex};
+    use graph::{PassContext, RenderGraph, TargetDesc, TargetFormat};
+    use pipeline::Pipeline;
+    use software::{Framebuffer, SoftwareBackend};
+
+    const DESC: TargetDesc = TargetDesc { width: 8, height: 8, format: TargetFormat::Rgba32F };
+
+    #[test]
+    fn culls_and_aliases() {
+        let mut graph = RenderGraph::<SoftwareBackend>::new();
+        let backbuffer = graph.import_target("backbuffer", DESC, Framebuffer::new(8, 8));
+        let (a, b, c) = (graph.create_target("a", DESC), graph.create_target("b", DESC), graph.create_target("c", DESC));
+        let unused = graph.create_target("unused", DESC);
+
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let logger = |name| logger(&log, name);
+        graph.add_pass("first").writes(a).execute(logger("first"));
+        let wasted = graph.add_pass("wasted").reads(a).writes(unused).execute(logger("wasted"));
+        graph.add_pass("second").reads(a).writes(b).execute(logger("second"));
+        graph.add_pass("third").reads(b).writes(c).execute(logger("third"));
+        graph.add_pass("present").reads(c).writes(backbuffer).execute(logger("present"));
+        graph.add_pass("readback").reads(c).side_effect().execute(logger("readback"));
+
+        let mut backend = SoftwareBackend::new();
+        graph.execute(&mut backend).unwrap();
+        assert_eq!(*log.borrow(), vec!["first", "second", "third", "present", "readback"]);
+        {
+            let compiled = graph.compiled().unwrap();
+            assert_eq!(compiled.culled, vec![wasted]);
+            // `a` is dead once `second` has run, so `c` takes its place
+            assert!(compiled.aliased(a, c));
+            assert!(!compiled.aliased(a, b) && !compiled.aliased(b, c));
+            assert_eq!(compiled.physical_count(), 3);
+        }
+        assert_eq!(backend.allocated(), 2);
+
+        // nothing changed: no new allocations
+        graph.execute(&mut backend).unwrap();
+        assert_eq!(backend.allocated(), 2);
+
+        // outputs are read after the frame, so later passes can't reuse their memory
+        graph.mark_output(a);
+        assert!(!graph.compile().unwrap().aliased(a, c));
+    }
+
+    fn logger(log: &Rc<RefCell<Vec<&'static str>>>, name: &'static str) -> impl FnMut(&mut PassContext<SoftwareBackend>) -> Result<(), String> {
+        let log = log.clone();
+        move |_| {
+            log.borrow_mut().push(name);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn draws_through_passes() {
+        let mut graph = RenderGraph::<SoftwareBackend>::new();
+        let backbuffer = graph.import_target("backbuffer", DESC, Framebuffer::new(8, 8));
+        let scene = graph.create_target("scene", DESC);
+
+        graph.add_pass("scene").writes(scene).execute(move |context| {
+            let color = [1., 0.25, 0., 1.];
+            let vertices = [
+                Vertex::new([-1., -1., 0.]).with_color(color),
+                Vertex::new([1., -1., 0.]).with_color(color),
+                Vertex::new([-1., 1., 0.]).with_color(color),
+            ];
+            let target = context.target_mut(scene)?;
+            target.clear([0., 0., 0., 1.], 1.);
+            target.draw(&Pipeline::default(), &DrawCall::new(&vertices, &[0, 1, 2]))
+        });
+        graph.add_pass("invert").reads(scene).writes(backbuffer).execute(move |context| {
+            let (source, destination) = context.target_pair(scene, backbuffer)?;
+            for y in 0..8 {
+                for x in 0..8 {
+                    let c = source.pixel(x, y);
+                    destination.set_pixel(x, y, [1. - c[0], 1. - c[1], 1. - c[2], c[3]]);
+                }
+            }
+            Ok(())
+        });
+        // nothing reads `scene` afterwards, so this is culled rather than failing
+        graph.add_pass("overdraw").modifies(scene).execute(move |context| context.target_mut(backbuffer).map(|_| ()));
+
+        graph.execute(&mut SoftwareBackend::new()).unwrap();
+        let bytes = graph.target(backbuffer).unwrap().to_rgba8();
+        let pixel = |x: usize, y: usize| &bytes[(y * 8 + x) * 4..(y * 8 + x) * 4 + 4];
+        assert_eq!(pixel(1, 6), [0, 191, 255, 255]);
+        assert_eq!(pixel(6, 1), [255, 255, 255, 255]);
+        assert!(graph.target(scene).is_none());
+    }
+
+    #[test]
+    fn rejects_reads_of_unwritten_transients() {
+        let mut graph = RenderGraph::<SoftwareBackend>::new();
+        let backbuffer = graph.import_target("backbuffer", DESC, Framebuffer::new(8, 8));
+        let empty = graph.create_target("empty", DESC);
+        graph.add_pass("present").reads(empty).writes(backbuffer).execute(|_| Ok(()));
+        assert!(graph.compile().is_err());
+    }
+}
@@ -0,0 +1,74 @@
+/// Handle to a resource declared on a `RenderGraph`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ResourceId(pub(crate) usize);
+
+/// Handle to a pass added to a `RenderGraph`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct PassId(pub(crate) usize);
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum TargetFormat {
+    Rgba8,
+    Rgba16F,
+    Rgba32F,
+    Depth32F,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct TargetDesc {
+    pub width: u32,
+    pub height: u32,
+    pub format: TargetFormat,
+}
+
+impl TargetDesc {
+    pub fn new(width: u32, height: u32, format: TargetFormat) -> Self {
+        TargetDesc { width, height, format }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct BufferDesc {
+    /// size in bytes
+    pub size: usize,
+}
+
+/// What a resource is; only resources with equal descriptions share memory.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum ResourceDesc {
+    Target(TargetDesc),
+    Buffer(BufferDesc),
+}
+
+/// Creates the physical resources a graph runs on.
+pub trait GraphBackend {
+    type Target;
+    type Buffer;
+
+    fn create_target(&mut self, desc: &TargetDesc) -> Self::Target;
+
+    fn create_buffer(&mut self, desc: &BufferDesc) -> Self::Buffer;
+}
+
+/// A resource as allocated by a backend.
+pub enum Physical<B: GraphBackend> {
+    Target(B::Target),
+    Buffer(B::Buffer),
+}
+
+impl<B: GraphBackend> Physical<B> {
+    pub(crate) fn create(backend: &mut B, desc: &ResourceDesc) -> Self {
+        match *desc {
+            ResourceDesc::Target(ref desc) => Physical::Target(backend.create_target(desc)),
+            ResourceDesc::Buffer(ref desc) => Physical::Buffer(backend.create_buffer(desc)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct ResourceInfo {
+    pub name: String,
+    pub desc: ResourceDesc,
+    pub imported: bool,
+    pub output: bool,
+}
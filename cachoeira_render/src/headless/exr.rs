@@ -0,0 +1,117 @@
+//! Minimal OpenEXR writer: single-part scanline images, uncompressed, half-float RGBA.
+
+use std::io::{self, Write};
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+const VERSION: u32 = 2;
+const HALF: i32 = 1;
+// channels must be listed, and stored, in alphabetical order
+const CHANNELS: [(&str, usize); 4] = [("A", 3), ("B", 2), ("G", 1), ("R", 0)];
+
+/// Writes linear RGBA pixels, rows top to bottom.
+pub fn write<W: Write>(writer: &mut W, width: u32, height: u32, pixels: &[[f32; 4]]) -> io::Result<()> {
+    assert_eq!(pixels.len(), width as usize * height as usize, "EXR pixel count must match its size");
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC);
+    put_u32(&mut header, VERSION);
+
+    let mut channels = Vec::new();
+    for &(name, _) in &CHANNELS {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        put_i32(&mut channels, HALF);
+        // linear flag and reserved bytes, then x and y sampling
+        channels.extend_from_slice(&[0; 4]);
+        put_i32(&mut channels, 1);
+        put_i32(&mut channels, 1);
+    }
+    channels.push(0);
+    attribute(&mut header, "channels", "chlist", &channels);
+    attribute(&mut header, "compression", "compression", &[0]);
+    let mut window = Vec::new();
+    for &value in &[0, 0, width as i32 - 1, height as i32 - 1] {
+        put_i32(&mut window, value);
+    }
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    let mut one = Vec::new();
+    put_u32(&mut one, 1f32.to_bits());
+    attribute(&mut header, "pixelAspectRatio", "float", &one);
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(&mut header, "screenWindowWidth", "float", &one);
+    header.push(0);
+
+    // one chunk per scanline: y, byte count, then each channel's row
+    let row_bytes = width as usize * CHANNELS.len() * 2;
+    let first_chunk = header.len() + height as usize * 8;
+    for y in 0..height as usize {
+        put_u64(&mut header, (first_chunk + y * (row_bytes + 8)) as u64);
+    }
+    writer.write_all(&header)?;
+
+    let mut chunk = Vec::with_capacity(row_bytes + 8);
+    for (y, row) in pixels.chunks(width as usize).enumerate() {
+        chunk.clear();
+        put_i32(&mut chunk, y as i32);
+        put_u32(&mut chunk, row_bytes as u32);
+        for &(_, channel) in &CHANNELS {
+            for pixel in row {
+                let half = to_half(pixel[channel]);
+                chunk.extend_from_slice(&[half as u8, (half >> 8) as u8]);
+            }
+        }
+        writer.write_all(&chunk)?;
+    }
+    Ok(())
+}
+
+/// Rounds to the nearest half float, overflowing to infinity.
+pub fn to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = (bits >> 16 & 0x8000) as u16;
+    let exponent = (bits >> 23 & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff {
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    // the bits shifted out decide the rounding, ties going to even
+    let (half, remainder, halfway) = if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        (mantissa >> shift, mantissa & ((1 << shift) - 1), 1 << (shift - 1))
+    } else {
+        ((exponent as u32) << 10 | mantissa >> 13, mantissa & 0x1fff, 0x1000)
+    };
+    let round_up = remainder > halfway || (remainder == halfway && half & 1 == 1);
+    sign | (half + round_up as u32) as u16
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    put_u32(header, value.len() as u32);
+    header.extend_from_slice(value);
+}
+
+fn put_i32(bytes: &mut Vec<u8>, value: i32) {
+    put_u32(bytes, value as u32);
+}
+
+fn put_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&[value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8]);
+}
+
+fn put_u64(bytes: &mut Vec<u8>, value: u64) {
+    put_u32(bytes, value as u32);
+    put_u32(bytes, (value >> 32) as u32);
+}
@@ -0,0 +1,347 @@
+//! Rendering without a window, for previews, CI and video deliverables.
+//!
+//! `HeadlessRenderer` steps the world's `Time` by exactly one frame of a fixed `FrameRate`
+//! per rendered frame, however long the frame took to render, and writes each frame out as a
+//! numbered PNG or EXR file or as raw pixels to a pipe. For example, frames written as
+//! `RawFormat::Rgba8` to stdout can be encoded with
+//! `ffmpeg -f rawvideo -pix_fmt rgba -s 1920x1080 -r 60 -i - out.mp4`.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use png::{self, HasParameters};
+use rayon::prelude::*;
+use specs::prelude::World;
+
+use cachoeira_core::Time;
+
+use color::Transfer;
+use software::{Framebuffer, SoftwareDevice};
+
+pub mod exr;
+
+/// Frames per second as a ratio, so that rates like 30000/1001 don't drift.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct FrameRate {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+impl FrameRate {
+    pub fn new(numerator: u32, denominator: u32) -> Self {
+        assert!(numerator > 0 && denominator > 0, "Frame rate must be positive");
+        FrameRate { numerator, denominator }
+    }
+
+    pub fn fps(fps: u32) -> Self {
+        FrameRate::new(fps, 1)
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        f64::from(self.numerator) / f64::from(self.denominator)
+    }
+
+    /// Time at which frame `frame`, counting from 0, is shown.
+    pub fn frame_start(&self, frame: u64) -> Duration {
+        let (ticks, numerator) = (frame * u64::from(self.denominator), u64::from(self.numerator));
+        Duration::new(ticks / numerator, (ticks % numerator * 1_000_000_000 / numerator) as u32)
+    }
+
+    /// How long frame `frame` is shown; varies by a nanosecond between frames when the rate
+    /// doesn't divide a second evenly.
+    pub fn frame_duration(&self, frame: u64) -> Duration {
+        self.frame_start(frame + 1) - self.frame_start(frame)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ImageFormat {
+    /// 8 bits per channel, encoded with `HeadlessRenderer::transfer`
+    Png,
+    /// 16 bits per channel, encoded with `HeadlessRenderer::transfer`
+    Png16,
+    /// linear half floats, uncompressed
+    Exr,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            ImageFormat::Png | ImageFormat::Png16 => "png",
+            ImageFormat::Exr => "exr",
+        }
+    }
+}
+
+/// Pixel layout of piped frames, rows top to bottom with no padding or framing between frames.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum RawFormat {
+    /// 8 bits per channel, encoded with `HeadlessRenderer::transfer`; ffmpeg's `rgba`
+    Rgba8,
+    /// linear little-endian floats; ffmpeg's `rgbaf32le`
+    RgbaF32,
+}
+
+/// Where rendered frames go.
+pub enum FrameOutput {
+    /// `directory/prefix0000.png` and so on, numbered from 0
+    Sequence { directory: PathBuf, prefix: String, digits: usize, format: ImageFormat },
+    /// every frame the same size, back to back
+    Pipe { writer: Box<dyn Write + Send>, format: RawFormat },
+    /// nothing kept, e.g. for runs that check frames as they are rendered
+    Discard,
+}
+
+impl FrameOutput {
+    pub fn sequence<P: Into<PathBuf>>(directory: P, prefix: &str, format: ImageFormat) -> Self {
+        FrameOutput::Sequence { directory: directory.into(), prefix: prefix.to_owned(), digits: 5, format }
+    }
+
+    pub fn stdout(format: RawFormat) -> Self {
+        FrameOutput::Pipe { writer: Box::new(BufWriter::with_capacity(1 << 20, io::stdout())), format }
+    }
+}
+
+/// Renders frames at a simulated frame rate and writes them to a `FrameOutput`.
+pub struct HeadlessRenderer {
+    rate: FrameRate,
+    output: FrameOutput,
+    /// encoding of 8 and 16-bit output, sRGB unless the frames were already graded
+    pub transfer: Transfer,
+    frame: u64,
+    size: Option<(u32, u32)>,
+}
+
+impl HeadlessRenderer {
+    pub fn new(rate: FrameRate, output: FrameOutput) -> Self {
+        HeadlessRenderer { rate, output, transfer: Transfer::Srgb, frame: 0, size: None }
+    }
+
+    pub fn rate(&self) -> FrameRate { self.rate }
+
+    /// Number of frames written so far, which is also the number of the next frame.
+    pub fn frame(&self) -> u64 { self.frame }
+
+    /// File the frame numbered `frame` is written to, for sequence outputs.
+    pub fn frame_path(&self, frame: u64) -> Option<PathBuf> {
+        match self.output {
+            FrameOutput::Sequence { ref directory, ref prefix, digits, format } => {
+                Some(directory.join(format!("{}{:0width$}.{}", prefix, frame, format.extension(), width = digits)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Moves `time` on to the next frame: no time passes before the first, then one frame
+    /// duration per frame, scaled by `Time::time_scale` like any other delta.
+    pub fn advance(&self, time: &mut Time) {
+        let delta = match self.frame {
+            0 => Duration::from_secs(0),
+            frame => self.rate.frame_duration(frame - 1),
+        };
+        time.set_delta_time(delta);
+        time.increment_frame_number();
+    }
+
+    pub fn write_frame(&mut self, framebuffer: &Framebuffer) -> Result<(), String> {
+        let size = (framebuffer.width(), framebuffer.height());
+        let transfer = self.transfer;
+        let path = self.frame_path(self.frame);
+        match self.output {
+            FrameOutput::Sequence { ref directory, format, .. } => {
+                let path = path.unwrap();
+                fs::create_dir_all(directory)
+                    .and_then(|_| write_image(&path, framebuffer, format, transfer))
+                    .map_err(|error| format!("Writing frame {}: {}", path.display(), error))?;
+            }
+            FrameOutput::Pipe { ref mut writer, format } => {
+                if self.size.is_some_and(|first| first != size) {
+                    return Err(format!("Frame size changed from {:?} to {:?} while piping raw frames", self.size.unwrap(), size));
+                }
+                let bytes = match format {
+                    RawFormat::Rgba8 => encode_8bit(framebuffer.colors(), transfer),
+                    RawFormat::RgbaF32 => encode_f32(framebuffer.colors()),
+                };
+                writer.write_all(&bytes)
+                    .and_then(|_| writer.flush())
+                    .map_err(|error| format!("Piping frame {}: {}", self.frame, error))?;
+            }
+            FrameOutput::Discard => {}
+        }
+        self.size = Some(size);
+        self.frame += 1;
+        Ok(())
+    }
+
+    /// Renders and writes `count` frames: for each, advances the world's `Time`, which is
+    /// added if missing, lets `render` update the world and draw into `device`, then writes
+    /// the device's framebuffer.
+    pub fn run<F>(&mut self, world: &mut World, device: &mut SoftwareDevice, count: u64, mut render: F) -> Result<(), String>
+    where
+        F: FnMut(&mut World, &mut SoftwareDevice) -> Result<(), String>,
+    {
+        if !world.res.has_value::<Time>() {
+            world.add_resource(Time::default());
+        }
+        for _ in 0..count {
+            self.advance(&mut world.write_resource::<Time>());
+            render(world, device)?;
+            self.write_frame(device.framebuffer())?;
+        }
+        Ok(())
+    }
+}
+
+fn write_image(path: &Path, framebuffer: &Framebuffer, format: ImageFormat, transfer: Transfer) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    let (width, height) = (framebuffer.width(), framebuffer.height());
+    match format {
+        ImageFormat::Png | ImageFormat::Png16 => {
+            let mut encoder = png::Encoder::new(file, width, height);
+            let (depth, bytes) = match format {
+                ImageFormat::Png => (png::BitDepth::Eight, encode_8bit(framebuffer.colors(), transfer)),
+                _ => (png::BitDepth::Sixteen, encode_16bit(framebuffer.colors(), transfer)),
+            };
+            encoder.set(png::ColorType::RGBA).set(depth);
+            encoder.write_header()?.write_image_data(&bytes)?;
+            Ok(())
+        }
+        ImageFormat::Exr => {
+            exr::write(&mut file, width, height, framebuffer.colors())?;
+            file.flush()
+        }
+    }
+}
+
+fn encode_8bit(pixels: &[[f32; 4]], transfer: Transfer) -> Vec<u8> {
+    let mut bytes = vec![0; pixels.len() * 4];
+    bytes.par_chunks_mut(4).zip(pixels.par_iter()).for_each(|(bytes, pixel)| {
+        for c in 0..4 {
+            let value = if c < 3 { transfer.encode(pixel[c]) } else { pixel[c] };
+            bytes[c] = (value.clamp(0., 1.) * 255.).round() as u8;
+        }
+    });
+    bytes
+}
+
+// big-endian, as PNG stores it
+fn encode_16bit(pixels: &[[f32; 4]], transfer: Transfer) -> Vec<u8> {
+    let mut bytes = vec![0; pixels.len() * 8];
+    bytes.par_chunks_mut(8).zip(pixels.par_iter()).for_each(|(bytes, pixel)| {
+        for c in 0..4 {
+            let value = if c < 3 { transfer.encode(pixel[c]) } else { pixel[c] };
+            let value = (value.clamp(0., 1.) * 65535.).round() as u16;
+            bytes[c * 2] = (value >> 8) as u8;
+            bytes[c * 2 + 1] = value as u8;
+        }
+    });
+    bytes
+}
+
+fn encode_f32(pixels: &[[f32; 4]]) -> Vec<u8> {
+    let mut bytes = vec![0; pixels.len() * 16];
+    bytes.par_chunks_mut(16).zip(pixels.par_iter()).for_each(|(bytes, pixel)| {
+        for c in 0..4 {
+            let bits = pixel[c].to_bits();
+            for byte in 0..4 {
+                bytes[c * 4 + byte] = (bits >> (byte * 8)) as u8;
+            }
+        }
+    });
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use std::io::{self, Read, Write};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use specs::prelude::World;
+
+    use cachoeira_core::Time;
+    use headless::{exr, FrameOutput, FrameRate, HeadlessRenderer, ImageFormat, RawFormat};
+    use software::SoftwareDevice;
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    #[test]
+    fn counts_frames_at_exact_rates() {
+        let ntsc = FrameRate::new(30000, 1001);
+        assert_eq!(ntsc.frame_start(30000), Duration::from_secs(1001));
+        assert_eq!(ntsc.frame_duration(0), Duration::new(0, 33_366_666));
+    }
+
+    #[test]
+    fn steps_time_and_pipes_frames() {
+        let piped = Shared::default();
+        let output = FrameOutput::Pipe { writer: Box::new(piped.clone()), format: RawFormat::Rgba8 };
+        let mut headless = HeadlessRenderer::new(FrameRate::fps(4), output);
+        let mut world = World::new();
+        let mut device = SoftwareDevice::new(2, 1);
+        let mut seconds = Vec::new();
+        headless.run(&mut world, &mut device, 3, |world, device| {
+            let time = world.read_resource::<Time>();
+            seconds.push(time.absolute_time_seconds());
+            let level = time.frame_number() as f32 / 4.;
+            device.framebuffer_mut().clear([level, 0., 1., 1.], 1.);
+            Ok(())
+        }).unwrap();
+        assert_eq!(seconds, vec![0., 0.25, 0.5]);
+        assert_eq!(headless.frame(), 3);
+        let bytes = piped.0.lock().unwrap();
+        assert_eq!(bytes.len(), 3 * 2 * 4);
+        // sRGB-encoded 0.5 linear
+        assert_eq!(&bytes[8..12], &[188, 0, 255, 255]);
+    }
+
+    #[test]
+    fn rejects_resized_piped_frames() {
+        let output = FrameOutput::Pipe { writer: Box::new(Shared::default()), format: RawFormat::RgbaF32 };
+        let mut headless = HeadlessRenderer::new(FrameRate::fps(4), output);
+        headless.write_frame(&::software::Framebuffer::new(2, 1)).unwrap();
+        assert!(headless.write_frame(&::software::Framebuffer::new(1, 1)).is_err());
+    }
+
+    #[test]
+    fn converts_to_half_floats() {
+        assert_eq!(exr::to_half(1.), 0x3c00);
+        assert_eq!(exr::to_half(-2.5), 0xc100);
+        assert_eq!(exr::to_half(65520.), 0x7c00);
+        assert_eq!(exr::to_half(5.96e-8), 0x0001);
+    }
+
+    #[test]
+    fn writes_numbered_images() {
+        let directory = ::std::env::temp_dir().join("cachoeira_headless_test");
+        let _ = fs::remove_dir_all(&directory);
+        let device = SoftwareDevice::new(3, 2);
+        for &format in &[ImageFormat::Png, ImageFormat::Exr] {
+            let mut headless = HeadlessRenderer::new(FrameRate::fps(60), FrameOutput::sequence(&directory, "frame", format));
+            headless.write_frame(device.framebuffer()).unwrap();
+            headless.write_frame(device.framebuffer()).unwrap();
+            let path = headless.frame_path(1).unwrap();
+            assert_eq!(path.file_name().unwrap().to_str(), Some(&format!("frame00001.{}", format.extension())[..]));
+            let mut bytes = Vec::new();
+            File::open(&path).unwrap().read_to_end(&mut bytes).unwrap();
+            let magic: &[u8] = match format {
+                ImageFormat::Exr => &[0x76, 0x2f, 0x31, 0x01],
+                _ => &[0x89, b'P', b'N', b'G'],
+            };
+            assert_eq!(&bytes[..4], magic);
+        }
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,876 @@
+//! glTF 2.0, both as `.gltf` JSON with external or data URI buffers and as binary `.glb`.
+//!
+//! Nodes of the default scene become the model's roots; meshes with several primitives are
+//! shared between the nodes using them. Sparse accessors and extensions the file requires are
+//! not supported.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+
+use base64;
+use cgmath::{Matrix4, Quaternion, Rad, Vector3};
+use serde_json;
+
+use cachoeira_core::transform::Transform;
+
+use camera::Projection;
+use import::{add_image, Error, ErrorKind, Model, ModelNode, Primitive, Result, ResultExt};
+use material::{AlphaMode, Material, MaterialHandle, MaterialTexture};
+use mesh::{Mesh, MeshHandle};
+use texture::{Filter, ImageSource, Sampler, Wrap};
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const CHUNK_JSON: u32 = 0x4E4F_534A;
+const CHUNK_BIN: u32 = 0x004E_4942;
+
+/// Far plane used for cameras with an infinite projection, in multiples of the near plane.
+const INFINITE_FAR: f32 = 1e6;
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Model> {
+    let path = path.as_ref();
+    let mut bytes = Vec::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .map_err(Error::from)
+        .and_then(|_| parse(&bytes, path.parent()))
+        .map(|model| Model { path: Some(path.display().to_string()), ..model })
+        .chain_err(|| ErrorKind::File(path.display().to_string()))
+}
+
+/// Parses a `.gltf` or `.glb` file's contents; external buffers and images are looked up in
+/// `directory`.
+pub fn parse(bytes: &[u8], directory: Option<&Path>) -> Result<Model> {
+    let (json, binary) = if bytes.len() >= 4 && read_u32(bytes, 0) == GLB_MAGIC {
+        split_glb(bytes)?
+    } else {
+        (bytes, None)
+    };
+    let document: Document = serde_json::from_slice(json)?;
+    if !document.asset.version.starts_with("2.") {
+        bail!(ErrorKind::Unsupported(format!("glTF version {}", document.asset.version)));
+    }
+    if let Some(extension) = document.extensions_required.first() {
+        bail!(ErrorKind::Unsupported(format!("required glTF extension {}", extension)));
+    }
+    Importer::new(document, binary, directory)?.import()
+}
+
+/// JSON and binary chunks of a `.glb` container.
+fn split_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>)> {
+    if bytes.len() < 20 {
+        bail!(ErrorKind::Invalid("GLB header is truncated".to_owned()));
+    }
+    let (version, length) = (read_u32(bytes, 4), read_u32(bytes, 8) as usize);
+    if version != 2 {
+        bail!(ErrorKind::Unsupported(format!("GLB container version {}", version)));
+    }
+    if length > bytes.len() {
+        bail!(ErrorKind::Invalid(format!("GLB claims {} bytes but has {}", length, bytes.len())));
+    }
+    let (mut json, mut binary) = (None, None);
+    let mut offset = 12;
+    while offset + 8 <= length {
+        let (chunk_length, kind) = (read_u32(bytes, offset) as usize, read_u32(bytes, offset + 4));
+        let start = offset + 8;
+        if start + chunk_length > length {
+            bail!(ErrorKind::Invalid("GLB chunk runs past the end of the file".to_owned()));
+        }
+        match kind {
+            CHUNK_JSON if json.is_none() => json = Some(&bytes[start..start + chunk_length]),
+            CHUNK_BIN if binary.is_none() => binary = Some(&bytes[start..start + chunk_length]),
+            _ => (),
+        }
+        // chunks are padded to 4 bytes
+        offset = start + chunk_length.div_ceil(4) * 4;
+    }
+    match json {
+        Some(json) => Ok((json, binary)),
+        None => bail!(ErrorKind::Invalid("GLB has no JSON chunk".to_owned())),
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    bytes[offset] as u32
+        | (bytes[offset + 1] as u32) << 8
+        | (bytes[offset + 2] as u32) << 16
+        | (bytes[offset + 3] as u32) << 24
+}
+
+/// Resolves the document's indices into engine types, building each mesh and material once.
+struct Importer<'a> {
+    document: Document,
+    buffers: Vec<Arc<Vec<u8>>>,
+    directory: Option<&'a Path>,
+    images: Vec<ImageSource>,
+}
+
+impl<'a> Importer<'a> {
+    fn new(document: Document, binary: Option<&[u8]>, directory: Option<&'a Path>) -> Result<Self> {
+        let buffers = document.buffers.iter().enumerate()
+            .map(|(index, buffer)| {
+                let data = match buffer.uri {
+                    Some(ref uri) => load_uri(uri, directory)?.1,
+                    None if index == 0 => binary
+                        .ok_or_else(|| ErrorKind::Invalid("Buffer 0 has no URI and there is no GLB binary chunk".to_owned()))?
+                        .to_vec(),
+                    None => bail!(ErrorKind::Invalid(format!("Buffer {} has no URI", index))),
+                };
+                if data.len() < buffer.byte_length {
+                    bail!(ErrorKind::Invalid(format!("Buffer {} holds {} bytes, {} expected", index, data.len(), buffer.byte_length)));
+                }
+                Ok(Arc::new(data))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Importer { document, buffers, directory, images: Vec::new() })
+    }
+
+    fn import(mut self) -> Result<Model> {
+        let materials = (0..self.document.materials.len())
+            .map(|index| self.material(index).map(MaterialHandle::new))
+            .collect::<Result<Vec<_>>>()?;
+        let meshes = (0..self.document.meshes.len())
+            .map(|index| self.mesh(index, &materials).chain_err(|| ErrorKind::Invalid(format!("In mesh {}", index))))
+            .collect::<Result<Vec<_>>>()?;
+
+        let node_count = self.document.nodes.len();
+        let mut nodes = Vec::with_capacity(node_count);
+        let mut has_parent = vec![false; node_count];
+        for (index, node) in self.document.nodes.iter().enumerate() {
+            for &child in &node.children {
+                if child >= node_count {
+                    bail!(ErrorKind::Invalid(format!("Node {} has unknown child {}", index, child)));
+                }
+                has_parent[child] = true;
+            }
+            let primitives = match node.mesh {
+                Some(mesh) => meshes.get(mesh)
+                    .ok_or_else(|| ErrorKind::Invalid(format!("Node {} uses unknown mesh {}", index, mesh)))?
+                    .clone(),
+                None => Vec::new(),
+            };
+            let camera = match node.camera {
+                Some(camera) => Some(self.camera(camera)?),
+                None => None,
+            };
+            nodes.push(ModelNode {
+                name: node.name.clone(),
+                transform: node.transform(),
+                children: node.children.clone(),
+                primitives,
+                camera,
+            });
+        }
+
+        let scene = self.document.scene.or(if self.document.scenes.is_empty() { None } else { Some(0) });
+        let roots = match scene {
+            Some(scene) => self.document.scenes.get(scene)
+                .ok_or_else(|| ErrorKind::Invalid(format!("Unknown scene {}", scene)))?
+                .nodes.clone(),
+            None => (0..node_count).filter(|&node| !has_parent[node]).collect(),
+        };
+        if let Some(&root) = roots.iter().find(|&&root| root >= node_count) {
+            bail!(ErrorKind::Invalid(format!("Scene refers to unknown node {}", root)));
+        }
+        Ok(Model { nodes, roots, images: self.images, path: None })
+    }
+
+    fn mesh(&self, index: usize, materials: &[MaterialHandle]) -> Result<Vec<Primitive>> {
+        self.document.meshes[index].primitives.iter()
+            .map(|primitive| {
+                let material = match primitive.material {
+                    Some(material) => Some(materials.get(material)
+                        .ok_or_else(|| ErrorKind::Invalid(format!("Unknown material {}", material)))?
+                        .clone()),
+                    None => None,
+                };
+                Ok(Primitive { mesh: MeshHandle::new(self.primitive(primitive)?), material })
+            })
+            .collect()
+    }
+
+    fn primitive(&self, primitive: &PrimitiveDef) -> Result<Mesh> {
+        let attribute = |name: &str| primitive.attributes.get(name).cloned();
+        let positions = match attribute("POSITION") {
+            Some(accessor) => self.vectors(accessor, 3)?,
+            None => bail!(ErrorKind::Invalid("Primitive has no POSITION".to_owned())),
+        };
+        let count = positions.len();
+        let vertices = match primitive.indices {
+            Some(accessor) => self.indices(accessor, count)?,
+            None => (0..count as u32).collect(),
+        };
+        let indices = match primitive.mode {
+            MODE_TRIANGLES => vertices,
+            MODE_TRIANGLE_STRIP => (2..vertices.len())
+                .flat_map(|i| if i % 2 == 0 {
+                    vec![vertices[i - 2], vertices[i - 1], vertices[i]]
+                } else {
+                    vec![vertices[i - 1], vertices[i - 2], vertices[i]]
+                })
+                .collect(),
+            MODE_TRIANGLE_FAN => (2..vertices.len())
+                .flat_map(|i| vec![vertices[0], vertices[i - 1], vertices[i]])
+                .collect(),
+            mode => bail!(ErrorKind::Unsupported(format!("primitive mode {}", mode))),
+        };
+
+        let mut mesh = Mesh::new(positions.into_iter().map(|p| [p[0], p[1], p[2]]).collect(), indices);
+        if let Some(accessor) = attribute("TEXCOORD_0") {
+            mesh = mesh.with_uvs(self.same_count(accessor, count, 2)?.into_iter().map(|uv| [uv[0], uv[1]]).collect());
+        }
+        if let Some(accessor) = attribute("COLOR_0") {
+            let components = self.accessor(accessor)?.components()?;
+            let colors = self.same_count(accessor, count, components)?;
+            mesh = mesh.with_colors(colors.into_iter()
+                .map(|c| [c[0], c[1], c[2], if components == 4 { c[3] } else { 1. }])
+                .collect());
+        }
+        match attribute("NORMAL") {
+            Some(accessor) => {
+                mesh = mesh.with_normals(self.same_count(accessor, count, 3)?.into_iter().map(|n| [n[0], n[1], n[2]]).collect());
+            }
+            None => mesh.compute_normals(),
+        }
+        match attribute("TANGENT") {
+            Some(accessor) => {
+                mesh = mesh.with_tangents(self.same_count(accessor, count, 4)?.into_iter().map(|t| [t[0], t[1], t[2], t[3]]).collect());
+            }
+            None => if mesh.uvs.is_some() {
+                mesh.compute_tangents()?;
+            },
+        }
+        mesh.validate().map_err(ErrorKind::Invalid)?;
+        Ok(mesh)
+    }
+
+    fn accessor(&self, index: usize) -> Result<&Accessor> {
+        self.document.accessors.get(index)
+            .ok_or_else(|| ErrorKind::Invalid(format!("Unknown accessor {}", index)).into())
+    }
+
+    /// Elements of a float (or normalized integer) accessor with `components` components.
+    fn vectors(&self, index: usize, components: usize) -> Result<Vec<Vec<f32>>> {
+        let accessor = self.accessor(index)?;
+        if accessor.components()? != components {
+            bail!(ErrorKind::Invalid(format!("Accessor {} is {}, {} components expected", index, accessor.kind, components)));
+        }
+        let values = self.read(accessor, index)?;
+        Ok(values.chunks(components).map(|chunk| chunk.to_vec()).collect())
+    }
+
+    fn same_count(&self, index: usize, count: usize, components: usize) -> Result<Vec<Vec<f32>>> {
+        let vectors = self.vectors(index, components)?;
+        if vectors.len() != count {
+            bail!(ErrorKind::Invalid(format!("Accessor {} has {} elements for {} vertices", index, vectors.len(), count)));
+        }
+        Ok(vectors)
+    }
+
+    fn indices(&self, index: usize, vertex_count: usize) -> Result<Vec<u32>> {
+        let accessor = self.accessor(index)?;
+        match (accessor.kind.as_str(), accessor.component_type) {
+            ("SCALAR", UNSIGNED_BYTE) | ("SCALAR", UNSIGNED_SHORT) | ("SCALAR", UNSIGNED_INT) => (),
+            _ => bail!(ErrorKind::Invalid(format!("Accessor {} cannot hold indices", index))),
+        }
+        let indices: Vec<u32> = self.read(accessor, index)?.into_iter().map(|index| index as u32).collect();
+        match indices.iter().find(|&&index| index as usize >= vertex_count) {
+            Some(bad) => bail!(ErrorKind::Invalid(format!("Index {} out of range for {} vertices", bad, vertex_count))),
+            None => Ok(indices),
+        }
+    }
+
+    /// Every component of every element as `f32`, normalizing integers if the accessor says so.
+    ///
+    /// Unsigned integers up to 2^24 convert exactly, which covers any sensible index.
+    fn read(&self, accessor: &Accessor, index: usize) -> Result<Vec<f32>> {
+        if accessor.sparse.is_some() {
+            bail!(ErrorKind::Unsupported(format!("sparse accessor {}", index)));
+        }
+        let components = accessor.components()?;
+        let size = component_size(accessor.component_type)
+            .ok_or_else(|| ErrorKind::Invalid(format!("Accessor {} has component type {}", index, accessor.component_type)))?;
+        let length = accessor.count.checked_mul(components)
+            .ok_or_else(|| ErrorKind::Invalid(format!("Accessor {} has too many elements", index)))?;
+        let view = match accessor.buffer_view {
+            Some(view) => view,
+            None => {
+                // zeros with no data behind them, so held to what the buffers declare
+                let declared: usize = self.document.buffers.iter().map(|buffer| buffer.byte_length).sum();
+                if length.checked_mul(size).is_none_or(|bytes| bytes > declared) {
+                    bail!(ErrorKind::Invalid(format!("Accessor {} is larger than the buffers", index)));
+                }
+                return Ok(vec![0.; length]);
+            }
+        };
+        let (data, stride) = self.view(view)?;
+        let element = components * size;
+        let stride = stride.unwrap_or(element);
+        if stride < element {
+            bail!(ErrorKind::Invalid(format!("Buffer view {} has a stride of {} for {}-byte elements", view, stride, element)));
+        }
+        let start = accessor.byte_offset;
+        let end = match accessor.count {
+            0 => Some(start),
+            count => stride.checked_mul(count - 1)
+                .and_then(|offset| offset.checked_add(start))
+                .and_then(|offset| offset.checked_add(element)),
+        };
+        if end.is_none_or(|end| end > data.len()) {
+            bail!(ErrorKind::Invalid(format!("Accessor {} runs past the end of buffer view {}", index, view)));
+        }
+        let mut values = Vec::with_capacity(length);
+        for element in 0..accessor.count {
+            for component in 0..components {
+                let bytes = &data[start + element * stride + component * size..];
+                values.push(component_value(bytes, accessor.component_type, accessor.normalized));
+            }
+        }
+        Ok(values)
+    }
+
+    fn view(&self, index: usize) -> Result<(&[u8], Option<usize>)> {
+        let view = self.document.buffer_views.get(index)
+            .ok_or_else(|| ErrorKind::Invalid(format!("Unknown buffer view {}", index)))?;
+        let buffer = self.buffers.get(view.buffer)
+            .ok_or_else(|| ErrorKind::Invalid(format!("Buffer view {} uses unknown buffer {}", index, view.buffer)))?;
+        let end = match view.byte_offset.checked_add(view.byte_length) {
+            Some(end) if end <= buffer.len() => end,
+            _ => bail!(ErrorKind::Invalid(format!("Buffer view {} runs past the end of buffer {}", index, view.buffer))),
+        };
+        Ok((&buffer[view.byte_offset..end], view.byte_stride))
+    }
+
+    fn material(&mut self, index: usize) -> Result<Material> {
+        let definition = self.document.materials[index].clone();
+        let pbr = definition.pbr_metallic_roughness.unwrap_or_default();
+        let mut material = Material {
+            name: definition.name,
+            base_color: pbr.base_color_factor.unwrap_or([1.; 4]),
+            metallic: pbr.metallic_factor.unwrap_or(1.),
+            roughness: pbr.roughness_factor.unwrap_or(1.),
+            emissive: definition.emissive_factor.unwrap_or([0.; 3]),
+            double_sided: definition.double_sided,
+            alpha_mode: match definition.alpha_mode.as_deref() {
+                None | Some("OPAQUE") => AlphaMode::Opaque,
+                Some("MASK") => AlphaMode::Mask(definition.alpha_cutoff.unwrap_or(0.5)),
+                Some("BLEND") => AlphaMode::Blend,
+                Some(other) => bail!(ErrorKind::Invalid(format!("Material {} has alpha mode {}", index, other))),
+            },
+            ..Default::default()
+        };
+        material.base_color_texture = self.texture(pbr.base_color_texture.as_ref())?;
+        material.metallic_roughness_texture = self.texture(pbr.metallic_roughness_texture.as_ref())?;
+        material.normal_texture = self.texture(definition.normal_texture.as_ref())?;
+        material.occlusion_texture = self.texture(definition.occlusion_texture.as_ref())?;
+        material.emissive_texture = self.texture(definition.emissive_texture.as_ref())?;
+        Ok(material)
+    }
+
+    fn texture(&mut self, info: Option<&TextureInfo>) -> Result<Option<MaterialTexture>> {
+        let info = match info {
+            Some(info) => info,
+            None => return Ok(None),
+        };
+        let texture = self.document.textures.get(info.index)
+            .ok_or_else(|| ErrorKind::Invalid(format!("Unknown texture {}", info.index)))?
+            .clone();
+        let image = match texture.source {
+            Some(image) => image,
+            None => bail!(ErrorKind::Unsupported(format!("texture {} without a core image source", info.index))),
+        };
+        let source = self.image(image)?;
+        add_image(&mut self.images, &source);
+        let sampler = match texture.sampler {
+            Some(sampler) => self.document.samplers.get(sampler)
+                .ok_or_else(|| ErrorKind::Invalid(format!("Unknown sampler {}", sampler)))?
+                .sampler(),
+            None => Sampler::default(),
+        };
+        Ok(Some(MaterialTexture { source, sampler, uv_set: info.tex_coord }))
+    }
+
+    fn image(&self, index: usize) -> Result<ImageSource> {
+        let image = self.document.images.get(index)
+            .ok_or_else(|| ErrorKind::Invalid(format!("Unknown image {}", index)))?;
+        match (image.uri.as_ref(), image.buffer_view) {
+            (Some(uri), _) if uri.starts_with("data:") => {
+                let (mime_type, bytes) = load_uri(uri, self.directory)?;
+                Ok(ImageSource::Embedded { mime_type: mime_type.or_else(|| image.mime_type.clone()), bytes: Arc::new(bytes) })
+            }
+            (Some(uri), _) => Ok(ImageSource::Path(match self.directory {
+                Some(directory) => directory.join(percent_decode(uri)),
+                None => percent_decode(uri).into(),
+            })),
+            (None, Some(view)) => Ok(ImageSource::Embedded {
+                mime_type: image.mime_type.clone(),
+                bytes: Arc::new(self.view(view)?.0.to_vec()),
+            }),
+            (None, None) => bail!(ErrorKind::Invalid(format!("Image {} has neither URI nor buffer view", index))),
+        }
+    }
+
+    fn camera(&self, index: usize) -> Result<Projection> {
+        let camera = self.document.cameras.get(index)
+            .ok_or_else(|| ErrorKind::Invalid(format!("Unknown camera {}", index)))?;
+        match (camera.kind.as_str(), camera.perspective.as_ref(), camera.orthographic.as_ref()) {
+            // without an aspect ratio, the viewport's is meant; `Projection::set_aspect` it
+            ("perspective", Some(p), _) => Ok(Projection::perspective(
+                Rad(p.yfov),
+                p.aspect_ratio.unwrap_or(1.),
+                p.znear,
+                p.zfar.unwrap_or(p.znear * INFINITE_FAR),
+            )),
+            ("orthographic", _, Some(o)) => Ok(Projection::orthographic(-o.xmag, o.xmag, -o.ymag, o.ymag, o.znear, o.zfar)),
+            (kind, _, _) => bail!(ErrorKind::Invalid(format!("Camera {} of type {} lacks its parameters", index, kind))),
+        }
+    }
+}
+
+/// Contents of a `data:` URI with its MIME type, or of a file relative to `directory`.
+fn load_uri(uri: &str, directory: Option<&Path>) -> Result<(Option<String>, Vec<u8>)> {
+    if uri.starts_with("data:") {
+        let comma = uri.find(',').ok_or_else(|| ErrorKind::Invalid("Data URI has no ',' before its data".to_owned()))?;
+        let header = &uri[5..comma];
+        if !header.ends_with(";base64") {
+            bail!(ErrorKind::Unsupported("data URI without base64 encoding".to_owned()));
+        }
+        let mime_type = &header[..header.len() - 7];
+        let mime_type = if mime_type.is_empty() { None } else { Some(mime_type.to_owned()) };
+        return Ok((mime_type, base64::decode(&uri[comma + 1..])?));
+    }
+    let directory = directory.ok_or_else(|| ErrorKind::Invalid(format!("No directory to resolve '{}' in", uri)))?;
+    let path = directory.join(percent_decode(uri));
+    let mut bytes = Vec::new();
+    File::open(&path)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .chain_err(|| ErrorKind::File(path.display().to_string()))?;
+    Ok((None, bytes))
+}
+
+/// Undoes the `%XX` escapes relative URIs may use, e.g. for spaces.
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = if bytes[i] == b'%' && i + 2 < bytes.len() {
+            ::std::str::from_utf8(&bytes[i + 1..i + 3]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+const BYTE: u32 = 5120;
+const UNSIGNED_BYTE: u32 = 5121;
+const SHORT: u32 = 5122;
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+
+const MODE_TRIANGLES: u32 = 4;
+const MODE_TRIANGLE_STRIP: u32 = 5;
+const MODE_TRIANGLE_FAN: u32 = 6;
+
+fn component_size(component_type: u32) -> Option<usize> {
+    match component_type {
+        BYTE | UNSIGNED_BYTE => Some(1),
+        SHORT | UNSIGNED_SHORT => Some(2),
+        UNSIGNED_INT | FLOAT => Some(4),
+        _ => None,
+    }
+}
+
+fn component_value(bytes: &[u8], component_type: u32, normalized: bool) -> f32 {
+    let short = bytes[0] as u16 | (bytes.get(1).cloned().unwrap_or(0) as u16) << 8;
+    match (component_type, normalized) {
+        (BYTE, false) => bytes[0] as i8 as f32,
+        (BYTE, true) => (bytes[0] as i8 as f32 / 127.).max(-1.),
+        (UNSIGNED_BYTE, false) => bytes[0] as f32,
+        (UNSIGNED_BYTE, true) => bytes[0] as f32 / 255.,
+        (SHORT, false) => short as i16 as f32,
+        (SHORT, true) => (short as i16 as f32 / 32767.).max(-1.),
+        (UNSIGNED_SHORT, false) => short as f32,
+        (UNSIGNED_SHORT, true) => short as f32 / 65535.,
+        (UNSIGNED_INT, _) => read_u32(bytes, 0) as f32,
+        _ => f32::from_bits(read_u32(bytes, 0)),
+    }
+}
+
+// The subset of the glTF JSON schema the importer reads.
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Document {
+    asset: Asset,
+    scene: Option<usize>,
+    #[serde(default)]
+    scenes: Vec<SceneDef>,
+    #[serde(default)]
+    nodes: Vec<NodeDef>,
+    #[serde(default)]
+    meshes: Vec<MeshDef>,
+    #[serde(default)]
+    accessors: Vec<Accessor>,
+    #[serde(default)]
+    buffer_views: Vec<BufferView>,
+    #[serde(default)]
+    buffers: Vec<Buffer>,
+    #[serde(default)]
+    materials: Vec<MaterialDef>,
+    #[serde(default)]
+    textures: Vec<TextureDef>,
+    #[serde(default)]
+    images: Vec<ImageDef>,
+    #[serde(default)]
+    samplers: Vec<SamplerDef>,
+    #[serde(default)]
+    cameras: Vec<CameraDef>,
+    #[serde(default)]
+    extensions_required: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    version: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SceneDef {
+    #[serde(default)]
+    nodes: Vec<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NodeDef {
+    name: Option<String>,
+    #[serde(default)]
+    children: Vec<usize>,
+    matrix: Option<[f32; 16]>,
+    translation: Option<[f32; 3]>,
+    /// x, y, z, w
+    rotation: Option<[f32; 4]>,
+    scale: Option<[f32; 3]>,
+    mesh: Option<usize>,
+    camera: Option<usize>,
+}
+
+impl NodeDef {
+    fn transform(&self) -> Transform {
+        if let Some(m) = self.matrix {
+            // column-major, like cgmath
+            return Transform::from_matrix(&Matrix4::new(
+                m[0], m[1], m[2], m[3], m[4], m[5], m[6], m[7],
+                m[8], m[9], m[10], m[11], m[12], m[13], m[14], m[15],
+            ));
+        }
+        let mut transform = Transform::default();
+        if let Some(t) = self.translation {
+            transform.translation = Vector3::from(t);
+        }
+        if let Some(r) = self.rotation {
+            transform.rotation = Quaternion::new(r[3], r[0], r[1], r[2]);
+        }
+        if let Some(s) = self.scale {
+            transform.scale = Vector3::from(s);
+        }
+        transform
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct MeshDef {
+    primitives: Vec<PrimitiveDef>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PrimitiveDef {
+    attributes: HashMap<String, usize>,
+    indices: Option<usize>,
+    material: Option<usize>,
+    #[serde(default = "default_mode")]
+    mode: u32,
+}
+
+fn default_mode() -> u32 { MODE_TRIANGLES }
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Accessor {
+    buffer_view: Option<usize>,
+    #[serde(default)]
+    byte_offset: usize,
+    component_type: u32,
+    #[serde(default)]
+    normalized: bool,
+    count: usize,
+    #[serde(rename = "type")]
+    kind: String,
+    sparse: Option<serde_json::Value>,
+}
+
+impl Accessor {
+    fn components(&self) -> Result<usize> {
+        match self.kind.as_str() {
+            "SCALAR" => Ok(1),
+            "VEC2" => Ok(2),
+            "VEC3" => Ok(3),
+            "VEC4" | "MAT2" => Ok(4),
+            "MAT3" => Ok(9),
+            "MAT4" => Ok(16),
+            other => bail!(ErrorKind::Invalid(format!("Unknown accessor type {}", other))),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BufferView {
+    buffer: usize,
+    #[serde(default)]
+    byte_offset: usize,
+    byte_length: usize,
+    byte_stride: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Buffer {
+    uri: Option<String>,
+    byte_length: usize,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MaterialDef {
+    name: Option<String>,
+    pbr_metallic_roughness: Option<PbrDef>,
+    normal_texture: Option<TextureInfo>,
+    occlusion_texture: Option<TextureInfo>,
+    emissive_texture: Option<TextureInfo>,
+    emissive_factor: Option<[f32; 3]>,
+    alpha_mode: Option<String>,
+    alpha_cutoff: Option<f32>,
+    #[serde(default)]
+    double_sided: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PbrDef {
+    base_color_factor: Option<[f32; 4]>,
+    base_color_texture: Option<TextureInfo>,
+    metallic_factor: Option<f32>,
+    roughness_factor: Option<f32>,
+    metallic_roughness_texture: Option<TextureInfo>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TextureInfo {
+    index: usize,
+    #[serde(default)]
+    tex_coord: u32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct TextureDef {
+    sampler: Option<usize>,
+    source: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImageDef {
+    uri: Option<String>,
+    mime_type: Option<String>,
+    buffer_view: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SamplerDef {
+    mag_filter: Option<u32>,
+    wrap_s: Option<u32>,
+}
+
+impl SamplerDef {
+    fn sampler(&self) -> Sampler {
+        Sampler {
+            filter: match self.mag_filter {
+                Some(9728) => Filter::Nearest,
+                _ => Filter::Linear,
+            },
+            // one wrap mode covers both axes here
+            wrap: match self.wrap_s {
+                Some(33071) => Wrap::Clamp,
+                Some(33648) => Wrap::Mirror,
+                _ => Wrap::Repeat,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CameraDef {
+    #[serde(rename = "type")]
+    kind: String,
+    perspective: Option<PerspectiveDef>,
+    orthographic: Option<OrthographicDef>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PerspectiveDef {
+    aspect_ratio: Option<f32>,
+    yfov: f32,
+    zfar: Option<f32>,
+    znear: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct OrthographicDef {
+    xmag: f32,
+    ymag: f32,
+    zfar: f32,
+    znear: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use base64;
+
+    use camera::Projection;
+    use import::ErrorKind;
+    use material::AlphaMode;
+    use texture::ImageSource;
+    use super::parse;
+
+    // a triangle with normals, drawn by a child node, and a camera at the root
+    fn document() -> String {
+        let mut buffer = Vec::new();
+        for value in &[0f32, 0., 0., 1., 0., 0., 0., 1., 0., 0., 0., 1., 0., 0., 1., 0., 0., 1.] {
+            let bits = value.to_bits();
+            buffer.extend_from_slice(&[bits as u8, (bits >> 8) as u8, (bits >> 16) as u8, (bits >> 24) as u8]);
+        }
+        buffer.extend_from_slice(&[0, 0, 2, 0, 1, 0]);
+        format!(r#"{{
+            "asset": {{ "version": "2.0" }},
+            "scene": 0,
+            "scenes": [{{ "nodes": [0] }}],
+            "nodes": [
+                {{ "name": "rig", "camera": 0, "children": [1], "translation": [0, 0, 5] }},
+                {{ "name": "triangle", "mesh": 0, "rotation": [0, 0, 1, 0] }}
+            ],
+            "cameras": [{{ "type": "perspective", "perspective": {{ "yfov": 1.0, "znear": 0.1, "zfar": 100 }} }}],
+            "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0, "NORMAL": 1 }}, "indices": 2, "material": 0 }}] }}],
+            "materials": [{{ "pbrMetallicRoughness": {{ "baseColorFactor": [1, 0, 0, 1] }}, "alphaMode": "MASK" }}],
+            "accessors": [
+                {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }},
+                {{ "bufferView": 0, "byteOffset": 36, "componentType": 5126, "count": 3, "type": "VEC3" }},
+                {{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+            ],
+            "bufferViews": [
+                {{ "buffer": 0, "byteLength": 72 }},
+                {{ "buffer": 0, "byteOffset": 72, "byteLength": 6 }}
+            ],
+            "buffers": [{{ "byteLength": 78, "uri": "data:application/octet-stream;base64,{}" }}]
+        }}"#, base64::encode(&buffer))
+    }
+
+    #[test]
+    fn imports_nodes_meshes_and_cameras() {
+        let model = parse(document().as_bytes(), None).unwrap();
+        assert_eq!(model.roots, vec![0]);
+        assert_eq!(model.nodes[0].children, vec![1]);
+        assert_eq!(model.nodes[1].transform.rotation.s, 0.);
+        match model.nodes[0].camera {
+            Some(Projection::Perspective { near, far, .. }) => assert_eq!((near, far), (0.1, 100.)),
+            ref other => panic!("unexpected camera {:?}", other),
+        }
+
+        let primitive = &model.nodes[1].primitives[0];
+        // indices are kept as given, whatever the normals say
+        assert_eq!(primitive.mesh.indices.to_u32(), vec![0, 2, 1]);
+        assert_eq!(primitive.mesh.normals.as_ref().unwrap()[2], [0., 0., 1.]);
+        let material = primitive.material.as_ref().unwrap();
+        assert_eq!((material.base_color, material.alpha_mode), ([1., 0., 0., 1.], AlphaMode::Mask(0.5)));
+
+        // the same document in a GLB container, with the buffer moved to the binary chunk
+        let json = document();
+        let start = json.find("base64,").unwrap() + 7;
+        let end = start + json[start..].find('"').unwrap();
+        let binary = base64::decode(&json[start..end]).unwrap();
+        let json = json.replace(&format!(r#", "uri": "data:application/octet-stream;base64,{}""#, &json[start..end]), "");
+        let mut glb = b"glTF\x02\0\0\0\0\0\0\0".to_vec();
+        let chunk = |glb: &mut Vec<u8>, kind: &[u8], data: &[u8], pad: u8| {
+            let length = data.len().div_ceil(4) * 4;
+            glb.extend_from_slice(&[length as u8, (length >> 8) as u8, 0, 0]);
+            glb.extend_from_slice(kind);
+            glb.extend_from_slice(data);
+            glb.extend(::std::iter::repeat_n(pad, length - data.len()));
+        };
+        chunk(&mut glb, b"JSON", json.as_bytes(), b' ');
+        chunk(&mut glb, b"BIN\0", &binary, 0);
+        let length = glb.len();
+        glb[8] = length as u8;
+        glb[9] = (length >> 8) as u8;
+        let model = parse(&glb, None).unwrap();
+        assert_eq!(model.nodes[1].primitives[0].mesh.positions[1], [1., 0., 0.]);
+
+        match *parse(br#"{ "asset": { "version": "1.0" } }"#, None).unwrap_err().kind() {
+            ErrorKind::Unsupported(_) => (),
+            ref other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_accessors() {
+        let huge = format!("{}", usize::MAX / 2);
+        let cases = [
+            // no buffer view: zeros larger than every buffer
+            r#"{ "componentType": 5126, "count": 1000, "type": "VEC3" }"#.to_owned(),
+            // element count times components overflows
+            format!(r#"{{ "componentType": 5126, "count": {}, "type": "MAT4" }}"#, huge),
+            // stride times count overflows
+            format!(r#"{{ "bufferView": 0, "componentType": 5126, "count": {}, "type": "VEC3" }}"#, huge),
+        ];
+        for accessor in &cases {
+            let document = document().replacen(
+                r#"{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }"#,
+                accessor,
+                1,
+            );
+            let error = parse(document.as_bytes(), None).unwrap_err();
+            let cause = error.iter().last().unwrap().to_string();
+            assert!(cause.starts_with("Invalid model: Accessor 0"), "{}", cause);
+        }
+    }
+
+    #[test]
+    fn decodes_escaped_image_uris() {
+        let document = document()
+            .replace(r#""baseColorFactor": [1, 0, 0, 1]"#, r#""baseColorTexture": { "index": 0 }"#)
+            .replace(r#""accessors": ["#, r#""textures": [{ "source": 0 }], "images": [{ "uri": "my%20wall.png" }], "accessors": ["#);
+        let model = parse(document.as_bytes(), Some(Path::new("assets"))).unwrap();
+        assert_eq!(model.images, vec![ImageSource::Path(Path::new("assets").join("my wall.png"))]);
+        let texture = model.nodes[1].primitives[0].material.as_ref().unwrap().base_color_texture.clone().unwrap();
+        assert_eq!(texture.source, model.images[0]);
+    }
+}
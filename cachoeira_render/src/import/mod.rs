@@ -0,0 +1,259 @@
+//! Model import from Wavefront OBJ (with MTL materials) and glTF 2.0 (`.gltf` and `.glb`).
+//!
+//! A `Model` is plain data; `instantiate` turns its node hierarchy into entities with
+//! `Transform`/`Parent`, `MeshHandle`, `MaterialHandle` and `Camera` components, as many times
+//! as needed.
+
+use std::path::Path;
+
+use specs::prelude::{Entity, EntityBuilder, World};
+
+use cachoeira_core::culling::Aabb;
+use cachoeira_core::scene::Named;
+use cachoeira_core::transform::{GlobalTransform, Parent, Transform};
+
+use camera::{Camera, Projection};
+use material::MaterialHandle;
+use mesh::MeshHandle;
+use texture::ImageSource;
+
+pub mod gltf;
+pub mod obj;
+
+pub use self::errors::*;
+
+// error_chain 0.11 still implements the deprecated `Error::description` and `Error::cause`
+#[allow(deprecated)]
+mod errors {
+    error_chain! {
+        foreign_links {
+            Io(::std::io::Error);
+            Json(::serde_json::Error);
+            Base64(::base64::DecodeError);
+        }
+
+        errors {
+            File(path: String) {
+                description("model file error")
+                display("In model file '{}'", path)
+            }
+            Line(line: usize, message: String) {
+                description("malformed model file")
+                display("Line {}: {}", line, message)
+            }
+            Invalid(message: String) {
+                description("invalid model")
+                display("Invalid model: {}", message)
+            }
+            Unsupported(feature: String) {
+                description("unsupported model feature")
+                display("Unsupported model feature: {}", feature)
+            }
+        }
+    }
+}
+
+/// A mesh drawn with a material.
+#[derive(Clone, Debug)]
+pub struct Primitive {
+    pub mesh: MeshHandle,
+    pub material: Option<MaterialHandle>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ModelNode {
+    pub name: Option<String>,
+    pub transform: Transform,
+    /// indices into `Model::nodes`
+    pub children: Vec<usize>,
+    pub primitives: Vec<Primitive>,
+    pub camera: Option<Projection>,
+}
+
+/// Everything imported from one model file.
+#[derive(Clone, Debug, Default)]
+pub struct Model {
+    pub nodes: Vec<ModelNode>,
+    /// indices of the nodes without a parent, in instantiation order
+    pub roots: Vec<usize>,
+    /// every image the materials refer to, e.g. for loading them up front
+    pub images: Vec<ImageSource>,
+    /// file the model was loaded from, named in errors
+    pub path: Option<String>,
+}
+
+/// Entities created by one instantiation of a model.
+#[derive(Clone, Debug, Default)]
+pub struct ModelInstance {
+    /// entity for each node, by node index
+    pub nodes: Vec<Option<Entity>>,
+    pub roots: Vec<Entity>,
+}
+
+impl Model {
+    /// Picks the importer from the file extension.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Model> {
+        let path = path.as_ref();
+        let extension = path.extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+        match extension.as_deref() {
+            Some("obj") => obj::load(path),
+            Some("gltf") | Some("glb") => gltf::load(path),
+            _ => Err(Error::from(ErrorKind::Unsupported(format!("model format of {}", path.display()))))
+                .chain_err(|| ErrorKind::File(path.display().to_string())),
+        }
+    }
+
+    /// Checks roots and children refer to existing nodes.
+    pub fn validate(&self) -> Result<()> {
+        match self.path {
+            Some(ref path) => self.validate_nodes().chain_err(|| ErrorKind::File(path.clone())),
+            None => self.validate_nodes(),
+        }
+    }
+
+    fn validate_nodes(&self) -> Result<()> {
+        let count = self.nodes.len();
+        if let Some(&root) = self.roots.iter().find(|&&root| root >= count) {
+            bail!(ErrorKind::Invalid(format!("Root refers to unknown node {}", root)));
+        }
+        for (index, node) in self.nodes.iter().enumerate() {
+            if let Some(&child) = node.children.iter().find(|&&child| child >= count) {
+                bail!(ErrorKind::Invalid(format!("Node {} has unknown child {}", index, child)));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn instantiate(&self, world: &mut World) -> Result<ModelInstance> {
+        self.instantiate_under(world, None)
+    }
+
+    /// Creates entities for every node reachable from the roots, the roots being attached to
+    /// `parent` if given.
+    ///
+    /// Nodes with several primitives get one child entity per primitive.
+    pub fn instantiate_under(&self, world: &mut World, parent: Option<Entity>) -> Result<ModelInstance> {
+        self.validate()?;
+        world.register::<Named>();
+        world.register::<Parent>();
+        world.register::<Transform>();
+        world.register::<GlobalTransform>();
+        world.register::<MeshHandle>();
+        world.register::<MaterialHandle>();
+        world.register::<Aabb>();
+        world.register::<Camera>();
+
+        let mut instance = ModelInstance { nodes: vec![None; self.nodes.len()], roots: Vec::new() };
+        let mut pending: Vec<(usize, Option<Entity>)> = self.roots.iter().rev().map(|&root| (root, parent)).collect();
+        while let Some((index, parent)) = pending.pop() {
+            if instance.nodes[index].is_some() {
+                continue;
+            }
+            let node = &self.nodes[index];
+            let entity = {
+                let mut builder = world.create_entity()
+                    .with(node.transform.clone())
+                    .with(GlobalTransform::default());
+                if let Some(ref name) = node.name {
+                    builder = builder.with(Named(name.clone()));
+                }
+                if let Some(parent) = parent {
+                    builder = builder.with(Parent { entity: parent });
+                }
+                if let Some(projection) = node.camera {
+                    builder = builder.with(Camera::new(projection));
+                }
+                if node.primitives.len() == 1 {
+                    builder = with_primitive(builder, &node.primitives[0]);
+                }
+                builder.build()
+            };
+            if node.primitives.len() > 1 {
+                for primitive in &node.primitives {
+                    let builder = world.create_entity()
+                        .with(Transform::default())
+                        .with(GlobalTransform::default())
+                        .with(Parent { entity });
+                    with_primitive(builder, primitive).build();
+                }
+            }
+            if self.roots.contains(&index) {
+                instance.roots.push(entity);
+            }
+            instance.nodes[index] = Some(entity);
+            pending.extend(node.children.iter().rev().map(|&child| (child, Some(entity))));
+        }
+        Ok(instance)
+    }
+}
+
+fn with_primitive<'a>(builder: EntityBuilder<'a>, primitive: &Primitive) -> EntityBuilder<'a> {
+    let mut builder = builder.with(primitive.mesh.clone());
+    if let Some(bounds) = primitive.mesh.bounds() {
+        builder = builder.with(bounds);
+    }
+    match primitive.material {
+        Some(ref material) => builder.with(material.clone()),
+        None => builder,
+    }
+}
+
+/// Appends `source` to `images` unless already listed.
+fn add_image(images: &mut Vec<ImageSource>, source: &ImageSource) {
+    if !images.contains(source) {
+        images.push(source.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use specs::prelude::{Join, World};
+
+    use cachoeira_core::transform::{Parent, Transform};
+    use mesh::MeshHandle;
+    use super::{obj, ErrorKind, Model, ModelNode};
+
+    #[test]
+    fn instantiates_hierarchy() {
+        let model = obj::parse(
+            "o first\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\no second\nf 1 3 2\n",
+            None,
+        ).unwrap();
+        let mut world = World::new();
+        let instance = model.instantiate(&mut world).unwrap();
+        assert_eq!(instance.roots.len(), 2);
+        let meshes = world.read_storage::<MeshHandle>();
+        assert!(instance.roots.iter().all(|&root| meshes.get(root).is_some()));
+        assert!(world.read_storage::<Parent>().get(instance.roots[0]).is_none());
+        assert_eq!(world.read_storage::<Transform>().get(instance.roots[1]), Some(&Transform::default()));
+
+        match *Model::load("missing.fbx").unwrap_err().kind() {
+            ErrorKind::File(ref path) => assert_eq!(path, "missing.fbx"),
+            ref other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_nodes() {
+        let mut model = Model { nodes: vec![ModelNode::default()], roots: vec![0], ..Model::default() };
+        model.nodes[0].children.push(3);
+        let mut world = World::new();
+        match *model.instantiate(&mut world).unwrap_err().kind() {
+            ErrorKind::Invalid(ref message) => assert_eq!(message, "Node 0 has unknown child 3"),
+            ref other => panic!("unexpected error {:?}", other),
+        }
+
+        model.path = Some("broken.gltf".to_owned());
+        model.nodes[0].children.clear();
+        model.roots.push(1);
+        let error = model.instantiate(&mut world).unwrap_err();
+        match *error.kind() {
+            ErrorKind::File(ref path) => assert_eq!(path, "broken.gltf"),
+            ref other => panic!("unexpected error {:?}", other),
+        }
+        assert_eq!(error.iter().nth(1).unwrap().to_string(), "Invalid model: Root refers to unknown node 1");
+        assert_eq!(world.entities().join().count(), 0);
+    }
+}
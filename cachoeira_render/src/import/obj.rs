@@ -0,0 +1,353 @@
+//! Wavefront OBJ geometry and MTL materials.
+//!
+//! Every object (`o` or `g`) becomes a root node with one primitive per material used in it.
+//! Polygons are triangulated as fans; missing normals are generated.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str::{FromStr, SplitWhitespace};
+
+use fnv::FnvHashMap;
+
+use import::{add_image, ErrorKind, Model, ModelNode, Primitive, Result, ResultExt};
+use material::{AlphaMode, Material, MaterialHandle, MaterialTexture};
+use mesh::{Mesh, MeshHandle};
+use texture::ImageSource;
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Model> {
+    let path = path.as_ref();
+    let source = read_to_string(path)?;
+    parse(&source, path.parent())
+        .map(|model| Model { path: Some(path.display().to_string()), ..model })
+        .chain_err(|| ErrorKind::File(path.display().to_string()))
+}
+
+/// Parses OBJ source; `mtllib` files and textures are looked up in `directory`, or skipped
+/// without one.
+pub fn parse(source: &str, directory: Option<&Path>) -> Result<Model> {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut colors: Vec<Option<[f32; 4]>> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+    let mut materials = HashMap::new();
+    let mut images = Vec::new();
+
+    let mut objects: Vec<Option<String>> = vec![None];
+    let mut parts: Vec<Part> = Vec::new();
+    let mut object = 0;
+    let mut material: Option<String> = None;
+
+    for (number, line) in source.lines().enumerate() {
+        let number = number + 1;
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        match keyword {
+            "v" => {
+                let values = floats(tokens, number)?;
+                match values.len() {
+                    3 | 4 => colors.push(None),
+                    6 => colors.push(Some([values[3], values[4], values[5], 1.])),
+                    _ => bail!(ErrorKind::Line(number, "Vertex position needs 3 coordinates".to_owned())),
+                }
+                positions.push([values[0], values[1], values[2]]);
+            }
+            "vt" => {
+                let values = floats(tokens, number)?;
+                if values.is_empty() {
+                    bail!(ErrorKind::Line(number, "Texture coordinate needs at least U".to_owned()));
+                }
+                // OBJ's V grows upwards
+                uvs.push([values[0], 1. - values.get(1).cloned().unwrap_or(0.)]);
+            }
+            "vn" => {
+                let values = floats(tokens, number)?;
+                if values.len() != 3 {
+                    bail!(ErrorKind::Line(number, "Normal needs 3 coordinates".to_owned()));
+                }
+                normals.push([values[0], values[1], values[2]]);
+            }
+            "f" => {
+                let counts = (positions.len(), uvs.len(), normals.len());
+                let corners = tokens
+                    .map(|token| corner(token, counts).map_err(|message| ErrorKind::Line(number, message).into()))
+                    .collect::<Result<Vec<_>>>()?;
+                if corners.len() < 3 {
+                    bail!(ErrorKind::Line(number, "Face needs at least 3 vertices".to_owned()));
+                }
+                let part = match parts.iter().position(|part| part.object == object && part.material == material) {
+                    Some(part) => part,
+                    None => {
+                        parts.push(Part::new(object, material.clone()));
+                        parts.len() - 1
+                    }
+                };
+                let part = &mut parts[part];
+                let first = part.vertex(corners[0]);
+                for pair in corners[1..].windows(2) {
+                    let (b, c) = (part.vertex(pair[0]), part.vertex(pair[1]));
+                    part.indices.extend_from_slice(&[first, b, c]);
+                }
+            }
+            "o" | "g" => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                objects.push(if name.is_empty() { None } else { Some(name) });
+                object = objects.len() - 1;
+            }
+            "usemtl" => material = tokens.next().map(str::to_owned),
+            "mtllib" => if let Some(directory) = directory {
+                for library in tokens {
+                    let path = directory.join(library);
+                    let loaded = read_to_string(&path)
+                        .and_then(|source| parse_mtl(&source, Some(directory)))
+                        .chain_err(|| ErrorKind::File(path.display().to_string()))
+                        .chain_err(|| ErrorKind::Line(number, format!("Loading material library {}", library)))?;
+                    for material in loaded {
+                        for texture in textures(&material) {
+                            add_image(&mut images, &texture.source);
+                        }
+                        let name = material.name.clone().unwrap_or_default();
+                        materials.insert(name, MaterialHandle::new(material));
+                    }
+                }
+            },
+            // smoothing groups, lines, points and the like
+            _ => (),
+        }
+    }
+
+    let mut nodes: Vec<ModelNode> = objects.into_iter()
+        .map(|name| ModelNode { name, ..Default::default() })
+        .collect();
+    for part in parts {
+        let node = part.object;
+        let material = part.material.as_ref().and_then(|name| materials.get(name)).cloned();
+        let mesh = part.build(&positions, &colors, &uvs, &normals);
+        nodes[node].primitives.push(Primitive { mesh: MeshHandle::new(mesh), material });
+    }
+    // objects without faces are left out
+    let nodes: Vec<ModelNode> = nodes.into_iter().filter(|node| !node.primitives.is_empty()).collect();
+    Ok(Model { roots: (0..nodes.len()).collect(), nodes, images, path: None })
+}
+
+/// Parses MTL source; texture paths are taken relative to `directory` if given.
+pub fn parse_mtl(source: &str, directory: Option<&Path>) -> Result<Vec<Material>> {
+    let mut materials: Vec<Material> = Vec::new();
+    for (number, line) in source.lines().enumerate() {
+        let number = number + 1;
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        if keyword == "newmtl" {
+            let name = tokens.collect::<Vec<_>>().join(" ");
+            // OBJ has no metalness, so start from a dielectric
+            materials.push(Material { name: Some(name), metallic: 0., ..Default::default() });
+            continue;
+        }
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => bail!(ErrorKind::Line(number, format!("'{}' before any newmtl", keyword))),
+        };
+        let texture = |mut tokens: SplitWhitespace| -> Result<MaterialTexture> {
+            // options such as `-bm 1` come before the file name
+            let name = tokens.next_back()
+                .ok_or_else(|| ErrorKind::Line(number, "Texture map needs a file name".to_owned()))?;
+            let path = match directory {
+                Some(directory) => directory.join(name),
+                None => name.into(),
+            };
+            Ok(MaterialTexture::new(ImageSource::Path(path)))
+        };
+        match keyword {
+            "Kd" => {
+                let color = color(tokens, number)?;
+                material.base_color = [color[0], color[1], color[2], material.base_color[3]];
+            }
+            "Ke" => material.emissive = color(tokens, number)?,
+            "d" => material.base_color[3] = float(tokens.next(), number)?,
+            "Tr" => material.base_color[3] = 1. - float(tokens.next(), number)?,
+            // Phong exponent to roughness, as in Blender's importer
+            "Ns" => material.roughness = (2. / (float(tokens.next(), number)?.max(0.) + 2.)).sqrt(),
+            "Pr" => material.roughness = float(tokens.next(), number)?,
+            "Pm" => material.metallic = float(tokens.next(), number)?,
+            "map_Kd" => material.base_color_texture = Some(texture(tokens)?),
+            "map_Ke" => material.emissive_texture = Some(texture(tokens)?),
+            "map_Bump" | "map_bump" | "bump" | "norm" => material.normal_texture = Some(texture(tokens)?),
+            _ => (),
+        }
+    }
+    for material in &mut materials {
+        if material.base_color[3] < 1. {
+            material.alpha_mode = AlphaMode::Blend;
+        }
+    }
+    Ok(materials)
+}
+
+type Corner = (usize, Option<usize>, Option<usize>);
+
+/// Triangles of one object drawn with one material, with their own vertex numbering.
+struct Part {
+    object: usize,
+    material: Option<String>,
+    vertices: FnvHashMap<Corner, u32>,
+    corners: Vec<Corner>,
+    indices: Vec<u32>,
+}
+
+impl Part {
+    fn new(object: usize, material: Option<String>) -> Self {
+        Part { object, material, vertices: FnvHashMap::default(), corners: Vec::new(), indices: Vec::new() }
+    }
+
+    fn vertex(&mut self, corner: Corner) -> u32 {
+        let corners = &mut self.corners;
+        *self.vertices.entry(corner).or_insert_with(|| {
+            corners.push(corner);
+            corners.len() as u32 - 1
+        })
+    }
+
+    fn build(self, positions: &[[f32; 3]], colors: &[Option<[f32; 4]>], uvs: &[[f32; 2]], normals: &[[f32; 3]]) -> Mesh {
+        let mut mesh = Mesh::new(self.corners.iter().map(|&(p, _, _)| positions[p]).collect(), self.indices);
+        if self.corners.iter().all(|&(p, _, _)| colors[p].is_some()) {
+            mesh = mesh.with_colors(self.corners.iter().map(|&(p, _, _)| colors[p].unwrap()).collect());
+        }
+        let has_uvs = self.corners.iter().any(|&(_, uv, _)| uv.is_some());
+        if has_uvs {
+            mesh = mesh.with_uvs(self.corners.iter().map(|&(_, uv, _)| uv.map_or([0., 0.], |uv| uvs[uv])).collect());
+        }
+        if self.corners.iter().all(|&(_, _, normal)| normal.is_some()) {
+            mesh = mesh.with_normals(self.corners.iter().map(|&(_, _, normal)| normals[normal.unwrap()]).collect());
+        } else {
+            mesh.compute_normals();
+        }
+        if has_uvs {
+            mesh.compute_tangents().expect("Mesh has normals and UVs");
+        }
+        mesh
+    }
+}
+
+/// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn`, resolving negative indices against the counts
+/// of positions, texture coordinates and normals read so far.
+fn corner(token: &str, counts: (usize, usize, usize)) -> ::std::result::Result<Corner, String> {
+    let mut fields = token.split('/');
+    let resolve = |field: Option<&str>, count: usize, what: &str| -> ::std::result::Result<Option<usize>, String> {
+        let field = match field {
+            Some(field) if !field.is_empty() => field,
+            _ => return Ok(None),
+        };
+        let index = i64::from_str(field).map_err(|_| format!("Bad {} index '{}'", what, field))?;
+        let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+        if index == 0 || resolved < 0 || resolved >= count as i64 {
+            return Err(format!("{} index {} out of range for {} defined", what, index, count));
+        }
+        Ok(Some(resolved as usize))
+    };
+    let position = resolve(fields.next(), counts.0, "Position")?
+        .ok_or_else(|| format!("Face vertex '{}' has no position", token))?;
+    let uv = resolve(fields.next(), counts.1, "Texture coordinate")?;
+    let normal = resolve(fields.next(), counts.2, "Normal")?;
+    Ok((position, uv, normal))
+}
+
+fn float(token: Option<&str>, line: usize) -> Result<f32> {
+    let token = token.ok_or_else(|| ErrorKind::Line(line, "Missing number".to_owned()))?;
+    f32::from_str(token).map_err(|_| ErrorKind::Line(line, format!("Bad number '{}'", token)).into())
+}
+
+fn floats(tokens: SplitWhitespace, line: usize) -> Result<Vec<f32>> {
+    tokens.map(|token| float(Some(token), line)).collect()
+}
+
+fn color(tokens: SplitWhitespace, line: usize) -> Result<[f32; 3]> {
+    let values = floats(tokens, line)?;
+    match values.len() {
+        1 => Ok([values[0]; 3]),
+        3 => Ok([values[0], values[1], values[2]]),
+        _ => bail!(ErrorKind::Line(line, "Colour needs 1 or 3 components".to_owned())),
+    }
+}
+
+fn textures(material: &Material) -> Vec<&MaterialTexture> {
+    [&material.base_color_texture, &material.emissive_texture, &material.normal_texture].iter()
+        .filter_map(|&texture| texture.as_ref())
+        .collect()
+}
+
+fn read_to_string(path: &Path) -> Result<String> {
+    let mut source = String::new();
+    File::open(path)?.read_to_string(&mut source)?;
+    Ok(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use material::AlphaMode;
+    use super::{parse, parse_mtl};
+    use import::ErrorKind;
+
+    const QUAD: &str = "
+        # two objects sharing vertices
+        v -1 -1 0
+        v 1 -1 0
+        v 1 1 0
+        v -1 1 0
+        vt 0 0
+        vt 1 0
+        vt 1 1
+        vt 0 1
+        vn 0 0 1
+        o quad
+        f 1/1/1 2/2/1 3/3/1 4/4/1
+        o back
+        usemtl red
+        f -1 -2 -3
+    ";
+
+    #[test]
+    fn fans_polygons_and_splits_objects() {
+        let model = parse(QUAD, None).unwrap();
+        assert_eq!(model.roots, vec![0, 1]);
+        assert_eq!(model.nodes[0].name, Some("quad".to_owned()));
+        let quad = &model.nodes[0].primitives[0].mesh;
+        assert_eq!((quad.vertex_count(), quad.indices.to_u32()), (4, vec![0, 1, 2, 0, 2, 3]));
+        assert_eq!(quad.uvs.as_ref().unwrap()[0], [0., 1.]);
+        assert!(quad.tangents.is_some());
+
+        // no normals given: generated, facing away as the winding is reversed
+        let back = &model.nodes[1].primitives[0].mesh;
+        assert_eq!(back.positions, vec![[-1., 1., 0.], [1., 1., 0.], [1., -1., 0.]]);
+        assert_eq!(back.normals.as_ref().unwrap()[0], [0., 0., -1.]);
+
+        match *parse("v 0 0 0\nf 1 2 3", None).unwrap_err().kind() {
+            ErrorKind::Line(2, ref message) => assert!(message.contains("out of range")),
+            ref other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn materials() {
+        let materials = parse_mtl("
+            newmtl glass
+            Kd 0.5 0.5 1
+            d 0.25
+            Ns 0
+            map_Kd -bm 1 glass.png
+        ", None).unwrap();
+        assert_eq!(materials[0].name, Some("glass".to_owned()));
+        assert_eq!(materials[0].base_color, [0.5, 0.5, 1., 0.25]);
+        assert_eq!((materials[0].alpha_mode, materials[0].roughness), (AlphaMode::Blend, 1.));
+        assert!(materials[0].base_color_texture.is_some());
+        assert!(parse_mtl("Kd 1 1 1", None).is_err());
+    }
+}
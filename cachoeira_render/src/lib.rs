@@ -1,3 +1,48 @@
+extern crate base64;
+extern crate cachoeira_core;
+#[macro_use]
+extern crate cgmath;
+#[macro_use]
+extern crate error_chain;
+extern crate fnv;
+extern crate image;
+extern crate png;
+extern crate rayon;
+extern crate ron;
+extern crate rusttype;
+#[macro_use]
+extern crate serde;
+extern crate serde_json;
+extern crate specs;
+
+#[macro_use]
+#[cfg(feature = "profiler")]
+extern crate thread_profiler;
+
+pub use camera::*;
+pub use device::{Device, DirectionalLight, DrawCall, UsesPipeline, Vertex};
+pub use material::{AlphaMode, Material, MaterialHandle, MaterialTexture};
+pub use mesh::{Indices, Mesh, MeshHandle, VertexAttribute, VertexLayout};
+pub use pipeline::{Blend, CullMode, DepthTest, Pipeline, Shading};
+pub use texture::{ColorSpace, Filter, ImageSource, PixelFormat, Sampler, Texture, Wrap};
+
+pub mod camera;
+pub mod color;
+pub mod debug;
+pub mod device;
+pub mod graph;
+pub mod headless;
+pub mod import;
+pub mod material;
+pub mod mesh;
+pub mod particles;
+pub mod pipeline;
+pub mod projection;
+pub mod software;
+pub mod sprite;
+pub mod text;
+pub mod texture;
+
 #[cfg(test)]
 mod tests {
     #[test]
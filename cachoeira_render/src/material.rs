@@ -0,0 +1,113 @@
+//! Surface descriptions following glTF's metallic-roughness model, shared between meshes.
+
+use std::sync::Arc;
+
+use specs::prelude::{
+    Component,
+    DenseVecStorage,
+};
+
+use pipeline::{Blend, CullMode, DepthTest, Pipeline};
+use texture::{ImageSource, Sampler};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlphaMode {
+    Opaque,
+    /// fully transparent below the cutoff, opaque above
+    Mask(f32),
+    Blend,
+}
+
+/// An image bound to a material slot.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MaterialTexture {
+    pub source: ImageSource,
+    pub sampler: Sampler,
+    /// which set of texture coordinates to sample with
+    pub uv_set: u32,
+}
+
+impl MaterialTexture {
+    pub fn new(source: ImageSource) -> Self {
+        MaterialTexture { source, sampler: Sampler::default(), uv_set: 0 }
+    }
+}
+
+/// Colours are linear; textures holding colours are expected in sRGB.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    pub name: Option<String>,
+    pub base_color: [f32; 4],
+    pub base_color_texture: Option<MaterialTexture>,
+    pub metallic: f32,
+    pub roughness: f32,
+    /// metalness in blue, roughness in green
+    pub metallic_roughness_texture: Option<MaterialTexture>,
+    pub normal_texture: Option<MaterialTexture>,
+    pub occlusion_texture: Option<MaterialTexture>,
+    pub emissive: [f32; 3],
+    pub emissive_texture: Option<MaterialTexture>,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+}
+
+impl Material {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_base_color(mut self, base_color: [f32; 4]) -> Self {
+        self.base_color = base_color;
+        self
+    }
+
+    /// Pipeline state drawing this material: transparent ones blend and leave depth alone,
+    /// double-sided ones are not culled.
+    pub fn pipeline(&self) -> Pipeline {
+        let cull_mode = if self.double_sided { CullMode::None } else { CullMode::Back };
+        let pipeline = Pipeline::new().with_cull_mode(cull_mode);
+        match self.alpha_mode {
+            AlphaMode::Blend => pipeline.with_blend(Blend::Alpha).with_depth(DepthTest::Less, false),
+            AlphaMode::Opaque | AlphaMode::Mask(_) => pipeline,
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            name: None,
+            base_color: [1.; 4],
+            base_color_texture: None,
+            metallic: 1.,
+            roughness: 1.,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            occlusion_texture: None,
+            emissive: [0.; 3],
+            emissive_texture: None,
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+        }
+    }
+}
+
+/// Shared reference to the `Material` an entity's `MeshHandle` is drawn with.
+#[derive(Clone, Debug)]
+pub struct MaterialHandle(pub Arc<Material>);
+
+impl MaterialHandle {
+    pub fn new(material: Material) -> Self {
+        MaterialHandle(Arc::new(material))
+    }
+}
+
+impl ::std::ops::Deref for MaterialHandle {
+    type Target = Material;
+
+    fn deref(&self) -> &Material { &self.0 }
+}
+
+impl Component for MaterialHandle {
+    type Storage = DenseVecStorage<Self>;
+}
@@ -0,0 +1,436 @@
+//! Triangle meshes: typed vertex attributes, index buffers, bounds, normal and tangent
+//! generation, and procedural primitives.
+
+use std::sync::Arc;
+
+use cgmath::{
+    InnerSpace,
+    Matrix,
+    Matrix3,
+    Matrix4,
+    Point3,
+    SquareMatrix,
+    Transform as CgTransform,
+    Vector2,
+    Vector3,
+    Zero,
+};
+use specs::prelude::{
+    Component,
+    DenseVecStorage,
+};
+
+use cachoeira_core::culling::{Aabb, BoundingSphere};
+use device::Vertex;
+
+mod primitives;
+
+/// Semantic of a vertex attribute.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum VertexAttribute {
+    /// 3 floats
+    Position,
+    /// 3 floats
+    Normal,
+    /// 2 floats
+    Uv,
+    /// 4 floats, linear RGBA
+    Color,
+    /// 4 floats, `w` being the handedness of the bitangent
+    Tangent,
+}
+
+impl VertexAttribute {
+    pub fn components(self) -> usize {
+        match self {
+            VertexAttribute::Position | VertexAttribute::Normal => 3,
+            VertexAttribute::Uv => 2,
+            VertexAttribute::Color | VertexAttribute::Tangent => 4,
+        }
+    }
+}
+
+/// Interleaved arrangement of float attributes, in order.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct VertexLayout {
+    pub attributes: Vec<VertexAttribute>,
+}
+
+impl VertexLayout {
+    pub fn new(attributes: Vec<VertexAttribute>) -> Self {
+        VertexLayout { attributes }
+    }
+
+    /// Floats per vertex.
+    pub fn components(&self) -> usize {
+        self.attributes.iter().map(|attribute| attribute.components()).sum()
+    }
+
+    /// Bytes per vertex.
+    pub fn stride(&self) -> usize {
+        self.components() * 4
+    }
+
+    /// Byte offset of `attribute` within a vertex.
+    pub fn offset(&self, attribute: VertexAttribute) -> Option<usize> {
+        let index = self.attributes.iter().position(|&a| a == attribute)?;
+        Some(self.attributes[..index].iter().map(|a| a.components() * 4).sum())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    /// Picks 16-bit indices when `vertex_count` and every index allow it, so an index out of
+    /// range is kept for `Mesh::validate` to report rather than wrapped.
+    pub fn new(indices: Vec<u32>, vertex_count: usize) -> Self {
+        let max = u32::from(u16::MAX);
+        if vertex_count <= max as usize + 1 && indices.iter().all(|&index| index <= max) {
+            Indices::U16(indices.into_iter().map(|index| index as u16).collect())
+        } else {
+            Indices::U32(indices)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match *self {
+            Indices::U16(ref indices) => indices.len(),
+            Indices::U32(ref indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    pub fn get(&self, index: usize) -> u32 {
+        match *self {
+            Indices::U16(ref indices) => indices[index] as u32,
+            Indices::U32(ref indices) => indices[index],
+        }
+    }
+
+    pub fn to_u32(&self) -> Vec<u32> {
+        match *self {
+            Indices::U16(ref indices) => indices.iter().map(|&index| index as u32).collect(),
+            Indices::U32(ref indices) => indices.clone(),
+        }
+    }
+}
+
+/// An indexed triangle list with optional per-vertex attributes.
+///
+/// Front faces wind counter-clockwise seen from outside. Texture coordinates put (0, 0) at the
+/// top left of an image. Attributes hold one entry per vertex; the `with_*` builders take them
+/// as given and `validate` reports any mismatch.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Mesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Option<Vec<[f32; 3]>>,
+    pub uvs: Option<Vec<[f32; 2]>>,
+    pub colors: Option<Vec<[f32; 4]>>,
+    pub tangents: Option<Vec<[f32; 4]>>,
+    pub indices: Indices,
+}
+
+impl Mesh {
+    pub fn new(positions: Vec<[f32; 3]>, indices: Vec<u32>) -> Self {
+        let indices = Indices::new(indices, positions.len());
+        Mesh { positions, normals: None, uvs: None, colors: None, tangents: None, indices }
+    }
+
+    pub fn with_normals(mut self, normals: Vec<[f32; 3]>) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: Vec<[f32; 2]>) -> Self {
+        self.uvs = Some(uvs);
+        self
+    }
+
+    pub fn with_colors(mut self, colors: Vec<[f32; 4]>) -> Self {
+        self.colors = Some(colors);
+        self
+    }
+
+    pub fn with_tangents(mut self, tangents: Vec<[f32; 4]>) -> Self {
+        self.tangents = Some(tangents);
+        self
+    }
+
+    pub fn vertex_count(&self) -> usize { self.positions.len() }
+
+    pub fn triangle_count(&self) -> usize { self.indices.len() / 3 }
+
+    /// Vertex indices of every triangle.
+    pub fn triangles<'a>(&'a self) -> Box<dyn Iterator<Item = [usize; 3]> + 'a> {
+        Box::new((0..self.triangle_count()).map(move |triangle| [
+            self.indices.get(triangle * 3) as usize,
+            self.indices.get(triangle * 3 + 1) as usize,
+            self.indices.get(triangle * 3 + 2) as usize,
+        ]))
+    }
+
+    /// Checks attribute lengths and index ranges.
+    pub fn validate(&self) -> Result<(), String> {
+        let count = self.vertex_count();
+        let lengths = [
+            ("normals", self.normals.as_ref().map(Vec::len)),
+            ("uvs", self.uvs.as_ref().map(Vec::len)),
+            ("colors", self.colors.as_ref().map(Vec::len)),
+            ("tangents", self.tangents.as_ref().map(Vec::len)),
+        ];
+        for &(name, length) in &lengths {
+            match length {
+                Some(length) if length != count => {
+                    return Err(format!("Mesh has {} {} for {} vertices", length, name, count));
+                }
+                _ => (),
+            }
+        }
+        if !self.indices.len().is_multiple_of(3) {
+            return Err(format!("Index count {} is not a multiple of 3", self.indices.len()));
+        }
+        match (0..self.indices.len()).map(|i| self.indices.get(i)).find(|&index| index as usize >= count) {
+            Some(index) => Err(format!("Index {} out of range for {} vertices", index, count)),
+            None => Ok(()),
+        }
+    }
+
+    /// Attributes present, in the canonical order.
+    pub fn layout(&self) -> VertexLayout {
+        let mut attributes = vec![VertexAttribute::Position];
+        if self.normals.is_some() { attributes.push(VertexAttribute::Normal); }
+        if self.uvs.is_some() { attributes.push(VertexAttribute::Uv); }
+        if self.colors.is_some() { attributes.push(VertexAttribute::Color); }
+        if self.tangents.is_some() { attributes.push(VertexAttribute::Tangent); }
+        VertexLayout::new(attributes)
+    }
+
+    /// Vertex data interleaved as `layout` describes, e.g. for upload to a GPU.
+    pub fn interleave(&self, layout: &VertexLayout) -> Result<Vec<f32>, String> {
+        self.validate()?;
+        let missing = |attribute| format!("Mesh has no {:?} attribute", attribute);
+        let mut data = Vec::with_capacity(layout.components() * self.vertex_count());
+        for vertex in 0..self.vertex_count() {
+            for &attribute in &layout.attributes {
+                match attribute {
+                    VertexAttribute::Position => data.extend_from_slice(&self.positions[vertex]),
+                    VertexAttribute::Normal => data.extend_from_slice(&self.normals.as_ref().ok_or_else(|| missing(attribute))?[vertex]),
+                    VertexAttribute::Uv => data.extend_from_slice(&self.uvs.as_ref().ok_or_else(|| missing(attribute))?[vertex]),
+                    VertexAttribute::Color => data.extend_from_slice(&self.colors.as_ref().ok_or_else(|| missing(attribute))?[vertex]),
+                    VertexAttribute::Tangent => data.extend_from_slice(&self.tangents.as_ref().ok_or_else(|| missing(attribute))?[vertex]),
+                }
+            }
+        }
+        Ok(data)
+    }
+
+    /// Vertices for a `DrawCall`; missing attributes take the `Vertex` defaults, as do the
+    /// entries an attribute that is too short lacks.
+    pub fn vertices(&self) -> Vec<Vertex> {
+        (0..self.vertex_count())
+            .map(|i| {
+                let mut vertex = Vertex::new(self.positions[i]);
+                if let Some(normal) = self.normals.as_ref().and_then(|normals| normals.get(i)) { vertex.normal = *normal; }
+                if let Some(uv) = self.uvs.as_ref().and_then(|uvs| uvs.get(i)) { vertex.uv = *uv; }
+                if let Some(color) = self.colors.as_ref().and_then(|colors| colors.get(i)) { vertex.color = *color; }
+                vertex
+            })
+            .collect()
+    }
+
+    /// Local-space bounds, `None` for a mesh without vertices.
+    pub fn bounds(&self) -> Option<Aabb> {
+        Aabb::from_points(self.positions.iter().map(|&p| Point3::from(p)))
+    }
+
+    /// Sphere around the bounds' centre enclosing every vertex.
+    pub fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        let center = self.bounds()?.center();
+        let radius = self.positions.iter()
+            .map(|&p| (Point3::from(p) - center).magnitude())
+            .fold(0., f32::max);
+        Some(BoundingSphere::new(center, radius))
+    }
+
+    /// Smooth normals, averaged over the adjacent triangles weighted by their area.
+    ///
+    /// Vertices are not split, so hard edges need separate vertices per face.
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![Vector3::zero(); self.vertex_count()];
+        for [a, b, c] in self.triangles() {
+            let (pa, pb, pc) = (Vector3::from(self.positions[a]), Vector3::from(self.positions[b]), Vector3::from(self.positions[c]));
+            let face = (pb - pa).cross(pc - pa);
+            for &vertex in &[a, b, c] {
+                normals[vertex] += face;
+            }
+        }
+        self.normals = Some(normals.into_iter().map(|n| normalize_or(n, Vector3::unit_y()).into()).collect());
+    }
+
+    /// Tangents along increasing U from normals and UVs, with the bitangent handedness in `w`.
+    pub fn compute_tangents(&mut self) -> Result<(), String> {
+        self.validate()?;
+        let tangents = match (self.normals.as_ref(), self.uvs.as_ref()) {
+            (Some(normals), Some(uvs)) => self.tangents_from(normals, uvs),
+            _ => return Err("Tangents need normals and UVs".to_owned()),
+        };
+        self.tangents = Some(tangents);
+        Ok(())
+    }
+
+    fn tangents_from(&self, normals: &[[f32; 3]], uvs: &[[f32; 2]]) -> Vec<[f32; 4]> {
+        let mut tangents = vec![Vector3::zero(); self.vertex_count()];
+        let mut bitangents = vec![Vector3::zero(); self.vertex_count()];
+        for [a, b, c] in self.triangles() {
+            let pa = Vector3::from(self.positions[a]);
+            let (edge1, edge2) = (Vector3::from(self.positions[b]) - pa, Vector3::from(self.positions[c]) - pa);
+            let ua = Vector2::from(uvs[a]);
+            let (duv1, duv2) = (Vector2::from(uvs[b]) - ua, Vector2::from(uvs[c]) - ua);
+            let determinant = duv1.x * duv2.y - duv2.x * duv1.y;
+            if determinant.abs() < f32::EPSILON {
+                continue;
+            }
+            let tangent = (edge1 * duv2.y - edge2 * duv1.y) / determinant;
+            let bitangent = (edge2 * duv1.x - edge1 * duv2.x) / determinant;
+            for &vertex in &[a, b, c] {
+                tangents[vertex] += tangent;
+                bitangents[vertex] += bitangent;
+            }
+        }
+        (0..self.vertex_count())
+            .map(|i| {
+                let normal = Vector3::from(normals[i]);
+                // Gram-Schmidt against the normal, any perpendicular if the UVs were degenerate
+                let tangent = tangents[i] - normal * normal.dot(tangents[i]);
+                let fallback = if normal.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+                let fallback = fallback - normal * normal.dot(fallback);
+                let tangent = normalize_or(tangent, normalize_or(fallback, Vector3::unit_x()));
+                let handedness = if normal.cross(tangent).dot(bitangents[i]) < 0. { -1. } else { 1. };
+                [tangent.x, tangent.y, tangent.z, handedness]
+            })
+            .collect()
+    }
+
+    /// Bakes an affine transform into positions, normals and tangents.
+    pub fn transform(&mut self, matrix: &Matrix4<f32>) {
+        let linear = Matrix3::from_cols(matrix.x.truncate(), matrix.y.truncate(), matrix.z.truncate());
+        let normal_matrix = linear.invert().map(|inverse| inverse.transpose()).unwrap_or(linear);
+        for position in &mut self.positions {
+            *position = matrix.transform_point(Point3::from(*position)).into();
+        }
+        if let Some(ref mut normals) = self.normals {
+            for normal in normals.iter_mut() {
+                *normal = normalize_or(normal_matrix * Vector3::from(*normal), Vector3::from(*normal)).into();
+            }
+        }
+        if let Some(ref mut tangents) = self.tangents {
+            let mirrored = linear.determinant() < 0.;
+            for tangent in tangents.iter_mut() {
+                let direction = Vector3::new(tangent[0], tangent[1], tangent[2]);
+                let direction = normalize_or(linear * direction, direction);
+                let handedness = if mirrored { -tangent[3] } else { tangent[3] };
+                *tangent = [direction.x, direction.y, direction.z, handedness];
+            }
+        }
+        if linear.determinant() < 0. {
+            self.flip_winding();
+        }
+    }
+
+    /// Reverses the winding of every triangle, turning it inside out.
+    pub fn flip_winding(&mut self) {
+        match self.indices {
+            Indices::U16(ref mut indices) => for triangle in indices.chunks_mut(3) { triangle.swap(1, 2); },
+            Indices::U32(ref mut indices) => for triangle in indices.chunks_mut(3) { triangle.swap(1, 2); },
+        }
+    }
+}
+
+fn normalize_or(vector: Vector3<f32>, fallback: Vector3<f32>) -> Vector3<f32> {
+    if vector.magnitude2() > 0. {
+        vector.normalize()
+    } else {
+        fallback
+    }
+}
+
+/// Shared reference to a `Mesh`, drawn at the entity's `GlobalTransform`.
+#[derive(Clone, Debug)]
+pub struct MeshHandle(pub Arc<Mesh>);
+
+impl MeshHandle {
+    pub fn new(mesh: Mesh) -> Self {
+        MeshHandle(Arc::new(mesh))
+    }
+}
+
+impl ::std::ops::Deref for MeshHandle {
+    type Target = Mesh;
+
+    fn deref(&self) -> &Mesh { &self.0 }
+}
+
+impl Component for MeshHandle {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[cfg(test)]
+mod tests {
+    use device::Vertex;
+    use super::{Indices, Mesh, VertexAttribute, VertexLayout};
+
+    #[test]
+    fn normals_tangents_and_layout() {
+        // unit quad in the XY plane, facing +Z
+        let mut mesh = Mesh::new(
+            vec![[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]],
+            vec![0, 1, 2, 0, 2, 3],
+        ).with_uvs(vec![[0., 1.], [1., 1.], [1., 0.], [0., 0.]]);
+        mesh.compute_normals();
+        mesh.compute_tangents().unwrap();
+        assert!(mesh.normals.as_ref().unwrap().iter().all(|n| *n == [0., 0., 1.]));
+        // V grows downwards, so the bitangent points along -Y: negative handedness
+        assert!(mesh.tangents.as_ref().unwrap().iter().all(|t| *t == [1., 0., 0., -1.]));
+
+        let layout = mesh.layout();
+        assert_eq!(layout, VertexLayout::new(vec![VertexAttribute::Position, VertexAttribute::Normal, VertexAttribute::Uv, VertexAttribute::Tangent]));
+        assert_eq!((layout.stride(), layout.offset(VertexAttribute::Uv)), (48, Some(24)));
+        let data = mesh.interleave(&layout).unwrap();
+        assert_eq!(&data[12..24], &[1., 0., 0., 0., 0., 1., 1., 1., 1., 0., 0., -1.]);
+        assert!(mesh.interleave(&VertexLayout::new(vec![VertexAttribute::Color])).is_err());
+        assert!(mesh.validate().is_ok());
+    }
+
+    #[test]
+    fn picks_index_width() {
+        assert_eq!(Indices::new(vec![0, 1, 65535], 65536), Indices::U16(vec![0, 1, 65535]));
+        assert_eq!(Indices::new(vec![0, 1, 65536], 3), Indices::U32(vec![0, 1, 65536]));
+        let invalid = Mesh::new(vec![[0.; 3]; 3], vec![0, 1, 65536]);
+        assert_eq!(invalid.validate(), Err("Index 65536 out of range for 3 vertices".to_owned()));
+
+        // a strip of triangles over 65537 vertices, the last one needing 32-bit indices
+        let count = 65537;
+        let positions = (0..count).map(|i| [i as f32, (i % 2) as f32, 0.]).collect();
+        let indices: Vec<u32> = (0..count as u32 - 2).flat_map(|i| vec![i, i + 1, i + 2]).collect();
+        let mesh = Mesh::new(positions, indices.clone());
+        assert_eq!(mesh.indices, Indices::U32(indices));
+        assert_eq!(mesh.indices.get(mesh.indices.len() - 1), 65536);
+        assert!(mesh.validate().is_ok());
+    }
+
+    #[test]
+    fn reports_attribute_length_mismatches() {
+        let mesh = Mesh::new(vec![[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]], vec![0, 1, 2])
+            .with_normals(vec![[0., 0., 1.]; 3])
+            .with_uvs(vec![[0., 0.], [1., 0.]]);
+        assert_eq!(mesh.validate(), Err("Mesh has 2 uvs for 3 vertices".to_owned()));
+        assert!(mesh.interleave(&mesh.layout()).is_err());
+        assert!(mesh.clone().compute_tangents().is_err());
+        assert_eq!(mesh.vertices()[2].uv, Vertex::new([0.; 3]).uv);
+    }
+}
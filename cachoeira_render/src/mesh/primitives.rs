@@ -0,0 +1,280 @@
+//! Procedural meshes, centred on the origin with normals, UVs and tangents.
+
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Vector3};
+use fnv::FnvHashMap;
+
+use super::Mesh;
+
+/// Indices for a `(columns + 1) x (rows + 1)` vertex grid, stored row after row, whose columns
+/// run along U (right) and rows along V (down) as seen from the front.
+///
+/// Triangles touching a pole are skipped when `rows` start or end in a single point.
+fn grid_indices(columns: u32, rows: u32, top_pole: bool, bottom_pole: bool) -> Vec<u32> {
+    let stride = columns + 1;
+    let mut indices = Vec::with_capacity((columns * rows * 6) as usize);
+    for row in 0..rows {
+        for column in 0..columns {
+            let a = row * stride + column;
+            let (b, c, d) = (a + 1, a + stride + 1, a + stride);
+            if !(top_pole && row == 0) {
+                indices.extend_from_slice(&[a, c, b]);
+            }
+            if !(bottom_pole && row == rows - 1) {
+                indices.extend_from_slice(&[a, d, c]);
+            }
+        }
+    }
+    indices
+}
+
+fn finish(mut mesh: Mesh) -> Mesh {
+    mesh.compute_tangents().expect("Primitives have normals and UVs");
+    mesh
+}
+
+impl Mesh {
+    /// Square of side `size` in the XZ plane, facing +Y.
+    pub fn plane(size: f32) -> Mesh {
+        Mesh::grid(size, size, 1, 1)
+    }
+
+    /// `width` along X by `depth` along Z in the XZ plane, facing +Y, split into
+    /// `columns x rows` quads. UVs span the whole grid, U along +X and V along +Z.
+    pub fn grid(width: f32, depth: f32, columns: u32, rows: u32) -> Mesh {
+        let (columns, rows) = (columns.max(1), rows.max(1));
+        let mut positions = Vec::with_capacity(((columns + 1) * (rows + 1)) as usize);
+        let mut uvs = Vec::with_capacity(positions.capacity());
+        for row in 0..rows + 1 {
+            let v = row as f32 / rows as f32;
+            for column in 0..columns + 1 {
+                let u = column as f32 / columns as f32;
+                positions.push([(u - 0.5) * width, 0., (v - 0.5) * depth]);
+                uvs.push([u, v]);
+            }
+        }
+        let normals = vec![[0., 1., 0.]; positions.len()];
+        finish(Mesh::new(positions, grid_indices(columns, rows, false, false)).with_normals(normals).with_uvs(uvs))
+    }
+
+    /// Axis-aligned cube with edges of length `size`; each face has its own vertices and the
+    /// whole texture.
+    pub fn cube(size: f32) -> Mesh {
+        // normal, then the directions of U and V on that face seen from outside
+        let faces: [([f32; 3], [f32; 3], [f32; 3]); 6] = [
+            ([1., 0., 0.], [0., 0., -1.], [0., -1., 0.]),
+            ([-1., 0., 0.], [0., 0., 1.], [0., -1., 0.]),
+            ([0., 1., 0.], [1., 0., 0.], [0., 0., 1.]),
+            ([0., -1., 0.], [1., 0., 0.], [0., 0., -1.]),
+            ([0., 0., 1.], [1., 0., 0.], [0., -1., 0.]),
+            ([0., 0., -1.], [-1., 0., 0.], [0., -1., 0.]),
+        ];
+        let half = size * 0.5;
+        let mut positions = Vec::with_capacity(24);
+        let (mut normals, mut uvs, mut indices) = (Vec::with_capacity(24), Vec::with_capacity(24), Vec::with_capacity(36));
+        for &(normal, u, v) in &faces {
+            let (n, s, t) = (Vector3::from(normal), Vector3::from(u), Vector3::from(v));
+            let base = positions.len() as u32;
+            for &(x, y) in &[(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)] {
+                positions.push(((n + s * x + t * y) * half).into());
+                normals.push(normal);
+                uvs.push([(x + 1.) * 0.5, (y + 1.) * 0.5]);
+            }
+            indices.extend_from_slice(&[base, base + 2, base + 1, base, base + 3, base + 2]);
+        }
+        finish(Mesh::new(positions, indices).with_normals(normals).with_uvs(uvs))
+    }
+
+    /// Latitude-longitude sphere with poles on the Y axis. U wraps around from +Z towards +X,
+    /// V runs from the north pole down.
+    pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> Mesh {
+        let (segments, rings) = (segments.max(3), rings.max(2));
+        let mut positions = Vec::with_capacity(((segments + 1) * (rings + 1)) as usize);
+        let (mut normals, mut uvs) = (Vec::with_capacity(positions.capacity()), Vec::with_capacity(positions.capacity()));
+        for ring in 0..rings + 1 {
+            let v = ring as f32 / rings as f32;
+            let theta = v * PI;
+            for segment in 0..segments + 1 {
+                let u = segment as f32 / segments as f32;
+                let phi = u * 2. * PI;
+                let normal = [theta.sin() * phi.sin(), theta.cos(), theta.sin() * phi.cos()];
+                positions.push([normal[0] * radius, normal[1] * radius, normal[2] * radius]);
+                normals.push(normal);
+                uvs.push([u, v]);
+            }
+        }
+        let indices = grid_indices(segments, rings, true, true);
+        finish(Mesh::new(positions, indices).with_normals(normals).with_uvs(uvs))
+    }
+
+    /// Sphere from a subdivided icosahedron, with evenly sized triangles.
+    ///
+    /// Vertices are shared, so spherical UVs stretch across the seam behind -Z.
+    pub fn ico_sphere(radius: f32, subdivisions: u32) -> Mesh {
+        let t = (1. + 5f32.sqrt()) * 0.5;
+        let mut directions: Vec<Vector3<f32>> = [
+            [-1., t, 0.], [1., t, 0.], [-1., -t, 0.], [1., -t, 0.],
+            [0., -1., t], [0., 1., t], [0., -1., -t], [0., 1., -t],
+            [t, 0., -1.], [t, 0., 1.], [-t, 0., -1.], [-t, 0., 1.],
+        ].iter().map(|&p| Vector3::from(p).normalize()).collect();
+        let mut triangles: Vec<[u32; 3]> = vec![
+            [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+            [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+            [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+            [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+        ];
+        for _ in 0..subdivisions {
+            let mut midpoints = FnvHashMap::default();
+            let mut next = Vec::with_capacity(triangles.len() * 4);
+            {
+                let mut midpoint = |a: u32, b: u32| -> u32 {
+                    let key = (a.min(b), a.max(b));
+                    *midpoints.entry(key).or_insert_with(|| {
+                        let direction = (directions[a as usize] + directions[b as usize]).normalize();
+                        directions.push(direction);
+                        directions.len() as u32 - 1
+                    })
+                };
+                for &[a, b, c] in &triangles {
+                    let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                    next.extend_from_slice(&[[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]);
+                }
+            }
+            triangles = next;
+        }
+
+        let mut indices = Vec::with_capacity(triangles.len() * 3);
+        for &[a, b, c] in &triangles {
+            let (pa, pb, pc) = (directions[a as usize], directions[b as usize], directions[c as usize]);
+            // wind counter-clockwise seen from outside whatever the table above says
+            if (pb - pa).cross(pc - pa).dot(pa + pb + pc) >= 0. {
+                indices.extend_from_slice(&[a, b, c]);
+            } else {
+                indices.extend_from_slice(&[a, c, b]);
+            }
+        }
+        let positions = directions.iter().map(|&d| (d * radius).into()).collect();
+        let normals = directions.iter().map(|&d| d.into()).collect();
+        let uvs = directions.iter()
+            .map(|d| {
+                let u = d.x.atan2(d.z) / (2. * PI);
+                [if u < 0. { u + 1. } else { u }, d.y.clamp(-1., 1.).acos() / PI]
+            })
+            .collect();
+        finish(Mesh::new(positions, indices).with_normals(normals).with_uvs(uvs))
+    }
+
+    /// Capped cylinder along Y. The side is unwrapped like `uv_sphere`; each cap maps the
+    /// texture onto a disc.
+    pub fn cylinder(radius: f32, height: f32, segments: u32) -> Mesh {
+        let segments = segments.max(3);
+        let half = height * 0.5;
+        let (mut positions, mut normals, mut uvs) = (Vec::new(), Vec::new(), Vec::new());
+        for &(y, v) in &[(half, 0.), (-half, 1.)] {
+            for segment in 0..segments + 1 {
+                let u = segment as f32 / segments as f32;
+                let (sin, cos) = (u * 2. * PI).sin_cos();
+                positions.push([sin * radius, y, cos * radius]);
+                normals.push([sin, 0., cos]);
+                uvs.push([u, v]);
+            }
+        }
+        let mut indices = grid_indices(segments, 1, false, false);
+
+        // caps: a centre vertex followed by the rim, seen from outside U along +X and V along
+        // +Z on top, -Z underneath
+        for &(y, facing) in &[(half, 1f32), (-half, -1f32)] {
+            let center = positions.len() as u32;
+            positions.push([0., y, 0.]);
+            normals.push([0., facing, 0.]);
+            uvs.push([0.5, 0.5]);
+            for segment in 0..segments {
+                let (sin, cos) = (segment as f32 / segments as f32 * 2. * PI).sin_cos();
+                positions.push([sin * radius, y, cos * radius]);
+                normals.push([0., facing, 0.]);
+                uvs.push([0.5 + sin * 0.5, 0.5 + cos * facing * 0.5]);
+            }
+            for segment in 0..segments {
+                let (current, next) = (center + 1 + segment, center + 1 + (segment + 1) % segments);
+                if facing > 0. {
+                    indices.extend_from_slice(&[center, current, next]);
+                } else {
+                    indices.extend_from_slice(&[center, next, current]);
+                }
+            }
+        }
+        finish(Mesh::new(positions, indices).with_normals(normals).with_uvs(uvs))
+    }
+
+    /// Torus around the Y axis: a tube of radius `minor` swept along a circle of radius `major`.
+    pub fn torus(major: f32, minor: f32, major_segments: u32, minor_segments: u32) -> Mesh {
+        let (major_segments, minor_segments) = (major_segments.max(3), minor_segments.max(3));
+        let count = ((major_segments + 1) * (minor_segments + 1)) as usize;
+        let (mut positions, mut normals, mut uvs) = (Vec::with_capacity(count), Vec::with_capacity(count), Vec::with_capacity(count));
+        // rows go around the tube starting on the outer equator and heading down
+        for ring in 0..minor_segments + 1 {
+            let v = ring as f32 / minor_segments as f32;
+            let (sin_theta, cos_theta) = (v * 2. * PI).sin_cos();
+            for segment in 0..major_segments + 1 {
+                let u = segment as f32 / major_segments as f32;
+                let (sin_phi, cos_phi) = (u * 2. * PI).sin_cos();
+                let radial = Vector3::new(sin_phi, 0., cos_phi);
+                let normal = radial * cos_theta - Vector3::unit_y() * sin_theta;
+                positions.push((radial * major + normal * minor).into());
+                normals.push(normal.into());
+                uvs.push([u, v]);
+            }
+        }
+        let indices = grid_indices(major_segments, minor_segments, false, false);
+        finish(Mesh::new(positions, indices).with_normals(normals).with_uvs(uvs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{InnerSpace, Point3, Vector3};
+
+    use super::super::Mesh;
+
+    fn check(mesh: &Mesh) {
+        mesh.validate().unwrap();
+        let normals = mesh.normals.as_ref().unwrap();
+        let tangents = mesh.tangents.as_ref().unwrap();
+        for [a, b, c] in mesh.triangles() {
+            let (pa, pb, pc) = (Vector3::from(mesh.positions[a]), Vector3::from(mesh.positions[b]), Vector3::from(mesh.positions[c]));
+            let face = (pb - pa).cross(pc - pa);
+            assert!(face.magnitude2() > 0., "degenerate triangle {:?}", [a, b, c]);
+            for &vertex in &[a, b, c] {
+                assert!(face.dot(Vector3::from(normals[vertex])) > 0., "triangle {:?} winds inwards", [a, b, c]);
+            }
+        }
+        for (normal, tangent) in normals.iter().zip(tangents) {
+            assert!(Vector3::from(*normal).dot(Vector3::new(tangent[0], tangent[1], tangent[2])).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn primitives_wind_outwards() {
+        let meshes = [
+            Mesh::plane(2.),
+            Mesh::grid(4., 2., 4, 2),
+            Mesh::cube(2.),
+            Mesh::uv_sphere(1., 16, 8),
+            Mesh::ico_sphere(1., 2),
+            Mesh::cylinder(1., 2., 12),
+            Mesh::torus(1., 0.25, 16, 8),
+        ];
+        for mesh in &meshes {
+            check(mesh);
+        }
+
+        let bounds = meshes[2].bounds().unwrap();
+        assert_eq!((bounds.min, bounds.max), (Point3::new(-1., -1., -1.), Point3::new(1., 1., 1.)));
+        assert_eq!(meshes[1].bounds().unwrap().max, Point3::new(2., 0., 1.));
+        assert_eq!(meshes[4].triangle_count(), 20 * 16);
+        assert!(meshes[4].positions.iter().all(|p| (Vector3::from(*p).magnitude() - 1.).abs() < 1e-5));
+        let torus = meshes[6].bounds().unwrap();
+        assert_ulps_eq!(torus.max, Point3::new(1.25, 0.25, 1.25), epsilon = 1e-5);
+    }
+}
@@ -0,0 +1,107 @@
+use cgmath::Vector3;
+use rayon::prelude::*;
+
+use particles::ParticleModifier;
+
+// particles per rayon task; smaller emitters are not worth splitting
+const MIN_TASK_LEN: usize = 512;
+
+/// Live particles of one emitter as structure-of-arrays, in world space. Every array has
+/// `len()` entries and particle `i` is entry `i` of each; order changes as particles die.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Particles {
+    positions: Vec<Vector3<f32>>,
+    velocities: Vec<Vector3<f32>>,
+    ages: Vec<f32>,
+    lifetimes: Vec<f32>,
+    // sizes before `SizeOverLife`
+    start_sizes: Vec<f32>,
+    sizes: Vec<f32>,
+    colors: Vec<[f32; 4]>,
+}
+
+impl Particles {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn len(&self) -> usize { self.positions.len() }
+    pub fn is_empty(&self) -> bool { self.positions.is_empty() }
+
+    pub fn positions(&self) -> &[Vector3<f32>] { &self.positions }
+    pub fn velocities(&self) -> &[Vector3<f32>] { &self.velocities }
+    /// seconds since each particle spawned
+    pub fn ages(&self) -> &[f32] { &self.ages }
+    pub fn lifetimes(&self) -> &[f32] { &self.lifetimes }
+    pub fn sizes(&self) -> &[f32] { &self.sizes }
+    /// linear, non-premultiplied
+    pub fn colors(&self) -> &[[f32; 4]] { &self.colors }
+
+    pub fn clear(&mut self) {
+        self.positions.clear();
+        self.velocities.clear();
+        self.ages.clear();
+        self.lifetimes.clear();
+        self.start_sizes.clear();
+        self.sizes.clear();
+        self.colors.clear();
+    }
+
+    pub fn spawn(&mut self, position: Vector3<f32>, velocity: Vector3<f32>, lifetime: f32, size: f32, color: [f32; 4]) {
+        self.positions.push(position);
+        self.velocities.push(velocity);
+        self.ages.push(0.);
+        self.lifetimes.push(lifetime);
+        self.start_sizes.push(size);
+        self.sizes.push(size);
+        self.colors.push(color);
+    }
+
+    /// Ages every particle by `seconds`, removes those past their lifetime, then moves the rest
+    /// and updates their appearance through `modifiers` in parallel. `color` is the emitter's,
+    /// which `ColorOverLife` multiplies.
+    pub fn update(&mut self, seconds: f32, modifiers: &[ParticleModifier], color: [f32; 4]) {
+        self.ages.par_iter_mut().with_min_len(MIN_TASK_LEN).for_each(|age| *age += seconds);
+        let mut index = self.len();
+        while index > 0 {
+            index -= 1;
+            if self.ages[index] >= self.lifetimes[index] {
+                self.swap_remove(index);
+            }
+        }
+
+        self.positions.par_iter_mut()
+            .zip(self.velocities.par_iter_mut())
+            .with_min_len(MIN_TASK_LEN)
+            .for_each(|(position, velocity)| {
+                for modifier in modifiers {
+                    modifier.accelerate(*position, velocity, seconds);
+                }
+                *position += *velocity * seconds;
+            });
+
+        let (ages, lifetimes, start_sizes) = (&self.ages, &self.lifetimes, &self.start_sizes);
+        self.sizes.par_iter_mut()
+            .zip(self.colors.par_iter_mut())
+            .enumerate()
+            .with_min_len(MIN_TASK_LEN)
+            .for_each(|(index, (size, particle_color))| {
+                let life = ages[index] / lifetimes[index];
+                *size = start_sizes[index];
+                *particle_color = color;
+                for modifier in modifiers {
+                    modifier.appearance(life, size, particle_color);
+                }
+            });
+    }
+
+    fn swap_remove(&mut self, index: usize) {
+        self.positions.swap_remove(index);
+        self.velocities.swap_remove(index);
+        self.ages.swap_remove(index);
+        self.lifetimes.swap_remove(index);
+        self.start_sizes.swap_remove(index);
+        self.sizes.swap_remove(index);
+        self.colors.swap_remove(index);
+    }
+}
@@ -0,0 +1,413 @@
+//! CPU particles.
+//!
+//! A `ParticleEmitter` component spawns particles at its entity's `GlobalTransform` and owns
+//! them, as structure-of-arrays `Particles` in world space, so a moving emitter leaves a trail.
+//! Particles are not entities: the `ParticleSystem` steps every emitter's particles in parallel
+//! with rayon, through the emitter's `ParticleModifier`s, and a `ParticleRenderer` draws them
+//! as screen-sized points or camera-facing sprites.
+
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use cgmath::{InnerSpace, Matrix4, One, Rad, Vector3, Vector4};
+use specs::prelude::{
+    Component,
+    DenseVecStorage,
+    Entities,
+    Join,
+    Read,
+    ReadStorage,
+    System,
+    WriteStorage,
+};
+
+use cachoeira_core::transform::GlobalTransform;
+use cachoeira_core::Time;
+
+use pipeline::Blend;
+use texture::Texture;
+
+pub use self::buffer::Particles;
+pub use self::modifier::ParticleModifier;
+pub use self::render::ParticleRenderer;
+
+mod buffer;
+mod modifier;
+mod render;
+
+static NEXT_SEED: AtomicUsize = AtomicUsize::new(1);
+
+/// Volume particles spawn in, in the emitter's local space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EmitterShape {
+    Point,
+    /// anywhere inside a sphere of this radius
+    Sphere(f32),
+    /// anywhere inside a box of these half extents
+    Box(Vector3<f32>),
+}
+
+/// Particles spawned at once, `at` seconds after the emitter started, then every `interval`
+/// seconds if given.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Burst {
+    pub at: f32,
+    pub count: u32,
+    pub interval: Option<f32>,
+}
+
+impl Burst {
+    pub fn new(at: f32, count: u32) -> Self {
+        Burst { at, count, interval: None }
+    }
+
+    pub fn repeating(mut self, interval: f32) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    // bursts in [from, to)
+    fn count_between(&self, from: f32, to: f32) -> u32 {
+        let bursts = match self.interval {
+            Some(interval) if interval > 0. => {
+                let first = ((from - self.at) / interval).ceil().max(0.);
+                let end = ((to - self.at) / interval).ceil().max(0.);
+                (end - first) as u32
+            }
+            _ => (from <= self.at && self.at < to) as u32,
+        };
+        bursts * self.count
+    }
+}
+
+/// How a `ParticleRenderer` draws an emitter's particles.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParticleDisplay {
+    /// squares facing the screen, their size in pixels
+    Points,
+    /// the whole texture on squares facing the camera, their size in world units
+    Sprites(Arc<Texture>),
+}
+
+/// Spawns and owns particles; ranges given as `[min, max]` are sampled uniformly per particle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParticleEmitter {
+    /// particles per second
+    pub rate: f32,
+    pub bursts: Vec<Burst>,
+    /// seconds
+    pub lifetime: [f32; 2],
+    pub shape: EmitterShape,
+    /// local direction particles leave in
+    pub direction: Vector3<f32>,
+    /// half-angle of the cone around `direction` particles leave in
+    pub spread: Rad<f32>,
+    /// units per second
+    pub speed: [f32; 2],
+    pub size: [f32; 2],
+    /// linear, non-premultiplied
+    pub color: [f32; 4],
+    pub modifiers: Vec<ParticleModifier>,
+    /// spawning stops while this many particles are alive
+    pub max_particles: usize,
+    /// stops spawning when false; live particles carry on
+    pub emitting: bool,
+    pub display: ParticleDisplay,
+    pub blend: Blend,
+    particles: Particles,
+    // seconds since the emitter started
+    time: f32,
+    // fraction of a particle owed by `rate`
+    owed: f32,
+    random: u32,
+}
+
+impl ParticleEmitter {
+    /// Emits `rate` particles per second upwards, living a second each, as white points.
+    pub fn new(rate: f32) -> Self {
+        ParticleEmitter {
+            rate,
+            bursts: Vec::new(),
+            lifetime: [1., 1.],
+            shape: EmitterShape::Point,
+            direction: Vector3::unit_y(),
+            spread: Rad(0.),
+            speed: [1., 1.],
+            size: [4., 4.],
+            color: [1.; 4],
+            modifiers: Vec::new(),
+            max_particles: 10_000,
+            emitting: true,
+            display: ParticleDisplay::Points,
+            blend: Blend::Alpha,
+            particles: Particles::new(),
+            time: 0.,
+            owed: 0.,
+            random: NEXT_SEED.fetch_add(1, Ordering::Relaxed) as u32,
+        }
+    }
+
+    pub fn with_burst(mut self, burst: Burst) -> Self {
+        self.bursts.push(burst);
+        self
+    }
+
+    pub fn with_lifetime(mut self, min: f32, max: f32) -> Self {
+        self.lifetime = [min, max];
+        self
+    }
+
+    pub fn with_shape(mut self, shape: EmitterShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    pub fn with_direction<A: Into<Rad<f32>>>(mut self, direction: Vector3<f32>, spread: A) -> Self {
+        self.direction = direction;
+        self.spread = spread.into();
+        self
+    }
+
+    pub fn with_speed(mut self, min: f32, max: f32) -> Self {
+        self.speed = [min, max];
+        self
+    }
+
+    pub fn with_size(mut self, min: f32, max: f32) -> Self {
+        self.size = [min, max];
+        self
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_modifier(mut self, modifier: ParticleModifier) -> Self {
+        self.modifiers.push(modifier);
+        self
+    }
+
+    pub fn with_max_particles(mut self, max_particles: usize) -> Self {
+        self.max_particles = max_particles;
+        self
+    }
+
+    pub fn with_display(mut self, display: ParticleDisplay) -> Self {
+        self.display = display;
+        self
+    }
+
+    pub fn with_blend(mut self, blend: Blend) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    /// Seeds the emitter's random numbers, for repeatable effects.
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.random = seed.max(1);
+        self
+    }
+
+    pub fn particles(&self) -> &Particles { &self.particles }
+
+    /// Seconds since the emitter started.
+    pub fn time(&self) -> f32 { self.time }
+
+    /// Removes every particle and starts the emitter, with its bursts, over.
+    pub fn restart(&mut self) {
+        self.particles.clear();
+        self.time = 0.;
+        self.owed = 0.;
+    }
+
+    /// Spawns what is due in the next `seconds` from `matrix`, the emitter's world matrix,
+    /// then steps every particle.
+    pub fn update(&mut self, seconds: f32, matrix: &Matrix4<f32>) {
+        if self.emitting {
+            self.owed += self.rate * seconds;
+            let due = self.owed.floor();
+            self.owed -= due;
+            let bursts: u32 = self.bursts.iter().map(|burst| burst.count_between(self.time, self.time + seconds)).sum();
+            let room = self.max_particles.saturating_sub(self.particles.len());
+            for _ in 0..(due as usize + bursts as usize).min(room) {
+                self.spawn(matrix);
+            }
+        }
+        self.time += seconds;
+        self.particles.update(seconds, &self.modifiers, self.color);
+    }
+
+    fn spawn(&mut self, matrix: &Matrix4<f32>) {
+        let local = match self.shape {
+            EmitterShape::Point => Vector3::new(0., 0., 0.),
+            EmitterShape::Sphere(radius) => self.in_unit_sphere() * radius,
+            EmitterShape::Box(half) => {
+                let (x, y, z) = (self.between(-1., 1.), self.between(-1., 1.), self.between(-1., 1.));
+                Vector3::new(x * half.x, y * half.y, z * half.z)
+            }
+        };
+        let position = (matrix * local.extend(1.)).truncate();
+
+        let direction = self.in_cone();
+        let direction = (matrix * Vector4::new(direction.x, direction.y, direction.z, 0.)).truncate();
+        let direction = if direction.magnitude2() > 0. { direction.normalize() } else { direction };
+        let speed = self.between(self.speed[0], self.speed[1]);
+        let lifetime = self.between(self.lifetime[0], self.lifetime[1]);
+        let size = self.between(self.size[0], self.size[1]);
+        self.particles.spawn(position, direction * speed, lifetime, size, self.color);
+    }
+
+    // xorshift, in 0..1
+    fn next(&mut self) -> f32 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 17;
+        self.random ^= self.random << 5;
+        (self.random >> 8) as f32 / (1 << 24) as f32
+    }
+
+    fn between(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next()
+    }
+
+    fn in_unit_sphere(&mut self) -> Vector3<f32> {
+        loop {
+            let point = Vector3::new(self.between(-1., 1.), self.between(-1., 1.), self.between(-1., 1.));
+            if point.magnitude2() <= 1. {
+                return point;
+            }
+        }
+    }
+
+    // uniform over the spherical cap around `direction`
+    fn in_cone(&mut self) -> Vector3<f32> {
+        let axis = if self.direction.magnitude2() > 0. { self.direction.normalize() } else { Vector3::unit_y() };
+        let cos_theta = self.between(self.spread.0.min(PI).cos(), 1.);
+        let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+        let phi = self.between(0., 2. * PI);
+        let helper = if axis.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+        let u = axis.cross(helper).normalize();
+        let v = axis.cross(u);
+        axis * cos_theta + (u * phi.cos() + v * phi.sin()) * sin_theta
+    }
+}
+
+impl Default for ParticleEmitter {
+    fn default() -> Self { ParticleEmitter::new(10.) }
+}
+
+impl Component for ParticleEmitter {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Steps every `ParticleEmitter` by the frame's game time; belongs after the `TransformSystem`.
+///
+/// Emitters without a `GlobalTransform` sit at the origin.
+pub struct ParticleSystem;
+
+impl<'a> System<'a> for ParticleSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        ReadStorage<'a, GlobalTransform>,
+        WriteStorage<'a, ParticleEmitter>,
+    );
+
+    fn run(&mut self, (entities, time, globals, mut emitters): Self::SystemData) {
+        #[cfg(feature="profiler")]
+        profile_scope!("particle_system");
+
+        let seconds = time.delta_seconds();
+        for (entity, emitter) in (&*entities, &mut emitters).join() {
+            let matrix = globals.get(entity).map_or(Matrix4::one(), |global| global.0);
+            emitter.update(seconds, &matrix);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, InnerSpace, Matrix4, One, Vector3};
+
+    use particles::{Burst, EmitterShape, ParticleEmitter, ParticleModifier};
+
+    // 10 a second, 5 at once and 2 every half second from 0.25, each living 0.45 seconds
+    fn fountain() -> ParticleEmitter {
+        ParticleEmitter::new(10.)
+            .with_burst(Burst::new(0., 5))
+            .with_burst(Burst::new(0.25, 2).repeating(0.5))
+            .with_lifetime(0.45, 0.45)
+            .with_speed(2., 2.)
+            .with_seed(7)
+    }
+
+    fn run(emitter: &mut ParticleEmitter, steps: usize) {
+        let at = Matrix4::from_translation(Vector3::new(1., 0., 0.));
+        for _ in 0..steps {
+            emitter.update(0.1, &at);
+        }
+    }
+
+    // 100,000 particles asked of a sphere capped at 50,000, aimed within 30 degrees of -Z
+    fn cone() -> ParticleEmitter {
+        let mut emitter = ParticleEmitter::new(0.)
+            .with_burst(Burst::new(0., 100_000))
+            .with_max_particles(50_000)
+            .with_shape(EmitterShape::Sphere(2.))
+            .with_direction(Vector3::new(0., 0., -1.), Deg(30.))
+            .with_lifetime(10., 10.)
+            .with_modifier(ParticleModifier::Gravity(Vector3::new(0., -1., 0.)));
+        emitter.update(0.01, &Matrix4::one());
+        emitter
+    }
+
+    #[test]
+    fn emits_bursts_and_rate() {
+        let mut emitter = fountain();
+        run(&mut emitter, 1);
+        // the burst at 0 and one for the rate, moved up for a step
+        assert_eq!(emitter.particles().len(), 6);
+        assert!((emitter.particles().positions()[0] - Vector3::new(1., 0.2, 0.)).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn expires_and_repeats_bursts() {
+        let mut emitter = fountain();
+        run(&mut emitter, 5);
+        // the first burst expired; ticks and the repeating burst at 0.25 remain
+        assert_eq!(emitter.particles().len(), 4 + 2);
+        run(&mut emitter, 5);
+        // the repeating burst again at 0.75
+        assert_eq!(emitter.particles().len(), 4 + 2);
+    }
+
+    #[test]
+    fn drains_once_stopped() {
+        let mut emitter = fountain();
+        run(&mut emitter, 10);
+        emitter.emitting = false;
+        run(&mut emitter, 5);
+        assert!(emitter.particles().is_empty());
+    }
+
+    #[test]
+    fn caps_particles_at_the_maximum() {
+        assert_eq!(cone().particles().len(), 50_000);
+    }
+
+    #[test]
+    fn spawns_within_the_shape() {
+        let emitter = cone();
+        assert!(emitter.particles().positions().iter().all(|position| position.magnitude() <= 2.02));
+    }
+
+    #[test]
+    fn aims_within_the_cone() {
+        let emitter = cone();
+        let velocities = emitter.particles().velocities();
+        let cos = 30f32.to_radians().cos();
+        assert!(velocities.iter().all(|velocity| -velocity.z >= cos - 1e-3));
+        assert!(velocities.iter().any(|velocity| -velocity.z < 0.9));
+    }
+}
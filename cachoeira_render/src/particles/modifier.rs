@@ -0,0 +1,159 @@
+use cgmath::{InnerSpace, Point3, Vector3};
+
+use color::Gradient;
+
+/// Changes particles every update, in the order the emitter lists them.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParticleModifier {
+    /// constant acceleration, in units per second squared
+    Gravity(Vector3<f32>),
+    /// fraction of velocity lost per second, applied exponentially
+    Drag(f32),
+    /// acceleration from a smooth random field that stays put in world space
+    Noise {
+        /// peak acceleration
+        strength: f32,
+        /// field features per world unit
+        frequency: f32,
+    },
+    /// acceleration towards a world position, negative strengths repelling
+    Attractor {
+        position: Point3<f32>,
+        /// acceleration at the position, fading linearly to nothing at `radius`
+        strength: f32,
+        /// reach in world units; 0 reaches everywhere, at full strength
+        radius: f32,
+    },
+    /// multiplies the emitter colour by the gradient at the particle's age over its lifetime
+    ColorOverLife(Gradient),
+    /// multiplies the start size, linearly between (age over lifetime, factor) keys sorted by
+    /// age
+    SizeOverLife(Vec<(f32, f32)>),
+}
+
+impl ParticleModifier {
+    pub(crate) fn accelerate(&self, position: Vector3<f32>, velocity: &mut Vector3<f32>, seconds: f32) {
+        match *self {
+            ParticleModifier::Gravity(acceleration) => *velocity += acceleration * seconds,
+            ParticleModifier::Drag(drag) => *velocity *= (-drag * seconds).exp(),
+            ParticleModifier::Noise { strength, frequency } => {
+                *velocity += noise(position * frequency) * strength * seconds;
+            }
+            ParticleModifier::Attractor { position: target, strength, radius } => {
+                let offset = Vector3::new(target.x, target.y, target.z) - position;
+                let distance = offset.magnitude();
+                if distance < 1e-6 || (radius > 0. && distance >= radius) {
+                    return;
+                }
+                let falloff = if radius > 0. { 1. - distance / radius } else { 1. };
+                *velocity += offset / distance * strength * falloff * seconds;
+            }
+            ParticleModifier::ColorOverLife(_) | ParticleModifier::SizeOverLife(_) => {}
+        }
+    }
+
+    pub(crate) fn appearance(&self, life: f32, size: &mut f32, color: &mut [f32; 4]) {
+        match *self {
+            ParticleModifier::ColorOverLife(ref gradient) => {
+                let factor = gradient.sample(life).to_array();
+                for (channel, factor) in color.iter_mut().zip(&factor) {
+                    *channel *= *factor;
+                }
+            }
+            ParticleModifier::SizeOverLife(ref keys) => *size *= piecewise_linear(keys, life),
+            _ => {}
+        }
+    }
+}
+
+// holds the end values outside the keys; 1 without keys
+fn piecewise_linear(keys: &[(f32, f32)], t: f32) -> f32 {
+    match keys.iter().position(|&(at, _)| at > t) {
+        None => keys.last().map_or(1., |&(_, value)| value),
+        Some(0) => keys[0].1,
+        Some(next) => {
+            let ((t0, v0), (t1, v1)) = (keys[next - 1], keys[next]);
+            v0 + (v1 - v0) * (t - t0) / (t1 - t0)
+        }
+    }
+}
+
+// value noise per axis, each component in -1..1
+fn noise(point: Vector3<f32>) -> Vector3<f32> {
+    Vector3::new(value_noise(point, 0), value_noise(point, 1), value_noise(point, 2))
+}
+
+fn value_noise(point: Vector3<f32>, seed: u32) -> f32 {
+    let (x, y, z) = (point.x.floor(), point.y.floor(), point.z.floor());
+    let smooth = |t: f32| t * t * (3. - 2. * t);
+    let (u, v, w) = (smooth(point.x - x), smooth(point.y - y), smooth(point.z - z));
+    let (x, y, z) = (x as i32, y as i32, z as i32);
+    let lattice = |dx: i32, dy: i32, dz: i32| hash(x + dx, y + dy, z + dz, seed);
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    lerp(
+        lerp(lerp(lattice(0, 0, 0), lattice(1, 0, 0), u), lerp(lattice(0, 1, 0), lattice(1, 1, 0), u), v),
+        lerp(lerp(lattice(0, 0, 1), lattice(1, 0, 1), u), lerp(lattice(0, 1, 1), lattice(1, 1, 1), u), v),
+        w,
+    )
+}
+
+// lattice value in -1..1
+fn hash(x: i32, y: i32, z: i32, seed: u32) -> f32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (y as u32).wrapping_mul(0xd816_3841)
+        ^ (z as u32).wrapping_mul(0xcb1a_b31f)
+        ^ seed.wrapping_mul(0x1656_67b1);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2c1b_3c6d);
+    h ^= h >> 12;
+    h as f32 / u32::MAX as f32 * 2. - 1.
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Point3, Vector3};
+
+    use color::{Gradient, Interpolation};
+    use particles::ParticleModifier;
+    use super::noise;
+
+    #[test]
+    fn applies_gravity_and_drag() {
+        let mut velocity = Vector3::new(1., 0., 0.);
+        ParticleModifier::Gravity(Vector3::new(0., -10., 0.)).accelerate(Vector3::new(0., 0., 0.), &mut velocity, 0.5);
+        assert_eq!(velocity, Vector3::new(1., -5., 0.));
+        ParticleModifier::Drag(2.).accelerate(Vector3::new(0., 0., 0.), &mut velocity, 0.5);
+        assert!((velocity.x - (-1f32).exp()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn attracts_within_reach() {
+        // half way out of reach, so at half strength
+        let attractor = ParticleModifier::Attractor { position: Point3::new(0., 2., 0.), strength: 4., radius: 4. };
+        let mut velocity = Vector3::new(0., 0., 0.);
+        attractor.accelerate(Vector3::new(0., 0., 0.), &mut velocity, 1.);
+        assert_eq!(velocity, Vector3::new(0., 2., 0.));
+        attractor.accelerate(Vector3::new(0., 10., 0.), &mut velocity, 1.);
+        assert_eq!(velocity, Vector3::new(0., 2., 0.));
+    }
+
+    #[test]
+    fn noise_is_smooth_and_differs_per_axis() {
+        let (a, b) = (noise(Vector3::new(0.3, 1.7, 2.2)), noise(Vector3::new(0.31, 1.7, 2.2)));
+        assert!((a.x - b.x).abs() < 0.05 && a.x != a.y);
+    }
+
+    #[test]
+    fn changes_color_and_size_over_life() {
+        let fade = ParticleModifier::ColorOverLife(Gradient::new(Interpolation::Linear)
+            .with_stop(0., [1., 1., 1., 1.])
+            .with_stop(1., [1., 0., 0., 0.]));
+        let grow = ParticleModifier::SizeOverLife(vec![(0., 1.), (0.5, 3.)]);
+        let (mut size, mut color) = (2., [0.5, 1., 1., 1.]);
+        fade.appearance(0.5, &mut size, &mut color);
+        grow.appearance(0.25, &mut size, &mut color);
+        assert_eq!((size, color), (4., [0.5, 0.5, 0.5, 0.5]));
+        grow.appearance(0.9, &mut size, &mut color);
+        assert_eq!(size, 12.);
+    }
+}
@@ -0,0 +1,187 @@
+use std::sync::{Arc, RwLock};
+
+use cgmath::{Matrix4, Vector3};
+use rayon::prelude::*;
+
+use camera::Camera;
+use device::{Device, DrawCall, Vertex};
+use particles::{ParticleDisplay, ParticleEmitter, Particles};
+use pipeline::{Blend, CullMode, DepthTest, Pipeline, Shading};
+use texture::{Filter, Sampler, Wrap};
+
+/// Draws `ParticleEmitter`s through any `Device`, one draw call per emitter with particles.
+///
+/// Particles are hidden by what is already in the depth buffer without writing to it, and are
+/// not sorted by depth, which additive blending does not need.
+pub struct ParticleRenderer {
+    pub sampler: Sampler,
+    opaque: Arc<RwLock<Pipeline>>,
+    alpha: Arc<RwLock<Pipeline>>,
+    additive: Arc<RwLock<Pipeline>>,
+}
+
+impl ParticleRenderer {
+    pub fn new() -> Self {
+        let pipeline = Pipeline::new()
+            .with_shading(Shading::Unlit)
+            .with_cull_mode(CullMode::None)
+            .with_depth(DepthTest::LessEqual, false);
+        ParticleRenderer {
+            sampler: Sampler { filter: Filter::Linear, wrap: Wrap::Clamp },
+            opaque: Arc::new(RwLock::new(pipeline.with_blend(Blend::Opaque))),
+            alpha: Arc::new(RwLock::new(pipeline.with_blend(Blend::Alpha))),
+            additive: Arc::new(RwLock::new(pipeline.with_blend(Blend::Additive))),
+        }
+    }
+
+    pub fn with_sampler(mut self, sampler: Sampler) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Leaves the device on the pipeline of the last emitter drawn.
+    pub fn draw<D: Device + ?Sized>(&self, device: &mut D, emitters: &[&ParticleEmitter], camera: &Camera) -> Result<(), String> {
+        let longest = emitters.iter().map(|emitter| emitter.particles().len()).max().unwrap_or(0);
+        if longest == 0 {
+            return Ok(());
+        }
+        let indices: Vec<u32> = (0..longest as u32)
+            .flat_map(|quad| [0, 1, 2, 0, 2, 3].iter().map(move |corner| quad * 4 + corner).collect::<Vec<_>>())
+            .collect();
+        let (width, height) = device.size();
+        let mut vertices = Vec::new();
+        for emitter in emitters.iter().filter(|emitter| !emitter.particles().is_empty()) {
+            let particles = emitter.particles();
+            vertices.clear();
+            vertices.resize(particles.len() * 4, Vertex::new([0.; 3]));
+            let pipeline = match emitter.blend {
+                Blend::Opaque => &self.opaque,
+                Blend::Alpha => &self.alpha,
+                Blend::Additive => &self.additive,
+            };
+            device.set_pipeline(pipeline.clone())?;
+            let indices = &indices[..particles.len() * 6];
+            match emitter.display {
+                ParticleDisplay::Points => {
+                    let view_projection = camera.view_projection();
+                    let pixel = [2. / width as f32, 2. / height as f32];
+                    vertices.par_chunks_mut(4).enumerate().for_each(|(index, quad)| {
+                        quad.copy_from_slice(&point(particles, index, &view_projection, pixel));
+                    });
+                    device.draw(&DrawCall::new(&vertices, indices))?;
+                }
+                ParticleDisplay::Sprites(ref texture) => {
+                    // the camera's axes in world space are the view matrix's rows
+                    let view = camera.view();
+                    let right = Vector3::new(view.x.x, view.y.x, view.z.x);
+                    let up = Vector3::new(view.x.y, view.y.y, view.z.y);
+                    vertices.par_chunks_mut(4).enumerate().for_each(|(index, quad)| {
+                        quad.copy_from_slice(&billboard(particles, index, right, up));
+                    });
+                    device.draw(&DrawCall::new(&vertices, indices)
+                        .with_view_projection(camera.view_projection())
+                        .with_texture(texture, self.sampler))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for ParticleRenderer {
+    fn default() -> Self { ParticleRenderer::new() }
+}
+
+// corners in normalized device coordinates, so the draw call needs no matrices; particles
+// outside the depth range collapse to nothing
+fn point(particles: &Particles, index: usize, view_projection: &Matrix4<f32>, pixel: [f32; 2]) -> [Vertex; 4] {
+    let position = particles.positions()[index];
+    let clip = view_projection * position.extend(1.);
+    let depth = clip.z / clip.w;
+    if clip.w <= 0. || !(-1. ..=1.).contains(&depth) {
+        return [Vertex::new([0.; 3]); 4];
+    }
+    let (x, y) = (clip.x / clip.w, clip.y / clip.w);
+    let half = particles.sizes()[index] * 0.5;
+    let (dx, dy) = (half * pixel[0], half * pixel[1]);
+    let color = particles.colors()[index];
+    let corner = |x: f32, y: f32| Vertex::new([x, y, depth]).with_color(color);
+    [corner(x - dx, y - dy), corner(x + dx, y - dy), corner(x + dx, y + dy), corner(x - dx, y + dy)]
+}
+
+fn billboard(particles: &Particles, index: usize, right: Vector3<f32>, up: Vector3<f32>) -> [Vertex; 4] {
+    let position = particles.positions()[index];
+    let half = particles.sizes()[index] * 0.5;
+    let (right, up) = (right * half, up * half);
+    let color = particles.colors()[index];
+    let corner = |offset: Vector3<f32>, u: f32, v: f32| {
+        let corner = position + offset;
+        Vertex::new([corner.x, corner.y, corner.z]).with_uv([u, v]).with_color(color)
+    };
+    [
+        corner(-right - up, 0., 1.),
+        corner(right - up, 1., 1.),
+        corner(right + up, 1., 0.),
+        corner(-right + up, 0., 0.),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use cgmath::{Matrix4, Vector3};
+
+    use camera::Camera;
+    use device::Device;
+    use particles::{Burst, ParticleDisplay, ParticleEmitter, ParticleRenderer};
+    use software::SoftwareDevice;
+    use texture::Texture;
+
+    const BLACK: [f32; 4] = [0., 0., 0., 1.];
+
+    // a single still particle at (x, 8, z)
+    fn emitter(x: f32, z: f32, color: [f32; 4], display: ParticleDisplay) -> ParticleEmitter {
+        let mut emitter = ParticleEmitter::new(0.)
+            .with_burst(Burst::new(0., 1))
+            .with_speed(0., 0.)
+            .with_color(color)
+            .with_display(display);
+        emitter.update(0.01, &Matrix4::from_translation(Vector3::new(x, 8., z)));
+        emitter
+    }
+
+    // a 16x16 world unit view, y up
+    fn draw(emitters: &[&ParticleEmitter]) -> SoftwareDevice {
+        let camera = Camera::orthographic(0., 16., 0., 16., -1., 1.);
+        let mut device = SoftwareDevice::new(16, 16);
+        device.clear(BLACK, 1.);
+        ParticleRenderer::new().draw(&mut device, emitters, &camera).unwrap();
+        device
+    }
+
+    #[test]
+    fn draws_points_in_pixels() {
+        let device = draw(&[&emitter(4., 0., [1., 0., 0., 1.], ParticleDisplay::Points)]);
+        let frame = device.framebuffer();
+        // 4 pixels across
+        assert_eq!(frame.pixel(4, 8), [1., 0., 0., 1.]);
+        assert_eq!(frame.pixel(7, 8), BLACK);
+    }
+
+    #[test]
+    fn draws_sprites_in_world_units() {
+        let sprites = emitter(12., 0., [1.; 4], ParticleDisplay::Sprites(Arc::new(Texture::solid([0., 1., 0., 1.]))));
+        let device = draw(&[&sprites]);
+        let frame = device.framebuffer();
+        // 4 world units across
+        assert_eq!(frame.pixel(12, 8), [0., 1., 0., 1.]);
+        assert_eq!(frame.pixel(12, 11), BLACK);
+    }
+
+    #[test]
+    fn skips_points_outside_the_depth_range() {
+        let device = draw(&[&emitter(4., 5., [1., 0., 0., 1.], ParticleDisplay::Points)]);
+        assert!(device.framebuffer().colors().iter().all(|&color| color == BLACK));
+    }
+}
@@ -1,4 +1,113 @@
+/// How fragments are lit.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Shading {
+    /// vertex color, tint and texture only
+    Unlit,
+    /// one normal per triangle
+    Flat,
+    /// interpolated vertex normals, diffuse only
+    Lambert,
+}
 
+/// Which triangles are discarded; front faces wind counter-clockwise on screen.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum CullMode {
+    None,
+    Back,
+    Front,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum DepthTest {
+    Always,
+    Less,
+    LessEqual,
+}
+
+impl DepthTest {
+    pub fn passes(self, depth: f32, stored: f32) -> bool {
+        match self {
+            DepthTest::Always => true,
+            DepthTest::Less => depth < stored,
+            DepthTest::LessEqual => depth <= stored,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Blend {
+    Opaque,
+    /// non-premultiplied source over destination
+    Alpha,
+    Additive,
+}
+
+impl Blend {
+    pub fn apply(self, source: [f32; 4], destination: [f32; 4]) -> [f32; 4] {
+        let alpha = source[3];
+        match self {
+            Blend::Opaque => source,
+            Blend::Alpha => [
+                source[0] * alpha + destination[0] * (1. - alpha),
+                source[1] * alpha + destination[1] * (1. - alpha),
+                source[2] * alpha + destination[2] * (1. - alpha),
+                alpha + destination[3] * (1. - alpha),
+            ],
+            Blend::Additive => [
+                destination[0] + source[0] * alpha,
+                destination[1] + source[1] * alpha,
+                destination[2] + source[2] * alpha,
+                destination[3],
+            ],
+        }
+    }
+}
+
+/// Fixed-function state a `Device` draws with.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Pipeline {
-    
-}
\ No newline at end of file
+    pub shading: Shading,
+    pub cull_mode: CullMode,
+    pub depth_test: DepthTest,
+    pub depth_write: bool,
+    pub blend: Blend,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_shading(mut self, shading: Shading) -> Self {
+        self.shading = shading;
+        self
+    }
+
+    pub fn with_cull_mode(mut self, cull_mode: CullMode) -> Self {
+        self.cull_mode = cull_mode;
+        self
+    }
+
+    pub fn with_depth(mut self, depth_test: DepthTest, depth_write: bool) -> Self {
+        self.depth_test = depth_test;
+        self.depth_write = depth_write;
+        self
+    }
+
+    pub fn with_blend(mut self, blend: Blend) -> Self {
+        self.blend = blend;
+        self
+    }
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Pipeline {
+            shading: Shading::Unlit,
+            cull_mode: CullMode::Back,
+            depth_test: DepthTest::Less,
+            depth_write: true,
+            blend: Blend::Opaque,
+        }
+    }
+}
@@ -0,0 +1,76 @@
+/// Ramps fading an output's edges where neighbouring projectors overlap it.
+///
+/// Weights are in light, so that the overlapping ramps of two projectors add up to one;
+/// `ProjectorOutput::gamma` turns them into signal.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct EdgeBlend {
+    /// ramp widths as fractions of the output's width or height, 0 where nothing overlaps
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+    /// 1 for a linear ramp; higher values flatten both ends, hiding small misalignments
+    pub exponent: f32,
+}
+
+impl Default for EdgeBlend {
+    fn default() -> Self {
+        EdgeBlend { left: 0., right: 0., top: 0., bottom: 0., exponent: 2. }
+    }
+}
+
+impl EdgeBlend {
+    /// Share of the light at output position (`u`, `v`), both 0 to 1.
+    pub fn weight(&self, u: f32, v: f32) -> f32 {
+        self.ramp(u, self.left) * self.ramp(1. - u, self.right) * self.ramp(v, self.top) * self.ramp(1. - v, self.bottom)
+    }
+
+    // symmetric about the middle of the ramp, so that opposite ramps add up to 1
+    fn ramp(&self, distance: f32, width: f32) -> f32 {
+        if width <= 0. || distance >= width {
+            return 1.;
+        }
+        let x = distance.max(0.) / width;
+        if x < 0.5 {
+            0.5 * (2. * x).powf(self.exponent)
+        } else {
+            1. - 0.5 * (2. * (1. - x)).powf(self.exponent)
+        }
+    }
+}
+
+/// Black-level compensation: projectors give off some light even for black, so overlaps show
+/// up as brighter bands in dark scenes. Raising the black of the rest of the output to match
+/// hides them.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct BlackLevel {
+    /// light given off for black, as a fraction of white; also how far black is raised
+    /// outside the blend ramps
+    pub level: f32,
+    /// distance over which the raise fades in, inwards from the ramps, as a fraction of the
+    /// output's width or height
+    pub feather: f32,
+}
+
+impl BlackLevel {
+    /// How much of the raise applies at output position (`u`, `v`), both 0 to 1.
+    pub fn coverage(&self, blend: &EdgeBlend, u: f32, v: f32) -> f32 {
+        if self.level <= 0. {
+            return 0.;
+        }
+        self.fade(u, blend.left) * self.fade(1. - u, blend.right) * self.fade(v, blend.top) * self.fade(1. - v, blend.bottom)
+    }
+
+    fn fade(&self, distance: f32, ramp: f32) -> f32 {
+        if ramp <= 0. {
+            return 1.;
+        }
+        if self.feather <= 0. {
+            return if distance >= ramp { 1. } else { 0. };
+        }
+        let t = ((distance - ramp) / self.feather).clamp(0., 1.);
+        t * t * (3. - 2. * t)
+    }
+}
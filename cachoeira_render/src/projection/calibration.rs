@@ -0,0 +1,748 @@
+use std::fs;
+use std::path::Path;
+
+use cgmath::{InnerSpace, Matrix3, Quaternion, Vector3};
+use ron;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use cachoeira_core::transform::Transform;
+
+use camera::Projection;
+use projection::{Error, ErrorKind, Result, ResultExt, WarpGrid};
+
+/// Projective mapping between two planes, solved from four or more point pairs; maps a flat
+/// screen seen by a projector to the frame, or the other way around.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Homography {
+    /// row-major, acting on `(x, y, 1)`
+    pub matrix: [[f64; 3]; 3],
+}
+
+impl Homography {
+    pub fn identity() -> Self {
+        Homography { matrix: [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]] }
+    }
+
+    /// Least-squares homography taking the first point of each pair to the second, by the
+    /// normalized direct linear transform. Exact for four pairs, no three of them collinear.
+    pub fn solve(pairs: &[([f64; 2], [f64; 2])]) -> Result<Self> {
+        if pairs.len() < 4 {
+            bail!(ErrorKind::Calibration(format!("a homography needs at least 4 point pairs, got {}", pairs.len())));
+        }
+        let from: Vec<[f64; 2]> = pairs.iter().map(|pair| pair.0).collect();
+        let to: Vec<[f64; 2]> = pairs.iter().map(|pair| pair.1).collect();
+        let (from_centre, from_scale) = normalization(&from).ok_or_else(|| degenerate("points must not coincide"))?;
+        let (to_centre, to_scale) = normalization(&to).ok_or_else(|| degenerate("points must not coincide"))?;
+
+        let mut normal = vec![0.; 81];
+        for (a, b) in from.iter().zip(&to) {
+            let (x, y) = ((a[0] - from_centre[0]) * from_scale, (a[1] - from_centre[1]) * from_scale);
+            let (u, v) = ((b[0] - to_centre[0]) * to_scale, (b[1] - to_centre[1]) * to_scale);
+            accumulate(&mut normal, &[-x, -y, -1., 0., 0., 0., u * x, u * y, u]);
+            accumulate(&mut normal, &[0., 0., 0., -x, -y, -1., v * x, v * y, v]);
+        }
+        let h = null_vector(normal, 9).ok_or_else(|| degenerate("points must not be collinear"))?;
+
+        // undo the normalization: H = T_to⁻¹ · H' · T_from
+        let normalized = [[h[0], h[1], h[2]], [h[3], h[4], h[5]], [h[6], h[7], h[8]]];
+        let from_transform = [
+            [from_scale, 0., -from_scale * from_centre[0]],
+            [0., from_scale, -from_scale * from_centre[1]],
+            [0., 0., 1.],
+        ];
+        let to_inverse = [[1. / to_scale, 0., to_centre[0]], [0., 1. / to_scale, to_centre[1]], [0., 0., 1.]];
+        let mut matrix = multiply(&to_inverse, &multiply(&normalized, &from_transform));
+        let scale = if matrix[2][2].abs() > 1e-12 {
+            1. / matrix[2][2]
+        } else {
+            1. / matrix.iter().flat_map(|row| row.iter()).map(|value| value * value).sum::<f64>().sqrt()
+        };
+        for value in matrix.iter_mut().flat_map(|row| row.iter_mut()) {
+            *value *= scale;
+        }
+        Ok(Homography { matrix })
+    }
+
+    pub fn apply(&self, point: [f64; 2]) -> [f64; 2] {
+        let m = &self.matrix;
+        let w = m[2][0] * point[0] + m[2][1] * point[1] + m[2][2];
+        [
+            (m[0][0] * point[0] + m[0][1] * point[1] + m[0][2]) / w,
+            (m[1][0] * point[0] + m[1][1] * point[1] + m[1][2]) / w,
+        ]
+    }
+
+    /// `None` if the homography collapses the plane.
+    pub fn inverse(&self) -> Option<Self> {
+        let m = &self.matrix;
+        let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+        let adjugate = [
+            [cofactor(1, 2, 1, 2), -cofactor(0, 2, 1, 2), cofactor(0, 1, 1, 2)],
+            [-cofactor(1, 2, 0, 2), cofactor(0, 2, 0, 2), -cofactor(0, 1, 0, 2)],
+            [cofactor(1, 2, 0, 1), -cofactor(0, 2, 0, 1), cofactor(0, 1, 0, 1)],
+        ];
+        let determinant = m[0][0] * adjugate[0][0] + m[0][1] * adjugate[1][0] + m[0][2] * adjugate[2][0];
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+        let mut matrix = adjugate;
+        for value in matrix.iter_mut().flat_map(|row| row.iter_mut()) {
+            *value /= determinant;
+        }
+        Some(Homography { matrix })
+    }
+
+    /// Warp grid for a homography from output positions to frame positions, both 0 to 1; use
+    /// the `inverse` of one solved the other way. A flat screen needs few points, but bicubic
+    /// interpolation only approximates strong keystone between them.
+    pub fn warp_grid(&self, columns: usize, rows: usize) -> WarpGrid {
+        let mut grid = WarpGrid::identity(columns, rows);
+        for point in &mut grid.points {
+            let mapped = self.apply([point[0] as f64, point[1] as f64]);
+            *point = [mapped[0] as f32, mapped[1] as f32];
+        }
+        grid
+    }
+
+    /// Rejects non-finite values and homographies that collapse the plane.
+    pub fn validate(&self) -> Result<()> {
+        if !self.matrix.iter().flat_map(|row| row.iter()).all(|value| value.is_finite()) {
+            bail!(ErrorKind::Invalid("homography has non-finite values".to_owned()));
+        }
+        if self.inverse().is_none() {
+            bail!(ErrorKind::Invalid("homography is singular".to_owned()));
+        }
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        load(path.as_ref(), Homography::validate)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        save(self, path.as_ref())
+    }
+}
+
+/// A projector's lens and placement, solved from six or more world points and the projector
+/// pixels that light them up.
+///
+/// Pixels are measured from the top-left corner of the projector's image, x to the right and
+/// y down.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ProjectorCalibration {
+    pub width: u32,
+    pub height: u32,
+    /// focal length in pixels, horizontally and vertically
+    pub focal_length: [f64; 2],
+    /// pixel the optical axis passes through; lens shift moves it away from the centre
+    pub principal_point: [f64; 2],
+    /// lens centre in world space
+    pub position: [f64; 3],
+    /// world-to-projector rotation, rows along the image's x and y and the throw direction
+    pub rotation: [[f64; 3]; 3],
+    /// root mean square distance between the given and the reprojected pixels
+    pub error: f64,
+}
+
+impl ProjectorCalibration {
+    /// Solves for the projector by the normalized direct linear transform, then refines focal
+    /// length, principal point and pose by Levenberg-Marquardt on the reprojection error.
+    ///
+    /// The world points must not all lie on one plane; use a `Homography` for flat screens.
+    pub fn solve(points: &[([f64; 3], [f64; 2])], width: u32, height: u32) -> Result<Self> {
+        if points.len() < 6 {
+            bail!(ErrorKind::Calibration(format!("a projector needs at least 6 point pairs, got {}", points.len())));
+        }
+        let world: Vec<[f64; 3]> = points.iter().map(|pair| pair.0).collect();
+        let image: Vec<[f64; 2]> = points.iter().map(|pair| pair.1).collect();
+        let (world_centre, world_scale) = normalization(&world).ok_or_else(|| degenerate("world points must not coincide"))?;
+        let (image_centre, image_scale) = normalization(&image).ok_or_else(|| degenerate("pixels must not coincide"))?;
+
+        let mut normal = vec![0.; 144];
+        for (a, b) in world.iter().zip(&image) {
+            let x = [
+                (a[0] - world_centre[0]) * world_scale,
+                (a[1] - world_centre[1]) * world_scale,
+                (a[2] - world_centre[2]) * world_scale,
+                1.,
+            ];
+            let (u, v) = ((b[0] - image_centre[0]) * image_scale, (b[1] - image_centre[1]) * image_scale);
+            accumulate(&mut normal, &[x[0], x[1], x[2], 1., 0., 0., 0., 0., -u * x[0], -u * x[1], -u * x[2], -u]);
+            accumulate(&mut normal, &[0., 0., 0., 0., x[0], x[1], x[2], 1., -v * x[0], -v * x[1], -v * x[2], -v]);
+        }
+        let p = null_vector(normal, 12).ok_or_else(|| degenerate("world points must not lie on one plane"))?;
+
+        // undo the normalization: P = T_image⁻¹ · P' · T_world
+        let mut matrix = [[0.; 4]; 3];
+        for row in 0..3 {
+            let normalized = &p[row * 4..row * 4 + 4];
+            for column in 0..3 {
+                matrix[row][column] = normalized[column] * world_scale;
+            }
+            matrix[row][3] = normalized[3] - (0..3).map(|column| matrix[row][column] * world_centre[column]).sum::<f64>();
+        }
+        let (image_rows, last) = matrix.split_at_mut(2);
+        for (row, values) in image_rows.iter_mut().enumerate() {
+            for (value, w) in values.iter_mut().zip(&last[0]) {
+                *value = *value / image_scale + image_centre[row] * w;
+            }
+        }
+
+        let mut model = Model::decompose(matrix).ok_or_else(|| degenerate("no projector fits the points"))?;
+        if world.iter().filter(|point| model.depth(point) <= 0.).count() * 2 > world.len() {
+            bail!(ErrorKind::Calibration("the points end up behind the projector; is the world left-handed?".to_owned()));
+        }
+        model.refine(&world, &image);
+
+        let error = (model.cost(&world, &image) / points.len() as f64).sqrt();
+        let r = &model.rotation;
+        let t = &model.translation;
+        let position = [
+            -(r[0][0] * t[0] + r[1][0] * t[1] + r[2][0] * t[2]),
+            -(r[0][1] * t[0] + r[1][1] * t[1] + r[2][1] * t[2]),
+            -(r[0][2] * t[0] + r[1][2] * t[1] + r[2][2] * t[2]),
+        ];
+        let calibration = ProjectorCalibration {
+            width,
+            height,
+            focal_length: [model.intrinsics[0], model.intrinsics[1]],
+            principal_point: [model.intrinsics[2], model.intrinsics[3]],
+            position,
+            rotation: model.rotation,
+            error,
+        };
+        calibration.validate()?;
+        Ok(calibration)
+    }
+
+    /// Pixel lit for a world point, `None` for points behind the projector.
+    pub fn project(&self, point: [f64; 3]) -> Option<[f64; 2]> {
+        let relative = [point[0] - self.position[0], point[1] - self.position[1], point[2] - self.position[2]];
+        let r = &self.rotation;
+        let camera = [dot(&r[0], &relative), dot(&r[1], &relative), dot(&r[2], &relative)];
+        if camera[2] <= 0. {
+            return None;
+        }
+        Some([
+            self.focal_length[0] * camera[0] / camera[2] + self.principal_point[0],
+            self.focal_length[1] * camera[1] / camera[2] + self.principal_point[1],
+        ])
+    }
+
+    /// Off-axis frustum covering the projector's image, for a camera placed by `transform`.
+    pub fn projection(&self, near: f32, far: f32) -> Projection {
+        let near64 = near as f64;
+        let (fx, fy) = (self.focal_length[0], self.focal_length[1]);
+        let (cx, cy) = (self.principal_point[0], self.principal_point[1]);
+        Projection::off_axis(
+            (-cx / fx * near64) as f32,
+            ((self.width as f64 - cx) / fx * near64) as f32,
+            (-(self.height as f64 - cy) / fy * near64) as f32,
+            (cy / fy * near64) as f32,
+            near,
+            far,
+        )
+    }
+
+    /// Places a camera at the projector's lens, looking down -Z along the throw direction with
+    /// +Y up the image.
+    pub fn transform(&self) -> Transform {
+        let r = &self.rotation;
+        let right = Vector3::new(r[0][0], r[0][1], r[0][2]).cast::<f32>().unwrap();
+        let up = -Vector3::new(r[1][0], r[1][1], r[1][2]).cast::<f32>().unwrap();
+        let back = -Vector3::new(r[2][0], r[2][1], r[2][2]).cast::<f32>().unwrap();
+        Transform {
+            translation: Vector3::new(self.position[0] as f32, self.position[1] as f32, self.position[2] as f32),
+            rotation: Quaternion::from(Matrix3::from_cols(right, up, back)).normalize(),
+            ..Transform::default()
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.width == 0 || self.height == 0 {
+            bail!(ErrorKind::Invalid("projector calibration size must not be 0".to_owned()));
+        }
+        if !(self.focal_length[0] > 0. && self.focal_length[1] > 0.) {
+            bail!(ErrorKind::Invalid(format!("focal length {:?} must be positive", self.focal_length)));
+        }
+        let finite = self.principal_point.iter()
+            .chain(&self.position)
+            .chain(self.rotation.iter().flat_map(|row| row.iter()))
+            .all(|value| value.is_finite());
+        if !finite {
+            bail!(ErrorKind::Invalid("projector calibration has non-finite values".to_owned()));
+        }
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        load(path.as_ref(), ProjectorCalibration::validate)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        save(self, path.as_ref())
+    }
+}
+
+fn load<T: DeserializeOwned>(path: &Path, validate: fn(&T) -> Result<()>) -> Result<T> {
+    fs::read_to_string(path)
+        .map_err(Error::from)
+        .and_then(|source| {
+            let value: T = ron::de::from_str(&source)?;
+            validate(&value)?;
+            Ok(value)
+        })
+        .chain_err(|| ErrorKind::File(path.display().to_string()))
+}
+
+fn save<T: Serialize>(value: &T, path: &Path) -> Result<()> {
+    ron::ser::to_string_pretty(value, Default::default())
+        .map_err(Error::from)
+        .and_then(|source| fs::write(path, source).map_err(Error::from))
+        .chain_err(|| ErrorKind::File(path.display().to_string()))
+}
+
+fn degenerate(message: &str) -> Error {
+    ErrorKind::Calibration(message.to_owned()).into()
+}
+
+// pinhole projector in computer vision convention: x right, y down, looking down +Z
+#[derive(Clone, Copy, Debug)]
+struct Model {
+    // fx, fy, cx, cy
+    intrinsics: [f64; 4],
+    rotation: [[f64; 3]; 3],
+    translation: [f64; 3],
+}
+
+impl Model {
+    // splits P = K · [R | t]; skew is dropped, as an off-axis frustum can't show it
+    fn decompose(mut matrix: [[f64; 4]; 3]) -> Option<Model> {
+        let m = |matrix: &[[f64; 4]; 3], row: usize| [matrix[row][0], matrix[row][1], matrix[row][2]];
+        let (m0, m1, m2) = (m(&matrix, 0), m(&matrix, 1), m(&matrix, 2));
+        let determinant = dot(&m0, &cross(&m1, &m2));
+        let scale = determinant.signum() / dot(&m2, &m2).sqrt();
+        if !scale.is_finite() || determinant == 0. {
+            return None;
+        }
+        for value in matrix.iter_mut().flat_map(|row| row.iter_mut()) {
+            *value *= scale;
+        }
+        let (m0, m1, r2) = (m(&matrix, 0), m(&matrix, 1), m(&matrix, 2));
+
+        let (cx, cy) = (dot(&m0, &r2), dot(&m1, &r2));
+        let q1 = [m1[0] - cy * r2[0], m1[1] - cy * r2[1], m1[2] - cy * r2[2]];
+        let fy = dot(&q1, &q1).sqrt();
+        let r1 = [q1[0] / fy, q1[1] / fy, q1[2] / fy];
+        let skew = dot(&m0, &r1);
+        let q0 = [
+            m0[0] - skew * r1[0] - cx * r2[0],
+            m0[1] - skew * r1[1] - cx * r2[1],
+            m0[2] - skew * r1[2] - cx * r2[2],
+        ];
+        let fx = dot(&q0, &q0).sqrt();
+        let r0 = [q0[0] / fx, q0[1] / fx, q0[2] / fx];
+
+        let tz = matrix[2][3];
+        let ty = (matrix[1][3] - cy * tz) / fy;
+        let tx = (matrix[0][3] - skew * ty - cx * tz) / fx;
+        if !(fx > 0. && fy > 0.) {
+            return None;
+        }
+        Some(Model { intrinsics: [fx, fy, cx, cy], rotation: [r0, r1, r2], translation: [tx, ty, tz] })
+    }
+
+    fn depth(&self, point: &[f64; 3]) -> f64 {
+        dot(&self.rotation[2], point) + self.translation[2]
+    }
+
+    fn residuals(&self, world: &[[f64; 3]], image: &[[f64; 2]], residuals: &mut Vec<f64>) {
+        residuals.clear();
+        let (fx, fy, cx, cy) = (self.intrinsics[0], self.intrinsics[1], self.intrinsics[2], self.intrinsics[3]);
+        for (point, pixel) in world.iter().zip(image) {
+            let r = &self.rotation;
+            let x = dot(&r[0], point) + self.translation[0];
+            let y = dot(&r[1], point) + self.translation[1];
+            let z = dot(&r[2], point) + self.translation[2];
+            residuals.push(fx * x / z + cx - pixel[0]);
+            residuals.push(fy * y / z + cy - pixel[1]);
+        }
+    }
+
+    fn cost(&self, world: &[[f64; 3]], image: &[[f64; 2]]) -> f64 {
+        let mut residuals = Vec::with_capacity(world.len() * 2);
+        self.residuals(world, image, &mut residuals);
+        residuals.iter().map(|residual| residual * residual).sum()
+    }
+
+    // parameters: the 4 intrinsics, a small rotation applied in front, and the translation
+    fn step(&self, delta: &[f64]) -> Model {
+        let mut model = *self;
+        for (intrinsic, change) in model.intrinsics.iter_mut().zip(delta) {
+            *intrinsic += change;
+        }
+        model.rotation = multiply(&rotation(&[delta[4], delta[5], delta[6]]), &self.rotation);
+        for (translation, change) in model.translation.iter_mut().zip(&delta[7..]) {
+            *translation += change;
+        }
+        model
+    }
+
+    fn refine(&mut self, world: &[[f64; 3]], image: &[[f64; 2]]) {
+        const PARAMETERS: usize = 10;
+        let mut cost = self.cost(world, image);
+        let mut damping = 1e-3;
+        let (mut plus, mut minus) = (Vec::new(), Vec::new());
+        let mut jacobian = vec![0.; world.len() * 2 * PARAMETERS];
+        let mut residuals = Vec::new();
+
+        for _ in 0..100 {
+            if cost < 1e-18 {
+                break;
+            }
+            // central differences, residual-major
+            let scale = self.translation.iter().fold(1., |scale: f64, value| scale.max(value.abs()));
+            for parameter in 0..PARAMETERS {
+                let h = match parameter {
+                    0..=3 => 1e-6 * self.intrinsics[parameter].abs().max(1.),
+                    4..=6 => 1e-7,
+                    _ => 1e-7 * scale,
+                };
+                let mut delta = [0.; PARAMETERS];
+                delta[parameter] = h;
+                self.step(&delta).residuals(world, image, &mut plus);
+                delta[parameter] = -h;
+                self.step(&delta).residuals(world, image, &mut minus);
+                for (row, (a, b)) in plus.iter().zip(&minus).enumerate() {
+                    jacobian[row * PARAMETERS + parameter] = (a - b) / (2. * h);
+                }
+            }
+            self.residuals(world, image, &mut residuals);
+
+            let mut normal = vec![0.; PARAMETERS * PARAMETERS];
+            let mut gradient = vec![0.; PARAMETERS];
+            for (row, residual) in residuals.iter().enumerate() {
+                let derivatives = &jacobian[row * PARAMETERS..(row + 1) * PARAMETERS];
+                accumulate(&mut normal, derivatives);
+                for (gradient, derivative) in gradient.iter_mut().zip(derivatives) {
+                    *gradient -= derivative * residual;
+                }
+            }
+
+            let mut improved = false;
+            while damping < 1e10 {
+                let mut damped = normal.clone();
+                for i in 0..PARAMETERS {
+                    damped[i * PARAMETERS + i] *= 1. + damping;
+                }
+                let candidate = match solve(damped, gradient.clone(), PARAMETERS) {
+                    Some(delta) => self.step(&delta),
+                    None => break,
+                };
+                let candidate_cost = candidate.cost(world, image);
+                if candidate_cost < cost {
+                    improved = cost - candidate_cost > cost * 1e-12;
+                    *self = candidate;
+                    cost = candidate_cost;
+                    damping = (damping * 0.1).max(1e-12);
+                    break;
+                }
+                damping *= 10.;
+            }
+            if !improved {
+                break;
+            }
+        }
+    }
+}
+
+// centroid and the scale bringing the mean distance from it to √dimensions
+fn normalization<T: AsRef<[f64]>>(points: &[T]) -> Option<(Vec<f64>, f64)> {
+    let dimensions = points[0].as_ref().len();
+    let mut centre = vec![0.; dimensions];
+    for point in points {
+        for (sum, value) in centre.iter_mut().zip(point.as_ref()) {
+            *sum += value / points.len() as f64;
+        }
+    }
+    let distance = points.iter()
+        .map(|point| point.as_ref().iter().zip(&centre).map(|(a, b)| (a - b) * (a - b)).sum::<f64>().sqrt())
+        .sum::<f64>() / points.len() as f64;
+    if distance > 1e-12 {
+        Some((centre, (dimensions as f64).sqrt() / distance))
+    } else {
+        None
+    }
+}
+
+// adds rowᵀ · row to a square matrix
+fn accumulate(matrix: &mut [f64], row: &[f64]) {
+    let n = row.len();
+    for i in 0..n {
+        for j in 0..n {
+            matrix[i * n + j] += row[i] * row[j];
+        }
+    }
+}
+
+// unit eigenvector of the smallest eigenvalue of a symmetric matrix, by cyclic Jacobi
+// rotations; None when the two smallest eigenvalues are both about zero
+fn null_vector(mut a: Vec<f64>, n: usize) -> Option<Vec<f64>> {
+    let mut vectors = vec![0.; n * n];
+    for i in 0..n {
+        vectors[i * n + i] = 1.;
+    }
+    for _ in 0..100 {
+        let off_diagonal: f64 = (0..n).flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j)))
+            .map(|(i, j)| a[i * n + j] * a[i * n + j])
+            .sum();
+        let diagonal: f64 = (0..n).map(|i| a[i * n + i] * a[i * n + i]).sum();
+        if off_diagonal <= diagonal * 1e-30 {
+            break;
+        }
+        for p in 0..n {
+            for q in p + 1..n {
+                let apq = a[p * n + q];
+                if apq == 0. {
+                    continue;
+                }
+                let theta = (a[q * n + q] - a[p * n + p]) / (2. * apq);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.).sqrt());
+                let c = 1. / (t * t + 1.).sqrt();
+                let s = t * c;
+                for k in 0..n {
+                    let (akp, akq) = (a[k * n + p], a[k * n + q]);
+                    a[k * n + p] = c * akp - s * akq;
+                    a[k * n + q] = s * akp + c * akq;
+                }
+                for k in 0..n {
+                    let (apk, aqk) = (a[p * n + k], a[q * n + k]);
+                    a[p * n + k] = c * apk - s * aqk;
+                    a[q * n + k] = s * apk + c * aqk;
+                }
+                for k in 0..n {
+                    let (vkp, vkq) = (vectors[k * n + p], vectors[k * n + q]);
+                    vectors[k * n + p] = c * vkp - s * vkq;
+                    vectors[k * n + q] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&i, &j| a[i * n + i].partial_cmp(&a[j * n + j]).unwrap_or(::std::cmp::Ordering::Equal));
+    let largest = a[order[n - 1] * n + order[n - 1]];
+    let second = a[order[1] * n + order[1]];
+    if second <= largest * 1e-9 || second.is_nan() {
+        return None;
+    }
+    Some((0..n).map(|k| vectors[k * n + order[0]]).collect())
+}
+
+// Gaussian elimination with partial pivoting
+fn solve(mut a: Vec<f64>, mut b: Vec<f64>, n: usize) -> Option<Vec<f64>> {
+    for column in 0..n {
+        let pivot = (column..n).max_by(|&i, &j| {
+            a[i * n + column].abs().partial_cmp(&a[j * n + column].abs()).unwrap_or(::std::cmp::Ordering::Equal)
+        })?;
+        let magnitude = a[pivot * n + column].abs();
+        if magnitude <= 1e-300 || magnitude.is_nan() {
+            return None;
+        }
+        for k in 0..n {
+            a.swap(column * n + k, pivot * n + k);
+        }
+        b.swap(column, pivot);
+        for row in column + 1..n {
+            let factor = a[row * n + column] / a[column * n + column];
+            for k in column..n {
+                a[row * n + k] -= factor * a[column * n + k];
+            }
+            b[row] -= factor * b[column];
+        }
+    }
+    let mut x = vec![0.; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| a[row * n + k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row * n + row];
+    }
+    Some(x)
+}
+
+// Rodrigues' formula
+fn rotation(axis_angle: &[f64; 3]) -> [[f64; 3]; 3] {
+    let angle = dot(axis_angle, axis_angle).sqrt();
+    if angle < 1e-15 {
+        return [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]];
+    }
+    let (x, y, z) = (axis_angle[0] / angle, axis_angle[1] / angle, axis_angle[2] / angle);
+    let (sin, cos) = angle.sin_cos();
+    let c = 1. - cos;
+    [
+        [cos + x * x * c, x * y * c - z * sin, x * z * c + y * sin],
+        [y * x * c + z * sin, cos + y * y * c, y * z * c - x * sin],
+        [z * x * c - y * sin, z * y * c + x * sin, cos + z * z * c],
+    ]
+}
+
+fn multiply(a: &[[f64; 3]; 3], b: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let mut product = [[0.; 3]; 3];
+    for row in 0..3 {
+        for column in 0..3 {
+            product[row][column] = (0..3).map(|k| a[row][k] * b[k][column]).sum();
+        }
+    }
+    product
+}
+
+fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: &[f64; 3], b: &[f64; 3]) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use cgmath::{Point3, Vector2};
+
+    use camera::Camera;
+    use projection::{Homography, ProjectorCalibration};
+
+    fn keystone() -> Homography {
+        Homography { matrix: [[1.2, 0.1, 30.], [-0.05, 0.9, 12.], [0.0004, -0.0002, 1.]] }
+    }
+
+    // looking at (0, 1, 0) from above and in front, image y down
+    fn projector() -> ProjectorCalibration {
+        let forward = [-0.4, -0.5, -5.];
+        let length = (0.16f64 + 0.25 + 25.).sqrt();
+        let f = [forward[0] / length, forward[1] / length, forward[2] / length];
+        let right_length = (f[0] * f[0] + f[2] * f[2]).sqrt();
+        let r = [-f[2] / right_length, 0., f[0] / right_length];
+        let down = [f[1] * r[2] - f[2] * r[1], f[2] * r[0] - f[0] * r[2], f[0] * r[1] - f[1] * r[0]];
+        ProjectorCalibration {
+            width: 1920,
+            height: 1080,
+            focal_length: [1800., 1790.],
+            principal_point: [900., 1000.],
+            position: [0.4, 1.5, 5.],
+            rotation: [r, down, f],
+            error: 0.,
+        }
+    }
+
+    fn lit_points(projector: &ProjectorCalibration) -> Vec<([f64; 3], [f64; 2])> {
+        let mut points = Vec::new();
+        for &x in &[-1., 0., 1.] {
+            for &y in &[0., 1., 2.] {
+                for &z in &[-1., 1.] {
+                    points.push(([x, y, z], projector.project([x, y, z]).unwrap()));
+                }
+            }
+        }
+        points
+    }
+
+    fn temp_file(name: &str) -> PathBuf {
+        let directory = ::std::env::temp_dir().join("cachoeira_calibration_test");
+        fs::create_dir_all(&directory).unwrap();
+        directory.join(name)
+    }
+
+    #[test]
+    fn solves_homography() {
+        let truth = keystone();
+        let from = [[0., 0.], [640., 0.], [640., 480.], [0., 480.], [320., 200.]];
+        let pairs: Vec<_> = from.iter().map(|&point| (point, truth.apply(point))).collect();
+        let solved = Homography::solve(&pairs).unwrap();
+        for (a, b) in solved.matrix.iter().flat_map(|row| row.iter()).zip(truth.matrix.iter().flat_map(|row| row.iter())) {
+            assert_relative_eq!(a, b, epsilon = 1e-9);
+        }
+        let back = solved.inverse().unwrap().apply(pairs[4].1);
+        assert_relative_eq!(back[0], 320., epsilon = 1e-6);
+        assert_relative_eq!(back[1], 200., epsilon = 1e-6);
+    }
+
+    #[test]
+    fn rejects_collinear_points() {
+        let collinear: Vec<_> = (0..4).map(|i| ([i as f64, i as f64], [i as f64, 0.])).collect();
+        assert!(Homography::solve(&collinear).is_err());
+    }
+
+    #[test]
+    fn solves_projector() {
+        let truth = projector();
+        let solved = ProjectorCalibration::solve(&lit_points(&truth), 1920, 1080).unwrap();
+        assert!(solved.error < 1e-6);
+        for i in 0..2 {
+            assert_relative_eq!(solved.focal_length[i], truth.focal_length[i], epsilon = 1e-4);
+            assert_relative_eq!(solved.principal_point[i], truth.principal_point[i], epsilon = 1e-4);
+        }
+        for i in 0..3 {
+            assert_relative_eq!(solved.position[i], truth.position[i], epsilon = 1e-7);
+        }
+    }
+
+    #[test]
+    fn engine_camera_lights_the_same_pixels() {
+        let points = lit_points(&projector());
+        let solved = ProjectorCalibration::solve(&points, 1920, 1080).unwrap();
+        let mut camera = Camera::new(solved.projection(0.1, 100.));
+        camera.update(solved.transform().view_matrix());
+        let screen = camera.world_to_screen(Point3::new(1., 2., 1.), Vector2::new(1920., 1080.)).unwrap();
+        assert_relative_eq!(screen.x as f64, points[17].1[0], epsilon = 0.05);
+        assert_relative_eq!(screen.y as f64, points[17].1[1], epsilon = 0.05);
+    }
+
+    #[test]
+    fn tolerates_noisy_pixels() {
+        let noisy: Vec<_> = lit_points(&projector()).into_iter().enumerate()
+            .map(|(i, (point, pixel))| (point, [pixel[0] + (i as f64 * 1.7).sin() * 0.5, pixel[1] + (i as f64 * 2.3).cos() * 0.5]))
+            .collect();
+        let solved = ProjectorCalibration::solve(&noisy, 1920, 1080).unwrap();
+        assert!(solved.error > 0.05 && solved.error < 0.5);
+        assert_relative_eq!(solved.focal_length[0], 1800., max_relative = 0.02);
+    }
+
+    #[test]
+    fn saves_and_loads_homographies() {
+        let path = temp_file("keystone.ron");
+        keystone().save(&path).unwrap();
+        assert_eq!(Homography::load(&path).unwrap(), keystone());
+
+        let singular = Homography { matrix: [[1., 2., 0.], [2., 4., 0.], [0., 0., 1.]] };
+        singular.save(&path).unwrap();
+        assert!(Homography::load(&path).is_err());
+        fs::remove_file(&path).unwrap();
+        assert!(Homography::load(&path).is_err());
+    }
+
+    #[test]
+    fn saves_and_loads_projectors() {
+        let path = temp_file("projector.ron");
+        projector().save(&path).unwrap();
+        assert_eq!(ProjectorCalibration::load(&path).unwrap(), projector());
+
+        let mut flat = projector();
+        flat.focal_length[1] = 0.;
+        flat.save(&path).unwrap();
+        let error = ProjectorCalibration::load(&path).unwrap_err();
+        let cause = error.iter().last().unwrap().to_string();
+        assert!(cause.contains("focal length"), "{}", cause);
+
+        let mut empty = projector();
+        empty.width = 0;
+        empty.save(&path).unwrap();
+        assert!(ProjectorCalibration::load(&path).is_err());
+
+        fs::write(&path, "(width: 1920)").unwrap();
+        assert!(ProjectorCalibration::load(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
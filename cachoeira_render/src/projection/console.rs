@@ -0,0 +1,202 @@
+use std::cell::RefCell;
+use std::sync::{Arc, RwLock};
+
+use cachoeira_core::console::{ConsoleContext, ConsoleVar, ConsoleVarResult};
+
+use projection::{ProjectionSetup, ProjectorOutput, WarpInterpolation};
+
+#[derive(Clone, Copy, Debug)]
+enum Setting {
+    BlendLeft,
+    BlendRight,
+    BlendTop,
+    BlendBottom,
+    BlendExponent,
+    Gamma,
+    BlackLevel,
+    BlackFeather,
+    Interpolation,
+    Point(usize, usize),
+}
+
+impl Setting {
+    fn name(&self) -> String {
+        match *self {
+            Setting::BlendLeft => "blend.left".to_owned(),
+            Setting::BlendRight => "blend.right".to_owned(),
+            Setting::BlendTop => "blend.top".to_owned(),
+            Setting::BlendBottom => "blend.bottom".to_owned(),
+            Setting::BlendExponent => "blend.exponent".to_owned(),
+            Setting::Gamma => "gamma".to_owned(),
+            Setting::BlackLevel => "black_level".to_owned(),
+            Setting::BlackFeather => "black_feather".to_owned(),
+            Setting::Interpolation => "warp.interpolation".to_owned(),
+            Setting::Point(column, row) => format!("warp.{}.{}", column, row),
+        }
+    }
+
+    fn get(&self, output: &ProjectorOutput) -> String {
+        match *self {
+            Setting::BlendLeft => output.blend.left.to_string(),
+            Setting::BlendRight => output.blend.right.to_string(),
+            Setting::BlendTop => output.blend.top.to_string(),
+            Setting::BlendBottom => output.blend.bottom.to_string(),
+            Setting::BlendExponent => output.blend.exponent.to_string(),
+            Setting::Gamma => output.gamma.to_string(),
+            Setting::BlackLevel => output.black_level.level.to_string(),
+            Setting::BlackFeather => output.black_level.feather.to_string(),
+            Setting::Interpolation => match output.warp.interpolation {
+                WarpInterpolation::Bilinear => "bilinear".to_owned(),
+                WarpInterpolation::Bicubic => "bicubic".to_owned(),
+            },
+            Setting::Point(column, row) => {
+                let point = output.warp.point(column, row);
+                format!("{} {}", point[0], point[1])
+            }
+        }
+    }
+
+    fn set(&self, output: &mut ProjectorOutput, value: &str) -> Result<(), String> {
+        let number = || -> Result<f32, String> {
+            match value.trim().parse::<f32>() {
+                Ok(number) if number >= 0. && number.is_finite() => Ok(number),
+                _ => Err(format!("Expected a non-negative number, got '{}'", value)),
+            }
+        };
+        match *self {
+            Setting::BlendLeft => output.blend.left = number()?,
+            Setting::BlendRight => output.blend.right = number()?,
+            Setting::BlendTop => output.blend.top = number()?,
+            Setting::BlendBottom => output.blend.bottom = number()?,
+            Setting::BlendExponent => output.blend.exponent = number()?,
+            Setting::Gamma => match number()? {
+                gamma if gamma > 0. => output.gamma = gamma,
+                _ => return Err("Gamma must be positive".to_owned()),
+            },
+            Setting::BlackLevel => output.black_level.level = number()?.min(1.),
+            Setting::BlackFeather => output.black_level.feather = number()?,
+            Setting::Interpolation => output.warp.interpolation = match value.trim() {
+                "bilinear" => WarpInterpolation::Bilinear,
+                "bicubic" => WarpInterpolation::Bicubic,
+                _ => return Err(format!("Expected bilinear or bicubic, got '{}'", value)),
+            },
+            Setting::Point(column, row) => {
+                let coordinates = value.split_whitespace().map(str::parse).collect::<Result<Vec<f32>, _>>();
+                match coordinates {
+                    Ok(ref point) if point.len() == 2 => output.warp.set_point(column, row, [point[0], point[1]]),
+                    _ => return Err(format!("Expected two coordinates, got '{}'", value)),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct ProjectionVar {
+    setup: Arc<RwLock<ProjectionSetup>>,
+    output: String,
+    setting: Setting,
+}
+
+impl ConsoleVar for ProjectionVar {
+    fn set(&mut self, value: String) -> ConsoleVarResult {
+        let mut setup = self.setup.write().map_err(|_| "Projection setup lock poisoned".to_owned())?;
+        let output = setup.outputs.iter_mut()
+            .find(|output| output.name == self.output)
+            .ok_or_else(|| format!("Projector output '{}' no longer exists", self.output))?;
+        if let Setting::Point(column, row) = self.setting {
+            if column >= output.warp.columns || row >= output.warp.rows {
+                return Err(format!("Warp grid of '{}' no longer has point {}, {}", self.output, column, row));
+            }
+        }
+        self.setting.set(output, &value)?;
+        Ok(None)
+    }
+
+    fn get(&self) -> String {
+        let setup = match self.setup.read() {
+            Ok(setup) => setup,
+            Err(_) => return String::new(),
+        };
+        match setup.output(&self.output) {
+            Some(output) => match self.setting {
+                Setting::Point(column, row) if column >= output.warp.columns || row >= output.warp.rows => String::new(),
+                setting => setting.get(output),
+            },
+            None => String::new(),
+        }
+    }
+}
+
+/// Adds a `projection.<output>.<setting>` console var for every setting of every output in
+/// `setup`, changing it in place: `blend.left`, `blend.right`, `blend.top`, `blend.bottom`,
+/// `blend.exponent`, `gamma`, `black_level`, `black_feather`, `warp.interpolation`
+/// (`bilinear` or `bicubic`), and `warp.<column>.<row>` for each control point, as `"x y"`.
+///
+/// Vars follow outputs by name; register again after loading a setup with other outputs or
+/// grid sizes.
+pub fn register_console_vars(setup: &Arc<RwLock<ProjectionSetup>>, console: &mut ConsoleContext) {
+    let outputs: Vec<(String, usize, usize)> = match setup.read() {
+        Ok(setup) => setup.outputs.iter().map(|output| (output.name.clone(), output.warp.columns, output.warp.rows)).collect(),
+        Err(_) => return,
+    };
+    for (name, columns, rows) in outputs {
+        let mut settings = vec![
+            Setting::BlendLeft,
+            Setting::BlendRight,
+            Setting::BlendTop,
+            Setting::BlendBottom,
+            Setting::BlendExponent,
+            Setting::Gamma,
+            Setting::BlackLevel,
+            Setting::BlackFeather,
+            Setting::Interpolation,
+        ];
+        for row in 0..rows {
+            settings.extend((0..columns).map(|column| Setting::Point(column, row)));
+        }
+        for setting in settings {
+            let var = ProjectionVar { setup: setup.clone(), output: name.clone(), setting };
+            console.add_var(&format!("projection.{}.{}", name, setting.name()), RefCell::new(Box::new(var) as Box<dyn ConsoleVar>));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+
+    use cachoeira_core::console::ConsoleContext;
+
+    use projection::{register_console_vars, ProjectionSetup, ProjectorOutput, WarpInterpolation};
+
+    fn wall() -> (Arc<RwLock<ProjectionSetup>>, ConsoleContext) {
+        let setup = Arc::new(RwLock::new(ProjectionSetup { outputs: vec![ProjectorOutput::new("wall", 8, 8)] }));
+        let mut console = ConsoleContext::default();
+        register_console_vars(&setup, &mut console);
+        (setup, console)
+    }
+
+    #[test]
+    fn edits_setup_live() {
+        let (setup, mut console) = wall();
+        assert_eq!(console.write_var("projection.wall.blend.left", "0.25"), Some(Ok(None)));
+        assert_eq!(console.write_var("projection.wall.warp.1.0", "0.9 0.1"), Some(Ok(None)));
+        assert_eq!(console.write_var("projection.wall.warp.interpolation", "bilinear"), Some(Ok(None)));
+        assert_eq!(console.query_var("projection.wall.warp.1.0"), Some("0.9 0.1".to_owned()));
+
+        let setup = setup.read().unwrap();
+        assert_eq!(setup.outputs[0].blend.left, 0.25);
+        assert_eq!(setup.outputs[0].warp.point(1, 0), [0.9, 0.1]);
+        assert_eq!(setup.outputs[0].warp.interpolation, WarpInterpolation::Bilinear);
+    }
+
+    #[test]
+    fn rejects_bad_values_and_unknown_outputs() {
+        let (setup, mut console) = wall();
+        assert!(console.write_var("projection.wall.gamma", "-1").unwrap().is_err());
+        assert!(console.write_var("projection.wall.warp.interpolation", "nearest").unwrap().is_err());
+        assert_eq!(console.write_var("projection.floor.gamma", "2"), None);
+        assert_eq!(setup.read().unwrap().outputs[0], ProjectorOutput::new("wall", 8, 8));
+    }
+}
@@ -0,0 +1,399 @@
+//! Output stage for installations with several overlapping projectors on curved surfaces.
+//!
+//! Each `ProjectorOutput` picks its part of the rendered frame through a `WarpGrid`, fades its
+//! edges into its neighbours with an `EdgeBlend`, and raises its black level to match the
+//! overlaps. A `ProjectionSetup` holds all outputs and is read from RON files:
+//!
+//! ```ron
+//! (
+//!     outputs: [
+//!         (
+//!             name: "left",
+//!             width: 1920,
+//!             height: 1080,
+//!             warp: (columns: 2, rows: 2, points: [(0.0, 0.0), (0.55, 0.0), (0.0, 1.0), (0.55, 1.0)]),
+//!             blend: (right: 0.18),
+//!             black_level: (level: 0.02, feather: 0.05),
+//!         ),
+//!     ],
+//! )
+//! ```
+//!
+//! `WarpBlend` renders the outputs on the CPU, and `register_console_vars` makes every setting
+//! editable while running.
+//!
+//! Rather than lining outputs up by hand, measure where known points land: a `Homography`
+//! from four or more corners of a flat screen gives its warp grid, and a `ProjectorCalibration`
+//! from six or more surveyed 3D points gives the projector's lens and pose, for rendering from
+//! its point of view.
+
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+
+use rayon::prelude::*;
+use ron;
+
+use graph::{PassId, RenderGraph, ResourceId};
+use software::{Framebuffer, SoftwareBackend};
+
+pub use self::blend::{BlackLevel, EdgeBlend};
+pub use self::calibration::{Homography, ProjectorCalibration};
+pub use self::console::register_console_vars;
+pub use self::warp::{WarpGrid, WarpInterpolation};
+
+mod blend;
+mod calibration;
+mod console;
+mod warp;
+
+pub use self::errors::*;
+
+// error_chain 0.11 still implements the deprecated `Error::description` and `Error::cause`
+#[allow(deprecated)]
+mod errors {
+    error_chain! {
+        foreign_links {
+            Io(::std::io::Error);
+            Deserialize(::ron::de::Error);
+            Serialize(::ron::ser::Error);
+        }
+
+        errors {
+            File(path: String) {
+                description("projection setup file error")
+                display("In projection setup file '{}'", path)
+            }
+            Invalid(message: String) {
+                description("invalid projection setup")
+                display("Invalid projection setup: {}", message)
+            }
+            Calibration(message: String) {
+                description("calibration failed")
+                display("Calibration failed: {}", message)
+            }
+        }
+    }
+}
+
+/// One projector: its resolution, which part of the frame it shows, and how it blends in.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ProjectorOutput {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub warp: WarpGrid,
+    #[serde(default)]
+    pub blend: EdgeBlend,
+    #[serde(default)]
+    pub black_level: BlackLevel,
+    /// the projector's response from signal to light, used to keep ramps and black level
+    /// linear in light
+    #[serde(default = "default_gamma")]
+    pub gamma: f32,
+}
+
+fn default_gamma() -> f32 { 2.2 }
+
+impl ProjectorOutput {
+    /// Shows the whole frame, without blending.
+    pub fn new(name: &str, width: u32, height: u32) -> Self {
+        ProjectorOutput {
+            name: name.to_owned(),
+            width,
+            height,
+            warp: WarpGrid::identity(2, 2),
+            blend: EdgeBlend::default(),
+            black_level: BlackLevel::default(),
+            gamma: default_gamma(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| Err(ErrorKind::Invalid(format!("output '{}': {}", self.name, message)).into());
+        if self.width == 0 || self.height == 0 {
+            return invalid("size must not be 0".to_owned());
+        }
+        if self.gamma <= 0. || self.gamma.is_nan() {
+            return invalid(format!("gamma {} must be positive", self.gamma));
+        }
+        self.warp.validate().or_else(invalid)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct ProjectionSetup {
+    pub outputs: Vec<ProjectorOutput>,
+}
+
+impl ProjectionSetup {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        fs::read_to_string(path)
+            .map_err(Error::from)
+            .and_then(|source| ProjectionSetup::parse(&source))
+            .chain_err(|| ErrorKind::File(path.display().to_string()))
+    }
+
+    pub fn parse(source: &str) -> Result<Self> {
+        let setup: ProjectionSetup = ron::de::from_str(source)?;
+        setup.validate()?;
+        Ok(setup)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        self.to_ron()
+            .and_then(|source| fs::write(path, source).map_err(Error::from))
+            .chain_err(|| ErrorKind::File(path.display().to_string()))
+    }
+
+    pub fn to_ron(&self) -> Result<String> {
+        Ok(ron::ser::to_string_pretty(self, Default::default())?)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for output in &self.outputs {
+            output.validate()?;
+        }
+        Ok(())
+    }
+
+    pub fn output(&self, name: &str) -> Option<&ProjectorOutput> {
+        self.outputs.iter().find(|output| output.name == name)
+    }
+}
+
+// what an output pixel shows, worked out once per change of its settings
+#[derive(Clone, Copy, Debug)]
+struct MapTexel {
+    source: [f32; 2],
+    scale: f32,
+    lift: f32,
+}
+
+struct Baked {
+    output: ProjectorOutput,
+    texels: Vec<MapTexel>,
+}
+
+/// Renders the outputs of a shared `ProjectionSetup` from one frame, in parallel.
+///
+/// Per-pixel warp and blend values are cached, and recomputed when an output's settings
+/// change.
+pub struct WarpBlend {
+    setup: Arc<RwLock<ProjectionSetup>>,
+    baked: Vec<Option<Baked>>,
+}
+
+impl WarpBlend {
+    pub fn new(setup: Arc<RwLock<ProjectionSetup>>) -> Self {
+        WarpBlend { setup, baked: Vec::new() }
+    }
+
+    pub fn setup(&self) -> &Arc<RwLock<ProjectionSetup>> { &self.setup }
+
+    /// Renders output `index` from `source` into `destination`, resizing it to the output.
+    pub fn render_output(&mut self, index: usize, source: &Framebuffer, destination: &mut Framebuffer) -> Result<()> {
+        {
+            let setup = self.setup.read().map_err(|_| ErrorKind::Invalid("setup lock poisoned".to_owned()))?;
+            let output = setup.outputs.get(index)
+                .ok_or_else(|| ErrorKind::Invalid(format!("no output {}", index)))?;
+            while self.baked.len() <= index {
+                self.baked.push(None);
+            }
+            let stale = self.baked[index].as_ref().is_none_or(|baked| baked.output != *output);
+            if stale {
+                output.validate()?;
+                self.baked[index] = Some(Baked { output: output.clone(), texels: bake(output) });
+            }
+        }
+        let baked = self.baked[index].as_ref().unwrap();
+        let (width, gamma) = (baked.output.width as usize, baked.output.gamma);
+        if (destination.width(), destination.height()) != (baked.output.width, baked.output.height) {
+            destination.resize(baked.output.width, baked.output.height);
+        }
+        destination.colors_mut()
+            .par_chunks_mut(width)
+            .zip(baked.texels.par_chunks(width))
+            .for_each(|(row, texels)| {
+                for (pixel, texel) in row.iter_mut().zip(texels) {
+                    *pixel = shade(source, texel, gamma);
+                }
+            });
+        Ok(())
+    }
+
+    /// Renders every output, growing `outputs` as needed.
+    pub fn render(&mut self, source: &Framebuffer, outputs: &mut Vec<Framebuffer>) -> Result<()> {
+        let count = self.setup.read().map(|setup| setup.outputs.len()).unwrap_or(0);
+        while outputs.len() < count {
+            outputs.push(Framebuffer::new(1, 1));
+        }
+        for (index, output) in outputs.iter_mut().enumerate().take(count) {
+            self.render_output(index, source, output)?;
+        }
+        Ok(())
+    }
+
+    /// Adds a pass rendering output `index` from `source` into `destination`, which must
+    /// already have the output's size.
+    pub fn add_pass(
+        stage: Arc<Mutex<WarpBlend>>,
+        graph: &mut RenderGraph<SoftwareBackend>,
+        source: ResourceId,
+        index: usize,
+        destination: ResourceId,
+    ) -> PassId {
+        graph.add_pass("warp blend").reads(source).writes(destination).execute(move |context| {
+            let (source, destination) = context.target_pair(source, destination)?;
+            let size = (destination.width(), destination.height());
+            let mut stage = stage.lock().map_err(|_| "Warp blend lock poisoned".to_owned())?;
+            stage.render_output(index, source, destination).map_err(|error| error.to_string())?;
+            if (destination.width(), destination.height()) != size {
+                return Err(format!("Projector output {} doesn't match its {}x{} target", index, size.0, size.1));
+            }
+            Ok(())
+        })
+    }
+}
+
+fn bake(output: &ProjectorOutput) -> Vec<MapTexel> {
+    let (width, height) = (output.width as usize, output.height as usize);
+    (0..width * height)
+        .into_par_iter()
+        .map(|i| {
+            let u = ((i % width) as f32 + 0.5) / width as f32;
+            let v = ((i / width) as f32 + 0.5) / height as f32;
+            let level = output.black_level.level;
+            MapTexel {
+                source: output.warp.map(u, v),
+                scale: (1. - level) * output.blend.weight(u, v),
+                lift: level * output.black_level.coverage(&output.blend, u, v),
+            }
+        })
+        .collect()
+}
+
+fn shade(source: &Framebuffer, texel: &MapTexel, gamma: f32) -> [f32; 4] {
+    let color = sample(source, texel.source);
+    if texel.scale == 1. && texel.lift == 0. {
+        return color;
+    }
+    let mut shaded = color;
+    for c in 0..3 {
+        let light = color[c].max(0.).powf(gamma);
+        shaded[c] = (texel.lift + texel.scale * light).powf(1. / gamma);
+    }
+    shaded
+}
+
+/// Bilinear lookup at frame coordinates, black outside the frame.
+fn sample(source: &Framebuffer, point: [f32; 2]) -> [f32; 4] {
+    if !(point[0] >= 0. && point[0] <= 1. && point[1] >= 0. && point[1] <= 1.) {
+        return [0., 0., 0., 1.];
+    }
+    let (width, height) = (source.width() as i64, source.height() as i64);
+    let (x, y) = (point[0] * width as f32 - 0.5, point[1] * height as f32 - 0.5);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let texel = |x: i64, y: i64| source.pixel(x.max(0).min(width - 1) as u32, y.max(0).min(height - 1) as u32);
+    let (x0, y0) = (x0 as i64, y0 as i64);
+    let mut color = [0.; 4];
+    let (a, b, c, d) = (texel(x0, y0), texel(x0 + 1, y0), texel(x0, y0 + 1), texel(x0 + 1, y0 + 1));
+    for channel in 0..4 {
+        let top = a[channel] + (b[channel] - a[channel]) * fx;
+        let bottom = c[channel] + (d[channel] - c[channel]) * fx;
+        color[channel] = top + (bottom - top) * fy;
+    }
+    color
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+
+    use projection::{ProjectionSetup, ProjectorOutput, WarpBlend, WarpGrid, WarpInterpolation};
+    use software::Framebuffer;
+
+    // a gradient along x, red rising by a quarter per pixel
+    fn source() -> Framebuffer {
+        let mut source = Framebuffer::new(4, 2);
+        for x in 0..4 {
+            source.set_pixel(x, 0, [x as f32 / 4., 1., 1., 1.]);
+            source.set_pixel(x, 1, [x as f32 / 4., 1., 1., 1.]);
+        }
+        source
+    }
+
+    // two 2x2 outputs showing the left and right halves of the frame
+    fn halves() -> ProjectionSetup {
+        let mut left = ProjectorOutput::new("left", 2, 2);
+        left.warp = WarpGrid::rect(2, 2, [0., 0.], [0.5, 1.]);
+        left.warp.interpolation = WarpInterpolation::Bilinear;
+        let mut right = left.clone();
+        right.name = "right".to_owned();
+        right.warp.points = WarpGrid::rect(2, 2, [0.5, 0.], [1., 1.]).points;
+        ProjectionSetup { outputs: vec![left, right] }
+    }
+
+    fn render(setup: ProjectionSetup, source: &Framebuffer) -> Vec<Framebuffer> {
+        let mut outputs = Vec::new();
+        WarpBlend::new(Arc::new(RwLock::new(setup))).render(source, &mut outputs).unwrap();
+        outputs
+    }
+
+    #[test]
+    fn round_trips_through_ron() {
+        let setup = halves();
+        assert_eq!(ProjectionSetup::parse(&setup.to_ron().unwrap()).unwrap(), setup);
+    }
+
+    #[test]
+    fn rejects_invalid_outputs() {
+        assert!(ProjectionSetup::parse("(outputs: [(name: \"bad\", width: 0, height: 1, warp: (columns: 2, rows: 2, points: []))])").is_err());
+    }
+
+    #[test]
+    fn warps_each_output() {
+        let outputs = render(halves(), &source());
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(outputs[1].pixel(0, 0), [0.5, 1., 1., 1.]);
+        assert_eq!(outputs[0].pixel(1, 1), [0.25, 1., 1., 1.]);
+    }
+
+    #[test]
+    fn fades_edges_linearly_in_light() {
+        // right half of the left output fades out
+        let mut setup = halves();
+        setup.outputs[0].blend.right = 1.;
+        setup.outputs[0].blend.exponent = 1.;
+        setup.outputs[0].gamma = 2.;
+        let faded = render(setup, &source())[0].pixel(1, 0);
+        assert_relative_eq!(faded[1], 0.25f32.sqrt(), epsilon = 1e-6);
+    }
+
+    #[test]
+    fn lifts_black_level() {
+        // black raised to the level outside the ramps, content scaled to fit
+        let mut setup = halves();
+        setup.outputs[1].black_level.level = 0.04;
+        setup.outputs[1].gamma = 2.;
+        let mut black = source();
+        black.clear([0., 1., 1., 1.], 1.);
+        let lifted = render(setup, &black)[1].pixel(0, 0);
+        assert_relative_eq!(lifted[0], 0.2, epsilon = 1e-6);
+        assert_relative_eq!(lifted[1], 1., epsilon = 1e-6);
+    }
+
+    #[test]
+    fn rebakes_after_live_edits() {
+        let setup = Arc::new(RwLock::new(halves()));
+        let mut stage = WarpBlend::new(setup.clone());
+        let mut outputs = Vec::new();
+        stage.render(&source(), &mut outputs).unwrap();
+        setup.write().unwrap().outputs[0].blend.right = 1.;
+        stage.render(&source(), &mut outputs).unwrap();
+        assert!(outputs[0].pixel(1, 0)[1] < 1.);
+    }
+}
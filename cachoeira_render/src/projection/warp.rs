@@ -0,0 +1,179 @@
+/// How positions between warp control points are found.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum WarpInterpolation {
+    /// straight between points, with creases along the grid lines
+    Bilinear,
+    /// Catmull-Rom through the points, smooth across cells; suits curved screens
+    #[default]
+    Bicubic,
+}
+
+/// Grid of control points spread evenly over an output, each giving the point of the rendered
+/// frame shown there.
+///
+/// Frame coordinates put (0, 0) at the top-left and (1, 1) at the bottom-right corner.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct WarpGrid {
+    pub columns: usize,
+    pub rows: usize,
+    /// row-major, rows top to bottom
+    pub points: Vec<[f32; 2]>,
+    #[serde(default)]
+    pub interpolation: WarpInterpolation,
+}
+
+impl WarpGrid {
+    /// Shows the whole frame undistorted.
+    pub fn identity(columns: usize, rows: usize) -> Self {
+        WarpGrid::rect(columns, rows, [0., 0.], [1., 1.])
+    }
+
+    /// Shows the part of the frame from `min` to `max` undistorted, e.g. one projector's share
+    /// of a wall.
+    pub fn rect(columns: usize, rows: usize, min: [f32; 2], max: [f32; 2]) -> Self {
+        assert!(columns >= 2 && rows >= 2, "Warp grid needs at least 2x2 points");
+        let mut points = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                let (u, v) = (column as f32 / (columns - 1) as f32, row as f32 / (rows - 1) as f32);
+                points.push([min[0] + (max[0] - min[0]) * u, min[1] + (max[1] - min[1]) * v]);
+            }
+        }
+        WarpGrid { columns, rows, points, interpolation: WarpInterpolation::default() }
+    }
+
+    pub fn point(&self, column: usize, row: usize) -> [f32; 2] {
+        self.points[row * self.columns + column]
+    }
+
+    pub fn set_point(&mut self, column: usize, row: usize, point: [f32; 2]) {
+        self.points[row * self.columns + column] = point;
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.columns < 2 || self.rows < 2 {
+            return Err(format!("Warp grid of {}x{} points is smaller than 2x2", self.columns, self.rows));
+        }
+        if self.points.len() != self.columns * self.rows {
+            return Err(format!("Warp grid of {}x{} needs {} points, has {}", self.columns, self.rows, self.columns * self.rows, self.points.len()));
+        }
+        Ok(())
+    }
+
+    /// Frame position shown at output position (`u`, `v`), both 0 to 1.
+    pub fn map(&self, u: f32, v: f32) -> [f32; 2] {
+        let (x, y) = (u * (self.columns - 1) as f32, v * (self.rows - 1) as f32);
+        let (column, row) = (cell(x, self.columns), cell(y, self.rows));
+        let (fx, fy) = (x - column as f32, y - row as f32);
+        let (column, row) = (column as isize, row as isize);
+        match self.interpolation {
+            WarpInterpolation::Bilinear => {
+                let top = lerp(self.extended(column, row), self.extended(column + 1, row), fx);
+                let bottom = lerp(self.extended(column, row + 1), self.extended(column + 1, row + 1), fx);
+                lerp(top, bottom, fy)
+            }
+            WarpInterpolation::Bicubic => {
+                let (wx, wy) = (catmull_rom(fx), catmull_rom(fy));
+                let mut point = [0.; 2];
+                for (j, wy) in wy.iter().enumerate() {
+                    for (i, wx) in wx.iter().enumerate() {
+                        let control = self.extended(column + i as isize - 1, row + j as isize - 1);
+                        let weight = wx * wy;
+                        point[0] += control[0] * weight;
+                        point[1] += control[1] * weight;
+                    }
+                }
+                point
+            }
+        }
+    }
+
+    // control points continued linearly past the edges, so border cells don't flatten out
+    fn extended(&self, column: isize, row: isize) -> [f32; 2] {
+        let (last_column, last_row) = (self.columns as isize - 1, self.rows as isize - 1);
+        if column < 0 || column > last_column {
+            let edge = column.max(0).min(last_column);
+            let inner = if column < 0 { 1 } else { last_column - 1 };
+            let (a, b) = (self.extended(edge, row), self.extended(inner, row));
+            return [a[0] * 2. - b[0], a[1] * 2. - b[1]];
+        }
+        if row < 0 || row > last_row {
+            let edge = row.max(0).min(last_row);
+            let inner = if row < 0 { 1 } else { last_row - 1 };
+            let (a, b) = (self.point(column as usize, edge as usize), self.point(column as usize, inner as usize));
+            return [a[0] * 2. - b[0], a[1] * 2. - b[1]];
+        }
+        self.point(column as usize, row as usize)
+    }
+}
+
+fn cell(position: f32, count: usize) -> usize {
+    if position > 0. { (position as usize).min(count - 2) } else { 0 }
+}
+
+fn lerp(a: [f32; 2], b: [f32; 2], t: f32) -> [f32; 2] {
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t]
+}
+
+fn catmull_rom(t: f32) -> [f32; 4] {
+    let (t2, t3) = (t * t, t * t * t);
+    [
+        (-t3 + 2. * t2 - t) * 0.5,
+        (3. * t3 - 5. * t2 + 2.) * 0.5,
+        (-3. * t3 + 4. * t2 + t) * 0.5,
+        (t3 - t2) * 0.5,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use projection::{WarpGrid, WarpInterpolation};
+
+    const BOTH: [WarpInterpolation; 2] = [WarpInterpolation::Bilinear, WarpInterpolation::Bicubic];
+
+    // the right half of the frame, with the middle point pulled to (0.8, 0.6)
+    fn bent() -> WarpGrid {
+        let mut grid = WarpGrid::rect(3, 3, [0.5, 0.], [1., 1.]);
+        grid.set_point(1, 1, [0.8, 0.6]);
+        grid
+    }
+
+    #[test]
+    fn undistorted_grid_stays_linear() {
+        let mut grid = WarpGrid::rect(3, 3, [0.5, 0.], [1., 1.]);
+        for &interpolation in &BOTH {
+            grid.interpolation = interpolation;
+            // border cells included
+            let mapped = grid.map(0.1, 0.8);
+            assert_relative_eq!(mapped[0], 0.55, epsilon = 1e-6);
+            assert_relative_eq!(mapped[1], 0.8, epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    fn passes_through_control_points() {
+        let mut grid = bent();
+        for &interpolation in &BOTH {
+            grid.interpolation = interpolation;
+            assert_eq!(grid.map(0.5, 0.5), [0.8, 0.6]);
+        }
+    }
+
+    #[test]
+    fn bicubic_bends_neighbouring_cells_smoothly() {
+        // bilinear moves the neighbouring cell linearly, bicubic bulges it
+        let mut grid = bent();
+        grid.interpolation = WarpInterpolation::Bilinear;
+        assert_relative_eq!(grid.map(0.25, 0.5)[0], 0.65, epsilon = 1e-6);
+        grid.interpolation = WarpInterpolation::Bicubic;
+        assert!(grid.map(0.25, 0.5)[0] > 0.655);
+    }
+
+    #[test]
+    fn rejects_missing_points() {
+        let mut grid = bent();
+        assert!(grid.validate().is_ok());
+        grid.points.pop();
+        assert!(grid.validate().is_err());
+    }
+}
@@ -0,0 +1,32 @@
+use graph::{BufferDesc, GraphBackend, TargetDesc};
+use software::Framebuffer;
+
+/// Runs render graphs on the CPU: targets are `Framebuffer`s, buffers plain bytes.
+#[derive(Clone, Debug, Default)]
+pub struct SoftwareBackend {
+    allocated: usize,
+}
+
+impl SoftwareBackend {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Number of resources created so far.
+    pub fn allocated(&self) -> usize { self.allocated }
+}
+
+impl GraphBackend for SoftwareBackend {
+    type Target = Framebuffer;
+    type Buffer = Vec<u8>;
+
+    fn create_target(&mut self, desc: &TargetDesc) -> Framebuffer {
+        self.allocated += 1;
+        Framebuffer::new(desc.width, desc.height)
+    }
+
+    fn create_buffer(&mut self, desc: &BufferDesc) -> Vec<u8> {
+        self.allocated += 1;
+        vec![0; desc.size]
+    }
+}
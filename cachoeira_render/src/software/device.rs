@@ -0,0 +1,205 @@
+use std::sync::{Arc, RwLock};
+
+use cachoeira_core::ThreadPool;
+
+use device::{Device, DrawCall, UsesPipeline};
+use pipeline::Pipeline;
+use software::Framebuffer;
+
+/// CPU rasterizer rendering into a `Framebuffer`; needs no GPU, so it also backs tests and
+/// headless runs.
+pub struct SoftwareDevice {
+    framebuffer: Framebuffer,
+    pipeline: Arc<RwLock<Pipeline>>,
+    pool: Option<ThreadPool>,
+}
+
+impl SoftwareDevice {
+    pub fn new(width: u32, height: u32) -> Self {
+        SoftwareDevice {
+            framebuffer: Framebuffer::new(width, height),
+            pipeline: Arc::new(RwLock::new(Pipeline::default())),
+            pool: None,
+        }
+    }
+
+    /// Rasterizes on `pool` instead of rayon's global pool.
+    pub fn with_pool(mut self, pool: ThreadPool) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer { &self.framebuffer }
+    pub fn framebuffer_mut(&mut self) -> &mut Framebuffer { &mut self.framebuffer }
+
+    pub fn pipeline(&self) -> Arc<RwLock<Pipeline>> { self.pipeline.clone() }
+}
+
+impl UsesPipeline for SoftwareDevice {
+    fn set_pipeline(&mut self, pipeline: Arc<RwLock<Pipeline>>) -> Result<bool, String> {
+        if Arc::ptr_eq(&self.pipeline, &pipeline) {
+            return Ok(false);
+        }
+        self.pipeline = pipeline;
+        Ok(true)
+    }
+}
+
+impl Device for SoftwareDevice {
+    fn size(&self) -> (u32, u32) {
+        (self.framebuffer.width(), self.framebuffer.height())
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.framebuffer.resize(width, height);
+    }
+
+    fn clear(&mut self, color: [f32; 4], depth: f32) {
+        self.framebuffer.clear(color, depth);
+    }
+
+    fn draw(&mut self, call: &DrawCall) -> Result<(), String> {
+        let pipeline = *self.pipeline.read().map_err(|err| format!("Pipeline lock poisoned: {}", err))?;
+        let framebuffer = &mut self.framebuffer;
+        match self.pool {
+            Some(ref pool) => pool.install(|| framebuffer.draw(&pipeline, call)),
+            None => framebuffer.draw(&pipeline, call),
+        }
+    }
+
+    fn read_rgba8(&self) -> Result<Vec<u8>, String> {
+        Ok(self.framebuffer.to_rgba8())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+
+    use cgmath::{perspective, Deg, Matrix4, Vector3};
+
+    use device::{Device, DirectionalLight, DrawCall, UsesPipeline, Vertex};
+    use pipeline::{Blend, CullMode, Pipeline, Shading};
+    use texture::{Sampler, Texture};
+    use super::SoftwareDevice;
+
+    const RED: [f32; 4] = [1., 0., 0., 1.];
+    const BLUE: [f32; 4] = [0., 0., 1., 1.];
+
+    // two triangles covering the normalized device rectangle (x0, y0)..(x1, y1)
+    fn quad(x0: f32, y0: f32, x1: f32, y1: f32, z: f32, color: [f32; 4]) -> Vec<Vertex> {
+        vec![
+            Vertex::new([x0, y0, z]).with_uv([0., 1.]).with_color(color),
+            Vertex::new([x1, y0, z]).with_uv([1., 1.]).with_color(color),
+            Vertex::new([x1, y1, z]).with_uv([1., 0.]).with_color(color),
+            Vertex::new([x0, y1, z]).with_uv([0., 0.]).with_color(color),
+        ]
+    }
+
+    const QUAD: [u32; 6] = [0, 1, 2, 0, 2, 3];
+
+    fn coverage(device: &SoftwareDevice) -> usize {
+        device.framebuffer().colors().iter().filter(|pixel| pixel[3] > 0.).count()
+    }
+
+    #[test]
+    fn shared_edges_cover_each_pixel_once() {
+        let mut device = SoftwareDevice::new(64, 48);
+        device.set_pipeline(Arc::new(RwLock::new(
+            Pipeline::new().with_blend(Blend::Additive).with_depth(::pipeline::DepthTest::Always, false),
+        ))).unwrap();
+        device.clear([0., 0., 0., 1.], 1.);
+        let vertices = quad(-0.5, -0.5, 0.5, 0.5, 0., [0.25, 0.25, 0.25, 1.]);
+        device.draw(&DrawCall::new(&vertices, &QUAD)).unwrap();
+
+        // exactly the 32x24 pixels in the middle, each written once
+        let frame = device.framebuffer();
+        for y in 0..48 {
+            for x in 0..64 {
+                let inside = (16..48).contains(&x) && (12..36).contains(&y);
+                assert_eq!(frame.pixel(x, y)[0], if inside { 0.25 } else { 0. }, "pixel {}, {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn depth_and_culling() {
+        let mut device = SoftwareDevice::new(40, 40);
+        device.clear([0.; 4], 1.);
+        let near = quad(-1., -1., 0., 1., -0.5, RED);
+        let far = quad(-1., -1., 1., 1., 0.5, BLUE);
+        device.draw(&DrawCall::new(&near, &QUAD)).unwrap();
+        device.draw(&DrawCall::new(&far, &QUAD)).unwrap();
+        assert_eq!(device.framebuffer().pixel(5, 20), RED);
+        assert_eq!(device.framebuffer().pixel(35, 20), BLUE);
+        assert_eq!(device.framebuffer().depth(5, 20), 0.25);
+
+        // clockwise: culled by default
+        device.clear([0.; 4], 1.);
+        device.draw(&DrawCall::new(&near, &[0, 2, 1, 0, 3, 2])).unwrap();
+        assert_eq!(coverage(&device), 0);
+        device.set_pipeline(Arc::new(RwLock::new(Pipeline::new().with_cull_mode(CullMode::Front)))).unwrap();
+        device.draw(&DrawCall::new(&near, &[0, 2, 1, 0, 3, 2])).unwrap();
+        assert_eq!(coverage(&device), 20 * 40);
+
+        assert!(device.draw(&DrawCall::new(&near, &[0, 1, 7])).is_err());
+    }
+
+    #[test]
+    fn textures_and_perspective() {
+        // 2x2 checkerboard, one texel per quarter of the screen
+        let texture = Texture::new(2, 2, vec![RED, BLUE, BLUE, RED]);
+        let mut device = SoftwareDevice::new(16, 16);
+        device.clear([0.; 4], 1.);
+        let vertices = quad(-1., -1., 1., 1., 0., [1.; 4]);
+        device.draw(&DrawCall::new(&vertices, &QUAD).with_texture(&texture, Sampler::NEAREST)).unwrap();
+        let frame = device.framebuffer();
+        assert_eq!((frame.pixel(0, 0), frame.pixel(15, 0), frame.pixel(0, 15), frame.pixel(15, 15)), (RED, BLUE, BLUE, RED));
+        assert_eq!((frame.pixel(7, 7), frame.pixel(8, 7), frame.pixel(7, 8), frame.pixel(8, 8)), (RED, BLUE, BLUE, RED));
+
+        // a floor receding from the camera, from z = -1 at the bottom edge of the screen to
+        // z = -3 at row 43; its middle, z = -2, projects to row 48 rather than halfway down
+        let floor = vec![
+            Vertex::new([-1., -1., -1.]).with_uv([0., 1.]),
+            Vertex::new([1., -1., -1.]).with_uv([1., 1.]),
+            Vertex::new([1., -1., -3.]).with_uv([1., 0.]),
+            Vertex::new([-1., -1., -3.]).with_uv([0., 0.]),
+        ];
+        let mut device = SoftwareDevice::new(64, 64);
+        device.clear([0.; 4], 1.);
+        let call = DrawCall::new(&floor, &QUAD)
+            .with_view_projection(perspective(Deg(90.), 1., 0.1, 10.))
+            .with_texture(&texture, Sampler::NEAREST);
+        device.draw(&call).unwrap();
+        // vertex colors go through perspective division, so compare what would be displayed
+        let bytes = device.read_rgba8().unwrap();
+        let pixel = |x: usize, y: usize| [bytes[(y * 64 + x) * 4], bytes[(y * 64 + x) * 4 + 1], bytes[(y * 64 + x) * 4 + 2]];
+        assert_eq!(pixel(20, 40), [0, 0, 0]);
+        assert_eq!(pixel(20, 47), [255, 0, 0]);
+        assert_eq!(pixel(20, 48), [0, 0, 255]);
+        assert_eq!(pixel(20, 52), [0, 0, 255]);
+    }
+
+    #[test]
+    fn lambert_and_flat() {
+        let mut device = SoftwareDevice::new(8, 8);
+        let light = DirectionalLight::new(Vector3::new(0., 0., -1.), [0.5; 3]).with_ambient([0.25; 3]);
+        let vertices = quad(-1., -1., 1., 1., 0., [1.; 4]);
+        for shading in &[Shading::Flat, Shading::Lambert] {
+            device.set_pipeline(Arc::new(RwLock::new(Pipeline::new().with_shading(*shading)))).unwrap();
+            device.clear([0.; 4], 1.);
+            device.draw(&DrawCall::new(&vertices, &QUAD).with_light(light)).unwrap();
+            assert_eq!(device.framebuffer().pixel(3, 3), [0.75, 0.75, 0.75, 1.]);
+
+            // turned away from the light: ambient only
+            device.clear([0.; 4], 1.);
+            let turned = Matrix4::from_angle_y(Deg(180.));
+            device.set_pipeline(Arc::new(RwLock::new(
+                Pipeline::new().with_shading(*shading).with_cull_mode(CullMode::None),
+            ))).unwrap();
+            device.draw(&DrawCall::new(&vertices, &QUAD).with_model(turned).with_light(light)).unwrap();
+            assert_eq!(device.framebuffer().pixel(3, 3), [0.25, 0.25, 0.25, 1.]);
+        }
+        assert_eq!(device.read_rgba8().unwrap()[..4], [64, 64, 64, 255]);
+    }
+}
@@ -0,0 +1,105 @@
+use device::DrawCall;
+use pipeline::Pipeline;
+use software::raster;
+
+/// Color and depth targets of the software backend, rows top to bottom.
+///
+/// Color is kept in linear floating point; depth runs from 0 at the near plane to 1 at the far
+/// plane.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    color: Vec<[f32; 4]>,
+    depth: Vec<f32>,
+}
+
+/// Rectangle of pixels, `x` and `y` being the top-left corner.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        let count = width as usize * height as usize;
+        Framebuffer { width, height, color: vec![[0.; 4]; count], depth: vec![1.; count] }
+    }
+
+    pub fn width(&self) -> u32 { self.width }
+    pub fn height(&self) -> u32 { self.height }
+    pub fn colors(&self) -> &[[f32; 4]] { &self.color }
+    pub fn colors_mut(&mut self) -> &mut [[f32; 4]] { &mut self.color }
+    pub fn depths(&self) -> &[f32] { &self.depth }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [f32; 4] {
+        self.color[self.index(x, y)]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: [f32; 4]) {
+        let index = self.index(x, y);
+        self.color[index] = color;
+    }
+
+    pub fn depth(&self, x: u32, y: u32) -> f32 {
+        self.depth[self.index(x, y)]
+    }
+
+    /// Resizes, discarding the contents.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        *self = Framebuffer::new(width, height);
+    }
+
+    pub fn clear(&mut self, color: [f32; 4], depth: f32) {
+        for pixel in &mut self.color {
+            *pixel = color;
+        }
+        for value in &mut self.depth {
+            *value = depth;
+        }
+    }
+
+    /// Clamped and rounded to 8 bits per channel, no colour-space conversion.
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        let quantize = |value: f32| (value.clamp(0., 1.) * 255.).round() as u8;
+        let mut bytes = Vec::with_capacity(self.color.len() * 4);
+        for pixel in &self.color {
+            bytes.extend(pixel.iter().map(|&channel| quantize(channel)));
+        }
+        bytes
+    }
+
+    /// Rasterizes `call` on the current rayon pool.
+    pub fn draw(&mut self, pipeline: &Pipeline, call: &DrawCall) -> Result<(), String> {
+        call.validate()?;
+        raster::draw(self, pipeline, call);
+        Ok(())
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+
+    pub(crate) fn read_region(&self, region: Region) -> (Vec<[f32; 4]>, Vec<f32>) {
+        let mut color = Vec::with_capacity(region.width * region.height);
+        let mut depth = Vec::with_capacity(region.width * region.height);
+        for row in region.y..region.y + region.height {
+            let start = row * self.width as usize + region.x;
+            color.extend_from_slice(&self.color[start..start + region.width]);
+            depth.extend_from_slice(&self.depth[start..start + region.width]);
+        }
+        (color, depth)
+    }
+
+    pub(crate) fn write_region(&mut self, region: Region, color: &[[f32; 4]], depth: &[f32]) {
+        for row in 0..region.height {
+            let start = (region.y + row) * self.width as usize + region.x;
+            let source = row * region.width;
+            self.color[start..start + region.width].copy_from_slice(&color[source..source + region.width]);
+            self.depth[start..start + region.width].copy_from_slice(&depth[source..source + region.width]);
+        }
+    }
+}
@@ -0,0 +1,16 @@
+//! CPU rasterizer backend.
+//!
+//! Draws indexed triangles with depth testing, perspective-correct interpolation, textures
+//! and flat or Lambert shading into an RGBA `Framebuffer`. Tiles are rasterized in parallel
+//! with rayon, and output is deterministic, which makes it suitable for pixel-exact tests.
+//! `SoftwareBackend` runs render graphs on the same framebuffers.
+
+pub use self::backend::SoftwareBackend;
+pub use self::device::SoftwareDevice;
+pub use self::framebuffer::Framebuffer;
+pub use self::raster::TILE_SIZE;
+
+mod backend;
+mod device;
+mod framebuffer;
+mod raster;
@@ -0,0 +1,309 @@
+use cgmath::{
+    InnerSpace,
+    Matrix,
+    Matrix3,
+    Matrix4,
+    SquareMatrix,
+    Vector2,
+    Vector3,
+    Vector4,
+    Zero,
+};
+use rayon::prelude::*;
+
+use device::{DrawCall, Vertex};
+use pipeline::{CullMode, Pipeline, Shading};
+use software::framebuffer::{Framebuffer, Region};
+
+/// Side of the square tiles rendered in parallel, in pixels.
+pub const TILE_SIZE: usize = 32;
+
+#[derive(Clone, Copy, Debug)]
+struct ClipVertex {
+    clip: Vector4<f32>,
+    normal: Vector3<f32>,
+    uv: Vector2<f32>,
+    color: Vector4<f32>,
+}
+
+impl ClipVertex {
+    fn lerp(&self, other: &ClipVertex, t: f32) -> ClipVertex {
+        ClipVertex {
+            clip: self.clip.lerp(other.clip, t),
+            normal: self.normal.lerp(other.normal, t),
+            uv: self.uv.lerp(other.uv, t),
+            color: self.color.lerp(other.color, t),
+        }
+    }
+}
+
+// attributes are stored divided by w, for perspective-correct interpolation
+#[derive(Clone, Copy, Debug)]
+struct ScreenVertex {
+    x: f32,
+    y: f32,
+    depth: f32,
+    inv_w: f32,
+    normal: Vector3<f32>,
+    uv: Vector2<f32>,
+    color: Vector4<f32>,
+}
+
+struct Triangle {
+    vertices: [ScreenVertex; 3],
+    face_normal: Vector3<f32>,
+    area: f32,
+    // whether the edge opposite each vertex is a top or left edge
+    top_left: [bool; 3],
+    // pixel bounds, max exclusive
+    min: (usize, usize),
+    max: (usize, usize),
+}
+
+/// Draws `call` into `framebuffer`, on the current rayon pool.
+///
+/// Triangles are clipped against the near plane, binned into tiles and the tiles rasterized in
+/// parallel; within a tile triangles keep their submission order, so results don't depend on
+/// the thread count.
+pub fn draw(framebuffer: &mut Framebuffer, pipeline: &Pipeline, call: &DrawCall) {
+    let (width, height) = (framebuffer.width() as usize, framebuffer.height() as usize);
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    let model_view_projection = call.view_projection * call.model;
+    let normal_matrix = normal_matrix(&call.model);
+    let transformed: Vec<ClipVertex> = call.vertices
+        .par_iter()
+        .map(|vertex| transform(vertex, &model_view_projection, &normal_matrix, call.color))
+        .collect();
+    let world: Vec<Vector3<f32>> = call.vertices
+        .par_iter()
+        .map(|vertex| (call.model * Vector3::from(vertex.position).extend(1.)).truncate())
+        .collect();
+
+    let cull_mode = pipeline.cull_mode;
+    let triangles: Vec<Triangle> = call.indices
+        .par_chunks(3)
+        .flat_map(|indices| {
+            let corner = |i: usize| indices[i] as usize;
+            let face_normal = (world[corner(1)] - world[corner(0)]).cross(world[corner(2)] - world[corner(0)]);
+            let face_normal = if face_normal.magnitude2() > 0. { face_normal.normalize() } else { face_normal };
+            setup(
+                [transformed[corner(0)], transformed[corner(1)], transformed[corner(2)]],
+                face_normal,
+                cull_mode,
+                width,
+                height,
+            )
+        })
+        .collect();
+
+    let tiles_x = width.div_ceil(TILE_SIZE);
+    let tiles_y = height.div_ceil(TILE_SIZE);
+    let mut bins = vec![Vec::new(); tiles_x * tiles_y];
+    for (index, triangle) in triangles.iter().enumerate() {
+        for tile_y in triangle.min.1 / TILE_SIZE..triangle.max.1.div_ceil(TILE_SIZE) {
+            for tile_x in triangle.min.0 / TILE_SIZE..triangle.max.0.div_ceil(TILE_SIZE) {
+                bins[tile_y * tiles_x + tile_x].push(index);
+            }
+        }
+    }
+
+    let rendered: Vec<(Region, Vec<[f32; 4]>, Vec<f32>)> = {
+        let target = &*framebuffer;
+        bins.par_iter()
+            .enumerate()
+            .filter(|&(_, bin)| !bin.is_empty())
+            .map(|(tile, bin)| {
+                let (x, y) = ((tile % tiles_x) * TILE_SIZE, (tile / tiles_x) * TILE_SIZE);
+                let region = Region {
+                    x,
+                    y,
+                    width: TILE_SIZE.min(width - x),
+                    height: TILE_SIZE.min(height - y),
+                };
+                let (mut color, mut depth) = target.read_region(region);
+                for &index in bin {
+                    rasterize(&triangles[index], region, &mut color, &mut depth, pipeline, call);
+                }
+                (region, color, depth)
+            })
+            .collect()
+    };
+    for (region, color, depth) in rendered {
+        framebuffer.write_region(region, &color, &depth);
+    }
+}
+
+fn normal_matrix(model: &Matrix4<f32>) -> Matrix3<f32> {
+    let linear = Matrix3::from_cols(model.x.truncate(), model.y.truncate(), model.z.truncate());
+    linear.invert().map(|inverse| inverse.transpose()).unwrap_or(linear)
+}
+
+fn transform(vertex: &Vertex, model_view_projection: &Matrix4<f32>, normal_matrix: &Matrix3<f32>, tint: [f32; 4]) -> ClipVertex {
+    let color = Vector4::from(vertex.color);
+    ClipVertex {
+        clip: model_view_projection * Vector3::from(vertex.position).extend(1.),
+        normal: normal_matrix * Vector3::from(vertex.normal),
+        uv: Vector2::from(vertex.uv),
+        color: Vector4::new(color.x * tint[0], color.y * tint[1], color.z * tint[2], color.w * tint[3]),
+    }
+}
+
+// keeps the part of the polygon in front of the near plane, z >= -w
+fn clip_near(corners: &[ClipVertex; 3]) -> Vec<ClipVertex> {
+    let distance = |vertex: &ClipVertex| vertex.clip.z + vertex.clip.w;
+    let mut polygon = Vec::with_capacity(4);
+    for i in 0..3 {
+        let (current, next) = (&corners[i], &corners[(i + 1) % 3]);
+        let (d_current, d_next) = (distance(current), distance(next));
+        if d_current >= 0. {
+            polygon.push(*current);
+        }
+        if (d_current >= 0.) != (d_next >= 0.) {
+            polygon.push(current.lerp(next, d_current / (d_current - d_next)));
+        }
+    }
+    polygon
+}
+
+fn to_screen(vertex: &ClipVertex, width: usize, height: usize) -> ScreenVertex {
+    let inv_w = 1. / vertex.clip.w;
+    ScreenVertex {
+        x: (vertex.clip.x * inv_w + 1.) * 0.5 * width as f32,
+        y: (1. - vertex.clip.y * inv_w) * 0.5 * height as f32,
+        depth: (vertex.clip.z * inv_w + 1.) * 0.5,
+        inv_w,
+        normal: vertex.normal * inv_w,
+        uv: vertex.uv * inv_w,
+        color: vertex.color * inv_w,
+    }
+}
+
+// twice the signed area of (a, b, p); positive when clockwise as seen on screen
+fn edge(a: &ScreenVertex, b: &ScreenVertex, x: f32, y: f32) -> f32 {
+    (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x)
+}
+
+fn is_top_left(a: &ScreenVertex, b: &ScreenVertex) -> bool {
+    (a.y == b.y && b.x > a.x) || b.y < a.y
+}
+
+fn setup(corners: [ClipVertex; 3], face_normal: Vector3<f32>, cull_mode: CullMode, width: usize, height: usize) -> Vec<Triangle> {
+    let polygon: Vec<ScreenVertex> = clip_near(&corners)
+        .iter()
+        .map(|vertex| to_screen(vertex, width, height))
+        .collect();
+    let mut triangles = Vec::new();
+    for i in 2..polygon.len() {
+        let (a, mut b, mut c) = (polygon[0], polygon[i - 1], polygon[i]);
+        // front faces, counter-clockwise as seen, have a negative area with y pointing down
+        let area = edge(&a, &b, c.x, c.y);
+        let keep = match cull_mode {
+            CullMode::None => area != 0.,
+            CullMode::Back => area < 0.,
+            CullMode::Front => area > 0.,
+        };
+        if !keep || !area.is_finite() {
+            continue;
+        }
+        if area < 0. {
+            ::std::mem::swap(&mut b, &mut c);
+        }
+
+        let min_x = a.x.min(b.x).min(c.x).floor().max(0.) as usize;
+        let min_y = a.y.min(b.y).min(c.y).floor().max(0.) as usize;
+        let max_x = (a.x.max(b.x).max(c.x).ceil().max(0.) as usize).min(width);
+        let max_y = (a.y.max(b.y).max(c.y).ceil().max(0.) as usize).min(height);
+        if min_x >= max_x || min_y >= max_y {
+            continue;
+        }
+        triangles.push(Triangle {
+            vertices: [a, b, c],
+            face_normal,
+            area: area.abs(),
+            top_left: [is_top_left(&b, &c), is_top_left(&c, &a), is_top_left(&a, &b)],
+            min: (min_x, min_y),
+            max: (max_x, max_y),
+        });
+    }
+    triangles
+}
+
+fn rasterize(
+    triangle: &Triangle,
+    region: Region,
+    color: &mut [[f32; 4]],
+    depth: &mut [f32],
+    pipeline: &Pipeline,
+    call: &DrawCall,
+) {
+    let [a, b, c] = triangle.vertices;
+    let (x0, y0) = (triangle.min.0.max(region.x), triangle.min.1.max(region.y));
+    let (x1, y1) = (triangle.max.0.min(region.x + region.width), triangle.max.1.min(region.y + region.height));
+    let covers = |weight: f32, top_left: bool| weight > 0. || (weight == 0. && top_left);
+
+    for py in y0..y1 {
+        for px in x0..x1 {
+            let (x, y) = (px as f32 + 0.5, py as f32 + 0.5);
+            let weights = [edge(&b, &c, x, y), edge(&c, &a, x, y), edge(&a, &b, x, y)];
+            if !(0..3).all(|i| covers(weights[i], triangle.top_left[i])) {
+                continue;
+            }
+            let (wa, wb, wc) = (weights[0] / triangle.area, weights[1] / triangle.area, weights[2] / triangle.area);
+
+            let z = wa * a.depth + wb * b.depth + wc * c.depth;
+            if !(0. ..=1.).contains(&z) {
+                continue;
+            }
+            let index = (py - region.y) * region.width + (px - region.x);
+            if !pipeline.depth_test.passes(z, depth[index]) {
+                continue;
+            }
+
+            let w = 1. / (wa * a.inv_w + wb * b.inv_w + wc * c.inv_w);
+            let fragment = Fragment {
+                normal: (a.normal * wa + b.normal * wb + c.normal * wc) * w,
+                uv: (a.uv * wa + b.uv * wb + c.uv * wc) * w,
+                color: (a.color * wa + b.color * wb + c.color * wc) * w,
+            };
+            let shaded = shade(&fragment, triangle.face_normal, pipeline.shading, call);
+            color[index] = pipeline.blend.apply(shaded, color[index]);
+            if pipeline.depth_write {
+                depth[index] = z;
+            }
+        }
+    }
+}
+
+struct Fragment {
+    normal: Vector3<f32>,
+    uv: Vector2<f32>,
+    color: Vector4<f32>,
+}
+
+fn shade(fragment: &Fragment, face_normal: Vector3<f32>, shading: Shading, call: &DrawCall) -> [f32; 4] {
+    let mut albedo = fragment.color;
+    if let Some(texture) = call.texture {
+        let texel = texture.sample(fragment.uv.x, fragment.uv.y, &call.sampler);
+        albedo = Vector4::new(albedo.x * texel[0], albedo.y * texel[1], albedo.z * texel[2], albedo.w * texel[3]);
+    }
+    let normal = match shading {
+        Shading::Unlit => return albedo.into(),
+        Shading::Flat => face_normal,
+        Shading::Lambert => fragment.normal,
+    };
+    let diffuse = if normal.is_zero() {
+        0.
+    } else {
+        normal.normalize().dot(-call.light.direction.normalize()).max(0.)
+    };
+    let light = &call.light;
+    [
+        albedo.x * (light.ambient[0] + light.color[0] * diffuse),
+        albedo.y * (light.ambient[1] + light.color[1] * diffuse),
+        albedo.z * (light.ambient[2] + light.color[2] * diffuse),
+        albedo.w,
+    ]
+}
@@ -0,0 +1,258 @@
+use std::ops::Range;
+use std::sync::{Arc, RwLock};
+
+use cgmath::{Matrix4, Vector4};
+use fnv::FnvHashMap;
+use rayon::prelude::*;
+
+use camera::Camera;
+use device::{Device, DrawCall, Vertex};
+use pipeline::{Blend, CullMode, DepthTest, Pipeline, Shading};
+use sprite::Sprite;
+use texture::{Filter, Sampler, Texture, Wrap};
+
+/// Consecutive sprites drawn together: same layer, order and texture.
+#[derive(Clone, Debug)]
+pub struct SpriteBatch {
+    pub texture: Arc<Texture>,
+    pub layer: i32,
+    /// indices into the sorted sprites; vertices `4 * start` to `4 * end`
+    pub sprites: Range<usize>,
+}
+
+impl SpriteBatch {
+    pub fn len(&self) -> usize { self.sprites.end - self.sprites.start }
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+}
+
+/// Resource holding this frame's sprite quads, sorted into batches.
+///
+/// Vertices are in world space, four per sprite, counter-clockwise from the bottom-left
+/// corner; every batch shares the same index pattern.
+#[derive(Clone, Debug, Default)]
+pub struct SpriteBatches {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    batches: Vec<SpriteBatch>,
+}
+
+impl SpriteBatches {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn vertices(&self) -> &[Vertex] { &self.vertices }
+    pub fn batches(&self) -> &[SpriteBatch] { &self.batches }
+
+    /// Indices of a batch's triangles, relative to its first vertex.
+    pub fn indices(&self, batch: &SpriteBatch) -> &[u32] {
+        &self.indices[..batch.len() * 6]
+    }
+
+    pub fn batch_vertices(&self, batch: &SpriteBatch) -> &[Vertex] {
+        &self.vertices[batch.sprites.start * 4..batch.sprites.end * 4]
+    }
+
+    /// Replaces the contents with `sprites`, each placed by its world matrix.
+    ///
+    /// Sprites are sorted by layer, then by `Sprite::order`, then by texture in order of first
+    /// use, then by position in `sprites`; so sprites of equal layer and order may be drawn out
+    /// of `sprites` order when their textures differ. Vertices are generated in parallel.
+    pub fn build(&mut self, sprites: &[(&Sprite, Matrix4<f32>)]) {
+        let mut textures = FnvHashMap::default();
+        let mut order: Vec<(i32, i32, usize, usize)> = sprites.iter().enumerate()
+            .map(|(index, &(sprite, _))| {
+                let next = textures.len();
+                let texture = *textures.entry(&*sprite.texture as *const Texture).or_insert(next);
+                (sprite.layer, sprite.order, texture, index)
+            })
+            .collect();
+        order.par_sort_unstable();
+
+        self.batches.clear();
+        for (position, &(layer, sprite_order, texture, index)) in order.iter().enumerate() {
+            let extends = match self.batches.last() {
+                Some(batch) => {
+                    let (_, first_order, first_texture, _) = order[batch.sprites.start];
+                    batch.layer == layer && first_order == sprite_order && first_texture == texture
+                }
+                None => false,
+            };
+            if extends {
+                self.batches.last_mut().unwrap().sprites.end += 1;
+            } else {
+                self.batches.push(SpriteBatch {
+                    texture: sprites[index].0.texture.clone(),
+                    layer,
+                    sprites: position..position + 1,
+                });
+            }
+        }
+
+        self.vertices.clear();
+        self.vertices.resize(sprites.len() * 4, Vertex::new([0.; 3]));
+        self.vertices.par_chunks_mut(4)
+            .zip(order.par_iter())
+            .for_each(|(quad, &(_, _, _, index))| {
+                let (sprite, ref matrix) = sprites[index];
+                quad.copy_from_slice(&corners(sprite, matrix));
+            });
+
+        let longest = self.batches.iter().map(SpriteBatch::len).max().unwrap_or(0);
+        if self.indices.len() < longest * 6 {
+            self.indices = (0..longest as u32)
+                .flat_map(|quad| [0, 1, 2, 0, 2, 3].iter().map(move |corner| quad * 4 + corner).collect::<Vec<_>>())
+                .collect();
+        }
+    }
+}
+
+fn corners(sprite: &Sprite, matrix: &Matrix4<f32>) -> [Vertex; 4] {
+    let (width, height) = (sprite.size[0], sprite.size[1]);
+    let (left, bottom) = (-sprite.pivot[0] * width, -sprite.pivot[1] * height);
+    let (right, top) = (left + width, bottom + height);
+    let (mut u0, mut u1) = (sprite.uv_min[0], sprite.uv_max[0]);
+    // texture rows run top to bottom
+    let (mut v_top, mut v_bottom) = (sprite.uv_min[1], sprite.uv_max[1]);
+    if sprite.flip_x {
+        ::std::mem::swap(&mut u0, &mut u1);
+    }
+    if sprite.flip_y {
+        ::std::mem::swap(&mut v_top, &mut v_bottom);
+    }
+    let corner = |x: f32, y: f32, u: f32, v: f32| {
+        let position = matrix * Vector4::new(x, y, 0., 1.);
+        Vertex::new([position.x, position.y, position.z]).with_uv([u, v]).with_color(sprite.tint)
+    };
+    [
+        corner(left, bottom, u0, v_bottom),
+        corner(right, bottom, u1, v_bottom),
+        corner(right, top, u1, v_top),
+        corner(left, top, u0, v_top),
+    ]
+}
+
+/// Draws `SpriteBatches` through any `Device`, one draw call per batch.
+///
+/// Sprites blend over each other in batch order, and are hidden by what is already in the depth
+/// buffer without writing to it.
+pub struct SpriteRenderer {
+    pub sampler: Sampler,
+    pipeline: Arc<RwLock<Pipeline>>,
+}
+
+impl SpriteRenderer {
+    pub fn new() -> Self {
+        let pipeline = Pipeline::new()
+            .with_shading(Shading::Unlit)
+            .with_cull_mode(CullMode::None)
+            .with_depth(DepthTest::LessEqual, false)
+            .with_blend(Blend::Alpha);
+        SpriteRenderer {
+            sampler: Sampler { filter: Filter::Linear, wrap: Wrap::Clamp },
+            pipeline: Arc::new(RwLock::new(pipeline)),
+        }
+    }
+
+    pub fn with_sampler(mut self, sampler: Sampler) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Leaves the device on the sprite pipeline.
+    pub fn draw<D: Device + ?Sized>(&self, device: &mut D, batches: &SpriteBatches, camera: &Camera) -> Result<(), String> {
+        if batches.batches().is_empty() {
+            return Ok(());
+        }
+        device.set_pipeline(self.pipeline.clone())?;
+        for batch in batches.batches() {
+            let call = DrawCall::new(batches.batch_vertices(batch), batches.indices(batch))
+                .with_view_projection(camera.view_projection())
+                .with_texture(&batch.texture, self.sampler);
+            device.draw(&call)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for SpriteRenderer {
+    fn default() -> Self { SpriteRenderer::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use cgmath::{Matrix4, Vector3};
+
+    use camera::Camera;
+    use device::Device;
+    use software::SoftwareDevice;
+    use sprite::{Sprite, SpriteBatches, SpriteRenderer};
+    use texture::{Sampler, Texture};
+
+    const RED: [f32; 4] = [1., 0., 0., 1.];
+    const GREEN: [f32; 4] = [0., 1., 0., 1.];
+    const BLUE: [f32; 4] = [0., 0., 1., 1.];
+
+    #[test]
+    fn sorts_batches_and_draws() {
+        // left half red, right half green
+        let sheet = Arc::new(Texture::new(2, 1, vec![RED, GREEN]));
+        let blue = Arc::new(Texture::solid(BLUE));
+        let at = |x: f32, y: f32| Matrix4::from_translation(Vector3::new(x, y, 0.));
+
+        let background = Sprite::new(blue.clone()).with_size(16., 16.).with_pivot(0., 0.).on_layer(-1);
+        let left = Sprite::new(sheet.clone()).with_size(8., 8.).with_pivot(0., 0.);
+        let right = left.clone().flipped(true, false);
+        let second = Sprite::new(sheet.clone()).with_pixels(1, 0, 1, 1).with_size(4., 4.).with_pivot(0., 0.).on_layer(1);
+        let sprites = [(&left, at(0., 0.)), (&background, at(0., 0.)), (&second, at(12., 12.)), (&right, at(8., 8.))];
+
+        let mut batches = SpriteBatches::new();
+        batches.build(&sprites);
+        let layers: Vec<(i32, usize)> = batches.batches().iter().map(|batch| (batch.layer, batch.len())).collect();
+        assert_eq!(layers, vec![(-1, 1), (0, 2), (1, 1)]);
+
+        // a 16x16 world unit view, y up
+        let camera = Camera::orthographic(0., 16., 0., 16., -1., 1.);
+        let mut device = SoftwareDevice::new(16, 16);
+        device.clear([0., 0., 0., 1.], 1.);
+        SpriteRenderer::new().with_sampler(Sampler::NEAREST).draw(&mut device, &batches, &camera).unwrap();
+        let frame = device.framebuffer();
+        // bottom-left quarter, unflipped: red then green
+        assert_eq!((frame.pixel(1, 12), frame.pixel(6, 12)), (RED, GREEN));
+        // top-right quarter, flipped: green then red, with the layer 1 sprite over its corner
+        assert_eq!((frame.pixel(9, 4), frame.pixel(14, 6)), (GREEN, RED));
+        assert_eq!(frame.pixel(14, 2), GREEN);
+        assert_eq!(frame.pixel(2, 2), BLUE);
+    }
+
+    #[test]
+    fn orders_within_a_layer_before_texture() {
+        let (red, blue) = (Arc::new(Texture::solid(RED)), Arc::new(Texture::solid(BLUE)));
+        let at = Matrix4::from_translation(Vector3::new(0., 0., 0.));
+        let back = Sprite::new(red.clone());
+        let middle = Sprite::new(blue.clone()).with_order(1);
+        let front = Sprite::new(red.clone()).with_order(2);
+        let beside = Sprite::new(blue.clone());
+        let sprites = [(&back, at), (&middle, at), (&front, at), (&beside, at)];
+
+        let mut batches = SpriteBatches::new();
+        batches.build(&sprites);
+        // without orders the two red sprites would share a batch, drawn under the blue ones
+        let textures: Vec<(bool, usize)> = batches.batches().iter().map(|batch| (Arc::ptr_eq(&batch.texture, &red), batch.len())).collect();
+        assert_eq!(textures, vec![(true, 1), (false, 1), (false, 1), (true, 1)]);
+    }
+
+    #[test]
+    fn batches_many_sprites() {
+        let texture = Arc::new(Texture::solid([1.; 4]));
+        let sprite = Sprite::new(texture);
+        let sprites: Vec<_> = (0..100_000).map(|i| (&sprite, Matrix4::from_translation(Vector3::new(i as f32, 0., 0.)))).collect();
+        let mut batches = SpriteBatches::new();
+        batches.build(&sprites);
+        assert_eq!(batches.batches().len(), 1);
+        assert_eq!(batches.vertices().len(), 400_000);
+        assert_eq!(batches.vertices()[4 * 99_999].position, [99_999. - 0.5, -0.5, 0.]);
+    }
+}
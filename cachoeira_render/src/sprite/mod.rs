@@ -0,0 +1,215 @@
+//! Textured quads for 2D scenes and billboards.
+//!
+//! `Sprite` components are placed by their entity's `GlobalTransform`, or `GlobalTransform2D`
+//! for 2D hierarchies. The `SpriteBatchSystem` sorts them by layer, order and texture into
+//! `SpriteBatches` each frame, generating vertices in parallel, and a `SpriteRenderer` draws
+//! the batches with one draw call each.
+
+use std::sync::Arc;
+
+use cgmath::{Matrix4, One};
+use specs::prelude::{
+    Component,
+    DenseVecStorage,
+    Entities,
+    Join,
+    ReadStorage,
+    System,
+    Write,
+};
+
+use cachoeira_core::transform::{GlobalTransform, GlobalTransform2D};
+
+use texture::Texture;
+
+pub use self::batch::{SpriteBatch, SpriteBatches, SpriteRenderer};
+
+mod batch;
+
+/// A region of a texture drawn as a rectangle in the entity's XY plane.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sprite {
+    pub texture: Arc<Texture>,
+    /// top-left corner of the region shown, in texture coordinates
+    pub uv_min: [f32; 2],
+    /// bottom-right corner of the region shown
+    pub uv_max: [f32; 2],
+    /// width and height in local units
+    pub size: [f32; 2],
+    /// multiplied into the texture
+    pub tint: [f32; 4],
+    /// point of the rectangle at the entity's origin, from (0, 0) at the bottom-left to (1, 1)
+    /// at the top-right
+    pub pivot: [f32; 2],
+    pub flip_x: bool,
+    pub flip_y: bool,
+    /// draw order: higher layers are drawn over lower ones
+    pub layer: i32,
+    /// draw order within a layer, higher over lower; sprites of equal order are grouped by
+    /// texture, so only those sharing a texture keep entity order
+    pub order: i32,
+}
+
+impl Sprite {
+    /// Shows the whole texture, one local unit per pixel, centred on the entity.
+    pub fn new(texture: Arc<Texture>) -> Self {
+        let size = [texture.width() as f32, texture.height() as f32];
+        Sprite {
+            texture,
+            uv_min: [0., 0.],
+            uv_max: [1., 1.],
+            size,
+            tint: [1.; 4],
+            pivot: [0.5, 0.5],
+            flip_x: false,
+            flip_y: false,
+            layer: 0,
+            order: 0,
+        }
+    }
+
+    /// Shows `width` by `height` pixels from (`x`, `y`), e.g. a frame of a sprite sheet, one
+    /// local unit per pixel.
+    pub fn with_pixels(mut self, x: u32, y: u32, width: u32, height: u32) -> Self {
+        let (texture_width, texture_height) = (self.texture.width() as f32, self.texture.height() as f32);
+        self.uv_min = [x as f32 / texture_width, y as f32 / texture_height];
+        self.uv_max = [(x + width) as f32 / texture_width, (y + height) as f32 / texture_height];
+        self.size = [width as f32, height as f32];
+        self
+    }
+
+    pub fn with_size(mut self, width: f32, height: f32) -> Self {
+        self.size = [width, height];
+        self
+    }
+
+    pub fn with_tint(mut self, tint: [f32; 4]) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    pub fn with_pivot(mut self, x: f32, y: f32) -> Self {
+        self.pivot = [x, y];
+        self
+    }
+
+    pub fn flipped(mut self, flip_x: bool, flip_y: bool) -> Self {
+        self.flip_x = flip_x;
+        self.flip_y = flip_y;
+        self
+    }
+
+    pub fn on_layer(mut self, layer: i32) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    pub fn with_order(mut self, order: i32) -> Self {
+        self.order = order;
+        self
+    }
+}
+
+impl Component for Sprite {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Rebuilds the `SpriteBatches` resource from every `Sprite`; belongs after the
+/// `TransformSystem`.
+///
+/// Sprites with neither a `GlobalTransform` nor a `GlobalTransform2D` sit at the origin.
+pub struct SpriteBatchSystem;
+
+impl<'a> System<'a> for SpriteBatchSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Sprite>,
+        ReadStorage<'a, GlobalTransform>,
+        ReadStorage<'a, GlobalTransform2D>,
+        Write<'a, SpriteBatches>,
+    );
+
+    fn run(&mut self, (entities, sprites, globals, globals_2d, mut batches): Self::SystemData) {
+        #[cfg(feature="profiler")]
+        profile_scope!("sprite_batch_system");
+
+        let placed: Vec<(&Sprite, Matrix4<f32>)> = (&*entities, &sprites).join()
+            .map(|(entity, sprite)| {
+                let matrix = match (globals.get(entity), globals_2d.get(entity)) {
+                    (Some(global), _) => global.0,
+                    (None, Some(global)) => global.to_matrix4(),
+                    (None, None) => Matrix4::one(),
+                };
+                (sprite, matrix)
+            })
+            .collect();
+        batches.build(&placed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use cgmath::{Matrix3, Matrix4, Vector3};
+    use specs::prelude::{DispatcherBuilder, World};
+
+    use cachoeira_core::transform::{GlobalTransform, GlobalTransform2D};
+
+    use sprite::{Sprite, SpriteBatchSystem, SpriteBatches};
+    use texture::Texture;
+
+    fn corner_positions(batches: &SpriteBatches) -> Vec<[f32; 3]> {
+        batches.vertices().iter().map(|vertex| vertex.position).collect()
+    }
+
+    #[test]
+    fn system_places_sprites_by_their_globals() {
+        let mut world = World::new();
+        let mut dispatcher = DispatcherBuilder::new().with(SpriteBatchSystem, "sprite_batch_system", &[]).build();
+        dispatcher.setup(&mut world.res);
+        world.register::<GlobalTransform>();
+        world.register::<GlobalTransform2D>();
+
+        let square = Sprite::new(Arc::new(Texture::solid([1.; 4]))).with_size(2., 2.).with_pivot(0., 0.);
+        world.create_entity()
+            .with(square.clone())
+            .with(GlobalTransform(Matrix4::from_translation(Vector3::new(1., 2., 3.))))
+            .build();
+        // a quarter turn, then moved to (5, 6)
+        world.create_entity()
+            .with(square.clone().on_layer(1))
+            .with(GlobalTransform2D(Matrix3::new(0., 1., 0., -1., 0., 0., 5., 6., 1.)))
+            .build();
+        world.create_entity().with(square.on_layer(2)).build();
+        dispatcher.dispatch(&world.res);
+
+        let batches = world.read_resource::<SpriteBatches>();
+        assert_eq!(batches.batches().len(), 3);
+        assert_eq!(corner_positions(&batches), vec![
+            [1., 2., 3.], [3., 2., 3.], [3., 4., 3.], [1., 4., 3.],
+            [5., 6., 0.], [5., 8., 0.], [3., 8., 0.], [3., 6., 0.],
+            [0., 0., 0.], [2., 0., 0.], [2., 2., 0.], [0., 2., 0.],
+        ]);
+    }
+
+    #[test]
+    fn system_rebuilds_every_frame() {
+        let mut world = World::new();
+        let mut dispatcher = DispatcherBuilder::new().with(SpriteBatchSystem, "sprite_batch_system", &[]).build();
+        dispatcher.setup(&mut world.res);
+        world.register::<GlobalTransform>();
+        world.register::<GlobalTransform2D>();
+
+        let sprite = world.create_entity().with(Sprite::new(Arc::new(Texture::solid([1.; 4])))).build();
+        dispatcher.dispatch(&world.res);
+        assert_eq!(world.read_resource::<SpriteBatches>().vertices().len(), 4);
+
+        world.delete_entity(sprite).unwrap();
+        world.maintain();
+        dispatcher.dispatch(&world.res);
+        let batches = world.read_resource::<SpriteBatches>();
+        assert!(batches.vertices().is_empty());
+        assert!(batches.batches().is_empty());
+    }
+}
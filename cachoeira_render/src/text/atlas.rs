@@ -0,0 +1,195 @@
+use std::sync::Arc;
+
+use fnv::FnvHashMap;
+use rusttype::{point, GlyphId, Scale};
+
+use text::Font;
+use texture::{RectPacker, Texture};
+
+// empty pixels around each glyph, so linear filtering does not bleed between neighbours
+const PADDING: u32 = 1;
+
+// font, glyph index and size in pixels per em, as bits
+type GlyphKey = (usize, u32, u32);
+
+/// Where a rasterized glyph sits in a `GlyphAtlas`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AtlasGlyph {
+    /// top-left corner in texture coordinates
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+    /// top-left corner of the bitmap from the pen position, in pixels, y down
+    pub offset: [f32; 2],
+    /// in pixels
+    pub size: [f32; 2],
+}
+
+/// Glyph coverage rasterized on demand into one texture, shared by every font and size.
+///
+/// Glyphs are packed as they come and kept until the atlas is cleared; the texture is white
+/// with the coverage in its alpha, so draw calls tint it through the vertex colour.
+pub struct GlyphAtlas {
+    width: u32,
+    height: u32,
+    coverage: Vec<f32>,
+    // glyphs without any pixels, such as spaces, map to None
+    glyphs: FnvHashMap<GlyphKey, Option<AtlasGlyph>>,
+    packer: RectPacker,
+    texture: Option<Arc<Texture>>,
+}
+
+impl GlyphAtlas {
+    pub fn new(width: u32, height: u32) -> Self {
+        GlyphAtlas {
+            width,
+            height,
+            coverage: vec![0.; (width * height) as usize],
+            glyphs: FnvHashMap::default(),
+            packer: RectPacker::new(width, height, PADDING),
+            texture: None,
+        }
+    }
+
+    pub fn width(&self) -> u32 { self.width }
+    pub fn height(&self) -> u32 { self.height }
+
+    /// Rebuilt only when glyphs were added since the last call.
+    pub fn texture(&mut self) -> Arc<Texture> {
+        if self.texture.is_none() {
+            let pixels = self.coverage.iter().map(|&alpha| [1., 1., 1., alpha]).collect();
+            self.texture = Some(Arc::new(Texture::new(self.width, self.height, pixels)));
+        }
+        self.texture.clone().unwrap()
+    }
+
+    /// Forgets every glyph, making room for new ones.
+    pub fn clear(&mut self) {
+        for alpha in &mut self.coverage {
+            *alpha = 0.;
+        }
+        self.glyphs.clear();
+        self.packer.clear();
+        self.texture = None;
+    }
+
+    /// A glyph added by `prepare`; None when it has no pixels or was never added.
+    pub fn glyph(&self, font: &Font, glyph: u32, size: f32) -> Option<&AtlasGlyph> {
+        self.glyphs.get(&(font.id(), glyph, size.to_bits())).and_then(Option::as_ref)
+    }
+
+    /// Rasterizes whichever of `glyphs` are missing, returning false if they do not all fit.
+    pub fn prepare<I: IntoIterator<Item = u32>>(&mut self, font: &Font, glyphs: I, size: f32) -> bool {
+        for glyph in glyphs {
+            let key = (font.id(), glyph, size.to_bits());
+            if self.glyphs.contains_key(&key) {
+                continue;
+            }
+            match self.rasterize(font, glyph, size) {
+                Some(entry) => {
+                    self.glyphs.insert(key, entry);
+                }
+                None => return false,
+            }
+        }
+        true
+    }
+
+    fn rasterize(&mut self, font: &Font, glyph: u32, size: f32) -> Option<Option<AtlasGlyph>> {
+        let positioned = font.face().glyph(GlyphId(glyph)).scaled(Scale::uniform(size)).positioned(point(0., 0.));
+        let bounds = match positioned.pixel_bounding_box() {
+            Some(bounds) => bounds,
+            None => return Some(None),
+        };
+        let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+        let (left, top) = self.packer.insert(width, height)?;
+        {
+            let (atlas_width, coverage) = (self.width, &mut self.coverage);
+            positioned.draw(|x, y, alpha| {
+                coverage[((top + y) * atlas_width + left + x) as usize] = alpha;
+            });
+        }
+        self.texture = None;
+        let (atlas_width, atlas_height) = (self.width as f32, self.height as f32);
+        Some(Some(AtlasGlyph {
+            uv_min: [left as f32 / atlas_width, top as f32 / atlas_height],
+            uv_max: [(left + width) as f32 / atlas_width, (top + height) as f32 / atlas_height],
+            offset: [bounds.min.x as f32, bounds.min.y as f32],
+            size: [width as f32, height as f32],
+        }))
+    }
+}
+
+impl Default for GlyphAtlas {
+    fn default() -> Self { GlyphAtlas::new(1024, 1024) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use text::tests::serif;
+    use text::{Font, GlyphAtlas, TextStyle};
+
+    fn ids(font: &Font, text: &str) -> Vec<u32> {
+        font.layout(text, &TextStyle::new(20.)).glyphs.iter().map(|glyph| glyph.id).collect()
+    }
+
+    // highest coverage inside a glyph's rectangle of the atlas texture
+    fn peak(atlas: &mut GlyphAtlas, font: &Font, glyph: u32) -> f32 {
+        let entry = *atlas.glyph(font, glyph, 20.).unwrap();
+        let texture = atlas.texture();
+        let (width, height) = (atlas.width() as f32, atlas.height() as f32);
+        let (left, top) = ((entry.uv_min[0] * width) as u32, (entry.uv_min[1] * height) as u32);
+        let mut peak: f32 = 0.;
+        for y in top..top + entry.size[1] as u32 {
+            for x in left..left + entry.size[0] as u32 {
+                peak = peak.max(texture.pixel(x, y)[3]);
+            }
+        }
+        peak
+    }
+
+    #[test]
+    fn rasterizes_each_glyph_once() {
+        let font = serif();
+        let mut atlas = GlyphAtlas::new(64, 64);
+        let glyphs = ids(&font, "aç ");
+        assert!(atlas.prepare(&font, glyphs.clone(), 20.));
+        assert!(peak(&mut atlas, &font, glyphs[0]) > 0.9);
+        assert!(peak(&mut atlas, &font, glyphs[1]) > 0.9);
+        // spaces have no pixels, other sizes are separate glyphs
+        assert_eq!(atlas.glyph(&font, glyphs[2], 20.), None);
+        assert_eq!(atlas.glyph(&font, glyphs[0], 10.), None);
+
+        let (entry, texture) = (*atlas.glyph(&font, glyphs[0], 20.).unwrap(), atlas.texture());
+        assert!(atlas.prepare(&font, glyphs, 20.));
+        assert_eq!(atlas.glyph(&font, ids(&font, "a")[0], 20.), Some(&entry));
+        assert!(Arc::ptr_eq(&texture, &atlas.texture()));
+    }
+
+    #[test]
+    fn places_accents_above_and_cedillas_below() {
+        let font = serif();
+        let mut atlas = GlyphAtlas::new(64, 64);
+        let glyphs = ids(&font, "aãç");
+        assert!(atlas.prepare(&font, glyphs.clone(), 20.));
+        let entry = |index: usize| *atlas.glyph(&font, glyphs[index], 20.).unwrap();
+        // offsets run y down from the pen on the baseline
+        assert!(entry(1).offset[1] < entry(0).offset[1] - 2.);
+        assert!(entry(2).offset[1] + entry(2).size[1] > entry(0).offset[1] + entry(0).size[1] + 2.);
+    }
+
+    #[test]
+    fn reports_a_full_atlas_until_cleared() {
+        let font = serif();
+        let mut atlas = GlyphAtlas::new(16, 16);
+        let glyphs = ids(&font, "ao");
+        assert!(!atlas.prepare(&font, glyphs.clone(), 20.));
+        assert!(atlas.glyph(&font, glyphs[0], 20.).is_some());
+
+        atlas.clear();
+        assert_eq!(atlas.glyph(&font, glyphs[0], 20.), None);
+        assert!(atlas.texture().pixels().iter().all(|pixel| pixel[3] == 0.));
+        assert!(atlas.prepare(&font, vec![glyphs[1]], 20.));
+    }
+}
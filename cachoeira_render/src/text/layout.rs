@@ -0,0 +1,197 @@
+use std::ops::Range;
+
+use rusttype::{self, GlyphId, Scale};
+
+use text::{TextAlign, TextStyle};
+
+/// A glyph placed by `Font::layout`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LaidGlyph {
+    /// glyph index in the font
+    pub id: u32,
+    pub character: char,
+    /// pen position on the baseline, in pixels from the top-left of the block, y down
+    pub position: [f32; 2],
+    pub advance: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextLine {
+    /// indices into `TextLayout::glyphs`
+    pub glyphs: Range<usize>,
+    /// without trailing whitespace
+    pub width: f32,
+    pub baseline: f32,
+}
+
+/// Glyphs of a text block laid out in lines, in pixels at the style's size.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextLayout {
+    pub glyphs: Vec<LaidGlyph>,
+    pub lines: Vec<TextLine>,
+    /// width of the widest line, or the wrapping width if there is one; height from the first
+    /// line's ascent to the last line's descent
+    pub size: [f32; 2],
+}
+
+pub(crate) trait Metrics {
+    fn glyph(&self, character: char) -> u32;
+    fn advance(&self, glyph: u32) -> f32;
+    fn kerning(&self, first: u32, second: u32) -> f32;
+    /// ascent, descent (negative) and line gap
+    fn vertical(&self) -> (f32, f32, f32);
+}
+
+pub(crate) struct ScaledFont<'f> {
+    pub font: &'f rusttype::Font<'static>,
+    pub scale: Scale,
+}
+
+impl<'f> Metrics for ScaledFont<'f> {
+    fn glyph(&self, character: char) -> u32 {
+        self.font.glyph(character).id().0
+    }
+
+    fn advance(&self, glyph: u32) -> f32 {
+        self.font.glyph(GlyphId(glyph)).scaled(self.scale).h_metrics().advance_width
+    }
+
+    fn kerning(&self, first: u32, second: u32) -> f32 {
+        self.font.pair_kerning(self.scale, GlyphId(first), GlyphId(second))
+    }
+
+    fn vertical(&self) -> (f32, f32, f32) {
+        let metrics = self.font.v_metrics(self.scale);
+        (metrics.ascent, metrics.descent, metrics.line_gap)
+    }
+}
+
+// spaces a tab stands for
+const TAB_WIDTH: f32 = 4.;
+
+/// Breaks lines at newlines, and where they would exceed the style's `max_width` at the last
+/// whitespace, or between characters for words longer than a whole line.
+pub(crate) fn layout<M: Metrics>(metrics: &M, text: &str, style: &TextStyle) -> TextLayout {
+    let (ascent, descent, gap) = metrics.vertical();
+    let line_height = (ascent - descent + gap) * style.line_spacing;
+    let space = metrics.glyph(' ');
+    let mut glyphs: Vec<LaidGlyph> = Vec::with_capacity(text.len());
+    let mut lines = Vec::new();
+
+    for paragraph in text.split('\n') {
+        let mut start = glyphs.len();
+        let mut pen = 0.;
+        let mut previous = None;
+        // first glyph after the last whitespace of the line
+        let mut last_break = None;
+        for character in paragraph.chars() {
+            if character.is_control() && character != '\t' {
+                continue;
+            }
+            let id = if character == '\t' { space } else { metrics.glyph(character) };
+            let advance = metrics.advance(id) * if character == '\t' { TAB_WIDTH } else { 1. };
+            let mut x = pen + previous.map_or(0., |previous| metrics.kerning(previous, id));
+
+            let overflows = style.max_width.is_some_and(|max| x + advance > max);
+            if overflows && !character.is_whitespace() && glyphs.len() > start {
+                let next = match last_break {
+                    Some(index) if index > start && index < glyphs.len() => index,
+                    // the word alone fills the line
+                    _ => glyphs.len(),
+                };
+                lines.push(finish_line(&glyphs, start..next));
+                let shift = if next < glyphs.len() { glyphs[next].position[0] } else { x };
+                for glyph in &mut glyphs[next..] {
+                    glyph.position[0] -= shift;
+                }
+                x -= shift;
+                start = next;
+                last_break = None;
+            }
+
+            glyphs.push(LaidGlyph { id, character, position: [x, 0.], advance });
+            pen = x + advance;
+            previous = Some(id);
+            if character.is_whitespace() {
+                last_break = Some(glyphs.len());
+            }
+        }
+        lines.push(finish_line(&glyphs, start..glyphs.len()));
+    }
+
+    let width = match style.max_width {
+        Some(max) => max,
+        None => lines.iter().fold(0., |width: f32, line| width.max(line.width)),
+    };
+    for (index, line) in lines.iter_mut().enumerate() {
+        line.baseline = ascent + index as f32 * line_height;
+        let offset = match style.align {
+            TextAlign::Left => 0.,
+            TextAlign::Center => (width - line.width) * 0.5,
+            TextAlign::Right => width - line.width,
+        };
+        for glyph in &mut glyphs[line.glyphs.clone()] {
+            glyph.position = [glyph.position[0] + offset, line.baseline];
+        }
+    }
+    let height = ascent - descent + (lines.len() - 1) as f32 * line_height;
+    TextLayout { glyphs, lines, size: [width, height] }
+}
+
+fn finish_line(glyphs: &[LaidGlyph], range: Range<usize>) -> TextLine {
+    let width = glyphs[range.clone()].iter()
+        .filter(|glyph| !glyph.character.is_whitespace())
+        .fold(0., |width: f32, glyph| width.max(glyph.position[0] + glyph.advance));
+    TextLine { glyphs: range, width, baseline: 0. }
+}
+
+#[cfg(test)]
+mod tests {
+    use text::{TextAlign, TextStyle};
+    use super::{layout, Metrics};
+
+    // 10 pixels a character, "ol" kerned together by 2
+    struct Monospace;
+
+    impl Metrics for Monospace {
+        fn glyph(&self, character: char) -> u32 { character as u32 }
+        fn advance(&self, _: u32) -> f32 { 10. }
+        fn kerning(&self, first: u32, second: u32) -> f32 {
+            if (first, second) == ('o' as u32, 'l' as u32) { -2. } else { 0. }
+        }
+        fn vertical(&self) -> (f32, f32, f32) { (8., -2., 0.) }
+    }
+
+    fn positions(text: &str, style: &TextStyle) -> Vec<(char, [f32; 2])> {
+        layout(&Monospace, text, style).glyphs.iter().map(|glyph| (glyph.character, glyph.position)).collect()
+    }
+
+    #[test]
+    fn wraps_at_whitespace_and_kerns() {
+        let style = TextStyle::new(10.).wrapped(55.);
+        let laid = layout(&Monospace, "olá mundo", &style);
+        assert_eq!(laid.glyphs.len(), 9);
+        assert_eq!(laid.lines.len(), 2);
+        assert_eq!((laid.lines[0].width, laid.lines[1].width), (28., 50.));
+        assert_eq!(laid.size, [55., 20.]);
+        let glyphs = positions("olá mundo", &style);
+        assert_eq!(glyphs[1], ('l', [8., 8.]));
+        assert_eq!(glyphs[2], ('á', [18., 8.]));
+        assert_eq!(glyphs[4], ('m', [0., 18.]));
+    }
+
+    #[test]
+    fn centres_lines_within_the_wrapping_width() {
+        let style = TextStyle::new(10.).wrapped(55.).with_align(TextAlign::Center);
+        let centred = positions("olá mundo", &style);
+        assert_eq!((centred[0].1, centred[4].1), ([13.5, 8.], [2.5, 18.]));
+    }
+
+    #[test]
+    fn breaks_at_newlines_and_inside_long_words() {
+        let broken = positions("ação\nabcdefgh", &TextStyle::new(10.).wrapped(35.));
+        let rows: Vec<f32> = broken.iter().map(|glyph| glyph.1[1]).collect();
+        assert_eq!(rows, vec![8., 8., 8., 18., 28., 28., 28., 38., 38., 38., 48., 48.]);
+        assert_eq!(broken[7], ('d', [0., 38.]));
+    }
+}
@@ -0,0 +1,239 @@
+//! TrueType and OpenType text.
+//!
+//! A `Font` lays text out into lines with kerning, wrapping and alignment; `TextRenderer`
+//! rasterizes the glyphs it needs into a shared `GlyphAtlas` and draws them, either as `Text`
+//! components placed in the world or as a screen-space `TextOverlay`. Text is UTF-8 and drawn
+//! one glyph per character, without shaping, so accented Latin text should use precomposed
+//! characters, as typed text usually does.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use rusttype::{self, Scale};
+use specs::prelude::{Component, DenseVecStorage};
+
+pub use self::atlas::{AtlasGlyph, GlyphAtlas};
+pub use self::layout::{LaidGlyph, TextLayout, TextLine};
+pub use self::render::TextRenderer;
+
+mod atlas;
+mod layout;
+mod render;
+
+static NEXT_FONT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A loaded font face; cheap to clone, and compared by identity.
+#[derive(Clone)]
+pub struct Font {
+    face: Arc<rusttype::Font<'static>>,
+    id: usize,
+}
+
+impl Font {
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, String> {
+        let face = rusttype::Font::from_bytes(bytes).map_err(|err| err.to_string())?;
+        Ok(Font { face: Arc::new(face), id: NEXT_FONT_ID.fetch_add(1, Ordering::Relaxed) })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        fs::read(path)
+            .map_err(|err| err.to_string())
+            .and_then(Font::from_bytes)
+            .map_err(|err| format!("In font file '{}': {}", path.display(), err))
+    }
+
+    /// Distance between baselines at `size` pixels per em, before the style's line spacing.
+    pub fn line_height(&self, size: f32) -> f32 {
+        let metrics = self.face.v_metrics(Scale::uniform(size));
+        metrics.ascent - metrics.descent + metrics.line_gap
+    }
+
+    pub fn layout(&self, text: &str, style: &TextStyle) -> TextLayout {
+        layout::layout(&layout::ScaledFont { font: &self.face, scale: Scale::uniform(style.size) }, text, style)
+    }
+
+    pub(crate) fn face(&self) -> &rusttype::Font<'static> { &self.face }
+    pub(crate) fn id(&self) -> usize { self.id }
+}
+
+impl fmt::Debug for Font {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Font({})", self.id)
+    }
+}
+
+impl PartialEq for Font {
+    fn eq(&self, other: &Font) -> bool { self.id == other.id }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextStyle {
+    /// pixels per em
+    pub size: f32,
+    /// linear, non-premultiplied
+    pub color: [f32; 4],
+    pub align: TextAlign,
+    /// width lines wrap at, in pixels; lines are aligned within it when given, within the
+    /// widest line otherwise
+    pub max_width: Option<f32>,
+    /// multiplies the font's line height
+    pub line_spacing: f32,
+}
+
+impl TextStyle {
+    pub fn new(size: f32) -> Self {
+        TextStyle { size, ..Default::default() }
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_align(mut self, align: TextAlign) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn wrapped(mut self, max_width: f32) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    pub fn with_line_spacing(mut self, line_spacing: f32) -> Self {
+        self.line_spacing = line_spacing;
+        self
+    }
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle { size: 16., color: [1.; 4], align: TextAlign::Left, max_width: None, line_spacing: 1. }
+    }
+}
+
+/// Text drawn in the entity's XY plane, one local unit per pixel like a `Sprite`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Text {
+    pub font: Font,
+    pub content: String,
+    pub style: TextStyle,
+    /// point of the text block at the entity's origin, from (0, 0) at the bottom-left to
+    /// (1, 1) at the top-right
+    pub pivot: [f32; 2],
+}
+
+impl Text {
+    /// Centred on the entity.
+    pub fn new<S: Into<String>>(font: &Font, content: S, style: TextStyle) -> Self {
+        Text { font: font.clone(), content: content.into(), style, pivot: [0.5, 0.5] }
+    }
+
+    pub fn with_pivot(mut self, x: f32, y: f32) -> Self {
+        self.pivot = [x, y];
+        self
+    }
+}
+
+impl Component for Text {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OverlayText {
+    pub font: Font,
+    pub text: String,
+    /// top-left corner of the block, in pixels from the top-left of the screen
+    pub position: [f32; 2],
+    pub style: TextStyle,
+}
+
+/// Resource of text to draw over everything in screen space, such as signage or debug
+/// readouts. Entries stay until cleared, so immediate-mode users clear it once drawn.
+#[derive(Clone, Debug, Default)]
+pub struct TextOverlay {
+    entries: Vec<OverlayText>,
+}
+
+impl TextOverlay {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn text<S: Into<String>>(&mut self, font: &Font, position: [f32; 2], text: S, style: TextStyle) {
+        self.entries.push(OverlayText { font: font.clone(), text: text.into(), position, style });
+    }
+
+    pub fn entries(&self) -> &[OverlayText] { &self.entries }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use text::{Font, TextStyle};
+
+    /// DejaVu Serif Condensed Bold, under the Bitstream Vera license in `fixtures/fonts`.
+    pub const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/fonts/DejaVuSerifCondensed-Bold.ttf");
+
+    pub fn serif() -> Font {
+        Font::load(FIXTURE).unwrap()
+    }
+
+    #[test]
+    fn loads_fonts_from_files_and_bytes() {
+        let loaded = serif();
+        let parsed = Font::from_bytes(fs::read(FIXTURE).unwrap()).unwrap();
+        assert_relative_eq!(loaded.line_height(20.), parsed.line_height(20.));
+        assert_relative_eq!(loaded.line_height(20.), 20., epsilon = 0.5);
+        // compared by identity, not contents
+        assert_ne!(loaded, parsed);
+        assert_eq!(loaded, loaded.clone());
+    }
+
+    #[test]
+    fn reports_bad_fonts() {
+        let missing = Font::load("fixtures/fonts/missing.ttf").unwrap_err();
+        assert!(missing.contains("missing.ttf"), "{}", missing);
+        assert!(Font::from_bytes(vec![0; 64]).is_err());
+    }
+
+    #[test]
+    fn kerns_pairs_from_the_font() {
+        let font = serif();
+        let style = TextStyle::new(20.);
+        let kerned = font.layout("To", &style);
+        let (t, o) = (kerned.glyphs[0], kerned.glyphs[1]);
+        assert!(o.position[0] < t.advance - 1.5, "{:?}", kerned.glyphs);
+        let plain = font.layout("ov", &style);
+        assert_relative_eq!(plain.glyphs[1].position[0], plain.glyphs[0].advance);
+    }
+
+    #[test]
+    fn lays_out_accented_letters_as_their_own_glyphs() {
+        let font = serif();
+        let style = TextStyle::new(20.);
+        let ids = |text: &str| font.layout(text, &style).glyphs.iter().map(|glyph| glyph.id).collect::<Vec<_>>();
+        let (accented, plain) = (ids("ação"), ids("acao"));
+        assert_eq!(accented.len(), 4);
+        // none fall back to the missing glyph
+        assert!(accented.iter().all(|&id| id != 0));
+        assert_eq!((accented[0], accented[3]), (plain[0], plain[3]));
+        assert!(accented[1] != plain[1] && accented[2] != plain[2]);
+    }
+}
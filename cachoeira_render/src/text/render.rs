@@ -0,0 +1,274 @@
+use std::sync::{Arc, RwLock};
+
+use cgmath::{Matrix4, One, Vector2, Vector4};
+
+use camera::Camera;
+use debug::DebugLines;
+use device::{Device, DrawCall, Vertex};
+use pipeline::{Blend, CullMode, DepthTest, Pipeline, Shading};
+use text::{Font, GlyphAtlas, Text, TextLayout, TextOverlay, TextStyle};
+use texture::{Filter, Sampler, Wrap};
+
+/// Draws text through any `Device`, rasterizing glyphs into its `GlyphAtlas` as they are
+/// first needed; each call to a draw method is a single draw call.
+///
+/// When the atlas fills up it is cleared and the glyphs of the current call rasterized again,
+/// so only text needing more glyphs than the atlas holds at once fails to draw.
+pub struct TextRenderer {
+    pub sampler: Sampler,
+    atlas: GlyphAtlas,
+    world: Arc<RwLock<Pipeline>>,
+    overlay: Arc<RwLock<Pipeline>>,
+}
+
+// a block of laid out text, and where its layout pixels go
+struct Block<'a, F> {
+    font: &'a Font,
+    layout: TextLayout,
+    style: &'a TextStyle,
+    place: F,
+}
+
+impl TextRenderer {
+    pub fn new() -> Self {
+        TextRenderer::with_atlas(GlyphAtlas::default())
+    }
+
+    pub fn with_atlas(atlas: GlyphAtlas) -> Self {
+        let pipeline = Pipeline::new()
+            .with_shading(Shading::Unlit)
+            .with_cull_mode(CullMode::None)
+            .with_blend(Blend::Alpha);
+        TextRenderer {
+            sampler: Sampler { filter: Filter::Linear, wrap: Wrap::Clamp },
+            atlas,
+            world: Arc::new(RwLock::new(pipeline.with_depth(DepthTest::LessEqual, false))),
+            overlay: Arc::new(RwLock::new(pipeline.with_depth(DepthTest::Always, false))),
+        }
+    }
+
+    pub fn atlas(&self) -> &GlyphAtlas { &self.atlas }
+
+    /// Draws `Text` components placed by their world matrices, hidden by what is already in
+    /// the depth buffer without writing to it. Leaves the device on the world text pipeline.
+    pub fn draw_world<D: Device + ?Sized>(&mut self, device: &mut D, texts: &[(&Text, Matrix4<f32>)], camera: &Camera) -> Result<(), String> {
+        let blocks: Vec<_> = texts.iter()
+            .map(|&(text, ref matrix)| {
+                let layout = text.font.layout(&text.content, &text.style);
+                // layout pixels run y down from the top-left; local units y up from the pivot
+                let (width, height) = (layout.size[0], layout.size[1]);
+                let (left, top) = (-text.pivot[0] * width, (1. - text.pivot[1]) * height);
+                let place = move |x: f32, y: f32| {
+                    let position = matrix * Vector4::new(left + x, top - y, 0., 1.);
+                    [position.x, position.y, position.z]
+                };
+                Block { font: &text.font, layout, style: &text.style, place }
+            })
+            .collect();
+        let world = self.world.clone();
+        self.draw_blocks(device, &blocks, world, camera.view_projection())
+    }
+
+    /// Draws the overlay's entries over everything. Leaves the device on the overlay pipeline.
+    pub fn draw_overlay<D: Device + ?Sized>(&mut self, device: &mut D, overlay: &TextOverlay) -> Result<(), String> {
+        let size = device_size(device);
+        let blocks: Vec<_> = overlay.entries().iter()
+            .map(|entry| {
+                let position = entry.position;
+                let place = move |x: f32, y: f32| to_ndc(position[0] + x, position[1] + y, size);
+                Block { font: &entry.font, layout: entry.font.layout(&entry.text, &entry.style), style: &entry.style, place }
+            })
+            .collect();
+        let overlay = self.overlay.clone();
+        self.draw_blocks(device, &blocks, overlay, Matrix4::one())
+    }
+
+    /// Draws the labels of `DebugLines` over everything, centred above their positions, in
+    /// `font` at `size` pixels per em. Leaves the device on the overlay pipeline.
+    pub fn draw_labels<D: Device + ?Sized>(&mut self, device: &mut D, lines: &DebugLines, camera: &Camera, font: &Font, size: f32) -> Result<(), String> {
+        let screen = device_size(device);
+        let styles: Vec<(TextStyle, _)> = lines.screen_labels(camera, screen).into_iter()
+            .map(|(anchor, label)| (TextStyle::new(size).with_color(label.style.color), (anchor, label)))
+            .collect();
+        let blocks: Vec<_> = styles.iter()
+            .map(|&(ref style, (anchor, label))| {
+                let layout = font.layout(&label.text, style);
+                let (left, top) = (anchor.x - layout.size[0] * 0.5, anchor.y - layout.size[1]);
+                let place = move |x: f32, y: f32| to_ndc(left + x, top + y, screen);
+                Block { font, layout, style, place }
+            })
+            .collect();
+        let overlay = self.overlay.clone();
+        self.draw_blocks(device, &blocks, overlay, Matrix4::one())
+    }
+
+    fn draw_blocks<D, F>(&mut self, device: &mut D, blocks: &[Block<F>], pipeline: Arc<RwLock<Pipeline>>, view_projection: Matrix4<f32>) -> Result<(), String>
+        where D: Device + ?Sized, F: Fn(f32, f32) -> [f32; 3]
+    {
+        if blocks.iter().all(|block| block.layout.glyphs.is_empty()) {
+            return Ok(());
+        }
+        if !self.prepare(blocks) {
+            self.atlas.clear();
+            if !self.prepare(blocks) {
+                return Err(format!("Text needs more glyphs than fit a {}x{} atlas", self.atlas.width(), self.atlas.height()));
+            }
+        }
+
+        let mut vertices = Vec::new();
+        for block in blocks {
+            for laid in &block.layout.glyphs {
+                let glyph = match self.atlas.glyph(block.font, laid.id, block.style.size) {
+                    Some(glyph) => glyph,
+                    None => continue,
+                };
+                // whole pixels keep glyphs as sharp as they were rasterized
+                let left = laid.position[0].round() + glyph.offset[0];
+                let top = laid.position[1].round() + glyph.offset[1];
+                let (right, bottom) = (left + glyph.size[0], top + glyph.size[1]);
+                let corner = |x: f32, y: f32, u: f32, v: f32| {
+                    Vertex::new((block.place)(x, y)).with_uv([u, v]).with_color(block.style.color)
+                };
+                vertices.push(corner(left, bottom, glyph.uv_min[0], glyph.uv_max[1]));
+                vertices.push(corner(right, bottom, glyph.uv_max[0], glyph.uv_max[1]));
+                vertices.push(corner(right, top, glyph.uv_max[0], glyph.uv_min[1]));
+                vertices.push(corner(left, top, glyph.uv_min[0], glyph.uv_min[1]));
+            }
+        }
+        if vertices.is_empty() {
+            return Ok(());
+        }
+        let indices: Vec<u32> = (0..vertices.len() as u32 / 4)
+            .flat_map(|quad| [0, 1, 2, 0, 2, 3].iter().map(move |corner| quad * 4 + corner).collect::<Vec<_>>())
+            .collect();
+        let texture = self.atlas.texture();
+        device.set_pipeline(pipeline)?;
+        device.draw(&DrawCall::new(&vertices, &indices)
+            .with_view_projection(view_projection)
+            .with_texture(&texture, self.sampler))
+    }
+
+    fn prepare<F>(&mut self, blocks: &[Block<F>]) -> bool {
+        let atlas = &mut self.atlas;
+        blocks.iter().all(|block| atlas.prepare(block.font, block.layout.glyphs.iter().map(|glyph| glyph.id), block.style.size))
+    }
+}
+
+impl Default for TextRenderer {
+    fn default() -> Self { TextRenderer::new() }
+}
+
+fn device_size<D: Device + ?Sized>(device: &D) -> Vector2<f32> {
+    let (width, height) = device.size();
+    Vector2::new(width as f32, height as f32)
+}
+
+// pixels from the top-left of the screen to normalized device coordinates, so overlay draw
+// calls need no matrices
+fn to_ndc(x: f32, y: f32, size: Vector2<f32>) -> [f32; 3] {
+    [x * 2. / size.x - 1., 1. - y * 2. / size.y, 0.]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use cgmath::{Matrix4, Point3, Vector3};
+
+    use camera::Camera;
+    use debug::DebugLines;
+    use device::Device;
+    use software::{Framebuffer, SoftwareDevice};
+    use text::tests::serif;
+    use text::{GlyphAtlas, Text, TextOverlay, TextRenderer, TextStyle};
+
+    // pixels at least half lit in red, over the rectangle
+    fn lit(frame: &Framebuffer, xs: Range<u32>, ys: Range<u32>) -> usize {
+        ys.flat_map(|y| xs.clone().map(move |x| (x, y)))
+            .filter(|&(x, y)| frame.pixel(x, y)[0] > 0.5)
+            .count()
+    }
+
+    fn device() -> SoftwareDevice {
+        let mut device = SoftwareDevice::new(64, 32);
+        device.clear([0., 0., 0., 1.], 1.);
+        device
+    }
+
+    #[test]
+    fn draws_accented_overlay_text() {
+        let font = serif();
+        let mut overlay = TextOverlay::new();
+        overlay.text(&font, [4., 4.], "ação", TextStyle::new(20.));
+        let mut device = device();
+        TextRenderer::new().draw_overlay(&mut device, &overlay).unwrap();
+        let frame = device.framebuffer();
+
+        // the block spans 40 by 20 pixels, its baseline 16 down
+        let total = lit(frame, 0..64, 0..32);
+        assert!(total > 100);
+        assert_eq!(lit(frame, 4..46, 4..28), total);
+        // the cedilla hangs below the baseline, the tilde rises above the "a"
+        assert!(lit(frame, 14..24, 21..25) > 0);
+        assert_eq!(lit(frame, 4..14, 22..25), 0);
+        assert!(lit(frame, 23..33, 6..9) > 0);
+        assert_eq!(lit(frame, 4..14, 6..9), 0);
+    }
+
+    #[test]
+    fn draws_world_text_through_the_camera() {
+        let font = serif();
+        let text = Text::new(&font, "To", TextStyle::new(20.).with_color([0., 1., 0., 1.])).with_pivot(0., 0.);
+        let placed = [(&text, Matrix4::from_translation(Vector3::new(4., 4., 0.)))];
+        // a 64x32 world unit view, y up
+        let camera = Camera::orthographic(0., 64., 0., 32., -1., 1.);
+        let mut device = device();
+        TextRenderer::new().draw_world(&mut device, &placed, &camera).unwrap();
+        let frame = device.framebuffer();
+
+        let green = |x: u32, y: u32| frame.pixel(x, y)[1] > 0.5;
+        let inside = (0..64).flat_map(|x| (0..32).map(move |y| (x, y))).filter(|&(x, y)| green(x, y)).collect::<Vec<_>>();
+        assert!(inside.len() > 50);
+        // the block's bottom-left corner sits on (4, 4), 28 pixels down the screen
+        assert!(inside.iter().all(|&(x, y)| (3..30).contains(&x) && (8..28).contains(&y)), "{:?}", inside);
+        assert_eq!(lit(frame, 0..64, 0..32), 0);
+    }
+
+    #[test]
+    fn draws_labels_above_their_anchors() {
+        let font = serif();
+        let mut lines = DebugLines::new();
+        lines.label(Point3::new(32., 16., 0.), "Oi", [1., 0., 0., 1.]);
+        let camera = Camera::orthographic(0., 64., 0., 32., -1., 1.);
+        let mut device = device();
+        TextRenderer::new().draw_labels(&mut device, &lines, &camera, &font, 12.).unwrap();
+        let frame = device.framebuffer();
+
+        let total = lit(frame, 0..64, 0..32);
+        assert!(total > 20);
+        // centred on x = 32, ending at y = 16
+        assert_eq!(lit(frame, 24..40, 4..16), total);
+    }
+
+    #[test]
+    fn clears_a_full_atlas_and_retries() {
+        let font = serif();
+        let mut renderer = TextRenderer::with_atlas(GlyphAtlas::new(24, 16));
+        let mut device = device();
+        let mut draw = |renderer: &mut TextRenderer, text: &str| {
+            let mut overlay = TextOverlay::new();
+            overlay.text(&font, [4., 4.], text, TextStyle::new(20.));
+            renderer.draw_overlay(&mut device, &overlay)
+        };
+        let id = |character: char| font.layout(&character.to_string(), &TextStyle::new(20.)).glyphs[0].id;
+
+        draw(&mut renderer, "ab").unwrap();
+        assert!(renderer.atlas().glyph(&font, id('a'), 20.).is_some());
+        draw(&mut renderer, "co").unwrap();
+        assert_eq!(renderer.atlas().glyph(&font, id('a'), 20.), None);
+        assert!(renderer.atlas().glyph(&font, id('o'), 20.).is_some());
+
+        let error = draw(&mut renderer, "abc").unwrap_err();
+        assert!(error.contains("24x16"), "{}", error);
+    }
+}
@@ -0,0 +1,366 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use ron;
+
+use sprite::Sprite;
+use texture::{ColorSpace, PixelFormat, Texture};
+
+/// Places rectangles in a fixed area, each as high up as it fits, along a skyline of the
+/// lowest free row under every column span.
+///
+/// Rectangles are kept `padding` pixels from each other and from the edges, so filtering
+/// does not bleed between neighbours.
+#[derive(Clone, Debug)]
+pub struct RectPacker {
+    width: u32,
+    height: u32,
+    padding: u32,
+    // x, lowest free row and width of each span, left to right
+    skyline: Vec<(u32, u32, u32)>,
+}
+
+impl RectPacker {
+    pub fn new(width: u32, height: u32, padding: u32) -> Self {
+        let mut packer = RectPacker { width, height, padding, skyline: Vec::new() };
+        packer.clear();
+        packer
+    }
+
+    pub fn width(&self) -> u32 { self.width }
+    pub fn height(&self) -> u32 { self.height }
+
+    /// Frees the whole area.
+    pub fn clear(&mut self) {
+        self.skyline.clear();
+        if self.width > self.padding {
+            self.skyline.push((self.padding, self.padding, self.width - self.padding));
+        }
+    }
+
+    /// Top-left corner of the space given to a `width` by `height` rectangle, or None if it
+    /// does not fit.
+    pub fn insert(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let (width, height) = (width + self.padding, height + self.padding);
+        let mut best: Option<(u32, u32, usize)> = None;
+        for index in 0..self.skyline.len() {
+            if let Some(top) = self.fit(index, width, height) {
+                let x = self.skyline[index].0;
+                if best.is_none_or(|(best_top, best_x, _)| (top, x) < (best_top, best_x)) {
+                    best = Some((top, x, index));
+                }
+            }
+        }
+        let (top, x, index) = best?;
+
+        self.skyline.insert(index, (x, top + height, width));
+        let next = index + 1;
+        while next < self.skyline.len() {
+            let end = self.skyline[next - 1].0 + self.skyline[next - 1].2;
+            let (span_x, _, span_width) = self.skyline[next];
+            if span_x >= end {
+                break;
+            }
+            let overlap = end - span_x;
+            if span_width <= overlap {
+                self.skyline.remove(next);
+            } else {
+                self.skyline[next].0 += overlap;
+                self.skyline[next].2 -= overlap;
+                break;
+            }
+        }
+        let mut index = 0;
+        while index + 1 < self.skyline.len() {
+            if self.skyline[index].1 == self.skyline[index + 1].1 {
+                self.skyline[index].2 += self.skyline[index + 1].2;
+                self.skyline.remove(index + 1);
+            } else {
+                index += 1;
+            }
+        }
+        Some((x, top))
+    }
+
+    // top of a padded rectangle starting at the span's left edge
+    fn fit(&self, index: usize, width: u32, height: u32) -> Option<u32> {
+        let x = self.skyline[index].0;
+        if x + width > self.width {
+            return None;
+        }
+        let (mut top, mut covered) = (0, 0);
+        for &(_, span_top, span_width) in &self.skyline[index..] {
+            top = top.max(span_top);
+            if top + height > self.height {
+                return None;
+            }
+            covered += span_width;
+            if covered >= width {
+                return Some(top);
+            }
+        }
+        None
+    }
+}
+
+/// An image's place in a `TextureAtlas`, in pixels.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AtlasRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Where each image of a `TextureAtlas` went, by name; saved next to the atlas image.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct AtlasMetadata {
+    pub width: u32,
+    pub height: u32,
+    pub regions: BTreeMap<String, AtlasRegion>,
+}
+
+impl AtlasMetadata {
+    /// Top-left and bottom-right corners of a region in texture coordinates.
+    pub fn uv(&self, name: &str) -> Option<([f32; 2], [f32; 2])> {
+        let (width, height) = (self.width as f32, self.height as f32);
+        self.regions.get(name).map(|region| (
+            [region.x as f32 / width, region.y as f32 / height],
+            [(region.x + region.width) as f32 / width, (region.y + region.height) as f32 / height],
+        ))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|source| ron::de::from_str(&source).map_err(|err| err.to_string()))
+            .map_err(|err| format!("In atlas file '{}': {}", path.display(), err))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        ron::ser::to_string_pretty(self, Default::default())
+            .map_err(|err| err.to_string())
+            .and_then(|source| fs::write(path, source).map_err(|err| err.to_string()))
+            .map_err(|err| format!("In atlas file '{}': {}", path.display(), err))
+    }
+}
+
+/// Many images packed into one texture, e.g. a sprite sheet or UI skin.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextureAtlas {
+    pub texture: Arc<Texture>,
+    pub metadata: AtlasMetadata,
+}
+
+impl TextureAtlas {
+    /// Loads an atlas image and its metadata, as written by `save`.
+    pub fn load<P: AsRef<Path>, Q: AsRef<Path>>(image: P, metadata: Q, color_space: ColorSpace) -> Result<Self, String> {
+        let texture = Texture::load(image, color_space)?;
+        let metadata = AtlasMetadata::load(metadata)?;
+        if (texture.width(), texture.height()) != (metadata.width, metadata.height) {
+            return Err(format!(
+                "Atlas image is {}x{}, its metadata {}x{}",
+                texture.width(), texture.height(), metadata.width, metadata.height,
+            ));
+        }
+        Ok(TextureAtlas { texture: Arc::new(texture), metadata })
+    }
+
+    /// Writes the image as an 8-bit PNG, and the metadata as RON.
+    pub fn save<P: AsRef<Path>, Q: AsRef<Path>>(&self, image: P, metadata: Q) -> Result<(), String> {
+        self.texture.save_png(image)?;
+        self.metadata.save(metadata)
+    }
+
+    pub fn region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.metadata.regions.get(name)
+    }
+
+    /// A sprite showing the named image, one local unit per pixel.
+    pub fn sprite(&self, name: &str) -> Option<Sprite> {
+        self.region(name).map(|region| {
+            Sprite::new(self.texture.clone()).with_pixels(region.x, region.y, region.width, region.height)
+        })
+    }
+}
+
+/// Collects named images and packs them into a `TextureAtlas`.
+///
+/// The atlas is the smallest power-of-two size, up to `max_size` a side, that fits every
+/// image, tallest first.
+#[derive(Clone, Debug)]
+pub struct AtlasBuilder {
+    pub padding: u32,
+    pub max_size: u32,
+    images: Vec<(String, Texture)>,
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        AtlasBuilder { padding: 1, max_size: 4096, images: Vec::new() }
+    }
+
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn with_max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    pub fn with_image<S: Into<String>>(mut self, name: S, texture: Texture) -> Self {
+        self.add(name, texture);
+        self
+    }
+
+    pub fn add<S: Into<String>>(&mut self, name: S, texture: Texture) {
+        self.images.push((name.into(), texture));
+    }
+
+    pub fn build(&self) -> Result<TextureAtlas, String> {
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|&index| {
+            let texture = &self.images[index].1;
+            Reverse((texture.height(), texture.width()))
+        });
+
+        let padded = |texture: &Texture| (texture.width() + self.padding, texture.height() + self.padding);
+        let area: u32 = self.images.iter().map(|(_, texture)| { let (w, h) = padded(texture); w * h }).sum();
+        let longest = self.images.iter().map(|(_, texture)| padded(texture).0.max(padded(texture).1)).max().unwrap_or(0);
+        let mut side = 1;
+        while side * side < area || side < longest + self.padding {
+            side *= 2;
+        }
+        let (mut width, mut height) = (side, side);
+
+        let places = loop {
+            if width > self.max_size || height > self.max_size {
+                return Err(format!("Images do not fit a {}x{} atlas", self.max_size, self.max_size));
+            }
+            let mut packer = RectPacker::new(width, height, self.padding);
+            let places: Option<Vec<(u32, u32)>> = order.iter()
+                .map(|&index| packer.insert(self.images[index].1.width(), self.images[index].1.height()))
+                .collect();
+            match places {
+                Some(places) => break places,
+                None if width == height => width *= 2,
+                None => height *= 2,
+            }
+        };
+
+        let mut pixels = vec![[0.; 4]; (width * height) as usize];
+        let mut regions = BTreeMap::new();
+        for (&index, &(x, y)) in order.iter().zip(&places) {
+            let (ref name, ref texture) = self.images[index];
+            let region = AtlasRegion { x, y, width: texture.width(), height: texture.height() };
+            if regions.insert(name.clone(), region).is_some() {
+                return Err(format!("Atlas has two images named '{}'", name));
+            }
+            for (row, source) in texture.pixels().chunks(texture.width() as usize).enumerate() {
+                let start = ((y + row as u32) * width + x) as usize;
+                pixels[start..start + source.len()].copy_from_slice(source);
+            }
+        }
+
+        // keep the sources' encoding when they agree on one
+        let color_space = match self.images.first() {
+            Some((_, first)) if self.images.iter().all(|(_, texture)| texture.color_space() == first.color_space()) => first.color_space(),
+            _ => ColorSpace::Linear,
+        };
+        let format = if self.images.iter().any(|(_, texture)| texture.format().is_float()) { PixelFormat::Rgba32F } else { PixelFormat::Rgba8 };
+        Ok(TextureAtlas {
+            texture: Arc::new(Texture::new(width, height, pixels).with_metadata(format, color_space)),
+            metadata: AtlasMetadata { width, height, regions },
+        })
+    }
+}
+
+impl Default for AtlasBuilder {
+    fn default() -> Self { AtlasBuilder::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use texture::{AtlasBuilder, AtlasMetadata, RectPacker, Texture, TextureAtlas};
+
+    fn solid(width: u32, height: u32, value: f32) -> Texture {
+        Texture::new(width, height, vec![[value, 0., 0., 1.]; (width * height) as usize])
+    }
+
+    fn shapes() -> TextureAtlas {
+        AtlasBuilder::new()
+            .with_image("small", solid(2, 2, 0.25))
+            .with_image("wide", solid(10, 3, 0.5))
+            .with_image("tall", solid(3, 9, 0.75))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn packs_rects_into_the_lowest_gap() {
+        let mut packer = RectPacker::new(16, 16, 1);
+        assert_eq!(packer.insert(6, 4), Some((1, 1)));
+        assert_eq!(packer.insert(6, 6), Some((8, 1)));
+        // fills the lowest gap first
+        assert_eq!(packer.insert(6, 2), Some((1, 6)));
+        assert_eq!(packer.insert(4, 4), Some((8, 8)));
+    }
+
+    #[test]
+    fn rejects_rects_that_do_not_fit() {
+        let mut packer = RectPacker::new(16, 16, 1);
+        assert_eq!(packer.insert(20, 2), None);
+        assert_eq!(packer.insert(6, 10), Some((1, 1)));
+        assert_eq!(packer.insert(14, 8), None);
+        packer.clear();
+        assert_eq!(packer.insert(14, 14), Some((1, 1)));
+    }
+
+    #[test]
+    fn builds_the_smallest_square_atlas() {
+        let atlas = shapes();
+        assert_eq!((atlas.metadata.width, atlas.metadata.height), (16, 16));
+        // tallest first
+        let tall = atlas.metadata.regions["tall"];
+        assert_eq!((tall.x, tall.y), (1, 1));
+    }
+
+    #[test]
+    fn copies_images_into_their_regions() {
+        let atlas = shapes();
+        let tall = atlas.metadata.regions["tall"];
+        assert_eq!(atlas.texture.pixel(tall.x + 2, tall.y + 8)[0], 0.75);
+        for (name, value) in [("small", 0.25), ("wide", 0.5)] {
+            let region = atlas.region(name).unwrap();
+            assert_eq!(atlas.texture.pixel(region.x, region.y)[0], value);
+        }
+    }
+
+    #[test]
+    fn makes_sprites_from_regions() {
+        let atlas = shapes();
+        let sprite = atlas.sprite("wide").unwrap();
+        assert_eq!((sprite.size, sprite.uv_min), ([10., 3.], atlas.metadata.uv("wide").unwrap().0));
+        assert!(atlas.sprite("missing").is_none());
+    }
+
+    #[test]
+    fn round_trips_metadata_through_ron() {
+        let atlas = shapes();
+        let source = ::ron::ser::to_string(&atlas.metadata).unwrap();
+        assert_eq!(::ron::de::from_str::<AtlasMetadata>(&source).unwrap(), atlas.metadata);
+    }
+
+    #[test]
+    fn rejects_oversized_and_duplicate_images() {
+        assert!(AtlasBuilder::new().with_max_size(8).with_image("big", solid(8, 8, 1.)).build().is_err());
+        assert!(AtlasBuilder::new().with_image("same", solid(1, 1, 1.)).with_image("same", solid(1, 1, 1.)).build().is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Cursor};
+use std::path::Path;
+
+use image::{self, DynamicImage, ImageFormat};
+use image::hdr::HDRDecoder;
+use png::{self, HasParameters};
+
+use color::{linear_to_srgb, srgb_to_linear};
+use texture::{ColorSpace, ImageSource, PixelFormat, Texture};
+
+/// Encoded image formats a `Texture` decodes from.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ImageFileFormat {
+    Png,
+    Jpeg,
+    Tga,
+    /// Radiance RGBE
+    Hdr,
+}
+
+impl ImageFileFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "png" => Some(ImageFileFormat::Png),
+            "jpg" | "jpeg" => Some(ImageFileFormat::Jpeg),
+            "tga" => Some(ImageFileFormat::Tga),
+            "hdr" | "rgbe" => Some(ImageFileFormat::Hdr),
+            _ => None,
+        }
+    }
+
+    pub fn from_mime_type(mime_type: &str) -> Option<Self> {
+        match mime_type {
+            "image/png" => Some(ImageFileFormat::Png),
+            "image/jpeg" => Some(ImageFileFormat::Jpeg),
+            "image/tga" | "image/x-tga" | "image/x-targa" => Some(ImageFileFormat::Tga),
+            "image/vnd.radiance" => Some(ImageFileFormat::Hdr),
+            _ => None,
+        }
+    }
+
+    /// From the signature at the start of the file; TGA files have none.
+    pub fn guess(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageFileFormat::Png)
+        } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(ImageFileFormat::Jpeg)
+        } else if bytes.starts_with(b"#?RADIANCE") || bytes.starts_with(b"#?RGBE") {
+            Some(ImageFileFormat::Hdr)
+        } else {
+            None
+        }
+    }
+}
+
+impl Texture {
+    /// Decodes an image in `format`, or the format its signature shows when None.
+    ///
+    /// `color_space` is how 8-bit images are encoded: `Srgb` for colours, `Linear` for data
+    /// such as normal maps. HDR images are always linear.
+    pub fn decode(bytes: &[u8], format: Option<ImageFileFormat>, color_space: ColorSpace) -> Result<Self, String> {
+        let format = match format.or_else(|| ImageFileFormat::guess(bytes)) {
+            Some(format) => format,
+            None => return Err("Unrecognized image format".to_owned()),
+        };
+        let image_format = match format {
+            ImageFileFormat::Png => ImageFormat::PNG,
+            ImageFileFormat::Jpeg => ImageFormat::JPEG,
+            ImageFileFormat::Tga => ImageFormat::TGA,
+            ImageFileFormat::Hdr => return decode_hdr(bytes),
+        };
+        let image = image::load_from_memory_with_format(bytes, image_format).map_err(|err| err.to_string())?;
+        let pixel_format = match image {
+            DynamicImage::ImageLuma8(_) => PixelFormat::Luma8,
+            DynamicImage::ImageLumaA8(_) => PixelFormat::LumaAlpha8,
+            DynamicImage::ImageRgb8(_) => PixelFormat::Rgb8,
+            DynamicImage::ImageRgba8(_) => PixelFormat::Rgba8,
+        };
+        let rgba = image.to_rgba();
+        let (width, height) = rgba.dimensions();
+        if width == 0 || height == 0 {
+            return Err("Image is empty".to_owned());
+        }
+        let table: Vec<f32> = (0..256)
+            .map(|value| match color_space {
+                ColorSpace::Srgb => srgb_to_linear(value as f32 / 255.),
+                ColorSpace::Linear => value as f32 / 255.,
+            })
+            .collect();
+        let pixels = rgba.into_raw().chunks(4)
+            .map(|p| [table[p[0] as usize], table[p[1] as usize], table[p[2] as usize], p[3] as f32 / 255.])
+            .collect();
+        Ok(Texture::new(width, height, pixels).with_metadata(pixel_format, color_space))
+    }
+
+    /// Decodes an image file, telling its format by extension, or else by signature.
+    pub fn load<P: AsRef<Path>>(path: P, color_space: ColorSpace) -> Result<Self, String> {
+        let path = path.as_ref();
+        let format = path.extension().and_then(|extension| extension.to_str()).and_then(ImageFileFormat::from_extension);
+        fs::read(path)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| Texture::decode(&bytes, format, color_space))
+            .map_err(|err| format!("In image file '{}': {}", path.display(), err))
+    }
+
+    /// Writes an 8-bit RGBA PNG, encoding colours in the texture's colour space and clamping
+    /// them to 0..1.
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let encode: fn(f32) -> f32 = match self.color_space {
+            ColorSpace::Srgb => linear_to_srgb,
+            ColorSpace::Linear => |value| value,
+        };
+        let byte = |value: f32| (value.clamp(0., 1.) * 255.).round() as u8;
+        let bytes: Vec<u8> = self.pixels.iter()
+            .flat_map(|p| [byte(encode(p[0])), byte(encode(p[1])), byte(encode(p[2])), byte(p[3])])
+            .collect();
+        let write = || -> io::Result<()> {
+            let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), self.width, self.height);
+            encoder.set(png::ColorType::RGBA).set(png::BitDepth::Eight);
+            encoder.write_header()?.write_image_data(&bytes)?;
+            Ok(())
+        };
+        write().map_err(|err| format!("In image file '{}': {}", path.display(), err))
+    }
+}
+
+impl ImageSource {
+    /// Decodes the image, see `Texture::decode`; embedded images are told apart by MIME type
+    /// when they have one.
+    pub fn decode(&self, color_space: ColorSpace) -> Result<Texture, String> {
+        match *self {
+            ImageSource::Path(ref path) => Texture::load(path, color_space),
+            ImageSource::Embedded { ref mime_type, ref bytes } => {
+                let format = mime_type.as_ref().and_then(|mime_type| ImageFileFormat::from_mime_type(mime_type));
+                Texture::decode(bytes, format, color_space).map_err(|err| format!("In embedded image: {}", err))
+            }
+        }
+    }
+}
+
+fn decode_hdr(bytes: &[u8]) -> Result<Texture, String> {
+    let decoder = HDRDecoder::new(Cursor::new(bytes)).map_err(|err| err.to_string())?;
+    let metadata = decoder.metadata();
+    if metadata.width == 0 || metadata.height == 0 {
+        return Err("Image is empty".to_owned());
+    }
+    let pixels = decoder.read_image_hdr().map_err(|err| err.to_string())?
+        .into_iter()
+        .map(|pixel| [pixel.data[0], pixel.data[1], pixel.data[2], 1.])
+        .collect();
+    Ok(Texture::new(metadata.width, metadata.height, pixels).with_metadata(PixelFormat::Rgb32F, ColorSpace::Linear))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use image::ColorType;
+    use image::jpeg::JPEGEncoder;
+
+    use texture::{ColorSpace, ImageFileFormat, ImageSource, PixelFormat, Texture};
+
+    fn temp_dir() -> PathBuf {
+        let directory = ::std::env::temp_dir().join("cachoeira_texture_test");
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    // uncompressed 24-bit, bottom-up, blue-green-red: red then white
+    fn tga() -> Vec<u8> {
+        let mut tga = vec![0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 1, 0, 24, 0];
+        tga.extend_from_slice(&[0, 0, 255, 255, 255, 255]);
+        tga
+    }
+
+    #[test]
+    fn round_trips_png() {
+        let path = temp_dir().join("saved.png");
+        let original = Texture::new(2, 1, vec![[0.5, 0., 1., 1.], [0., 0.25, 0., 0.5]]).with_metadata(PixelFormat::Rgba8, ColorSpace::Srgb);
+        original.save_png(&path).unwrap();
+        let loaded = ImageSource::Path(path).decode(ColorSpace::Srgb).unwrap();
+        assert_eq!((loaded.format(), loaded.color_space()), (PixelFormat::Rgba8, ColorSpace::Srgb));
+        for (loaded, original) in loaded.pixels().iter().zip(original.pixels()) {
+            assert!(loaded.iter().zip(original).all(|(a, b)| (a - b).abs() < 0.01));
+        }
+    }
+
+    #[test]
+    fn decodes_tga_by_explicit_format() {
+        let tga = tga();
+        // no magic number to guess from
+        assert_eq!(ImageFileFormat::guess(&tga), None);
+        let decoded = Texture::decode(&tga, Some(ImageFileFormat::Tga), ColorSpace::Linear).unwrap();
+        assert_eq!(decoded.format(), PixelFormat::Rgb8);
+        assert_eq!(decoded.pixels(), &[[1., 0., 0., 1.], [1., 1., 1., 1.]][..]);
+    }
+
+    #[test]
+    fn decodes_embedded_images_by_mime_type() {
+        let embedded = ImageSource::Embedded { mime_type: Some("image/x-tga".to_owned()), bytes: Arc::new(tga()) };
+        let decoded = Texture::decode(&tga(), Some(ImageFileFormat::Tga), ColorSpace::Linear).unwrap();
+        assert_eq!(embedded.decode(ColorSpace::Linear).unwrap(), decoded);
+    }
+
+    #[test]
+    fn decodes_hdr_as_linear_floats() {
+        let mut hdr = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n".to_vec();
+        hdr.extend_from_slice(&[128, 64, 0, 129, 128, 128, 128, 133]);
+        let decoded = Texture::decode(&hdr, None, ColorSpace::Srgb).unwrap();
+        assert_eq!((decoded.format(), decoded.color_space()), (PixelFormat::Rgb32F, ColorSpace::Linear));
+        assert_eq!(decoded.pixels(), &[[1., 0.5, 0., 1.], [16., 16., 16., 1.]][..]);
+    }
+
+    #[test]
+    fn decodes_jpeg() {
+        let mut jpeg = Vec::new();
+        JPEGEncoder::new(&mut jpeg).encode(&[200; 8 * 8 * 3], 8, 8, ColorType::RGB(8)).unwrap();
+        let decoded = Texture::decode(&jpeg, None, ColorSpace::Linear).unwrap();
+        assert_eq!((decoded.width(), decoded.format()), (8, PixelFormat::Rgb8));
+        assert!((decoded.pixel(3, 3)[1] - 200. / 255.).abs() < 0.02);
+    }
+
+    #[test]
+    fn reports_unreadable_images() {
+        assert!(Texture::decode(b"not an image", None, ColorSpace::Srgb).is_err());
+        assert!(Texture::load(temp_dir().join("missing.png"), ColorSpace::Srgb).unwrap_err().starts_with("In image file"));
+    }
+}
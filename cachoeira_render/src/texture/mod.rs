@@ -0,0 +1,263 @@
+//! Images sampled by the renderer.
+//!
+//! `Texture`s hold linear floating-point pixels whatever they were decoded from, remembering
+//! the source's pixel format and colour space. PNG, JPEG, TGA and Radiance HDR files decode
+//! into them, mipmap chains are generated on the CPU, and a `TextureAtlas` packs many images
+//! into one texture with serializable metadata describing where each went.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use rayon::prelude::*;
+
+pub use self::atlas::{AtlasBuilder, AtlasMetadata, AtlasRegion, RectPacker, TextureAtlas};
+pub use self::file::ImageFileFormat;
+
+mod atlas;
+mod file;
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Wrap {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Sampler {
+    pub filter: Filter,
+    pub wrap: Wrap,
+}
+
+impl Sampler {
+    pub const NEAREST: Sampler = Sampler { filter: Filter::Nearest, wrap: Wrap::Repeat };
+    pub const LINEAR: Sampler = Sampler { filter: Filter::Linear, wrap: Wrap::Repeat };
+}
+
+impl Default for Sampler {
+    fn default() -> Self { Sampler::LINEAR }
+}
+
+/// Where the encoded image behind a texture comes from, before it is decoded.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImageSource {
+    Path(PathBuf),
+    /// encoded file contents, e.g. from a glTF buffer or data URI
+    Embedded { mime_type: Option<String>, bytes: Arc<Vec<u8>> },
+}
+
+/// How pixels were stored before being decoded into a `Texture`.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum PixelFormat {
+    Luma8,
+    LumaAlpha8,
+    Rgb8,
+    Rgba8,
+    Rgb32F,
+    Rgba32F,
+}
+
+impl PixelFormat {
+    pub fn channels(self) -> u32 {
+        match self {
+            PixelFormat::Luma8 => 1,
+            PixelFormat::LumaAlpha8 => 2,
+            PixelFormat::Rgb8 | PixelFormat::Rgb32F => 3,
+            PixelFormat::Rgba8 | PixelFormat::Rgba32F => 4,
+        }
+    }
+
+    pub fn is_float(self) -> bool {
+        self == PixelFormat::Rgb32F || self == PixelFormat::Rgba32F
+    }
+}
+
+/// Encoding of the colour channels of a stored image; alpha is always linear.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ColorSpace {
+    /// gamma-encoded, as colour images usually are
+    Srgb,
+    /// stored as is, as HDR images and data such as normal or roughness maps are
+    Linear,
+}
+
+/// RGBA image in linear floating point, rows top to bottom.
+///
+/// Texture coordinates put (0, 0) on the top-left corner of the first pixel and (1, 1) on the
+/// bottom-right corner of the last. The format and colour space describe the image the pixels
+/// came from, for devices that upload textures in their original encoding.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Texture {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 4]>,
+    format: PixelFormat,
+    color_space: ColorSpace,
+    // halving in size down to 1x1, empty unless generated
+    mipmaps: Vec<Texture>,
+}
+
+impl Texture {
+    /// Linear `Rgba32F`, without mipmaps.
+    pub fn new(width: u32, height: u32, pixels: Vec<[f32; 4]>) -> Self {
+        assert_eq!(pixels.len(), (width * height) as usize, "Texture pixel count must match its size");
+        assert!(width > 0 && height > 0, "Texture must not be empty");
+        Texture { width, height, pixels, format: PixelFormat::Rgba32F, color_space: ColorSpace::Linear, mipmaps: Vec::new() }
+    }
+
+    /// Single-pixel texture.
+    pub fn solid(color: [f32; 4]) -> Self {
+        Texture::new(1, 1, vec![color])
+    }
+
+    /// Takes 8-bit RGBA as is, without any colour-space conversion.
+    pub fn from_rgba8(width: u32, height: u32, bytes: &[u8]) -> Self {
+        let pixels = bytes.chunks(4)
+            .map(|p| [p[0] as f32 / 255., p[1] as f32 / 255., p[2] as f32 / 255., p[3] as f32 / 255.])
+            .collect();
+        Texture::new(width, height, pixels).with_metadata(PixelFormat::Rgba8, ColorSpace::Linear)
+    }
+
+    pub fn with_metadata(mut self, format: PixelFormat, color_space: ColorSpace) -> Self {
+        self.format = format;
+        self.color_space = color_space;
+        for level in &mut self.mipmaps {
+            level.format = format;
+            level.color_space = color_space;
+        }
+        self
+    }
+
+    /// Generates every mipmap level below this one, each averaging 2x2 pixels of the level
+    /// above in linear light, weighted by alpha so transparent pixels do not darken edges.
+    pub fn with_mipmaps(mut self) -> Self {
+        let mut mipmaps: Vec<Texture> = Vec::new();
+        loop {
+            let next = match mipmaps.last().unwrap_or(&self) {
+                level if level.width == 1 && level.height == 1 => break,
+                level => level.downsample(),
+            };
+            mipmaps.push(next);
+        }
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    pub fn width(&self) -> u32 { self.width }
+    pub fn height(&self) -> u32 { self.height }
+    pub fn pixels(&self) -> &[[f32; 4]] { &self.pixels }
+    pub fn format(&self) -> PixelFormat { self.format }
+    pub fn color_space(&self) -> ColorSpace { self.color_space }
+    /// Levels below this one, largest first.
+    pub fn mipmaps(&self) -> &[Texture] { &self.mipmaps }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [f32; 4] {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    pub fn sample(&self, u: f32, v: f32, sampler: &Sampler) -> [f32; 4] {
+        let (x, y) = (u * self.width as f32, v * self.height as f32);
+        match sampler.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64, sampler.wrap),
+            Filter::Linear => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let top = mix(self.texel(x0, y0, sampler.wrap), self.texel(x0 + 1, y0, sampler.wrap), fx);
+                let bottom = mix(self.texel(x0, y0 + 1, sampler.wrap), self.texel(x0 + 1, y0 + 1, sampler.wrap), fx);
+                mix(top, bottom, fy)
+            }
+        }
+    }
+
+    fn texel(&self, x: i64, y: i64, wrap: Wrap) -> [f32; 4] {
+        let (width, height) = (self.width as i64, self.height as i64);
+        let (x, y) = match wrap {
+            Wrap::Repeat => (((x % width) + width) % width, ((y % height) + height) % height),
+            Wrap::Clamp => (x.max(0).min(width - 1), y.max(0).min(height - 1)),
+            Wrap::Mirror => (mirror(x, width), mirror(y, height)),
+        };
+        self.pixels[(y * width + x) as usize]
+    }
+
+    // odd sizes fold their last row or column into the one before
+    fn downsample(&self) -> Texture {
+        let (width, height) = ((self.width / 2).max(1), (self.height / 2).max(1));
+        let mut pixels = vec![[0.; 4]; (width * height) as usize];
+        pixels.par_chunks_mut(width as usize).enumerate().for_each(|(y, row)| {
+            let y = y as u32;
+            let rows = if height == 1 { 0..self.height } else if y == height - 1 { y * 2..self.height } else { y * 2..y * 2 + 2 };
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let x = x as u32;
+                let columns = if width == 1 { 0..self.width } else if x == width - 1 { x * 2..self.width } else { x * 2..x * 2 + 2 };
+                let (mut weighted, mut plain, mut alpha, mut count) = ([0.; 3], [0.; 3], 0., 0.);
+                for source_y in rows.clone() {
+                    for source_x in columns.clone() {
+                        let source = self.pixel(source_x, source_y);
+                        for c in 0..3 {
+                            weighted[c] += source[c] * source[3];
+                            plain[c] += source[c];
+                        }
+                        alpha += source[3];
+                        count += 1.;
+                    }
+                }
+                *pixel = if alpha > 0. {
+                    [weighted[0] / alpha, weighted[1] / alpha, weighted[2] / alpha, alpha / count]
+                } else {
+                    [plain[0] / count, plain[1] / count, plain[2] / count, 0.]
+                };
+            }
+        });
+        Texture { width, height, pixels, format: self.format, color_space: self.color_space, mipmaps: Vec::new() }
+    }
+}
+
+fn mirror(coordinate: i64, size: i64) -> i64 {
+    let period = ((coordinate % (size * 2)) + size * 2) % (size * 2);
+    if period < size { period } else { size * 2 - 1 - period }
+}
+
+fn mix(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    [
+        a[0] + (b[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+        a[3] + (b[3] - a[3]) * t,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use texture::{ColorSpace, PixelFormat, Texture};
+
+    fn sizes(texture: &Texture) -> Vec<(u32, u32)> {
+        texture.mipmaps().iter().map(|level| (level.width(), level.height())).collect()
+    }
+
+    #[test]
+    fn weights_mipmaps_by_coverage() {
+        // opaque red beside transparent black, then a row of grey
+        let pixels = vec![[1., 0., 0., 1.], [0., 0., 0., 0.], [1., 0., 0., 1.], [0.5; 4], [0.5; 4], [0.5; 4]];
+        let texture = Texture::new(3, 2, pixels).with_metadata(PixelFormat::Rgb8, ColorSpace::Srgb).with_mipmaps();
+        assert_eq!(sizes(&texture), vec![(1, 1)]);
+        let level = &texture.mipmaps()[0];
+        assert_eq!(level.color_space(), ColorSpace::Srgb);
+        // the transparent pixel adds no black
+        let pixel = level.pixel(0, 0);
+        assert!((pixel[0] - 2.75 / 3.5).abs() < 1e-6 && (pixel[3] - 3.5 / 6.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn halves_mipmaps_down_to_one_pixel() {
+        let texture = Texture::new(8, 2, vec![[1.; 4]; 16]).with_mipmaps();
+        assert_eq!(sizes(&texture), vec![(4, 1), (2, 1), (1, 1)]);
+    }
+}